@@ -0,0 +1,122 @@
+use std::process::Command;
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
+// Rust の型を C の型に直す。ポインタは指す先の型を先に直す
+fn c_type(ty: &str) -> String {
+    let ty = ty.trim();
+    if let Some(inner) = ty.strip_prefix("*mut ") {
+        return format!("{} *", c_type(inner));
+    }
+    if let Some(inner) = ty.strip_prefix("*const ") {
+        return format!("const {} *", c_type(inner));
+    }
+    match ty {
+        "u8" => "uint8_t",
+        "i32" => "int32_t",
+        "u64" => "uint64_t",
+        "usize" => "size_t",
+        "c_char" => "char",
+        _ => ty,
+    }
+    .to_string()
+}
+
+// src/capi.rs の extern "C" 関数から C のヘッダを作る。関数の直前の // コメントも写す
+fn generate_header(source: &str) -> String {
+    let mut header = String::new();
+    header.push_str("// build.rs が src/capi.rs から生成する。手で書き換えない\n");
+    header.push_str("#ifndef GAME_SEARCH_ALGORITHM_H\n#define GAME_SEARCH_ALGORITHM_H\n\n");
+    header.push_str("#include <stddef.h>\n#include <stdint.h>\n\n");
+    header.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+    header.push_str("typedef struct GsaAgent GsaAgent;\n");
+    let mut comments: Vec<&str> = Vec::new();
+    let mut lines = source.lines();
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if line.starts_with("//") {
+            comments.push(line);
+            continue;
+        }
+        if line.starts_with("#[") {
+            continue;
+        }
+        if line.starts_with("pub extern \"C\" fn") || line.starts_with("pub unsafe extern \"C\" fn")
+        {
+            let mut signature = line.to_string();
+            while !signature.contains('{') {
+                signature.push(' ');
+                signature.push_str(lines.next().unwrap().trim());
+            }
+            let signature =
+                &signature[signature.find("fn ").unwrap() + 3..signature.find('{').unwrap()];
+            let (name, rest) = signature.split_once('(').unwrap();
+            let (params, ret) = rest.rsplit_once(')').unwrap();
+            let params: Vec<String> = params
+                .split(',')
+                .filter(|param| !param.trim().is_empty())
+                .map(|param| {
+                    let (name, ty) = param.split_once(':').unwrap();
+                    format!("{} {}", c_type(ty), name.trim()).replace("* ", "*")
+                })
+                .collect();
+            let ret = ret
+                .trim()
+                .strip_prefix("->")
+                .map_or("void".to_string(), c_type);
+            header.push('\n');
+            for comment in &comments {
+                header.push_str(comment);
+                header.push('\n');
+            }
+            let params = if params.is_empty() {
+                "void".to_string()
+            } else {
+                params.join(", ")
+            };
+            header.push_str(&format!("{} {}({});\n", ret, name.trim(), params).replace("* ", "*"));
+        }
+        comments.clear();
+    }
+    header.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n#endif\n");
+    header
+}
+
+// 結果ファイルに書き込むビルド情報を埋め込む
+fn main() {
+    // git がない環境 (ソースの tarball やジャッジ) では環境変数 GIT_COMMIT を使い、それもなければ unknown
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    let commit = std::env::var("GIT_COMMIT")
+        .ok()
+        .filter(|commit| !commit.is_empty())
+        .or_else(|| git(&["rev-parse", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=GIT_COMMIT={}", commit);
+    // コミットやチェックアウトのたびに logs/HEAD が更新されるので、それを見て作り直す
+    // git が使えなければ何も見ない (build.rs は src/capi.rs が変わったときだけ動き直す)
+    if let Some(git_dir) = git(&["rev-parse", "--absolute-git-dir"]) {
+        println!("cargo:rerun-if-changed={}/HEAD", git_dir);
+        println!("cargo:rerun-if-changed={}/logs/HEAD", git_dir);
+    }
+
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(str::to_lowercase))
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=ENABLED_FEATURES={}", features.join(","));
+
+    // capi feature のときは C から使うためのヘッダを OUT_DIR に書き出す。ソースの木には書かない
+    println!("cargo:rerun-if-changed=src/capi.rs");
+    if std::env::var("CARGO_FEATURE_CAPI").is_ok() {
+        let source = std::fs::read_to_string("src/capi.rs").unwrap();
+        let out_dir = std::env::var("OUT_DIR").unwrap();
+        let path = std::path::Path::new(&out_dir).join("game_search_algorithm.h");
+        std::fs::write(path, generate_header(&source)).unwrap();
+    }
+}
@@ -0,0 +1,206 @@
+use crate::agent::Agent;
+use crate::hooks;
+use crate::ids::Seed;
+use crate::provenance::Provenance;
+use crate::solver::exhaustive_search;
+use crate::{MazeParams, MazeState, ScoreType};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+// 厳密解は全探索で求めるので、これより長いゲームは扱わない
+pub const MAX_EXACT_TURNS: usize = 8;
+
+// 始めの局面の配置。乱数から作る代わりに、局所探索で直接書き換える
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoardLayout {
+    pub params: MazeParams,
+    pub character: (usize, usize),
+    pub points: Vec<Vec<ScoreType>>,
+}
+
+impl BoardLayout {
+    pub fn from_seed(seed: Seed, params: MazeParams) -> Self {
+        let state = MazeState::from_seed_with_params(seed, params);
+        Self {
+            params,
+            character: (state.character.y as usize, state.character.x as usize),
+            points: state.points,
+        }
+    }
+
+    pub fn state(&self) -> MazeState {
+        MazeState::from_layout(self.points.clone(), self.character, self.params)
+    }
+
+    // 1マスの点を変える、2マスの点を入れ替える、キャラクターを別のマスへ移す、のどれか
+    // キャラクターのいるマスは始めに点が 0 なので、移すときは元のマスに移った先の点を置く
+    fn mutate(&mut self, rng: &mut ChaCha8Rng) {
+        let cell = |rng: &mut ChaCha8Rng| {
+            (
+                rng.gen_range(0..self.params.h),
+                rng.gen_range(0..self.params.w),
+            )
+        };
+        match rng.gen_range(0..3) {
+            0 => {
+                let (y, x) = cell(rng);
+                if (y, x) != self.character {
+                    self.points[y][x] = rng.gen_range(0..10);
+                }
+            }
+            1 => {
+                let ((y1, x1), (y2, x2)) = (cell(rng), cell(rng));
+                if (y1, x1) != self.character && (y2, x2) != self.character {
+                    let point = self.points[y1][x1];
+                    self.points[y1][x1] = self.points[y2][x2];
+                    self.points[y2][x2] = point;
+                }
+            }
+            _ => {
+                let (y, x) = cell(rng);
+                let (cy, cx) = self.character;
+                self.points[cy][cx] = self.points[y][x];
+                self.points[y][x] = 0;
+                self.character = (y, x);
+            }
+        }
+    }
+}
+
+// 1行に "大きさ | y x | 点 (行優先)"。例: "h=3 w=4 end_turn=4 | 1 2 | 3 0 4 ..."
+impl Display for BoardLayout {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let points: Vec<String> = self
+            .points
+            .iter()
+            .flatten()
+            .map(|point| point.to_string())
+            .collect();
+        write!(
+            f,
+            "{} | {} {} | {}",
+            self.params,
+            self.character.0,
+            self.character.1,
+            points.join(" ")
+        )
+    }
+}
+
+impl BoardLayout {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+        let [params, character, points, ..] = fields[..] else {
+            return Err(format!("expected `params | y x | points`, got `{}`", line));
+        };
+        let params: MazeParams = params.parse()?;
+        let numbers = |text: &str| {
+            text.split_whitespace()
+                .map(|n| n.parse().map_err(|_| format!("invalid number `{}`", n)))
+                .collect::<Result<Vec<ScoreType>, String>>()
+        };
+        let character = match numbers(character)?[..] {
+            [y, x] if (y as usize) < params.h && (x as usize) < params.w => {
+                (y as usize, x as usize)
+            }
+            _ => return Err(format!("invalid character position `{}`", character)),
+        };
+        let points = numbers(points)?;
+        if points.len() != params.h * params.w {
+            return Err(format!(
+                "expected {} points, got {}",
+                params.h * params.w,
+                points.len()
+            ));
+        }
+        Ok(Self {
+            params,
+            character,
+            points: points.chunks(params.w).map(<[ScoreType]>::to_vec).collect(),
+        })
+    }
+}
+
+// 対象のエージェントの得点と厳密解の得点
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Gap {
+    pub agent: ScoreType,
+    pub optimal: ScoreType,
+}
+
+impl Gap {
+    pub fn regret(&self) -> ScoreType {
+        self.optimal - self.agent
+    }
+}
+
+// エージェントは盤面ごとに作り直す (木や乱数を前の盤面から持ち越さない)
+pub fn measure_gap(make_agent: &dyn Fn() -> Box<dyn Agent>, layout: &BoardLayout) -> Gap {
+    let state = layout.state();
+    let optimal = exhaustive_search(&state).0;
+    let agent = hooks::play_from(make_agent().as_mut(), state, &mut []).game_score;
+    Gap { agent, optimal }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HardInstance {
+    pub seed: Seed,
+    pub layout: BoardLayout,
+    pub gap: Gap,
+}
+
+// seed の盤面から始め、配置を少しずつ変えて対象のエージェントが厳密解に届かない分を大きくする山登り
+// 同じ値への移動も受け入れるので、差がつかない盤面が続いても動き回れる
+pub fn mine_hard_instance(
+    make_agent: &dyn Fn() -> Box<dyn Agent>,
+    seed: Seed,
+    params: MazeParams,
+    iterations: usize,
+) -> Result<HardInstance, String> {
+    if params.end_turn > MAX_EXACT_TURNS {
+        return Err(format!(
+            "the exact solver is exhaustive; end_turn {} is over {}",
+            params.end_turn, MAX_EXACT_TURNS
+        ));
+    }
+    let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+    let mut layout = BoardLayout::from_seed(seed, params);
+    let mut gap = measure_gap(make_agent, &layout);
+    for _ in 0..iterations {
+        let mut candidate = layout.clone();
+        candidate.mutate(&mut rng);
+        let candidate_gap = measure_gap(make_agent, &candidate);
+        if candidate_gap.regret() >= gap.regret() {
+            layout = candidate;
+            gap = candidate_gap;
+        }
+    }
+    Ok(HardInstance { seed, layout, gap })
+}
+
+// 難しい盤面の集まり。1行目に Provenance、2行目に対象のエージェント、続いて1行1盤面で
+// "配置 | optimal=.. agent=.." を書く。読むときは配置だけを使う
+pub fn save_corpus(path: &str, agent: &str, instances: &[HardInstance]) -> io::Result<()> {
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    writeln!(writer, "{}", Provenance::collect(1))?;
+    writeln!(writer, "# mined against {}", agent)?;
+    for instance in instances {
+        writeln!(
+            writer,
+            "{} | seed={} optimal={} agent={}",
+            instance.layout, instance.seed, instance.gap.optimal, instance.gap.agent
+        )?;
+    }
+    writer.flush()
+}
+
+pub fn load_corpus(path: &str) -> Result<Vec<BoardLayout>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    text.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| BoardLayout::parse(line).map_err(|e| format!("{}: {}", path, e)))
+        .collect()
+}
@@ -0,0 +1,594 @@
+use crate::budget::Budget;
+use crate::explain::Explanation;
+use crate::macro_action::MacroBeamAgent;
+use crate::mcts::{MctsAgent, PrimitiveMonteCarloAgent};
+use crate::nested::{NestedMonteCarloAgent, NestedRolloutPolicyAgent};
+use crate::pool::PooledBeamAgent;
+use crate::search;
+use crate::sequential_halving::{RootPolicy, SequentialHalvingAgent};
+use crate::zobrist_hash::DedupBeamAgent;
+use crate::zoo::LookaheadAgent;
+use crate::{GameState, MazeState, ScoreType};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
+
+pub trait Agent: Send {
+    fn name(&self) -> String;
+    fn select_action(&mut self, state: &MazeState) -> usize;
+
+    // 探索が見積もった評価値を行動と一緒に返す。見積もりを持たないエージェントは None
+    fn select_action_with_score(&mut self, state: &MazeState) -> (usize, Option<ScoreType>) {
+        (self.select_action(state), None)
+    }
+
+    // 次の意思決定に使ってよい時間を受け取る。None なら前に受け取った制限を外す
+    // 時間で打ち切れないエージェント (capabilities の time_limit が false) は無視する
+    fn set_time_limit(&mut self, _time: Option<Duration>) {}
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::SEARCH
+    }
+
+    // 直前の select_action でなぜその手を選んだか。説明を持たないエージェントは None
+    fn explain_last_decision(&self) -> Option<Explanation> {
+        None
+    }
+
+    // 意思決定をまたいで持ち続けている状態の数 (プールや置換表)。持たないエージェントは None
+    fn retained_states(&self) -> Option<usize> {
+        None
+    }
+}
+
+// エージェントが対応していること。対戦や実験の前に組み合わせを確かめるのに使う
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    // set_time_limit で探索を打ち切れる
+    pub time_limit: bool,
+    pub two_player: bool,
+    // 同じ局面・同じシードで同じ手を返す
+    pub deterministic: bool,
+}
+
+impl Capabilities {
+    // 一人用の迷路を決定的に探索するエージェントの既定値
+    pub const SEARCH: Capabilities = Capabilities {
+        time_limit: false,
+        two_player: false,
+        deterministic: true,
+    };
+
+    // Budget で止まる探索。set_time_limit の時間を budget に重ねるので time_limit に対応する
+    // 時間で止まりうる (budget に time があるか、時間の制限を受け取っている) なら決定的でない
+    pub fn budgeted(budget: &Budget, time_limit: Option<Duration>) -> Capabilities {
+        Capabilities {
+            time_limit: true,
+            deterministic: budget.with_time_limit(time_limit).is_deterministic(),
+            ..Capabilities::SEARCH
+        }
+    }
+}
+
+impl Display for Capabilities {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let flag = |b: bool| if b { "yes" } else { "no" };
+        write!(
+            f,
+            "time_limit={} two_player={} deterministic={}",
+            flag(self.time_limit),
+            flag(self.two_player),
+            flag(self.deterministic)
+        )
+    }
+}
+
+pub struct RandomAgent {
+    rng: ChaCha8Rng,
+}
+
+impl RandomAgent {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Agent for RandomAgent {
+    fn name(&self) -> String {
+        "random".to_string()
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        state.random_action(&mut self.rng)
+    }
+}
+
+pub struct GreedyAgent;
+
+impl Agent for GreedyAgent {
+    fn name(&self) -> String {
+        "greedy".to_string()
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        search::greedy_action(state)
+    }
+
+    fn select_action_with_score(&mut self, state: &MazeState) -> (usize, Option<ScoreType>) {
+        let (action, score) = search::greedy_action_with_score(state);
+        (action, Some(score))
+    }
+}
+
+// 深さを指定しなければ局面の残りターンまで読む
+pub(crate) fn depth_or_remaining(depth: Option<usize>, state: &MazeState) -> usize {
+    depth.unwrap_or_else(|| state.remaining_turns())
+}
+
+// 名前に入れる深さ。指定しなければ end
+pub(crate) fn describe_depth(depth: Option<usize>) -> String {
+    depth.map_or("end".to_string(), |depth| depth.to_string())
+}
+
+// ビームサーチの budget を名前に入れる形。何も指定しなければ終局まで読むので depth=end
+pub(crate) fn describe_beam_budget(budget: &Budget) -> String {
+    if budget.is_unlimited() {
+        "depth=end".to_string()
+    } else {
+        budget.describe("depth")
+    }
+}
+
+// budget の iterations は深さ、nodes は展開した子の数。何も指定しなければ終局まで読む
+pub struct BeamSearchAgent {
+    pub beam_width: usize,
+    pub budget: Budget,
+    // set_time_limit で受け取った1手の時間
+    time_limit: Option<Duration>,
+    // 説明は求められたときに作るので、直前の局面と手だけ覚えておく
+    last_decision: Option<(MazeState, usize)>,
+}
+
+impl BeamSearchAgent {
+    pub fn new(beam_width: usize, budget: Budget) -> Self {
+        Self {
+            beam_width,
+            budget,
+            time_limit: None,
+            last_decision: None,
+        }
+    }
+}
+
+impl Agent for BeamSearchAgent {
+    fn name(&self) -> String {
+        format!(
+            "beam(width={}, {})",
+            self.beam_width,
+            describe_beam_budget(&self.budget)
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        self.select_action_with_score(state).0
+    }
+
+    fn select_action_with_score(&mut self, state: &MazeState) -> (usize, Option<ScoreType>) {
+        let budget = self.budget.with_time_limit(self.time_limit);
+        let (best, _) = search::beam_search_with_budget(state, self.beam_width, &budget);
+        self.last_decision = Some((state.clone(), best.first_action));
+        (best.first_action, Some(best.score))
+    }
+
+    fn set_time_limit(&mut self, time: Option<Duration>) {
+        self.time_limit = time;
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::budgeted(&self.budget, self.time_limit)
+    }
+
+    fn explain_last_decision(&self) -> Option<Explanation> {
+        let (state, action) = self.last_decision.as_ref()?;
+        Some(state.explain_beam_search(*action, self.beam_width, self.budget))
+    }
+}
+
+// budget の iterations は全ての層を通して取り出す回数 (beam_number)、nodes は展開した子の数
+// beam_depth が None なら局面の残りターンを深さにする
+pub struct ChokudaiSearchAgent {
+    pub beam_width: usize,
+    pub beam_depth: Option<usize>,
+    pub budget: Budget,
+    // set_time_limit で受け取った1手の時間
+    time_limit: Option<Duration>,
+}
+
+impl ChokudaiSearchAgent {
+    pub fn new(beam_width: usize, beam_depth: Option<usize>, budget: Budget) -> Self {
+        Self {
+            beam_width,
+            beam_depth,
+            budget,
+            time_limit: None,
+        }
+    }
+}
+
+impl Agent for ChokudaiSearchAgent {
+    fn name(&self) -> String {
+        format!(
+            "chokudai(width={}, depth={}, {})",
+            self.beam_width,
+            describe_depth(self.beam_depth),
+            self.budget.describe("number")
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        let beam_depth = depth_or_remaining(self.beam_depth, state);
+        let budget = self.budget.with_time_limit(self.time_limit);
+        search::chokudai_search_with_budget(state, self.beam_width, beam_depth, &budget).0
+    }
+
+    fn set_time_limit(&mut self, time: Option<Duration>) {
+        self.time_limit = time;
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::budgeted(&self.budget, self.time_limit)
+    }
+}
+
+// ビームサーチで最良の局面までの手の列を求め、その手を順に指す
+// 局面が計画どおりに進んでいる間は探索し直さない。計画を使い切るか局面がずれたら探し直す
+// beam_depth が None なら局面の残りターンを深さにする
+pub struct PlanBeamAgent {
+    pub beam_width: usize,
+    pub beam_depth: Option<usize>,
+    // これから指す手と、その手を指す局面
+    plan: VecDeque<(MazeState, usize)>,
+    // 探索した回数
+    pub searches: usize,
+}
+
+impl PlanBeamAgent {
+    pub fn new(beam_width: usize, beam_depth: Option<usize>) -> Self {
+        Self {
+            beam_width,
+            beam_depth,
+            plan: VecDeque::new(),
+            searches: 0,
+        }
+    }
+
+    fn replan(&mut self, state: &MazeState) {
+        self.searches += 1;
+        let beam_depth = depth_or_remaining(self.beam_depth, state);
+        let plan = search::beam_search_plan(state, self.beam_width, beam_depth);
+        let mut now_state = state.clone();
+        self.plan.clear();
+        for action in plan.actions {
+            self.plan.push_back((now_state.clone(), action));
+            now_state.advance(action);
+        }
+    }
+}
+
+impl Agent for PlanBeamAgent {
+    fn name(&self) -> String {
+        format!(
+            "plan_beam(width={}, depth={})",
+            self.beam_width,
+            describe_depth(self.beam_depth)
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        // evaluated_score などの探索用の値は比べない
+        let on_plan = |(planned, _): &(MazeState, usize)| {
+            planned.turn == state.turn
+                && planned.character == state.character
+                && planned.points == state.points
+        };
+        if !self.plan.front().map_or(false, on_plan) {
+            self.replan(state);
+        }
+        self.plan.pop_front().unwrap().1
+    }
+}
+
+// parse_agent が作れるエージェントの一覧
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AgentKind {
+    pub name: &'static str,
+    pub description: &'static str,
+    // (パラメータ名, 既定値)
+    pub parameters: &'static [(&'static str, &'static str)],
+}
+
+impl AgentKind {
+    // 既定のパラメータで作ったエージェントが対応していること
+    pub fn capabilities(&self) -> Capabilities {
+        parse_agent(self.name)
+            .unwrap_or_else(|e| {
+                panic!(
+                    "{} is listed in AGENT_KINDS but not built: {}",
+                    self.name, e
+                )
+            })
+            .capabilities()
+    }
+}
+
+pub const AGENT_KINDS: &[AgentKind] = &[
+    AgentKind {
+        name: "random",
+        description: "uniformly random legal move",
+        parameters: &[("seed", "0")],
+    },
+    AgentKind {
+        name: "greedy",
+        description: "best one-step score",
+        parameters: &[],
+    },
+    AgentKind {
+        name: "beam",
+        description:
+            "beam search stopped by whichever of depth, expanded nodes or time runs out first",
+        parameters: &[
+            ("width", "2"),
+            ("depth", "end"),
+            ("nodes", "-"),
+            ("time_ms", "-"),
+        ],
+    },
+    AgentKind {
+        name: "chokudai",
+        description: "Chokudai search: narrow beams at every depth, repeated until number, expanded nodes or time runs out",
+        parameters: &[
+            ("width", "1"),
+            ("depth", "end"),
+            ("number", "2"),
+            ("nodes", "-"),
+            ("time_ms", "-"),
+        ],
+    },
+    AgentKind {
+        name: "plan",
+        description:
+            "beam search to the best final position, then follow its whole action sequence",
+        parameters: &[("width", "2"), ("depth", "end")],
+    },
+    AgentKind {
+        name: "dedup",
+        description: "beam search that skips positions already expanded at the same depth",
+        parameters: &[("width", "2"), ("depth", "end")],
+    },
+    AgentKind {
+        name: "pooled",
+        description: "beam search that reuses search states from a pool kept across decisions",
+        parameters: &[
+            ("width", "2"),
+            ("depth", "end"),
+            ("nodes", "-"),
+            ("time_ms", "-"),
+        ],
+    },
+    AgentKind {
+        name: "macro",
+        description: "beam search over shortest-path macro-actions",
+        parameters: &[("width", "2")],
+    },
+    AgentKind {
+        name: "lookahead",
+        description: "exhaustive lookahead to a fixed depth",
+        parameters: &[("depth", "2")],
+    },
+    AgentKind {
+        name: "halving",
+        description: "sequential halving over random playouts",
+        parameters: &[
+            ("playouts", "100"),
+            ("nodes", "-"),
+            ("time_ms", "-"),
+            ("seed", "0"),
+        ],
+    },
+    AgentKind {
+        name: "shot",
+        description: "sequential halving applied recursively (SHOT)",
+        parameters: &[
+            ("playouts", "100"),
+            ("nodes", "-"),
+            ("time_ms", "-"),
+            ("seed", "0"),
+        ],
+    },
+    AgentKind {
+        name: "montecarlo",
+        description: "flat Monte Carlo: random playouts split evenly over the legal actions",
+        parameters: &[("playouts", "100"), ("seed", "0")],
+    },
+    AgentKind {
+        name: "mcts",
+        description: "Monte Carlo tree search with UCB1 and random playouts; root=halving|shot splits root playouts by sequential halving",
+        parameters: &[("playouts", "100"), ("seed", "0"), ("root", "ucb")],
+    },
+    AgentKind {
+        name: "puct",
+        description: "MCTS choosing children by PUCT with a softmax prior over one-step scores",
+        parameters: &[("playouts", "100"), ("seed", "0"), ("root", "ucb")],
+    },
+    AgentKind {
+        name: "nmcs",
+        description: "nested Monte Carlo search, cut short by playouts, playout nodes or time",
+        parameters: &[
+            ("level", "2"),
+            ("playouts", "-"),
+            ("nodes", "-"),
+            ("time_ms", "-"),
+            ("seed", "0"),
+        ],
+    },
+    AgentKind {
+        name: "nrpa",
+        description: "nested rollout policy adaptation, cut short by playouts, playout nodes or time",
+        parameters: &[
+            ("level", "2"),
+            ("iterations", "10"),
+            ("playouts", "-"),
+            ("nodes", "-"),
+            ("time_ms", "-"),
+            ("seed", "0"),
+        ],
+    },
+];
+
+// 機械でも読めるように1行1種類で "名前 パラメータ=既定値... | 対応 | 説明" を出す
+impl Display for AgentKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        for (name, default) in self.parameters {
+            write!(f, " {}={}", name, default)?;
+        }
+        write!(f, " | {} | {}", self.capabilities(), self.description)
+    }
+}
+
+// "greedy", "random", "beam width=2 depth=4", "beam width=5 nodes=64" のような文字列からエージェントを作る
+// 深さを指定しなかったビームサーチの仲間は、手を選ぶ局面の残りターンまで読む
+pub fn parse_agent(spec: &str) -> Result<Box<dyn Agent>, String> {
+    parse_agent_with_budget(spec, Budget::default())
+}
+
+// 予算を指定しなかったビームサーチには default_budget を使う (iterations は深さ、何もなければ終局まで)
+pub fn parse_agent_with_budget(
+    spec: &str,
+    default_budget: Budget,
+) -> Result<Box<dyn Agent>, String> {
+    let mut words = spec.split_whitespace();
+    let kind = words.next().ok_or("empty agent spec")?;
+    let agent_kind = AGENT_KINDS
+        .iter()
+        .find(|agent_kind| agent_kind.name == kind)
+        .ok_or(format!("unknown agent `{}`", kind))?;
+    let mut params = Vec::new();
+    // root だけは数ではなく名前で受け取る
+    let mut root_policy = None;
+    for word in words {
+        let (key, value) = word
+            .split_once('=')
+            .ok_or(format!("expected key=value, got `{}`", word))?;
+        if !agent_kind.parameters.iter().any(|&(name, _)| name == key) {
+            return Err(format!("`{}` does not take `{}`", kind, key));
+        }
+        if key == "root" {
+            root_policy = match value {
+                "ucb" => None,
+                "halving" => Some(RootPolicy::SequentialHalving),
+                "shot" => Some(RootPolicy::Shot),
+                _ => {
+                    return Err(format!(
+                        "unknown root policy `{}` (expected ucb, halving or shot)",
+                        value
+                    ))
+                }
+            };
+            continue;
+        }
+        let value: usize = value
+            .parse()
+            .map_err(|_| format!("invalid number `{}` for `{}`", value, key))?;
+        // 幅・深さ・回数が 0 の探索は手を選べない。0 にしてよいのはシードと予算の上限だけ
+        if value == 0 && !["seed", "nodes", "time_ms"].contains(&key) {
+            return Err(format!("`{}` must be at least 1", key));
+        }
+        params.push((key, value));
+    }
+    let param = |key: &str| params.iter().find(|(k, _)| *k == key).map(|&(_, v)| v);
+    let seed = param("seed").unwrap_or(0) as u64;
+    // nodes と time_ms、それに手法ごとの名前で書いた iterations から作る予算。どれもなければ None
+    let budget = |iterations: &str| {
+        let budget = Budget {
+            time: param("time_ms").map(|ms| Duration::from_millis(ms as u64)),
+            nodes: param("nodes"),
+            iterations: param(iterations),
+        };
+        (!budget.is_unlimited()).then_some(budget)
+    };
+
+    match kind {
+        "random" => Ok(Box::new(RandomAgent::new(seed))),
+        "greedy" => Ok(Box::new(GreedyAgent)),
+        "beam" => {
+            let beam_width = param("width").unwrap_or(2);
+            let budget = budget("depth").unwrap_or(default_budget);
+            Ok(Box::new(BeamSearchAgent::new(beam_width, budget)))
+        }
+        "chokudai" => Ok(Box::new(ChokudaiSearchAgent::new(
+            param("width").unwrap_or(1),
+            param("depth"),
+            budget("number").unwrap_or(Budget::iterations(2)),
+        ))),
+        "plan" => Ok(Box::new(PlanBeamAgent::new(
+            param("width").unwrap_or(2),
+            param("depth"),
+        ))),
+        "dedup" => Ok(Box::new(DedupBeamAgent::new(
+            param("width").unwrap_or(2),
+            param("depth"),
+        ))),
+        "pooled" => Ok(Box::new(PooledBeamAgent::new(
+            param("width").unwrap_or(2),
+            budget("depth").unwrap_or_default(),
+        ))),
+        "macro" => Ok(Box::new(MacroBeamAgent {
+            beam_width: param("width").unwrap_or(2),
+        })),
+        "lookahead" => Ok(Box::new(LookaheadAgent {
+            depth: param("depth").unwrap_or(2),
+        })),
+        "halving" | "shot" => {
+            let policy = if kind == "shot" {
+                RootPolicy::Shot
+            } else {
+                RootPolicy::SequentialHalving
+            };
+            let budget = budget("playouts").unwrap_or(Budget::iterations(100));
+            Ok(Box::new(SequentialHalvingAgent::with_budget(
+                policy, budget, seed,
+            )))
+        }
+        "montecarlo" => Ok(Box::new(PrimitiveMonteCarloAgent::new(
+            param("playouts").unwrap_or(100),
+            seed,
+        ))),
+        "mcts" | "puct" => {
+            let playouts = param("playouts").unwrap_or(100);
+            let mut agent = if kind == "puct" {
+                MctsAgent::puct(playouts, seed)
+            } else {
+                MctsAgent::new(playouts, seed)
+            };
+            agent.root_policy = root_policy;
+            Ok(Box::new(agent))
+        }
+        "nmcs" => Ok(Box::new(NestedMonteCarloAgent::with_budget(
+            param("level").unwrap_or(2),
+            budget("playouts").unwrap_or_default(),
+            seed,
+        ))),
+        "nrpa" => Ok(Box::new(NestedRolloutPolicyAgent::with_budget(
+            param("level").unwrap_or(2),
+            param("iterations").unwrap_or(10),
+            budget("playouts").unwrap_or_default(),
+            seed,
+        ))),
+        _ => unreachable!("{} is listed in AGENT_KINDS but not built", kind),
+    }
+}
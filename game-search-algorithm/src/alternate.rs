@@ -0,0 +1,747 @@
+use crate::budget::{Budget, BudgetMeter, Consumed};
+use crate::ids::{PlayerId, Seed};
+use crate::prior::{ordered_actions, PriorPolicy, TwoPlayerGreedyPrior};
+use crate::simultaneous::GameResult;
+use crate::stats;
+use crate::{Coord, ScoreType, DX, DY, END_TURN, H, INF, W};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+
+// 2人で交互に動くので、1人あたりの手数を一人用の迷路と揃える
+pub const ALTERNATE_END_TURN: usize = 2 * END_TURN;
+
+// 2人が交互に指すゲームの操作。minimax・Alpha-Beta・Thunder 探索・MCTS はこれだけで動く
+pub trait TwoPlayerGame: Clone {
+    // 手番のプレイヤーの合法手
+    fn legal_actions(&self) -> Vec<usize>;
+    fn advance(&mut self, action: usize);
+    fn is_done(&self) -> bool;
+    fn current_player(&self) -> PlayerId;
+    // 手番のプレイヤーから見た評価値。大きいほど手番のプレイヤーに良い
+    fn evaluate_score(&self) -> ScoreType;
+    fn result(&self) -> GameResult;
+    // 終局までに指す手数の上限。反復深化はこれより深く読まない
+    fn remaining_turns(&self) -> usize;
+    // 終局前の局面での、手番のプレイヤーの勝率の見積もり (0 から 1)。Thunder 探索の葉で使う
+    fn win_rate_estimate(&self) -> f64;
+
+    fn random_action(&self, rng: &mut impl Rng) -> usize {
+        let legal_actions = self.legal_actions();
+        legal_actions[rng.gen_range(0..legal_actions.len())]
+    }
+}
+
+// 2人が交互に1マスずつ動いて点を取り合う迷路。手番は turn の偶奇で決まる
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AlternateMazeState {
+    points: Vec<Vec<ScoreType>>,
+    turn: usize,
+    characters: [Coord; 2],
+    pub game_scores: [ScoreType; 2],
+}
+
+impl AlternateMazeState {
+    // 初期位置は中央の行に左右対称に置き、点だけを乱数で決める
+    pub fn from_seed(seed: Seed) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        let starts = [(H / 2, W / 2 - 1), (H / 2, W / 2 + 1)];
+
+        let mut points = vec![vec![0; W]; H];
+        for (j, row) in points.iter_mut().enumerate() {
+            for (i, point) in row.iter_mut().enumerate() {
+                if starts.contains(&(j, i)) {
+                    continue;
+                }
+                *point = rng.gen_range(0..10);
+            }
+        }
+
+        let coord = |(y, x): (usize, usize)| Coord {
+            y: y as i64,
+            x: x as i64,
+        };
+        Self {
+            points,
+            turn: 0,
+            characters: [coord(starts[0]), coord(starts[1])],
+            game_scores: [0, 0],
+        }
+    }
+}
+
+impl TwoPlayerGame for AlternateMazeState {
+    fn current_player(&self) -> PlayerId {
+        PlayerId((self.turn % 2) as u8)
+    }
+
+    // 点を取り尽くしたら ALTERNATE_END_TURN を待たずに終わる
+    fn is_done(&self) -> bool {
+        self.turn == ALTERNATE_END_TURN || self.points.iter().flatten().all(|&point| point == 0)
+    }
+
+    // 手番のプレイヤーの合法手。相手のいるマスにも入れる
+    fn legal_actions(&self) -> Vec<usize> {
+        let character = &self.characters[self.current_player().index()];
+        (0..4)
+            .filter(|&action| {
+                let y = character.y + DY[action];
+                let x = character.x + DX[action];
+                y >= 0 && y < H as i64 && x >= 0 && x < W as i64
+            })
+            .collect()
+    }
+
+    fn advance(&mut self, action: usize) {
+        let player = self.current_player();
+        let character = &mut self.characters[player.index()];
+        character.y += DY[action];
+        character.x += DX[action];
+        let point = &mut self.points[character.y as usize][character.x as usize];
+        self.game_scores[player.index()] += *point;
+        *point = 0;
+        self.turn += 1;
+    }
+
+    // 手番のプレイヤーから見た評価値 (自分の点 - 相手の点)
+    fn evaluate_score(&self) -> ScoreType {
+        let player = self.current_player();
+        self.game_scores[player.index()] - self.game_scores[player.opponent().index()]
+    }
+
+    // 点が同じなら引き分け
+    fn result(&self) -> GameResult {
+        let [first, second] = self.game_scores;
+        match first.cmp(&second) {
+            std::cmp::Ordering::Greater => GameResult::Win(PlayerId::FIRST),
+            std::cmp::Ordering::Less => GameResult::Win(PlayerId::SECOND),
+            std::cmp::Ordering::Equal => GameResult::Draw,
+        }
+    }
+
+    fn remaining_turns(&self) -> usize {
+        ALTERNATE_END_TURN - self.turn
+    }
+
+    // 手番のプレイヤーが取った点の割合 (どちらも 0 点なら 0.5)
+    fn win_rate_estimate(&self) -> f64 {
+        let player = self.current_player();
+        let own = self.game_scores[player.index()];
+        let total = own + self.game_scores[player.opponent().index()];
+        if total == 0 {
+            0.5
+        } else {
+            own as f64 / total as f64
+        }
+    }
+}
+
+impl Display for AlternateMazeState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "turn:{}", self.turn)?;
+        writeln!(
+            f,
+            "score(A):{} score(B):{}",
+            self.game_scores[0], self.game_scores[1]
+        )?;
+        for j in 0..H {
+            for i in 0..W {
+                let at = |player: usize| {
+                    self.characters[player].y == j as i64 && self.characters[player].x == i as i64
+                };
+                if at(0) {
+                    write!(f, "A")?;
+                } else if at(1) {
+                    write!(f, "B")?;
+                } else if self.points[j][i] > 0 {
+                    write!(f, "{}", self.points[j][i])?;
+                } else {
+                    write!(f, ".")?;
+                }
+            }
+            writeln!(f)?;
+        }
+        writeln!(f)
+    }
+}
+
+// depth 手先まで読んだ、手番のプレイヤーから見た評価値。nodes に訪れた局面の数を足す
+// 子の評価値は相手の手番から見たものなので、符号を反転して最大を取る
+fn minimax_score<G: TwoPlayerGame>(state: &G, depth: usize, nodes: &mut usize) -> ScoreType {
+    *nodes += 1;
+    if state.is_done() || depth == 0 {
+        return state.evaluate_score();
+    }
+    stats::record_expansion();
+    state
+        .legal_actions()
+        .into_iter()
+        .map(|action| {
+            stats::record_clones(1);
+            let mut next_state = state.clone();
+            next_state.advance(action);
+            -minimax_score(&next_state, depth - 1, nodes)
+        })
+        .max()
+        .unwrap()
+}
+
+// 同じ評価値の手が複数あれば先に並んでいる手を選ぶ
+pub fn minimax_action<G: TwoPlayerGame>(state: &G, depth: usize) -> usize {
+    minimax_action_with_nodes(state, depth).0
+}
+
+// (選んだ手, 訪れた局面の数)
+pub fn minimax_action_with_nodes<G: TwoPlayerGame>(state: &G, depth: usize) -> (usize, usize) {
+    let mut nodes = 1;
+    let mut best_action = None;
+    let mut best_score = ScoreType::MIN;
+    stats::record_expansion();
+    stats::record_depth(depth.max(1));
+    for action in state.legal_actions() {
+        stats::record_clones(1);
+        let mut next_state = state.clone();
+        next_state.advance(action);
+        let score = -minimax_score(&next_state, depth.saturating_sub(1), &mut nodes);
+        if best_action.is_none() || score > best_score {
+            best_action = Some(action);
+            best_score = score;
+        }
+    }
+    (best_action.unwrap(), nodes)
+}
+
+// prior があれば事前確率の高い順、なければ legal_actions の順に読む
+fn search_order<G: TwoPlayerGame>(state: &G, prior: Option<&dyn PriorPolicy<G>>) -> Vec<usize> {
+    match prior {
+        Some(prior) => ordered_actions(state, prior),
+        None => state.legal_actions(),
+    }
+}
+
+// 評価値が (alpha, beta) の外に出ると分かった時点で残りの手を読まない
+// 窓の外の値は真の値ではなく上界・下界になるが、根で選ぶ手は minimax と変わらない
+// abortable なら meter の budget を使い切った時点で None を返し、途中までの結果は捨てる
+// prior で手を並べ替えると良い手を先に読めて枝刈りが増える。窓の中の値は並びによらない
+fn alpha_beta_score<G: TwoPlayerGame>(
+    state: &G,
+    mut alpha: ScoreType,
+    beta: ScoreType,
+    depth: usize,
+    meter: &mut BudgetMeter,
+    abortable: bool,
+    prior: Option<&dyn PriorPolicy<G>>,
+) -> Option<ScoreType> {
+    if abortable && meter.exhausted().is_some() {
+        return None;
+    }
+    meter.add_nodes(1);
+    if state.is_done() || depth == 0 {
+        return Some(state.evaluate_score());
+    }
+    stats::record_expansion();
+    for action in search_order(state, prior) {
+        stats::record_clones(1);
+        let mut next_state = state.clone();
+        next_state.advance(action);
+        let score = -alpha_beta_score(
+            &next_state,
+            -beta,
+            -alpha,
+            depth - 1,
+            meter,
+            abortable,
+            prior,
+        )?;
+        if score > alpha {
+            alpha = score;
+        }
+        if alpha >= beta {
+            return Some(alpha);
+        }
+    }
+    Some(alpha)
+}
+
+// 同じ評価値なら minimax と同じく legal_actions で先に並んでいる手を選ぶ
+// 並べ替えて読むときは、今の最善より前に並んでいる手だけ窓を 1 広げ、同じ値かどうかを確かめる
+fn alpha_beta_root<G: TwoPlayerGame>(
+    state: &G,
+    depth: usize,
+    meter: &mut BudgetMeter,
+    abortable: bool,
+    prior: Option<&dyn PriorPolicy<G>>,
+) -> Option<usize> {
+    let legal_actions = state.legal_actions();
+    // (legal_actions での位置, 手)
+    let mut best: Option<(usize, usize)> = None;
+    // -ScoreType::MIN は溢れるので、窓は ±INF で取る
+    let mut alpha = -INF;
+    let beta = INF;
+    stats::record_expansion();
+    stats::record_depth(depth.max(1));
+    for action in search_order(state, prior) {
+        let index = legal_actions.iter().position(|&a| a == action).unwrap();
+        let earlier = best.map_or(false, |(best_index, _)| index < best_index);
+        stats::record_clones(1);
+        let mut next_state = state.clone();
+        next_state.advance(action);
+        let lower = if earlier { alpha - 1 } else { alpha };
+        let score = -alpha_beta_score(
+            &next_state,
+            -beta,
+            -lower,
+            depth.saturating_sub(1),
+            meter,
+            abortable,
+            prior,
+        )?;
+        if best.is_none() || score > alpha || (earlier && score == alpha) {
+            best = Some((index, action));
+            alpha = score;
+        }
+    }
+    best.map(|(_, action)| action)
+}
+
+pub fn alpha_beta_action<G: TwoPlayerGame>(state: &G, depth: usize) -> usize {
+    alpha_beta_action_with_nodes(state, depth).0
+}
+
+// (選んだ手, 訪れた局面の数)
+pub fn alpha_beta_action_with_nodes<G: TwoPlayerGame>(state: &G, depth: usize) -> (usize, usize) {
+    alpha_beta_search(state, depth, None)
+}
+
+// prior の事前確率の高い順に手を読む Alpha-Beta。選ぶ手は並べ替えないときと同じ
+// (選んだ手, 訪れた局面の数)
+pub fn alpha_beta_action_with_prior<G: TwoPlayerGame>(
+    state: &G,
+    depth: usize,
+    prior: &dyn PriorPolicy<G>,
+) -> (usize, usize) {
+    alpha_beta_search(state, depth, Some(prior))
+}
+
+fn alpha_beta_search<G: TwoPlayerGame>(
+    state: &G,
+    depth: usize,
+    prior: Option<&dyn PriorPolicy<G>>,
+) -> (usize, usize) {
+    let mut meter = BudgetMeter::new(Budget::default());
+    meter.add_nodes(1);
+    let action = alpha_beta_root(state, depth, &mut meter, false, prior).unwrap();
+    (action, meter.nodes)
+}
+
+// 深さ 1, 2, ... と Alpha-Beta を繰り返し、時間切れになったら最後に読み切った深さの手を返す
+pub fn iterative_deepening_action<G: TwoPlayerGame>(state: &G, time_threshold_ms: u64) -> usize {
+    iterative_deepening_action_with_budget(state, &Budget::time_ms(time_threshold_ms)).0
+}
+
+// (選んだ手, 使った分)。budget の iterations は読み切る深さの上限、nodes は訪れた局面の数
+// 深さ 1 は budget に関係なく読むので必ず手を返す。consumed.iterations が読み切った深さになる
+// 残りの手数より深く読んでも結果は変わらないので、そこで打ち切る
+pub fn iterative_deepening_action_with_budget<G: TwoPlayerGame>(
+    state: &G,
+    budget: &Budget,
+) -> (usize, Consumed) {
+    let mut meter = BudgetMeter::new(*budget);
+    meter.add_nodes(1);
+    let mut best_action = alpha_beta_root(state, 1, &mut meter, false, None).unwrap();
+    meter.add_iteration();
+    for depth in 2..=state.remaining_turns() {
+        if meter.exhausted().is_some() {
+            break;
+        }
+        match alpha_beta_root(state, depth, &mut meter, true, None) {
+            Some(action) => {
+                best_action = action;
+                meter.add_iteration();
+            }
+            None => break,
+        }
+    }
+    (best_action, meter.consumed())
+}
+
+// Thunder 探索で葉に使う、手番のプレイヤーから見た勝率
+// 終局していれば勝ち 1、引き分け 0.5、負け 0、そうでなければ win_rate_estimate
+fn thunder_value<G: TwoPlayerGame>(state: &G) -> f64 {
+    let player = state.current_player();
+    if !state.is_done() {
+        return state.win_rate_estimate();
+    }
+    match state.result() {
+        GameResult::Win(winner) if winner == player => 1.0,
+        GameResult::Win(_) => 0.0,
+        GameResult::Draw => 0.5,
+    }
+}
+
+// Thunder 探索の節点。w は手番のプレイヤーから見た勝率の合計
+struct ThunderNode<G> {
+    state: G,
+    w: f64,
+    n: usize,
+    children: Vec<(usize, ThunderNode<G>)>,
+}
+
+impl<G: TwoPlayerGame> ThunderNode<G> {
+    fn new(state: G) -> Self {
+        Self {
+            state,
+            w: 0.0,
+            n: 0,
+            children: Vec::new(),
+        }
+    }
+
+    fn expand(&mut self, meter: &mut BudgetMeter) {
+        for action in self.state.legal_actions() {
+            let mut next_state = self.state.clone();
+            next_state.advance(action);
+            self.children.push((action, ThunderNode::new(next_state)));
+        }
+        stats::record_expansion();
+        stats::record_clones(self.children.len());
+        meter.add_nodes(self.children.len());
+    }
+
+    // 1回たどって手番のプレイヤーから見た勝率を返す
+    // MCTS のプレイアウトの代わりに、初めて来た葉では thunder_value をそのまま使って子を作る
+    fn evaluate(&mut self, meter: &mut BudgetMeter) -> f64 {
+        let value = if self.state.is_done() || self.children.is_empty() {
+            let value = thunder_value(&self.state);
+            if !self.state.is_done() {
+                self.expand(meter);
+            }
+            value
+        } else {
+            1.0 - self.next_child().evaluate(meter)
+        };
+        self.w += value;
+        self.n += 1;
+        value
+    }
+
+    // まだ一度も評価していない子を先に、あとは自分から見た勝率 (1 - 子の勝率) が最大の子
+    // 評価関数の値にぶれがないので UCB1 の探索項はつけない
+    fn next_child(&mut self) -> &mut ThunderNode<G> {
+        let k = match self.children.iter().position(|(_, child)| child.n == 0) {
+            Some(k) => k,
+            None => {
+                let score = |child: &ThunderNode<G>| 1.0 - child.w / child.n as f64;
+                (0..self.children.len())
+                    .max_by(|&a, &b| {
+                        score(&self.children[a].1).total_cmp(&score(&self.children[b].1))
+                    })
+                    .unwrap()
+            }
+        };
+        &mut self.children[k].1
+    }
+}
+
+// playout_number 回たどる Thunder 探索
+pub fn thunder_search_action<G: TwoPlayerGame>(state: &G, playout_number: usize) -> usize {
+    thunder_search_action_with_budget(state, &Budget::iterations(playout_number)).0
+}
+
+// 1手あたり time_threshold_ms だけたどる Thunder 探索
+pub fn thunder_search_action_with_time_threshold<G: TwoPlayerGame>(
+    state: &G,
+    time_threshold_ms: u64,
+) -> usize {
+    thunder_search_action_with_budget(state, &Budget::time_ms(time_threshold_ms)).0
+}
+
+// (選んだ手, 使った分)。budget の iterations はたどった回数、nodes は木に足した局面の数
+// 根は budget に関係なく展開するので必ず手を返す。最も多くたどった子の手を選ぶ
+pub fn thunder_search_action_with_budget<G: TwoPlayerGame>(
+    state: &G,
+    budget: &Budget,
+) -> (usize, Consumed) {
+    assert!(
+        !budget.is_unlimited(),
+        "Thunder search needs a finite budget"
+    );
+    let mut meter = BudgetMeter::new(*budget);
+    let mut root = ThunderNode::new(state.clone());
+    root.expand(&mut meter);
+    while meter.exhausted().is_none() {
+        root.evaluate(&mut meter);
+        meter.add_iteration();
+    }
+    let mut best = 0;
+    for k in 0..root.children.len() {
+        if root.children[k].1.n > root.children[best].1.n {
+            best = k;
+        }
+    }
+    (root.children[best].0, meter.consumed())
+}
+
+// 報酬は勝率なので UCB1 の定数は 1 前後でよい
+pub const DEFAULT_TWO_PLAYER_EXPLORATION: f64 = 1.0;
+// 葉をこの回数たどったら子を作る
+const MCTS_EXPAND_THRESHOLD: usize = 10;
+
+// 終局までランダムに指し、state の手番のプレイヤーから見た勝ち 1、引き分け 0.5、負け 0 を返す
+fn two_player_playout<G: TwoPlayerGame>(
+    state: &G,
+    rng: &mut impl Rng,
+    meter: &mut BudgetMeter,
+) -> f64 {
+    let player = state.current_player();
+    stats::record_clones(1);
+    let mut state = state.clone();
+    while !state.is_done() {
+        state.advance(state.random_action(rng));
+        meter.add_nodes(1);
+    }
+    match state.result() {
+        GameResult::Win(winner) if winner == player => 1.0,
+        GameResult::Win(_) => 0.0,
+        GameResult::Draw => 0.5,
+    }
+}
+
+// 2人ゲームの MCTS の節点。Thunder 探索と同じく w は手番のプレイヤーから見た勝率の合計
+struct MctsNode<G> {
+    state: G,
+    w: f64,
+    n: usize,
+    children: Vec<(usize, MctsNode<G>)>,
+}
+
+impl<G: TwoPlayerGame> MctsNode<G> {
+    fn new(state: G) -> Self {
+        Self {
+            state,
+            w: 0.0,
+            n: 0,
+            children: Vec::new(),
+        }
+    }
+
+    fn expand(&mut self, meter: &mut BudgetMeter) {
+        for action in self.state.legal_actions() {
+            let mut next_state = self.state.clone();
+            next_state.advance(action);
+            self.children.push((action, MctsNode::new(next_state)));
+        }
+        stats::record_expansion();
+        stats::record_clones(self.children.len());
+        meter.add_nodes(self.children.len());
+    }
+
+    // 葉ではプレイアウトし、MCTS_EXPAND_THRESHOLD 回たどったら子を作る
+    fn evaluate(&mut self, exploration: f64, rng: &mut impl Rng, meter: &mut BudgetMeter) -> f64 {
+        let value = if self.state.is_done() || self.children.is_empty() {
+            let value = two_player_playout(&self.state, rng, meter);
+            if !self.state.is_done() && self.n + 1 >= MCTS_EXPAND_THRESHOLD {
+                self.expand(meter);
+            }
+            value
+        } else {
+            1.0 - self
+                .next_child(exploration)
+                .evaluate(exploration, rng, meter)
+        };
+        self.w += value;
+        self.n += 1;
+        value
+    }
+
+    // まだ一度もたどっていない子を先に、あとは自分から見た UCB1 が最大の子
+    fn next_child(&mut self, exploration: f64) -> &mut MctsNode<G> {
+        let k = match self.children.iter().position(|(_, child)| child.n == 0) {
+            Some(k) => k,
+            None => {
+                let total: usize = self.children.iter().map(|(_, child)| child.n).sum();
+                let ucb1 = |child: &MctsNode<G>| {
+                    1.0 - child.w / child.n as f64
+                        + exploration * (2.0 * (total as f64).ln() / child.n as f64).sqrt()
+                };
+                (0..self.children.len())
+                    .max_by(|&a, &b| {
+                        ucb1(&self.children[a].1).total_cmp(&ucb1(&self.children[b].1))
+                    })
+                    .unwrap()
+            }
+        };
+        &mut self.children[k].1
+    }
+}
+
+// playout_number 回プレイアウトする2人ゲームの MCTS
+pub fn mcts_action<G: TwoPlayerGame>(
+    state: &G,
+    playout_number: usize,
+    rng: &mut impl Rng,
+) -> usize {
+    mcts_action_with_budget(
+        state,
+        &Budget::iterations(playout_number),
+        DEFAULT_TWO_PLAYER_EXPLORATION,
+        rng,
+    )
+    .0
+}
+
+// (選んだ手, 使った分)。budget の iterations はたどった回数、nodes は木に足した局面とプレイアウトで進めた局面の数
+// 根は budget に関係なく展開するので必ず手を返す。最も多くたどった子の手を選ぶ
+pub fn mcts_action_with_budget<G: TwoPlayerGame>(
+    state: &G,
+    budget: &Budget,
+    exploration: f64,
+    rng: &mut impl Rng,
+) -> (usize, Consumed) {
+    assert!(!budget.is_unlimited(), "MCTS needs a finite budget");
+    let mut meter = BudgetMeter::new(*budget);
+    let mut root = MctsNode::new(state.clone());
+    root.expand(&mut meter);
+    while meter.exhausted().is_none() {
+        root.evaluate(exploration, rng, &mut meter);
+        meter.add_iteration();
+    }
+    let mut best = 0;
+    for k in 0..root.children.len() {
+        if root.children[k].1.n > root.children[best].1.n {
+            best = k;
+        }
+    }
+    (root.children[best].0, meter.consumed())
+}
+
+// 手番のプレイヤーから見た評価値を返す関数。TwoPlayerGame::evaluate_score の代わりに差し込める
+pub type TwoPlayerEvaluator<'a, G> = &'a dyn Fn(&G) -> ScoreType;
+
+// 相手の最善の応手を指された後の評価値が最も高い手 (評価関数は evaluate_score)
+pub fn min_regret_action<G: TwoPlayerGame>(state: &G) -> usize {
+    min_regret_action_with(state, &G::evaluate_score)
+}
+
+// 自分の手ごとに相手の応手をすべて試し、自分から見た評価値の最小を取る。その最小が最大の手を選ぶ
+// 自分の手で終局すれば、その局面を相手から見た評価値の符号を反転したもので比べる
+// 同じ評価値の手が複数あれば先に並んでいる手を選ぶ
+pub fn min_regret_action_with<G: TwoPlayerGame>(
+    state: &G,
+    evaluate: TwoPlayerEvaluator<G>,
+) -> usize {
+    let mut best_action = None;
+    let mut best_score = ScoreType::MIN;
+    for action in state.legal_actions() {
+        let mut next_state = state.clone();
+        next_state.advance(action);
+        let score = if next_state.is_done() {
+            -evaluate(&next_state)
+        } else {
+            next_state
+                .legal_actions()
+                .into_iter()
+                .map(|reply| {
+                    let mut reply_state = next_state.clone();
+                    reply_state.advance(reply);
+                    evaluate(&reply_state)
+                })
+                .min()
+                .unwrap()
+        };
+        if best_action.is_none() || score > best_score {
+            best_action = Some(action);
+            best_score = score;
+        }
+    }
+    best_action.unwrap()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PruningReport {
+    pub positions: usize,
+    pub minimax_nodes: usize,
+    pub alpha_beta_nodes: usize,
+    // TwoPlayerGreedyPrior で手を並べ替えた Alpha-Beta
+    pub ordered_nodes: usize,
+}
+
+// ランダムに進めた対局の各局面で、Alpha-Beta が (手を並べ替えても) minimax と同じ手を選ぶかを確かめる
+// 食い違えばその局面と両者の手を返す
+pub fn check_alpha_beta(game_number: usize, depth: usize) -> Result<PruningReport, String> {
+    let mut report = PruningReport::default();
+    let prior = TwoPlayerGreedyPrior { temperature: 1.0 };
+    for seed in Seed::first(game_number) {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        let mut state = AlternateMazeState::from_seed(seed);
+        while !state.is_done() {
+            let (minimax, minimax_nodes) = minimax_action_with_nodes(&state, depth);
+            let (alpha_beta, alpha_beta_nodes) = alpha_beta_action_with_nodes(&state, depth);
+            let (ordered, ordered_nodes) = alpha_beta_action_with_prior(&state, depth, &prior);
+            for (name, action) in [("alpha-beta", alpha_beta), ("ordered alpha-beta", ordered)] {
+                if minimax != action {
+                    return Err(format!(
+                        "seed {}: minimax chose {} but {} chose {}\n{}",
+                        seed, minimax, name, action, state
+                    ));
+                }
+            }
+            report.positions += 1;
+            report.minimax_nodes += minimax_nodes;
+            report.alpha_beta_nodes += alpha_beta_nodes;
+            report.ordered_nodes += ordered_nodes;
+            state.advance(state.random_action(&mut rng));
+        }
+    }
+    Ok(report)
+}
+
+pub type AlternateActionFn<'a> = &'a mut dyn FnMut(&AlternateMazeState) -> usize;
+
+// first を先手と後手で半分ずつ指させたときの勝率。引き分けは 0.5 勝と数える
+pub fn win_rate(first: AlternateActionFn, second: AlternateActionFn, game_number: usize) -> f64 {
+    win_rate_with(&AlternateMazeState::from_seed, first, second, game_number)
+}
+
+// win_rate をほかの2人ゲームで。i 局目は new_game(i / 2) から始め、先後を1局ごとに入れ替える
+pub fn win_rate_with<G: TwoPlayerGame>(
+    new_game: &dyn Fn(Seed) -> G,
+    first: &mut dyn FnMut(&G) -> usize,
+    second: &mut dyn FnMut(&G) -> usize,
+    game_number: usize,
+) -> f64 {
+    let mut points = 0.0;
+    for i in 0..game_number {
+        let mut state = new_game(Seed((i / 2) as u64));
+        let first_seat = PlayerId((i % 2) as u8);
+        while !state.is_done() {
+            let action = if state.current_player() == first_seat {
+                first(&state)
+            } else {
+                second(&state)
+            };
+            state.advance(action);
+        }
+        points += match state.result() {
+            GameResult::Win(player) if player == first_seat => 1.0,
+            GameResult::Win(_) => 0.0,
+            GameResult::Draw => 0.5,
+        };
+    }
+    points / game_number as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alpha_beta_agrees_with_minimax() {
+        for depth in 1..=END_TURN {
+            let report = check_alpha_beta(20, depth).unwrap();
+            assert!(report.positions > 0);
+            assert!(report.alpha_beta_nodes <= report.minimax_nodes);
+            assert!(report.ordered_nodes <= report.minimax_nodes);
+        }
+    }
+}
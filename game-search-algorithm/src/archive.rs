@@ -0,0 +1,88 @@
+use crate::provenance::Provenance;
+use crate::ScoreType;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+type Distance<T> = Box<dyn Fn(&T, &T) -> usize>;
+
+// 最適化の途中で見つけた解のうち、互いに min_distance 以上離れた上位 capacity 個を残す
+// 近い解どうしでは良い方だけを残すので、似た解ばかりで埋まらない
+pub struct SolutionArchive<T> {
+    pub capacity: usize,
+    pub min_distance: usize,
+    distance: Distance<T>,
+    // 得点の高い順
+    entries: Vec<(ScoreType, T)>,
+}
+
+impl<T: Clone> SolutionArchive<T> {
+    // distance は問題側が決める解どうしの距離。min_distance が 1 なら「距離 0 の解は同じ解」とだけ見なす
+    pub fn new(
+        capacity: usize,
+        min_distance: usize,
+        distance: impl Fn(&T, &T) -> usize + 'static,
+    ) -> Self {
+        Self {
+            capacity,
+            min_distance,
+            distance: Box::new(distance),
+            entries: Vec::new(),
+        }
+    }
+
+    // 残したら true。近くにそれ以上の得点の解があれば捨て、近くのより悪い解は置き換える
+    pub fn insert(&mut self, score: ScoreType, solution: &T) -> bool {
+        if self.capacity == 0
+            || (self.entries.len() == self.capacity
+                && self
+                    .entries
+                    .last()
+                    .map_or(false, |&(worst, _)| score <= worst))
+        {
+            return false;
+        }
+        let near =
+            |(_, other): &(ScoreType, T)| (self.distance)(other, solution) < self.min_distance;
+        if self
+            .entries
+            .iter()
+            .any(|entry| near(entry) && entry.0 >= score)
+        {
+            return false;
+        }
+        let kept: Vec<(ScoreType, T)> = self
+            .entries
+            .iter()
+            .filter(|entry| !near(entry))
+            .cloned()
+            .collect();
+        self.entries = kept;
+        // 同点は先に入った解を前に置く
+        let position = self.entries.partition_point(|&(other, _)| other >= score);
+        self.entries.insert(position, (score, solution.clone()));
+        self.entries.truncate(self.capacity);
+        true
+    }
+
+    pub fn entries(&self) -> &[(ScoreType, T)] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // 1行に1つ "得点 解" を得点の高い順に書き出す。解の書き方は describe で決める
+    pub fn save(&self, path: &str, describe: impl Fn(&T) -> String) -> io::Result<()> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        writeln!(writer, "{}", Provenance::collect(1))?;
+        for (score, solution) in &self.entries {
+            writeln!(writer, "{} {}", score, describe(solution))?;
+        }
+        writer.flush()
+    }
+}
@@ -0,0 +1,250 @@
+use crate::agent::Agent;
+use crate::MazeState;
+use std::cell::RefCell;
+use std::fmt::{self, Display, Formatter};
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread;
+
+// 非同期のサーバーから探索を止めるための札。複製しても同じ札を指す
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+thread_local! {
+    // プールのスレッドで今動いている探索の札。BudgetMeter が反復ごとに見る
+    static CURRENT_TOKEN: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
+}
+
+// 今のスレッドの探索が止めるように言われているか。プールの外では常に false
+pub(crate) fn is_cancelled() -> bool {
+    CURRENT_TOKEN.with(|token| token.borrow().as_ref().map_or(false, |t| t.is_cancelled()))
+}
+
+// プールに渡した仕事が結果を返せなかった理由
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaskError {
+    Cancelled,
+    // 仕事の中で panic した。中身は panic のメッセージ
+    Panicked(String),
+}
+
+impl Display for TaskError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            TaskError::Cancelled => write!(f, "search cancelled"),
+            TaskError::Panicked(message) => write!(f, "search panicked: {}", message),
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or_else(
+            || "unknown panic".to_string(),
+            |message| message.to_string(),
+        ),
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// 探索のようにスレッドを塞ぐ仕事を流すための決まった数のスレッド
+// 非同期ランタイムのスレッドでは探索を動かさず、ここに渡して結果を Future で待つ
+pub struct BlockingPool {
+    sender: Mutex<Sender<Job>>,
+}
+
+impl BlockingPool {
+    pub fn new(threads: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..threads.max(1) {
+            let receiver = Arc::clone(&receiver);
+            thread::spawn(move || loop {
+                let job = match receiver.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => return,
+                };
+                job();
+            });
+        }
+        Self {
+            sender: Mutex::new(sender),
+        }
+    }
+
+    // token を付けて f を動かす。f の中の探索は BudgetMeter を通して token を見て止まる
+    pub fn spawn<T: Send + 'static>(
+        &self,
+        token: CancelToken,
+        f: impl FnOnce() -> T + Send + 'static,
+    ) -> TaskFuture<T> {
+        let shared = Arc::new(Mutex::new(Shared {
+            result: None,
+            waker: None,
+        }));
+        let task = TaskFuture {
+            shared: Arc::clone(&shared),
+            token: token.clone(),
+        };
+        let job: Job = Box::new(move || {
+            CURRENT_TOKEN.with(|current| *current.borrow_mut() = Some(token.clone()));
+            // f が panic しても結果を入れて待っている側を起こす。スレッドもプールに残る
+            let value = panic::catch_unwind(AssertUnwindSafe(f));
+            CURRENT_TOKEN.with(|current| *current.borrow_mut() = None);
+            let mut shared = shared.lock().unwrap_or_else(PoisonError::into_inner);
+            shared.result = Some(match value {
+                Err(payload) => Err(TaskError::Panicked(panic_message(payload))),
+                Ok(_) if token.is_cancelled() => Err(TaskError::Cancelled),
+                Ok(value) => Ok(value),
+            });
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        });
+        self.sender.lock().unwrap().send(job).unwrap();
+        task
+    }
+}
+
+struct Shared<T> {
+    result: Option<Result<T, TaskError>>,
+    waker: Option<Waker>,
+}
+
+// プールに渡した仕事の結果。止められていれば Err(TaskError::Cancelled)、panic すれば Err(TaskError::Panicked) になる
+pub struct TaskFuture<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+    token: CancelToken,
+}
+
+impl<T> TaskFuture<T> {
+    pub fn token(&self) -> &CancelToken {
+        &self.token
+    }
+}
+
+impl<T> Future for TaskFuture<T> {
+    type Output = Result<T, TaskError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        match shared.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+// Agent を非同期のサーバーから使うための包み。探索はプールのスレッドで動かす
+// 同じエージェントへの問い合わせは順番に処理される
+pub struct AsyncAgent {
+    agent: Arc<Mutex<Box<dyn Agent>>>,
+    pool: Arc<BlockingPool>,
+}
+
+impl AsyncAgent {
+    pub fn new(agent: Box<dyn Agent>, pool: Arc<BlockingPool>) -> Self {
+        Self {
+            agent: Arc::new(Mutex::new(agent)),
+            pool,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.agent
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .name()
+    }
+
+    // BudgetMeter で打ち切るエージェント (時間・ノード数を指定したビームサーチや MCTS など) は
+    // token を止めると次の反復で探索をやめる。深さだけで打ち切るものは探索が終わってから Err を返す
+    // 前の問い合わせが panic してもエージェントはそのまま使い続ける
+    pub fn select_action(&self, state: &MazeState, token: CancelToken) -> TaskFuture<usize> {
+        let agent = Arc::clone(&self.agent);
+        let state = state.clone();
+        self.pool.spawn(token, move || {
+            agent
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .select_action(&state)
+        })
+    }
+}
+
+struct ThreadWaker(thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// ランタイムのない同期のコードから Future を待つ
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Seed;
+
+    struct PanickingAgent;
+
+    impl Agent for PanickingAgent {
+        fn name(&self) -> String {
+            "panicking".to_string()
+        }
+
+        fn select_action(&mut self, _state: &MazeState) -> usize {
+            panic!("no action");
+        }
+    }
+
+    #[test]
+    fn panicking_agent_completes_with_error() {
+        let pool = Arc::new(BlockingPool::new(1));
+        let agent = AsyncAgent::new(Box::new(PanickingAgent), pool);
+        let state = MazeState::from_seed(Seed(0));
+        for _ in 0..2 {
+            let result = block_on(agent.select_action(&state, CancelToken::new()));
+            assert_eq!(result, Err(TaskError::Panicked("no action".to_string())));
+        }
+        assert_eq!(agent.name(), "panicking");
+    }
+}
@@ -0,0 +1,348 @@
+use crate::ids::{PlayerId, Seed};
+use crate::simultaneous::GameResult;
+use crate::{Coord, MazeParams, ScoreType, DX, DY};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+
+// 1ターンの行動。自分の点から払う入札額と移動の向き
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AuctionMove {
+    pub bid: ScoreType,
+    pub direction: usize,
+}
+
+// 2人が毎ターン同時に入札と移動を選び、高く入札したほうが入札額を払って先に動く迷路
+// 先に動いたほうが移動先の点を取り、後から同じマスに入っても点は残っていない
+// 同額なら誰も払わず、前のターンに後から動いたほうが先に動く
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuctionMazeState {
+    points: Vec<Vec<ScoreType>>,
+    turn: usize,
+    characters: [Coord; 2],
+    pub game_scores: [ScoreType; 2],
+    // 入札で払った点の合計
+    pub paid: [ScoreType; 2],
+    // 直前のターンに先に動いたプレイヤー。始めは後手なので、最初の同額は先手が先に動く
+    pub initiative: PlayerId,
+    params: MazeParams,
+}
+
+impl AuctionMazeState {
+    // 乱数を引く順番は CooperativeMazeState と同じ (2人の初期位置、続いて点)
+    pub fn from_seed(seed: Seed, params: MazeParams) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        let mut cell = || (rng.gen_range(0..params.h), rng.gen_range(0..params.w));
+        let first = cell();
+        let mut second = first;
+        while second == first {
+            second = cell();
+        }
+        let mut points = vec![vec![0; params.w]; params.h];
+        for (j, row) in points.iter_mut().enumerate() {
+            for (i, point) in row.iter_mut().enumerate() {
+                if [first, second].contains(&(j, i)) {
+                    continue;
+                }
+                *point = rng.gen_range(0..10);
+            }
+        }
+        let coord = |(y, x): (usize, usize)| Coord {
+            y: y as i64,
+            x: x as i64,
+        };
+        Self {
+            points,
+            turn: 0,
+            characters: [coord(first), coord(second)],
+            game_scores: [0; 2],
+            paid: [0; 2],
+            initiative: PlayerId::SECOND,
+            params,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.turn == self.params.end_turn || self.points.iter().flatten().all(|&point| point == 0)
+    }
+
+    // 盤面の外に出ない向き。相手と同じマスに入ってもよい
+    pub fn directions(&self, player: PlayerId) -> Vec<usize> {
+        let character = &self.characters[player.index()];
+        (0..4)
+            .filter(|&direction| {
+                let y = character.y + DY[direction];
+                let x = character.x + DX[direction];
+                y >= 0 && y < self.params.h as i64 && x >= 0 && x < self.params.w as i64
+            })
+            .collect()
+    }
+
+    // 入札は 0 から自分の点まで
+    pub fn is_legal(&self, player: PlayerId, auction_move: AuctionMove) -> bool {
+        (0..=self.game_scores[player.index()]).contains(&auction_move.bid)
+            && self.directions(player).contains(&auction_move.direction)
+    }
+
+    // このターンに先に動くプレイヤー
+    pub fn initiative_winner(&self, bids: [ScoreType; 2]) -> PlayerId {
+        match bids[0].cmp(&bids[1]) {
+            std::cmp::Ordering::Greater => PlayerId::FIRST,
+            std::cmp::Ordering::Less => PlayerId::SECOND,
+            std::cmp::Ordering::Equal => self.initiative.opponent(),
+        }
+    }
+
+    pub fn advance(&mut self, moves: [AuctionMove; 2]) {
+        debug_assert!(PlayerId::BOTH
+            .iter()
+            .all(|&player| self.is_legal(player, moves[player.index()])));
+        let winner = self.initiative_winner(moves.map(|auction_move| auction_move.bid));
+        if moves[0].bid != moves[1].bid {
+            let bid = moves[winner.index()].bid;
+            self.game_scores[winner.index()] -= bid;
+            self.paid[winner.index()] += bid;
+        }
+        for player in [winner, winner.opponent()] {
+            let character = &mut self.characters[player.index()];
+            let direction = moves[player.index()].direction;
+            character.y += DY[direction];
+            character.x += DX[direction];
+            let point = &mut self.points[character.y as usize][character.x as usize];
+            self.game_scores[player.index()] += *point;
+            *point = 0;
+        }
+        self.initiative = winner;
+        self.turn += 1;
+    }
+
+    // 点が同じなら引き分け
+    pub fn result(&self) -> GameResult {
+        let [first, second] = self.game_scores;
+        match first.cmp(&second) {
+            std::cmp::Ordering::Greater => GameResult::Win(PlayerId::FIRST),
+            std::cmp::Ordering::Less => GameResult::Win(PlayerId::SECOND),
+            std::cmp::Ordering::Equal => GameResult::Draw,
+        }
+    }
+
+    fn target_point(&self, player: PlayerId, direction: usize) -> ScoreType {
+        let character = &self.characters[player.index()];
+        let y = (character.y + DY[direction]) as usize;
+        let x = (character.x + DX[direction]) as usize;
+        self.points[y][x]
+    }
+
+    // 隣のマスの点が最も高い向き。同じなら先に並んでいる向き
+    pub fn greedy_direction(&self, player: PlayerId) -> usize {
+        let mut best = None;
+        for direction in self.directions(player) {
+            let point = self.target_point(player, direction);
+            if best.map_or(true, |(_, best_point)| point > best_point) {
+                best = Some((direction, point));
+            }
+        }
+        best.unwrap().0
+    }
+
+    // scale で絞った入札と向きのすべての組
+    pub fn candidate_moves(&self, player: PlayerId, scale: BidScale) -> Vec<AuctionMove> {
+        let directions = self.directions(player);
+        scale
+            .bids(self.game_scores[player.index()])
+            .into_iter()
+            .flat_map(|bid| {
+                directions
+                    .iter()
+                    .map(move |&direction| AuctionMove { bid, direction })
+            })
+            .collect()
+    }
+}
+
+// キャラクターは A と B。同じマスにいれば A だけ描く
+impl Display for AuctionMazeState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "turn:{} score:{}-{} paid:{}-{}",
+            self.turn, self.game_scores[0], self.game_scores[1], self.paid[0], self.paid[1]
+        )?;
+        for (y, row) in self.points.iter().enumerate() {
+            for (x, &point) in row.iter().enumerate() {
+                let here = |player: PlayerId| {
+                    let character = &self.characters[player.index()];
+                    character.y == y as i64 && character.x == x as i64
+                };
+                if here(PlayerId::FIRST) {
+                    write!(f, "A")?;
+                } else if here(PlayerId::SECOND) {
+                    write!(f, "B")?;
+                } else if point > 0 {
+                    write!(f, "{}", point)?;
+                } else {
+                    write!(f, ".")?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// 入札額の抽象化。自分の点を levels 等分した額 (0 と全額を含む) だけを候補にする
+// levels が 0 なら入札しない
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BidScale {
+    pub levels: usize,
+}
+
+impl BidScale {
+    // 点が少ないと同じ額が重なるので、重なりは除く
+    pub fn bids(&self, score: ScoreType) -> Vec<ScoreType> {
+        let levels = self.levels.max(1) as ScoreType;
+        let mut bids: Vec<ScoreType> = (0..=self.levels as ScoreType)
+            .map(|k| score * k / levels)
+            .collect();
+        bids.dedup();
+        bids
+    }
+}
+
+// 入札と移動の決め方
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuctionPolicy {
+    // 入札せず、隣の点が最も高いマスへ動く
+    Greedy,
+    // Greedy と同じマスへ動き、相手も同じマスを狙っていればそのマスの点より 1 少なく入札する
+    Contest,
+    // 自分の候補 (scale で絞った入札と向き) ごとに、相手の候補を一様ランダムに選んで1ターン進め、
+    // そのあとは2人とも入札せずランダムに動くプレイアウトで評価する
+    // 勝ち負けではなく点差で評価するので、入札で払った点はそのまま損に数えられる
+    MonteCarlo { scale: BidScale, playouts: usize },
+}
+
+impl Display for AuctionPolicy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            AuctionPolicy::Greedy => write!(f, "greedy"),
+            AuctionPolicy::Contest => write!(f, "contest"),
+            AuctionPolicy::MonteCarlo { scale, playouts } => write!(
+                f,
+                "montecarlo(playouts={}, bid_levels={})",
+                playouts, scale.levels
+            ),
+        }
+    }
+}
+
+// 2人とも入札せずランダムに動いて終局まで進め、player から見た点差を返す
+fn random_playout(state: &AuctionMazeState, player: PlayerId, rng: &mut impl Rng) -> ScoreType {
+    let mut state = state.clone();
+    while !state.is_done() {
+        let moves = PlayerId::BOTH.map(|mover| {
+            let directions = state.directions(mover);
+            AuctionMove {
+                bid: 0,
+                direction: directions[rng.gen_range(0..directions.len())],
+            }
+        });
+        state.advance(moves);
+    }
+    state.game_scores[player.index()] - state.game_scores[player.opponent().index()]
+}
+
+impl AuctionPolicy {
+    pub fn select_move(
+        &self,
+        state: &AuctionMazeState,
+        player: PlayerId,
+        rng: &mut impl Rng,
+    ) -> AuctionMove {
+        match *self {
+            AuctionPolicy::Greedy => AuctionMove {
+                bid: 0,
+                direction: state.greedy_direction(player),
+            },
+            AuctionPolicy::Contest => {
+                let direction = state.greedy_direction(player);
+                let target = |player: PlayerId, direction: usize| {
+                    let character = &state.characters[player.index()];
+                    (character.y + DY[direction], character.x + DX[direction])
+                };
+                let opponent = player.opponent();
+                let contested =
+                    target(player, direction) == target(opponent, state.greedy_direction(opponent));
+                let bid = if contested {
+                    (state.target_point(player, direction) - 1)
+                        .clamp(0, state.game_scores[player.index()])
+                } else {
+                    0
+                };
+                AuctionMove { bid, direction }
+            }
+            AuctionPolicy::MonteCarlo { scale, playouts } => {
+                let candidates = state.candidate_moves(player, scale);
+                let replies = state.candidate_moves(player.opponent(), scale);
+                let mut totals = vec![(0, 0); candidates.len()];
+                for i in 0..playouts.max(candidates.len()) {
+                    let k = i % candidates.len();
+                    let mut moves = [candidates[k]; 2];
+                    moves[player.opponent().index()] = replies[rng.gen_range(0..replies.len())];
+                    let mut next_state = state.clone();
+                    next_state.advance(moves);
+                    totals[k].0 += random_playout(&next_state, player, rng);
+                    totals[k].1 += 1;
+                }
+                let mean = |(total, count): (ScoreType, usize)| total as f64 / count as f64;
+                let mut best = 0;
+                for k in 0..candidates.len() {
+                    if mean(totals[k]) > mean(totals[best]) {
+                        best = k;
+                    }
+                }
+                candidates[best]
+            }
+        }
+    }
+}
+
+// seed の盤面を1局指す。乱数も seed から作るので、同じ seed なら同じ対局になる
+pub fn play(policies: [AuctionPolicy; 2], seed: Seed, params: MazeParams) -> AuctionMazeState {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+    let mut state = AuctionMazeState::from_seed(seed, params);
+    while !state.is_done() {
+        let moves = PlayerId::BOTH
+            .map(|player| policies[player.index()].select_move(&state, player, &mut rng));
+        state.advance(moves);
+    }
+    state
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AuctionSummary {
+    pub wins: [usize; 2],
+    pub draws: usize,
+    pub total_scores: [ScoreType; 2],
+    pub total_paid: [ScoreType; 2],
+}
+
+pub fn compare(
+    policies: [AuctionPolicy; 2],
+    params: MazeParams,
+    game_number: usize,
+) -> AuctionSummary {
+    let mut summary = AuctionSummary::default();
+    for seed in Seed::first(game_number) {
+        let state = play(policies, seed, params);
+        match state.result() {
+            GameResult::Win(player) => summary.wins[player.index()] += 1,
+            GameResult::Draw => summary.draws += 1,
+        }
+        for player in 0..2 {
+            summary.total_scores[player] += state.game_scores[player];
+            summary.total_paid[player] += state.paid[player];
+        }
+    }
+    summary
+}
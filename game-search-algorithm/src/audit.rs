@@ -0,0 +1,90 @@
+use crate::agent::Agent;
+use crate::episode::replay_from;
+use crate::hooks::Observer;
+use crate::ids::{Seed, Turn};
+use crate::{GameState, MazeState, ScoreType};
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug)]
+pub struct Divergence {
+    pub agent: String,
+    pub seed: Seed,
+    pub turn: Turn,
+    pub first_action: usize,
+    pub second_action: usize,
+    pub board: String,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "nondeterminism detected: agent={} seed={} turn={} first_run={} second_run={}",
+            self.agent, self.seed, self.turn, self.first_action, self.second_action
+        )?;
+        write!(f, "{}", self.board)
+    }
+}
+
+// 同じ (アルゴリズム, シード, 予算) で2回ずつ意思決定させ、結果が食い違えば報告する
+pub fn audit_determinism<F>(make_agent: F, seed: Seed) -> Result<(), Divergence>
+where
+    F: Fn() -> Box<dyn Agent>,
+{
+    let mut first = make_agent();
+    let mut second = make_agent();
+    let mut state = MazeState::from_seed(seed);
+    while !state.is_done() {
+        let first_action = first.select_action(&state);
+        let second_action = second.select_action(&state);
+        if first_action != second_action {
+            return Err(Divergence {
+                agent: first.name(),
+                seed,
+                turn: state.turn(),
+                first_action,
+                second_action,
+                board: state.to_string(),
+            });
+        }
+        state.advance(first_action);
+    }
+    Ok(())
+}
+
+pub fn audit_all<F>(make_agent: F, game_number: usize) -> Result<(), Divergence>
+where
+    F: Fn() -> Box<dyn Agent>,
+{
+    for seed in Seed::first(game_number) {
+        audit_determinism(&make_agent, seed)?;
+    }
+    Ok(())
+}
+
+// 記録した手を新しく作った盤面 fresh で指し直し、対局で報告されたスコアと同じになるかを確かめる
+// undo のスタックや共有した盤面、並列化で対局中の局面が壊れていれば食い違う
+pub fn audit_score(fresh: MazeState, actions: &[usize], reported: ScoreType) -> Result<(), String> {
+    let replayed = replay_from(fresh, actions)?.game_score;
+    if replayed != reported {
+        return Err(format!(
+            "reported score {} but replaying {} actions scores {}",
+            reported,
+            actions.len(),
+            replayed
+        ));
+    }
+    Ok(())
+}
+
+// audit_score にかけるために、対局で指した手を記録する
+#[derive(Clone, Debug, Default)]
+pub struct ActionRecorder {
+    pub actions: Vec<usize>,
+}
+
+impl Observer for ActionRecorder {
+    fn on_action_chosen(&mut self, _state: &MazeState, action: usize) {
+        self.actions.push(action);
+    }
+}
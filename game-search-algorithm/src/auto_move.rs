@@ -0,0 +1,260 @@
+use crate::budget::{Budget, Consumed};
+use crate::ids::Seed;
+use crate::mutation::{self, Mutation};
+use crate::{ScoreType, DX, DY};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+
+pub const AUTO_MOVE_H: usize = 5;
+pub const AUTO_MOVE_W: usize = 5;
+pub const AUTO_MOVE_END_TURN: usize = 5;
+pub const CHARACTER_N: usize = 3;
+
+// 最初に CHARACTER_N 体のキャラクターを置くだけで、あとは各キャラクターが勝手に動く迷路
+// 動き方は決まっているので、AI が決めるのは初期配置だけ (配置の最適化問題)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AutoMoveMazeState {
+    points: Vec<Vec<ScoreType>>,
+    turn: usize,
+    characters: [(usize, usize); CHARACTER_N],
+    pub game_score: ScoreType,
+}
+
+impl AutoMoveMazeState {
+    // キャラクターはまだ置かれていない (すべて左上にいる) 状態で作る
+    pub fn from_seed(seed: Seed) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        let points = (0..AUTO_MOVE_H)
+            .map(|_| (0..AUTO_MOVE_W).map(|_| rng.gen_range(1..10)).collect())
+            .collect();
+        Self {
+            points,
+            turn: 0,
+            characters: [(0, 0); CHARACTER_N],
+            game_score: 0,
+        }
+    }
+
+    pub fn set_character(&mut self, id: usize, y: usize, x: usize) {
+        self.characters[id] = (y, x);
+    }
+
+    pub fn characters(&self) -> &[(usize, usize); CHARACTER_N] {
+        &self.characters
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.turn == AUTO_MOVE_END_TURN
+    }
+
+    // 上下左右のうち点が最も高いマスへ動く。同点なら DX, DY の順で先の方向
+    fn move_character(&mut self, id: usize) {
+        let (y, x) = self.characters[id];
+        let mut best: Option<(ScoreType, (usize, usize))> = None;
+        for action in 0..4 {
+            let ty = y as i64 + DY[action];
+            let tx = x as i64 + DX[action];
+            if ty < 0 || ty >= AUTO_MOVE_H as i64 || tx < 0 || tx >= AUTO_MOVE_W as i64 {
+                continue;
+            }
+            let (ty, tx) = (ty as usize, tx as usize);
+            let point = self.points[ty][tx];
+            if best.map_or(true, |(best_point, _)| point > best_point) {
+                best = Some((point, (ty, tx)));
+            }
+        }
+        self.characters[id] = best.unwrap().1;
+    }
+
+    // 全員が1マスずつ動き、乗ったマスの点を取る。同じマスに複数いれば1回だけ取る
+    pub fn advance(&mut self) {
+        for id in 0..CHARACTER_N {
+            self.move_character(id);
+        }
+        for &(y, x) in &self.characters {
+            self.game_score += self.points[y][x];
+            self.points[y][x] = 0;
+        }
+        self.turn += 1;
+    }
+
+    // 今の配置で最後まで動かしたときの得点。置いたマスの点は取らずに消える
+    pub fn get_score(&self) -> ScoreType {
+        let mut state = self.clone();
+        for &(y, x) in &self.characters {
+            state.points[y][x] = 0;
+        }
+        while !state.is_done() {
+            state.advance();
+        }
+        state.game_score
+    }
+
+    fn random_cell(rng: &mut ChaCha8Rng) -> (usize, usize) {
+        (rng.gen_range(0..AUTO_MOVE_H), rng.gen_range(0..AUTO_MOVE_W))
+    }
+
+    // 1体を選んでランダムなマスに置き直す (山登り・焼きなましの近傍)
+    pub fn transition(&mut self, rng: &mut ChaCha8Rng) {
+        let id = rng.gen_range(0..CHARACTER_N);
+        let (y, x) = Self::random_cell(rng);
+        self.set_character(id, y, x);
+    }
+}
+
+impl Mutation for AutoMoveMazeState {
+    fn mutate(&mut self, rng: &mut ChaCha8Rng) {
+        self.transition(rng);
+    }
+
+    fn score(&self) -> ScoreType {
+        self.get_score()
+    }
+}
+
+impl Display for AutoMoveMazeState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "turn:{}", self.turn)?;
+        writeln!(f, "score:{}", self.game_score)?;
+        for j in 0..AUTO_MOVE_H {
+            for i in 0..AUTO_MOVE_W {
+                if self.characters.contains(&(j, i)) {
+                    write!(f, "@")?;
+                } else if self.points[j][i] > 0 {
+                    write!(f, "{}", self.points[j][i])?;
+                } else {
+                    write!(f, ".")?;
+                }
+            }
+            writeln!(f)?;
+        }
+        writeln!(f)
+    }
+}
+
+// 全員をランダムなマスに置く
+pub fn random_placement(state: &AutoMoveMazeState, rng: &mut ChaCha8Rng) -> AutoMoveMazeState {
+    let mut state = state.clone();
+    for id in 0..CHARACTER_N {
+        let (y, x) = AutoMoveMazeState::random_cell(rng);
+        state.set_character(id, y, x);
+    }
+    state
+}
+
+// ランダムな配置から始め、1体を置き直して得点が下がらなければ移る
+// budget の iterations は置き直した回数、nodes は get_score を呼んだ回数
+pub fn hill_climb(
+    state: &AutoMoveMazeState,
+    budget: &Budget,
+    rng: &mut ChaCha8Rng,
+) -> (AutoMoveMazeState, Consumed) {
+    simulated_annealing(state, budget, 0.0, 0.0, rng)
+}
+
+// ランダムな配置から始めて mutation::simulated_annealing_with_budget で焼きなます
+pub fn simulated_annealing(
+    state: &AutoMoveMazeState,
+    budget: &Budget,
+    start_temperature: f64,
+    end_temperature: f64,
+    rng: &mut ChaCha8Rng,
+) -> (AutoMoveMazeState, Consumed) {
+    let start = random_placement(state, rng);
+    mutation::simulated_annealing_with_budget(
+        &start,
+        budget,
+        start_temperature,
+        end_temperature,
+        rng,
+    )
+}
+
+// seed 0..game_number の盤面で place が決めた配置の平均得点
+pub fn average_score(
+    game_number: usize,
+    place: &mut dyn FnMut(&AutoMoveMazeState) -> AutoMoveMazeState,
+) -> f64 {
+    let total: ScoreType = Seed::first(game_number)
+        .map(|seed| place(&AutoMoveMazeState::from_seed(seed)).get_score())
+        .sum();
+    total as f64 / game_number as f64
+}
+
+// 同じ盤面・同じランダム配置から山登りと焼きなましを number_of_iterations 回ずつ動かした平均得点
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OptimizerComparison {
+    pub games: usize,
+    pub number_of_iterations: usize,
+    pub random: f64,
+    pub hill_climb: f64,
+    pub simulated_annealing: f64,
+    // 焼きなましが山登りより良かった / 悪かった盤面の数
+    pub annealing_wins: usize,
+    pub annealing_losses: usize,
+}
+
+impl Display for OptimizerComparison {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} games, {} iterations each",
+            self.games, self.number_of_iterations
+        )?;
+        writeln!(f, "random:              {:.2}", self.random)?;
+        writeln!(f, "hill climbing:       {:.2}", self.hill_climb)?;
+        writeln!(f, "simulated annealing: {:.2}", self.simulated_annealing)?;
+        write!(
+            f,
+            "annealing better on {} games, worse on {}",
+            self.annealing_wins, self.annealing_losses
+        )
+    }
+}
+
+// seed ごとに乱数を作り直すので、どちらの手法も同じ初期配置から始まり、盤面ごとに結果を比べられる
+pub fn compare_optimizers(
+    game_number: usize,
+    number_of_iterations: usize,
+    start_temperature: f64,
+    end_temperature: f64,
+) -> OptimizerComparison {
+    let mut comparison = OptimizerComparison {
+        games: game_number,
+        number_of_iterations,
+        random: 0.0,
+        hill_climb: 0.0,
+        simulated_annealing: 0.0,
+        annealing_wins: 0,
+        annealing_losses: 0,
+    };
+    for seed in Seed::first(game_number) {
+        let state = AutoMoveMazeState::from_seed(seed);
+        let start = random_placement(&state, &mut ChaCha8Rng::seed_from_u64(seed.0));
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        let hill = mutation::hill_climb(&start, number_of_iterations, &mut rng).get_score();
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        let annealing = mutation::simulated_annealing(
+            &start,
+            number_of_iterations,
+            start_temperature,
+            end_temperature,
+            &mut rng,
+        )
+        .get_score();
+        comparison.random += start.get_score() as f64;
+        comparison.hill_climb += hill as f64;
+        comparison.simulated_annealing += annealing as f64;
+        match annealing.cmp(&hill) {
+            std::cmp::Ordering::Greater => comparison.annealing_wins += 1,
+            std::cmp::Ordering::Less => comparison.annealing_losses += 1,
+            std::cmp::Ordering::Equal => {}
+        }
+    }
+    let games = game_number.max(1) as f64;
+    comparison.random /= games;
+    comparison.hill_climb /= games;
+    comparison.simulated_annealing /= games;
+    comparison
+}
@@ -0,0 +1,189 @@
+use crate::ids::{PlayerId, Seed};
+use crate::simultaneous::{CollisionRule, GameResult, PlayerRules, SimultaneousMazeState, PASS};
+use crate::{ScoreType, DX, DY, H, W};
+
+// マスの点は 0..10 (SimultaneousMazeState::from_seed)
+pub const POINT_VALUES: usize = 10;
+
+// 視界の外のマスごとに、そのマスの点が各値である確率を持つ
+// 見えたマスはその値に確定し、見えなくなっても最後に見た値を覚えておく
+// 相手のキャラクターが乗ったマスは点を取られたので 0 に確定する
+#[derive(Clone, Debug, PartialEq)]
+pub struct BeliefGrid {
+    pub player: PlayerId,
+    probabilities: Vec<Vec<[f64; POINT_VALUES]>>,
+}
+
+impl BeliefGrid {
+    // まだ一度も見ていないマスは 0..10 の一様分布
+    pub fn new(state: &SimultaneousMazeState, player: PlayerId) -> Self {
+        let mut belief = Self {
+            player,
+            probabilities: vec![vec![[1.0 / POINT_VALUES as f64; POINT_VALUES]; W]; H],
+        };
+        belief.update(state);
+        belief
+    }
+
+    // 毎ターン呼ぶ。呼ばなかったターンに相手が通ったマスは分からないまま残る
+    pub fn update(&mut self, state: &SimultaneousMazeState) {
+        let opponent = state.position(self.player.opponent());
+        for (y, row) in state.observe(self.player).iter().enumerate() {
+            for (x, point) in row.iter().enumerate() {
+                let known = match point {
+                    Some(point) => Some(*point),
+                    None if (y, x) == opponent => Some(0),
+                    None => None,
+                };
+                if let Some(point) = known {
+                    let mut distribution = [0.0; POINT_VALUES];
+                    distribution[point as usize] = 1.0;
+                    self.probabilities[y][x] = distribution;
+                }
+            }
+        }
+    }
+
+    pub fn probabilities(&self, y: usize, x: usize) -> &[f64; POINT_VALUES] {
+        &self.probabilities[y][x]
+    }
+
+    // マスの点の期待値。見えないマスを 0 とみなす代わりに使う
+    pub fn expected_point(&self, y: usize, x: usize) -> f64 {
+        self.probabilities[y][x]
+            .iter()
+            .enumerate()
+            .map(|(value, p)| value as f64 * p)
+            .sum()
+    }
+
+    // マスの点の分からなさ (ビット)。確定したマスは 0
+    pub fn entropy(&self, y: usize, x: usize) -> f64 {
+        self.probabilities[y][x]
+            .iter()
+            .filter(|&&p| p > 0.0)
+            .map(|&p| -p * p.log2())
+            .sum()
+    }
+
+    // action で動いた先から新しく見えるマスのエントロピーの和。動いて分かることの量
+    pub fn information_gain(&self, state: &SimultaneousMazeState, action: usize) -> f64 {
+        let Some(vision) = state.player_rules[self.player.index()].vision else {
+            return 0.0;
+        };
+        let Some((ny, nx)) = destination(state, self.player, action) else {
+            return 0.0;
+        };
+        let (y, x) = state.position(self.player);
+        let within = |(cy, cx): (usize, usize), y: usize, x: usize| {
+            cy.abs_diff(y) + cx.abs_diff(x) <= vision
+        };
+        let mut gain = 0.0;
+        for cy in 0..H {
+            for cx in 0..W {
+                if within((ny, nx), cy, cx) && !within((y, x), cy, cx) {
+                    gain += self.entropy(cy, cx);
+                }
+            }
+        }
+        gain
+    }
+}
+
+fn destination(
+    state: &SimultaneousMazeState,
+    player: PlayerId,
+    action: usize,
+) -> Option<(usize, usize)> {
+    if action == PASS {
+        return None;
+    }
+    let (y, x) = state.position(player);
+    Some((
+        (y as i64 + DY[action]) as usize,
+        (x as i64 + DX[action]) as usize,
+    ))
+}
+
+// 移動先の点の期待値に、information_bonus × 情報量を足した値が最も高い行動を選ぶ
+// information_bonus = 0 なら、見えないマスを期待値で数える貪欲法
+#[derive(Clone, Debug)]
+pub struct BeliefGreedyPlayer {
+    pub belief: BeliefGrid,
+    pub information_bonus: f64,
+}
+
+impl BeliefGreedyPlayer {
+    pub fn new(state: &SimultaneousMazeState, player: PlayerId, information_bonus: f64) -> Self {
+        Self {
+            belief: BeliefGrid::new(state, player),
+            information_bonus,
+        }
+    }
+
+    pub fn action_value(&self, state: &SimultaneousMazeState, action: usize) -> f64 {
+        let Some((y, x)) = destination(state, self.belief.player, action) else {
+            return 0.0;
+        };
+        self.belief.expected_point(y, x)
+            + self.information_bonus * self.belief.information_gain(state, action)
+    }
+
+    pub fn select_action(&mut self, state: &SimultaneousMazeState) -> usize {
+        self.belief.update(state);
+        state
+            .legal_actions(self.belief.player)
+            .into_iter()
+            .max_by(|&a, &b| {
+                self.action_value(state, a)
+                    .partial_cmp(&self.action_value(state, b))
+                    .unwrap()
+            })
+            .unwrap()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BeliefReport {
+    pub games: usize,
+    pub belief_wins: usize,
+    pub greedy_wins: usize,
+    pub draws: usize,
+    pub belief_score: ScoreType,
+    pub greedy_score: ScoreType,
+}
+
+// 各盤面で BeliefGreedyPlayer と貪欲法の先手・後手を入れ替えて2局ずつ指す
+pub fn belief_vs_greedy(
+    rule: CollisionRule,
+    player_rules: [PlayerRules; 2],
+    game_number: usize,
+    information_bonus: f64,
+) -> BeliefReport {
+    let mut report = BeliefReport::default();
+    for seed in Seed::first(game_number) {
+        for belief_player in PlayerId::BOTH {
+            let mut state = SimultaneousMazeState::from_seed(seed, rule, player_rules);
+            let mut player = BeliefGreedyPlayer::new(&state, belief_player, information_bonus);
+            while !state.is_done() {
+                let actions = PlayerId::BOTH.map(|p| {
+                    if p == belief_player {
+                        player.select_action(&state)
+                    } else {
+                        state.greedy_action(p)
+                    }
+                });
+                state.advance(actions);
+            }
+            report.games += 1;
+            report.belief_score += state.game_scores[belief_player.index()];
+            report.greedy_score += state.game_scores[belief_player.opponent().index()];
+            match state.result() {
+                GameResult::Win(p) if p == belief_player => report.belief_wins += 1,
+                GameResult::Win(_) => report.greedy_wins += 1,
+                GameResult::Draw => report.draws += 1,
+            }
+        }
+    }
+    report
+}
@@ -0,0 +1,238 @@
+use crate::packing::PACKED_LEN;
+use crate::{GameState, MazeState, ScoreType};
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_LIST_ID: AtomicUsize = AtomicUsize::new(0);
+
+// 優先度 (得点の上界) が高いものから取り出す探索ノード
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Node {
+    pub bound: ScoreType,
+    pub first_action: u8,
+    pub packed: Vec<u8>,
+}
+
+const RECORD_LEN: usize = 8 + 1 + PACKED_LEN;
+
+impl Node {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.bound.to_le_bytes())?;
+        writer.write_all(&[self.first_action])?;
+        writer.write_all(&self.packed)
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Option<Node>> {
+        let mut record = [0; RECORD_LEN];
+        match reader.read_exact(&mut record) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let mut bound = [0; 8];
+        bound.copy_from_slice(&record[..8]);
+        Ok(Some(Node {
+            bound: ScoreType::from_le_bytes(bound),
+            first_action: record[8],
+            packed: record[9..].to_vec(),
+        }))
+    }
+}
+
+pub trait OpenList {
+    fn push(&mut self, node: Node) -> io::Result<()>;
+    fn pop(&mut self) -> io::Result<Option<Node>>;
+}
+
+#[derive(Default)]
+pub struct MemoryOpenList {
+    heap: BinaryHeap<Node>,
+}
+
+impl OpenList for MemoryOpenList {
+    fn push(&mut self, node: Node) -> io::Result<()> {
+        self.heap.push(node);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> io::Result<Option<Node>> {
+        Ok(self.heap.pop())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IoStats {
+    pub runs_written: usize,
+    pub nodes_written: usize,
+    pub nodes_read: usize,
+    pub bytes_written: usize,
+    pub bytes_read: usize,
+    pub max_in_memory: usize,
+}
+
+// 優先度の降順に並んだディスク上のラン。先頭の1件だけをメモリに置く
+struct Run {
+    path: PathBuf,
+    reader: BufReader<File>,
+    head: Option<Node>,
+}
+
+// メモリ上のヒープが memory_limit を超えたら、整列したランとしてディスクに書き出す外部記憶版のオープンリスト
+// 取り出すときはヒープの先頭と各ランの先頭をマージする
+pub struct ExternalOpenList {
+    memory_limit: usize,
+    directory: PathBuf,
+    heap: BinaryHeap<Node>,
+    runs: Vec<Run>,
+    pub stats: IoStats,
+}
+
+impl ExternalOpenList {
+    pub fn new(memory_limit: usize) -> io::Result<Self> {
+        let directory = std::env::temp_dir().join(format!(
+            "game-search-open-list-{}-{}",
+            std::process::id(),
+            NEXT_LIST_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&directory)?;
+        Ok(Self {
+            memory_limit: memory_limit.max(1),
+            directory,
+            heap: BinaryHeap::new(),
+            runs: Vec::new(),
+            stats: IoStats::default(),
+        })
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        let path = self
+            .directory
+            .join(format!("run-{}.bin", self.stats.runs_written));
+        let nodes = std::mem::take(&mut self.heap).into_sorted_vec();
+        let mut writer = BufWriter::new(File::create(&path)?);
+        for node in nodes.iter().rev() {
+            node.write_to(&mut writer)?;
+        }
+        writer.flush()?;
+        self.stats.runs_written += 1;
+        self.stats.nodes_written += nodes.len();
+        self.stats.bytes_written += nodes.len() * RECORD_LEN;
+
+        let mut reader = BufReader::new(File::open(&path)?);
+        let head = Node::read_from(&mut reader)?;
+        self.count_read();
+        self.runs.push(Run { path, reader, head });
+        Ok(())
+    }
+
+    fn count_read(&mut self) {
+        self.stats.nodes_read += 1;
+        self.stats.bytes_read += RECORD_LEN;
+    }
+}
+
+impl OpenList for ExternalOpenList {
+    fn push(&mut self, node: Node) -> io::Result<()> {
+        self.heap.push(node);
+        self.stats.max_in_memory = self.stats.max_in_memory.max(self.heap.len());
+        if self.heap.len() >= self.memory_limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) -> io::Result<Option<Node>> {
+        let mut best_run: Option<usize> = None;
+        for (i, run) in self.runs.iter().enumerate() {
+            let best = match best_run {
+                Some(j) => self.runs[j].head.as_ref(),
+                None => self.heap.peek(),
+            };
+            if run.head.as_ref() > best {
+                best_run = Some(i);
+            }
+        }
+        let Some(i) = best_run else {
+            return Ok(self.heap.pop());
+        };
+        let run = &mut self.runs[i];
+        let node = run.head.take();
+        run.head = Node::read_from(&mut run.reader)?;
+        if run.head.is_some() {
+            self.count_read();
+        } else {
+            let run = self.runs.swap_remove(i);
+            fs::remove_file(run.path)?;
+        }
+        Ok(node)
+    }
+}
+
+impl Drop for ExternalOpenList {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+// 残りターン数ぶんの上位の点を全て取れたとした得点。実際の最終得点を下回らない
+pub fn upper_bound(state: &MazeState) -> ScoreType {
+    let mut points: Vec<ScoreType> = state.points.iter().flatten().copied().collect();
+    points.sort_unstable_by(|a, b| b.cmp(a));
+    state.game_score
+        + points
+            .iter()
+            .take(state.remaining_turns())
+            .sum::<ScoreType>()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BestFirstResult {
+    pub score: ScoreType,
+    pub first_action: usize,
+    pub expanded: usize,
+}
+
+// 上界が最大のノードから展開する最良優先探索。終局のノードが取り出された時点でそれが最適
+pub fn best_first_solve(
+    state: &MazeState,
+    open_list: &mut dyn OpenList,
+) -> io::Result<BestFirstResult> {
+    let mut expanded = 0;
+    for action in state.legal_actions() {
+        let mut next_state = state.clone();
+        next_state.advance(action);
+        open_list.push(Node {
+            bound: upper_bound(&next_state),
+            first_action: action as u8,
+            packed: next_state.pack(),
+        })?;
+    }
+    while let Some(node) = open_list.pop()? {
+        let now_state = MazeState::unpack(&node.packed)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if now_state.is_done() {
+            return Ok(BestFirstResult {
+                score: now_state.game_score,
+                first_action: node.first_action as usize,
+                expanded,
+            });
+        }
+        expanded += 1;
+        for action in now_state.legal_actions() {
+            let mut next_state = now_state.clone();
+            next_state.advance(action);
+            open_list.push(Node {
+                bound: upper_bound(&next_state),
+                first_action: node.first_action,
+                packed: next_state.pack(),
+            })?;
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "no terminal state reachable",
+    ))
+}
@@ -0,0 +1,69 @@
+use crate::budget::Budget;
+use crate::episode::replay;
+use crate::ids::{Seed, Turn};
+use crate::{GameState, MazeState, ScoreType, END_TURN};
+use std::fmt::{self, Display, Formatter};
+
+// 指した手の評価値が最善手より margin 以上低かった局面
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Blunder {
+    pub seed: Seed,
+    pub turn: Turn,
+    pub played: usize,
+    pub played_value: ScoreType,
+    pub best: usize,
+    pub best_value: ScoreType,
+    pub state: MazeState,
+}
+
+impl Display for Blunder {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "seed {} turn {}: played {} ({}), best {} ({}), loss {}",
+            self.seed,
+            self.turn,
+            self.played,
+            self.played_value,
+            self.best,
+            self.best_value,
+            self.best_value - self.played_value
+        )
+    }
+}
+
+// 記録の各局面を幅 beam_width の読み切りで探索し直し、根の手ごとの評価値を比べる
+pub fn find_blunders(
+    seed: Seed,
+    actions: &[usize],
+    beam_width: usize,
+    margin: ScoreType,
+) -> Result<Vec<Blunder>, String> {
+    replay(seed, actions)?;
+    let budget = Budget::iterations(END_TURN);
+    let mut blunders = Vec::new();
+    let mut state = MazeState::from_seed(seed);
+    for &played in actions {
+        let value = |action| state.beam_search_value_with_first_action(action, beam_width, budget);
+        let played_value = value(played);
+        let (best, best_value) = state
+            .legal_actions()
+            .into_iter()
+            .map(|action| (action, value(action)))
+            .max_by_key(|&(action, value)| (value, std::cmp::Reverse(action)))
+            .unwrap();
+        if best_value - played_value >= margin {
+            blunders.push(Blunder {
+                seed,
+                turn: state.turn(),
+                played,
+                played_value,
+                best,
+                best_value,
+                state: state.clone(),
+            });
+        }
+        state.advance(played);
+    }
+    Ok(blunders)
+}
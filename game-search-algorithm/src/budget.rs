@@ -0,0 +1,239 @@
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
+
+// 探索と最適化で共通の打ち切り条件。指定したもののうち最初に使い切ったもので止める
+// nodes は探索なら生成した局面の数、最適化なら評価した解の数
+// iterations はビームサーチの深さ、プレイアウトの回数、局所探索の反復回数など、手法ごとの外側のループの回数
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub time: Option<Duration>,
+    pub nodes: Option<usize>,
+    pub iterations: Option<usize>,
+}
+
+impl Budget {
+    pub fn iterations(iterations: usize) -> Self {
+        Self {
+            iterations: Some(iterations),
+            ..Self::default()
+        }
+    }
+
+    pub fn nodes(nodes: usize) -> Self {
+        Self {
+            nodes: Some(nodes),
+            ..Self::default()
+        }
+    }
+
+    pub fn time_ms(time_ms: u64) -> Self {
+        Self {
+            time: Some(Duration::from_millis(time_ms)),
+            ..Self::default()
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.time.is_none() && self.nodes.is_none() && self.iterations.is_none()
+    }
+
+    // 時間で止まりうるなら結果はマシンの速さで変わる
+    pub fn is_deterministic(&self) -> bool {
+        self.time.is_none()
+    }
+
+    // set_time_limit で受け取った1手の時間を重ねる。time と両方あれば短い方を使う
+    pub fn with_time_limit(&self, limit: Option<Duration>) -> Self {
+        Self {
+            time: match (self.time, limit) {
+                (Some(time), Some(limit)) => Some(time.min(limit)),
+                (time, limit) => time.or(limit),
+            },
+            ..*self
+        }
+    }
+
+    // エージェントの名前に入れる形。iterations は手法ごとの呼び名 (depth, number, playouts など) で書く
+    // 何も指定していなければ空
+    pub fn describe(&self, iterations: &str) -> String {
+        let mut limits = Vec::new();
+        if let Some(count) = self.iterations {
+            limits.push(format!("{}={}", iterations, count));
+        }
+        if let Some(nodes) = self.nodes {
+            limits.push(format!("nodes={}", nodes));
+        }
+        if let Some(time) = self.time {
+            limits.push(format!("time_ms={}", time.as_millis()));
+        }
+        limits.join(", ")
+    }
+
+    // "time_ms=5 nodes=1000 iterations=10" のような文字列から作る。どれも省略できるが、1つは必要
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut budget = Budget::default();
+        for word in spec.split_whitespace() {
+            let (key, value) = word
+                .split_once('=')
+                .ok_or(format!("expected key=value, got `{}`", word))?;
+            let value: u64 = value
+                .parse()
+                .map_err(|_| format!("invalid number `{}` for `{}`", value, key))?;
+            match key {
+                "time_ms" => budget.time = Some(Duration::from_millis(value)),
+                "nodes" => budget.nodes = Some(value as usize),
+                "iterations" => budget.iterations = Some(value as usize),
+                _ => {
+                    return Err(format!(
+                        "unknown budget `{}` (time_ms, nodes, iterations)",
+                        key
+                    ))
+                }
+            }
+        }
+        if budget.is_unlimited() {
+            return Err("a budget needs at least one of time_ms, nodes or iterations".to_string());
+        }
+        Ok(budget)
+    }
+}
+
+impl Display for Budget {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut limits = Vec::new();
+        if let Some(time) = self.time {
+            limits.push(format!("time_ms={}", time.as_millis()));
+        }
+        if let Some(nodes) = self.nodes {
+            limits.push(format!("nodes={}", nodes));
+        }
+        if let Some(iterations) = self.iterations {
+            limits.push(format!("iterations={}", iterations));
+        }
+        if limits.is_empty() {
+            return write!(f, "unlimited");
+        }
+        write!(f, "{}", limits.join(" "))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetLimit {
+    Time,
+    Nodes,
+    Iterations,
+    // async_agent の CancelToken で止められた
+    Cancelled,
+}
+
+impl Display for BudgetLimit {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let name = match self {
+            BudgetLimit::Time => "time",
+            BudgetLimit::Nodes => "nodes",
+            BudgetLimit::Iterations => "iterations",
+            BudgetLimit::Cancelled => "cancel",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// 実際に使った分。exhausted は使い切って止めたときの条件で、ほかの理由 (終局など) で止めたなら None
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Consumed {
+    pub time: Duration,
+    pub nodes: usize,
+    pub iterations: usize,
+    pub exhausted: Option<BudgetLimit>,
+}
+
+impl Display for Consumed {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "time {:?}, {} nodes, {} iterations",
+            self.time, self.nodes, self.iterations
+        )?;
+        if let Some(limit) = self.exhausted {
+            write!(f, ", stopped by {}", limit)?;
+        }
+        Ok(())
+    }
+}
+
+// 1回の探索の間、使った分を数えて Budget を使い切ったかを判定する
+pub struct BudgetMeter {
+    budget: Budget,
+    start: Instant,
+    pub nodes: usize,
+    pub iterations: usize,
+}
+
+impl BudgetMeter {
+    pub fn new(budget: Budget) -> Self {
+        Self {
+            budget,
+            start: Instant::now(),
+            nodes: 0,
+            iterations: 0,
+        }
+    }
+
+    pub fn add_nodes(&mut self, nodes: usize) {
+        self.nodes += nodes;
+    }
+
+    pub fn add_iteration(&mut self) {
+        self.iterations += 1;
+        crate::hooks::notify_search_iteration(self.iterations, self.nodes);
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    // 使い切った条件。複数なら反復回数、ノード数、時間の順に報告する (時間以外は再現できるので先に見る)
+    // どれも残っていても、プールのスレッドで CancelToken が止められていれば Cancelled
+    pub fn exhausted(&self) -> Option<BudgetLimit> {
+        let budget = &self.budget;
+        if budget
+            .iterations
+            .map_or(false, |max| self.iterations >= max)
+        {
+            Some(BudgetLimit::Iterations)
+        } else if budget.nodes.map_or(false, |max| self.nodes >= max) {
+            Some(BudgetLimit::Nodes)
+        } else if budget.time.map_or(false, |time| self.elapsed() >= time) {
+            Some(BudgetLimit::Time)
+        } else if crate::async_agent::is_cancelled() {
+            Some(BudgetLimit::Cancelled)
+        } else {
+            None
+        }
+    }
+
+    // 使った割合。複数の条件があれば最も進んだもの。焼きなましの温度を下げるのに使う
+    pub fn progress(&self) -> f64 {
+        let budget = &self.budget;
+        let mut progress: f64 = 0.0;
+        if let Some(max) = budget.iterations {
+            progress = progress.max(self.iterations as f64 / max as f64);
+        }
+        if let Some(max) = budget.nodes {
+            progress = progress.max(self.nodes as f64 / max as f64);
+        }
+        if let Some(time) = budget.time {
+            progress = progress.max(self.elapsed().as_secs_f64() / time.as_secs_f64());
+        }
+        progress.min(1.0)
+    }
+
+    pub fn consumed(&self) -> Consumed {
+        Consumed {
+            time: self.elapsed(),
+            nodes: self.nodes,
+            iterations: self.iterations,
+            exhausted: self.exhausted(),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+// C/C++ で書かれたゲームエンジンからエージェントを使うための C ABI (capi feature)
+// 関数の前のコメントは build.rs がそのまま OUT_DIR の game_search_algorithm.h に写す (HEADER で取り出せる)
+// ポインタの約束 (create で得たものを free まで使う、buffer は len バイト読める) はヘッダのコメントに書く
+#![allow(clippy::missing_safety_doc)]
+
+use crate::agent::{parse_agent, Agent};
+use crate::ids::Seed;
+use crate::packing::PACKED_LEN;
+use crate::{GameState, MazeState};
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+// build.rs が生成した C のヘッダ。C/C++ 側へはこれをファイルに書き出して渡す
+pub const HEADER: &str = include_str!(concat!(env!("OUT_DIR"), "/game_search_algorithm.h"));
+
+// C からは中身の見えない構造体として扱う
+pub struct GsaAgent {
+    agent: Box<dyn Agent>,
+    state: Option<MazeState>,
+    last_error: CString,
+}
+
+impl GsaAgent {
+    fn fail(&mut self, message: String) -> i32 {
+        self.last_error = CString::new(message).unwrap_or_default();
+        -1
+    }
+}
+
+// gsa_agent_set_position に渡す局面のバイト数
+#[no_mangle]
+pub extern "C" fn gsa_packed_len() -> usize {
+    PACKED_LEN
+}
+
+// seed の初期局面を詰めて buffer に書く。len が gsa_packed_len() と違えば -1
+#[no_mangle]
+pub unsafe extern "C" fn gsa_position_from_seed(seed: u64, buffer: *mut u8, len: usize) -> i32 {
+    if buffer.is_null() || len != PACKED_LEN {
+        return -1;
+    }
+    let bytes = MazeState::from_seed(Seed(seed)).pack();
+    std::slice::from_raw_parts_mut(buffer, len).copy_from_slice(&bytes);
+    0
+}
+
+// spec は "beam width=2 depth=4" のようなエージェントの指定。読めなければ NULL
+// 返したポインタは gsa_agent_free で解放する
+#[no_mangle]
+pub unsafe extern "C" fn gsa_agent_create(spec: *const c_char) -> *mut GsaAgent {
+    if spec.is_null() {
+        return std::ptr::null_mut();
+    }
+    let spec = match CStr::from_ptr(spec).to_str() {
+        Ok(spec) => spec,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    match parse_agent(spec) {
+        Ok(agent) => Box::into_raw(Box::new(GsaAgent {
+            agent,
+            state: None,
+            last_error: CString::default(),
+        })),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+// buffer の len バイトを詰めた局面として読む。成功なら 0、失敗なら -1 (理由は gsa_agent_last_error)
+#[no_mangle]
+pub unsafe extern "C" fn gsa_agent_set_position(
+    agent: *mut GsaAgent,
+    buffer: *const u8,
+    len: usize,
+) -> i32 {
+    let agent = match agent.as_mut() {
+        Some(agent) => agent,
+        None => return -1,
+    };
+    if buffer.is_null() {
+        return agent.fail("buffer is null".to_string());
+    }
+    match MazeState::unpack(std::slice::from_raw_parts(buffer, len)) {
+        Ok(state) => {
+            agent.state = Some(state);
+            0
+        }
+        Err(e) => agent.fail(e),
+    }
+}
+
+// time_ms が 0 でなければその時間で打ち切るように伝えてから手を選び、action に書く
+// 0 なら前の呼び出しで伝えた時間の制限を外す
+// 局面が未設定か終局しているとき、探索が panic したときは -1
+// 時間で打ち切れないエージェントに 0 でない time_ms を渡したときは手を選ばずに -2
+#[no_mangle]
+pub unsafe extern "C" fn gsa_agent_request_move(
+    agent: *mut GsaAgent,
+    time_ms: u64,
+    action: *mut i32,
+) -> i32 {
+    let agent = match agent.as_mut() {
+        Some(agent) => agent,
+        None => return -1,
+    };
+    if action.is_null() {
+        return agent.fail("action is null".to_string());
+    }
+    let state = match &agent.state {
+        Some(state) if !state.is_done() => state.clone(),
+        Some(_) => return agent.fail("the game is over".to_string()),
+        None => return agent.fail("no position has been set".to_string()),
+    };
+    let time_limit = if time_ms > 0 {
+        Some(Duration::from_millis(time_ms))
+    } else {
+        None
+    };
+    if time_limit.is_some() && !agent.agent.capabilities().time_limit {
+        agent.fail(format!(
+            "{} cannot stop at a time limit",
+            agent.agent.name()
+        ));
+        return -2;
+    }
+    agent.agent.set_time_limit(time_limit);
+    let inner = &mut agent.agent;
+    match panic::catch_unwind(AssertUnwindSafe(|| inner.select_action(&state))) {
+        Ok(chosen) => {
+            *action = chosen as i32;
+            0
+        }
+        Err(_) => agent.fail("the search panicked".to_string()),
+    }
+}
+
+// 直前に失敗した理由。agent が生きている間だけ有効
+#[no_mangle]
+pub unsafe extern "C" fn gsa_agent_last_error(agent: *const GsaAgent) -> *const c_char {
+    match agent.as_ref() {
+        Some(agent) => agent.last_error.as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+// gsa_agent_create で作ったエージェントを解放する。NULL なら何もしない
+#[no_mangle]
+pub unsafe extern "C" fn gsa_agent_free(agent: *mut GsaAgent) {
+    if !agent.is_null() {
+        drop(Box::from_raw(agent));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_move_with_time_limit() {
+        let mut position = vec![0; gsa_packed_len()];
+        unsafe {
+            assert_eq!(
+                gsa_position_from_seed(0, position.as_mut_ptr(), position.len()),
+                0
+            );
+            let spec = CString::new("beam width=2 time_ms=50").unwrap();
+            let agent = gsa_agent_create(spec.as_ptr());
+            assert!(!agent.is_null());
+            assert_eq!(
+                gsa_agent_set_position(agent, position.as_ptr(), position.len()),
+                0
+            );
+            let mut action = -1;
+            assert_eq!(gsa_agent_request_move(agent, 10, &mut action), 0);
+            let state = MazeState::from_seed(Seed(0));
+            assert!(state.legal_actions().contains(&(action as usize)));
+            gsa_agent_free(agent);
+
+            // 時間で打ち切れないエージェントには手を選ばせない
+            let spec = CString::new("greedy").unwrap();
+            let agent = gsa_agent_create(spec.as_ptr());
+            gsa_agent_set_position(agent, position.as_ptr(), position.len());
+            assert_eq!(gsa_agent_request_move(agent, 10, &mut action), -2);
+            gsa_agent_free(agent);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use crate::ids::Seed;
+use crate::solver::position_hash;
+use crate::{GameState, MazeState, END_TURN};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
+// 新しいゲームを足したときに check_conformance で基本的な性質を確かめるための窓口
+// 探索に要る操作は GameState にあるので、ここでは検査に使う分だけを足す
+pub trait GameSpec: GameState {
+    fn game_name() -> &'static str;
+    fn from_seed(seed: Seed) -> Self;
+    // これより長いゲームはない
+    fn max_turns() -> usize;
+    fn position_hash(&self) -> u64;
+    fn encode(&self) -> Vec<u8>;
+    fn decode(bytes: &[u8]) -> Result<Self, String>;
+}
+
+impl GameSpec for MazeState {
+    fn game_name() -> &'static str {
+        "maze"
+    }
+
+    fn from_seed(seed: Seed) -> Self {
+        MazeState::from_seed(seed)
+    }
+
+    fn max_turns() -> usize {
+        END_TURN
+    }
+
+    fn position_hash(&self) -> u64 {
+        position_hash(self)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        self.pack()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, String> {
+        MazeState::unpack(bytes)
+    }
+}
+
+// encode したバイト列の FNV-1a。position_hash を持たないゲームの GameSpec::position_hash に使う
+pub fn encoded_hash(bytes: &[u8]) -> u64 {
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    bytes.iter().fold(OFFSET, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConformanceFailure {
+    pub game: &'static str,
+    pub seed: Seed,
+    pub turn: usize,
+    pub check: &'static str,
+    pub detail: String,
+}
+
+impl Display for ConformanceFailure {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} seed {} turn {}: {}: {}",
+            self.game, self.seed, self.turn, self.check, self.detail
+        )
+    }
+}
+
+// 各シードでランダムに1局進めながら、通った局面すべてで性質を確かめる
+// advance を取り消す操作はないので、代わりに複製への advance が元の局面を変えないことを確かめる
+pub fn check_conformance<G: GameSpec>(seeds: Range<u64>) -> Vec<ConformanceFailure> {
+    let mut failures = Vec::new();
+    for seed in seeds.map(Seed) {
+        let mut fail = |turn: usize, check: &'static str, detail: String| {
+            failures.push(ConformanceFailure {
+                game: G::game_name(),
+                seed,
+                turn,
+                check,
+                detail,
+            })
+        };
+        if G::from_seed(seed).encode() != G::from_seed(seed).encode() {
+            fail(
+                0,
+                "deterministic setup",
+                "from_seed gave two positions".to_string(),
+            );
+        }
+
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        let mut state = G::from_seed(seed);
+        let mut turn = 0;
+        loop {
+            let encoded = state.encode();
+            match G::decode(&encoded) {
+                Ok(decoded) => {
+                    if decoded.encode() != encoded {
+                        fail(turn, "round trip", "decode(encode(s)) differs".to_string());
+                    }
+                    if decoded.position_hash() != state.position_hash() {
+                        fail(turn, "hash stability", "decoded hash differs".to_string());
+                    }
+                }
+                Err(e) => fail(turn, "round trip", e),
+            }
+            if state.clone().position_hash() != state.position_hash() {
+                fail(turn, "hash stability", "clone hash differs".to_string());
+            }
+
+            let legal_actions = state.legal_actions();
+            let mut sorted = legal_actions.clone();
+            sorted.sort_unstable();
+            sorted.dedup();
+            if sorted.len() != legal_actions.len() {
+                fail(
+                    turn,
+                    "legal actions",
+                    format!("duplicates in {:?}", legal_actions),
+                );
+            }
+            // 終局後の手は使われないので、列挙されていても誤りにはしない
+            if state.is_done() {
+                break;
+            }
+            if legal_actions.is_empty() {
+                fail(
+                    turn,
+                    "terminal detection",
+                    "no legal actions in a non-terminal position".to_string(),
+                );
+                break;
+            }
+            if turn >= G::max_turns() {
+                fail(
+                    turn,
+                    "terminal detection",
+                    format!("game not over after {} turns", G::max_turns()),
+                );
+                break;
+            }
+
+            for &action in &legal_actions {
+                let mut child = state.clone();
+                child.advance(action);
+                if state.encode() != encoded {
+                    fail(
+                        turn,
+                        "advance isolation",
+                        format!("advancing a clone with {} changed the original", action),
+                    );
+                }
+                if child.encode() == encoded {
+                    fail(
+                        turn,
+                        "advance",
+                        format!("action {} did not change the position", action),
+                    );
+                }
+            }
+
+            state.advance(legal_actions[rng.gen_range(0..legal_actions.len())]);
+            turn += 1;
+        }
+    }
+    failures
+}
+
+// テストから呼ぶ窓口。新しいゲームは GameSpec を実装し、自分のテストで
+// assert_conformance::<NewGame>(0..100) を呼べば同じ検査を受けられる
+pub fn assert_conformance<G: GameSpec>(seeds: Range<u64>) {
+    let failures = check_conformance::<G>(seeds);
+    assert!(
+        failures.is_empty(),
+        "{} failed {} conformance checks:\n{}",
+        G::game_name(),
+        failures.len(),
+        failures
+            .iter()
+            .map(|failure| failure.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maze_state_conforms() {
+        assert_conformance::<MazeState>(0..100);
+    }
+}
@@ -0,0 +1,399 @@
+use crate::alternate::TwoPlayerGame;
+use crate::ids::{PlayerId, Seed};
+use crate::simultaneous::GameResult;
+use crate::ScoreType;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
+
+pub const CONNECT_FOUR_H: usize = 6;
+pub const CONNECT_FOUR_W: usize = 7;
+// from_seed で最初にランダムに指す手数。同じ局面からばかり始まらないようにする
+pub const OPENING_MOVES: usize = 2;
+// 4つ並べたときの評価値。途中の局面の評価値はこれよりずっと小さい
+pub const CONNECT_FOUR_WIN_SCORE: ScoreType = 1_000_000;
+// 評価値を勝率に直すときの尺度
+const WIN_RATE_SCALE: f64 = 10.0;
+
+// 右、上、右上、右下
+const DIRECTIONS: [(i64, i64); 4] = [(0, 1), (1, 0), (1, 1), (-1, 1)];
+
+// 四目並べ。行動は石を落とす列 (0 から CONNECT_FOUR_W - 1)。board[y][x] の y = 0 が一番下
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectFourState {
+    board: [[Option<PlayerId>; CONNECT_FOUR_W]; CONNECT_FOUR_H],
+    heights: [usize; CONNECT_FOUR_W],
+    turn: usize,
+    winner: Option<PlayerId>,
+}
+
+impl Default for ConnectFourState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConnectFourState {
+    pub fn new() -> Self {
+        Self {
+            board: [[None; CONNECT_FOUR_W]; CONNECT_FOUR_H],
+            heights: [0; CONNECT_FOUR_W],
+            turn: 0,
+            winner: None,
+        }
+    }
+
+    // 空の盤面から OPENING_MOVES 手をシードで決まる乱数で指した局面
+    pub fn from_seed(seed: Seed) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        let mut state = Self::new();
+        for _ in 0..OPENING_MOVES {
+            state.advance(state.random_action(&mut rng));
+        }
+        state
+    }
+
+    fn at(&self, y: i64, x: i64) -> Option<Option<PlayerId>> {
+        if y < 0 || y >= CONNECT_FOUR_H as i64 || x < 0 || x >= CONNECT_FOUR_W as i64 {
+            return None;
+        }
+        Some(self.board[y as usize][x as usize])
+    }
+
+    // (y, x) に置いた石を含めて、dir の向きに同じ色が何個続くか
+    fn line_length(&self, y: usize, x: usize, (dy, dx): (i64, i64)) -> usize {
+        let player = self.board[y][x];
+        let mut length = 1;
+        for sign in [1, -1] {
+            let (mut ny, mut nx) = (y as i64 + sign * dy, x as i64 + sign * dx);
+            while self.at(ny, nx) == Some(player) {
+                length += 1;
+                ny += sign * dy;
+                nx += sign * dx;
+            }
+        }
+        length
+    }
+
+    // 盤面上の4マスの並びすべてについて、片方の石しかない並びに石の数に応じた点をつける
+    fn window_score(&self, player: PlayerId) -> ScoreType {
+        const WEIGHTS: [ScoreType; 4] = [0, 1, 4, 16];
+        let mut score = 0;
+        for y in 0..CONNECT_FOUR_H as i64 {
+            for x in 0..CONNECT_FOUR_W as i64 {
+                for (dy, dx) in DIRECTIONS {
+                    let cells: Option<Vec<Option<PlayerId>>> =
+                        (0..4).map(|k| self.at(y + k * dy, x + k * dx)).collect();
+                    let cells = match cells {
+                        Some(cells) => cells,
+                        None => continue,
+                    };
+                    let own = cells.iter().filter(|&&c| c == Some(player)).count();
+                    let other = cells
+                        .iter()
+                        .filter(|&&c| c == Some(player.opponent()))
+                        .count();
+                    if other == 0 && own < 4 {
+                        score += WEIGHTS[own];
+                    } else if own == 0 && other < 4 {
+                        score -= WEIGHTS[other];
+                    }
+                }
+            }
+        }
+        score
+    }
+}
+
+impl TwoPlayerGame for ConnectFourState {
+    fn legal_actions(&self) -> Vec<usize> {
+        (0..CONNECT_FOUR_W)
+            .filter(|&x| self.heights[x] < CONNECT_FOUR_H)
+            .collect()
+    }
+
+    fn advance(&mut self, action: usize) {
+        let player = self.current_player();
+        let y = self.heights[action];
+        self.board[y][action] = Some(player);
+        self.heights[action] += 1;
+        self.turn += 1;
+        if DIRECTIONS
+            .iter()
+            .any(|&dir| self.line_length(y, action, dir) >= 4)
+        {
+            self.winner = Some(player);
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.winner.is_some() || self.turn == CONNECT_FOUR_H * CONNECT_FOUR_W
+    }
+
+    fn current_player(&self) -> PlayerId {
+        PlayerId((self.turn % 2) as u8)
+    }
+
+    // 勝ち負けが決まっていれば ±CONNECT_FOUR_WIN_SCORE (早く勝つほど大きい)、そうでなければ並びの点の差
+    fn evaluate_score(&self) -> ScoreType {
+        let player = self.current_player();
+        match self.winner {
+            Some(winner) => {
+                let score = CONNECT_FOUR_WIN_SCORE + self.remaining_turns() as ScoreType;
+                if winner == player {
+                    score
+                } else {
+                    -score
+                }
+            }
+            None => self.window_score(player),
+        }
+    }
+
+    // 盤面が埋まるまで誰も並べなければ引き分け
+    fn result(&self) -> GameResult {
+        match self.winner {
+            Some(winner) => GameResult::Win(winner),
+            None => GameResult::Draw,
+        }
+    }
+
+    fn remaining_turns(&self) -> usize {
+        CONNECT_FOUR_H * CONNECT_FOUR_W - self.turn
+    }
+
+    // 評価値をロジスティック関数で 0 から 1 に直す
+    fn win_rate_estimate(&self) -> f64 {
+        1.0 / (1.0 + (-(self.evaluate_score() as f64) / WIN_RATE_SCALE).exp())
+    }
+}
+
+// 先手を X、後手を O で、上の行から書く
+impl Display for ConnectFourState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "turn:{}", self.turn)?;
+        for row in self.board.iter().rev() {
+            for cell in row {
+                let c = match cell {
+                    Some(PlayerId::FIRST) => 'X',
+                    Some(_) => 'O',
+                    None => '.',
+                };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f)?;
+        }
+        for x in 0..CONNECT_FOUR_W {
+            write!(f, "{}", x)?;
+        }
+        writeln!(f)
+    }
+}
+
+// ビットボードでの盤面の幅。列ごとに番兵の1ビットを足して、列をまたいで並びを数えないようにする
+const BIT_COLUMN: usize = CONNECT_FOUR_H + 1;
+
+// (y, x) のマスのビット
+fn bit(y: usize, x: usize) -> u64 {
+    1 << (x * BIT_COLUMN + y)
+}
+
+// 4つ並んだ石があるか。縦・横・右上・右下の向きにずらして重ねる
+fn has_four(board: u64) -> bool {
+    [1, BIT_COLUMN, BIT_COLUMN - 1, BIT_COLUMN + 1]
+        .iter()
+        .any(|&shift| {
+            let pairs = board & (board >> shift);
+            pairs & (pairs >> (2 * shift)) != 0
+        })
+}
+
+// ConnectFourState と同じ規則と評価値で、盤面をプレイヤーごとの u64 で持つ
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectFourBitState {
+    boards: [u64; 2],
+    heights: [usize; CONNECT_FOUR_W],
+    turn: usize,
+    winner: Option<PlayerId>,
+}
+
+impl Default for ConnectFourBitState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConnectFourBitState {
+    pub fn new() -> Self {
+        Self {
+            boards: [0; 2],
+            heights: [0; CONNECT_FOUR_W],
+            turn: 0,
+            winner: None,
+        }
+    }
+
+    // ConnectFourState::from_seed と同じ局面
+    pub fn from_seed(seed: Seed) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        let mut state = Self::new();
+        for _ in 0..OPENING_MOVES {
+            state.advance(state.random_action(&mut rng));
+        }
+        state
+    }
+
+    // ConnectFourState::window_score と同じ点を、4マスの並びのビットと石のビットの重なりで数える
+    fn window_score(&self, player: PlayerId) -> ScoreType {
+        const WEIGHTS: [ScoreType; 4] = [0, 1, 4, 16];
+        let own_board = self.boards[player.index()];
+        let other_board = self.boards[player.opponent().index()];
+        let mut score = 0;
+        for y in 0..CONNECT_FOUR_H as i64 {
+            for x in 0..CONNECT_FOUR_W as i64 {
+                for (dy, dx) in DIRECTIONS {
+                    let (end_y, end_x) = (y + 3 * dy, x + 3 * dx);
+                    if end_y < 0 || end_y >= CONNECT_FOUR_H as i64 || end_x >= CONNECT_FOUR_W as i64
+                    {
+                        continue;
+                    }
+                    let window = (0..4).fold(0, |window, k| {
+                        window | bit((y + k * dy) as usize, (x + k * dx) as usize)
+                    });
+                    let own = (own_board & window).count_ones() as usize;
+                    let other = (other_board & window).count_ones() as usize;
+                    if other == 0 && own < 4 {
+                        score += WEIGHTS[own];
+                    } else if own == 0 && other < 4 {
+                        score -= WEIGHTS[other];
+                    }
+                }
+            }
+        }
+        score
+    }
+}
+
+impl TwoPlayerGame for ConnectFourBitState {
+    fn legal_actions(&self) -> Vec<usize> {
+        (0..CONNECT_FOUR_W)
+            .filter(|&x| self.heights[x] < CONNECT_FOUR_H)
+            .collect()
+    }
+
+    fn advance(&mut self, action: usize) {
+        let player = self.current_player();
+        let board = &mut self.boards[player.index()];
+        *board |= bit(self.heights[action], action);
+        self.heights[action] += 1;
+        self.turn += 1;
+        if has_four(*board) {
+            self.winner = Some(player);
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.winner.is_some() || self.turn == CONNECT_FOUR_H * CONNECT_FOUR_W
+    }
+
+    fn current_player(&self) -> PlayerId {
+        PlayerId((self.turn % 2) as u8)
+    }
+
+    fn evaluate_score(&self) -> ScoreType {
+        let player = self.current_player();
+        match self.winner {
+            Some(winner) => {
+                let score = CONNECT_FOUR_WIN_SCORE + self.remaining_turns() as ScoreType;
+                if winner == player {
+                    score
+                } else {
+                    -score
+                }
+            }
+            None => self.window_score(player),
+        }
+    }
+
+    fn result(&self) -> GameResult {
+        match self.winner {
+            Some(winner) => GameResult::Win(winner),
+            None => GameResult::Draw,
+        }
+    }
+
+    fn remaining_turns(&self) -> usize {
+        CONNECT_FOUR_H * CONNECT_FOUR_W - self.turn
+    }
+
+    fn win_rate_estimate(&self) -> f64 {
+        1.0 / (1.0 + (-(self.evaluate_score() as f64) / WIN_RATE_SCALE).exp())
+    }
+}
+
+// 2つの実装で同じ乱数の手を進め、各局面で合法手・評価値・勝敗が一致するかを確かめる
+// 食い違えば最初の局面を返す。一致すれば確かめた局面の数
+pub fn check_bitboard(game_number: usize) -> Result<usize, String> {
+    let mut positions = 0;
+    for seed in Seed::first(game_number) {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        let mut state = ConnectFourState::from_seed(seed);
+        let mut bit_state = ConnectFourBitState::from_seed(seed);
+        loop {
+            positions += 1;
+            if state.legal_actions() != bit_state.legal_actions()
+                || state.evaluate_score() != bit_state.evaluate_score()
+                || state.is_done() != bit_state.is_done()
+                || state.result() != bit_state.result()
+            {
+                return Err(format!(
+                    "seed {}: the bitboard disagrees with\n{}",
+                    seed, state
+                ));
+            }
+            if state.is_done() {
+                break;
+            }
+            let action = state.random_action(&mut rng);
+            state.advance(action);
+            bit_state.advance(action);
+        }
+    }
+    Ok(positions)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlayoutThroughput {
+    pub playouts: usize,
+    pub turns: usize,
+    pub elapsed: Duration,
+}
+
+impl PlayoutThroughput {
+    pub fn playouts_per_second(&self) -> f64 {
+        self.playouts as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+// new_game(seed) から終局までランダムに指すのを playout_number 回繰り返す時間を測る
+pub fn measure_playouts<G: TwoPlayerGame>(
+    new_game: &dyn Fn(Seed) -> G,
+    playout_number: usize,
+) -> PlayoutThroughput {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    let mut turns = 0;
+    let start = Instant::now();
+    for seed in Seed::first(playout_number) {
+        let mut state = new_game(seed);
+        while !state.is_done() {
+            state.advance(state.random_action(&mut rng));
+            turns += 1;
+        }
+    }
+    PlayoutThroughput {
+        playouts: playout_number,
+        turns,
+        elapsed: start.elapsed(),
+    }
+}
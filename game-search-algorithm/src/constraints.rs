@@ -0,0 +1,155 @@
+use crate::local_search::{plan_score, Plan};
+use crate::{GameState, MazeState, ScoreType};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+
+// 行動列が満たすべき条件。違反の大きさを数え、満たすように行動列を直す
+pub trait Constraint: Send + Sync {
+    fn name(&self) -> String;
+
+    // 0 なら満たしている。行動列の先頭部分にも使うので、手を足して減ることはないように数える
+    fn violation(&self, state: &MazeState, plan: &[usize]) -> ScoreType;
+
+    // 先頭から順に、違反を増やす遺伝子を増やさない遺伝子に書き換える。どれでも増えるなら最も小さいものにする
+    fn repair(&self, state: &MazeState, plan: &mut Plan, rng: &mut ChaCha8Rng) {
+        for i in 0..plan.len() {
+            let before = self.violation(state, &plan[..i]);
+            if self.violation(state, &plan[..=i]) <= before {
+                continue;
+            }
+            let mut genes: Vec<usize> = (0..4).collect();
+            genes.shuffle(rng);
+            let mut best = (ScoreType::MAX, plan[i]);
+            for gene in genes {
+                plan[i] = gene;
+                let violation = self.violation(state, &plan[..=i]);
+                if violation < best.0 {
+                    best = (violation, gene);
+                }
+                if violation <= before {
+                    break;
+                }
+            }
+            plan[i] = best.1;
+        }
+    }
+}
+
+// plan が実際に指す手と、その後のキャラクターの位置 (y, x)。ゲームが終われば残りの遺伝子は読まない
+fn trajectory(state: &MazeState, plan: &[usize]) -> Vec<(usize, (usize, usize))> {
+    let mut state = state.clone();
+    let mut steps = Vec::new();
+    for &gene in plan {
+        if state.is_done() {
+            break;
+        }
+        let legal_actions = state.legal_actions();
+        let action = legal_actions[gene % legal_actions.len()];
+        state.advance(action);
+        steps.push((
+            action,
+            (state.character.y as usize, state.character.x as usize),
+        ));
+    }
+    steps
+}
+
+// 入ってはいけないマス。違反は入った回数
+pub struct ForbiddenCells {
+    pub cells: Vec<(usize, usize)>,
+}
+
+impl Constraint for ForbiddenCells {
+    fn name(&self) -> String {
+        let cells: Vec<String> = self
+            .cells
+            .iter()
+            .map(|(y, x)| format!("{},{}", y, x))
+            .collect();
+        format!("forbid({})", cells.join(" "))
+    }
+
+    fn violation(&self, state: &MazeState, plan: &[usize]) -> ScoreType {
+        trajectory(state, plan)
+            .iter()
+            .filter(|(_, cell)| self.cells.contains(cell))
+            .count() as ScoreType
+    }
+}
+
+// ある向きの移動を使える回数の上限。違反は超えた回数
+pub struct ActionBudget {
+    pub action: usize,
+    pub max_uses: usize,
+}
+
+impl Constraint for ActionBudget {
+    fn name(&self) -> String {
+        format!("budget(action {} <= {})", self.action, self.max_uses)
+    }
+
+    fn violation(&self, state: &MazeState, plan: &[usize]) -> ScoreType {
+        let uses = trajectory(state, plan)
+            .iter()
+            .filter(|&&(action, _)| action == self.action)
+            .count();
+        uses.saturating_sub(self.max_uses) as ScoreType
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConstraintHandling {
+    // 違反の大きさ × weight を得点から引く。実行不能な解を通り抜けて良い解に辿り着ける
+    Penalty { weight: ScoreType },
+    // 評価の前に行動列を修復し、実行可能な解の間だけを動く。修復しきれなかった違反は weight で減点する
+    Repair { weight: ScoreType },
+}
+
+impl Display for ConstraintHandling {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ConstraintHandling::Penalty { weight } => write!(f, "penalty(weight={})", weight),
+            ConstraintHandling::Repair { weight } => write!(f, "repair(weight={})", weight),
+        }
+    }
+}
+
+pub struct ConstraintSet {
+    pub constraints: Vec<Box<dyn Constraint>>,
+    pub handling: ConstraintHandling,
+}
+
+impl ConstraintSet {
+    pub fn violation(&self, state: &MazeState, plan: &[usize]) -> ScoreType {
+        self.constraints
+            .iter()
+            .map(|constraint| constraint.violation(state, plan))
+            .sum()
+    }
+
+    pub fn is_feasible(&self, state: &MazeState, plan: &[usize]) -> bool {
+        self.violation(state, plan) == 0
+    }
+
+    // 局所探索の目的関数。Repair なら plan を修復してから評価するので、plan は書き換わることがある
+    pub fn objective(&self, state: &MazeState, plan: &mut Plan, rng: &mut ChaCha8Rng) -> ScoreType {
+        let weight = match self.handling {
+            ConstraintHandling::Penalty { weight } => weight,
+            ConstraintHandling::Repair { weight } => {
+                for constraint in &self.constraints {
+                    constraint.repair(state, plan, rng);
+                }
+                weight
+            }
+        };
+        plan_score(state, plan) - weight * self.violation(state, plan)
+    }
+}
+
+impl Display for ConstraintSet {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let names: Vec<String> = self.constraints.iter().map(|c| c.name()).collect();
+        write!(f, "[{}] {}", names.join(", "), self.handling)
+    }
+}
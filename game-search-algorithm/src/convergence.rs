@@ -0,0 +1,202 @@
+use crate::local_search::{LocalSearchOutcome, TracePoint};
+use crate::provenance::Provenance;
+use crate::ScoreType;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::str::FromStr;
+
+// 収束の図の横軸
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConvergenceAxis {
+    Iterations,
+    // 評価した解の数。1反復の重さが手法ごとに違っても比べられる
+    Nodes,
+    Time,
+}
+
+impl FromStr for ConvergenceAxis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "iterations" => Ok(ConvergenceAxis::Iterations),
+            "nodes" => Ok(ConvergenceAxis::Nodes),
+            "time" => Ok(ConvergenceAxis::Time),
+            _ => Err(format!("unknown axis `{}` (iterations, nodes, time)", s)),
+        }
+    }
+}
+
+impl ConvergenceAxis {
+    fn value(self, point: &TracePoint) -> f64 {
+        match self {
+            ConvergenceAxis::Iterations => point.iteration as f64,
+            ConvergenceAxis::Nodes => point.nodes as f64,
+            ConvergenceAxis::Time => point.elapsed.as_secs_f64() * 1000.0,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ConvergenceAxis::Iterations => "iterations",
+            ConvergenceAxis::Nodes => "evaluated solutions",
+            ConvergenceAxis::Time => "time (ms)",
+        }
+    }
+}
+
+// 1つの最適化手法の推移。end は止まった時点で、最後の更新から end までは横に伸ばす
+pub struct ConvergenceSeries {
+    pub name: String,
+    pub trace: Vec<TracePoint>,
+    pub end: TracePoint,
+}
+
+impl ConvergenceSeries {
+    pub fn new(name: &str, outcome: &LocalSearchOutcome) -> Self {
+        Self {
+            name: name.to_string(),
+            trace: outcome.trace.clone(),
+            end: TracePoint {
+                iteration: outcome.consumed.iterations,
+                nodes: outcome.consumed.nodes,
+                elapsed: outcome.consumed.time,
+                best: outcome.score,
+            },
+        }
+    }
+}
+
+// 1行に1点 "iteration nodes elapsed_us best" で書き出す。外部のツールで図を描き直せるようにする
+pub fn save_traces(path: &str, series: &[ConvergenceSeries]) -> io::Result<()> {
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    writeln!(writer, "{}", Provenance::collect(1))?;
+    for series in series {
+        writeln!(writer, "series {}", series.name)?;
+        for point in series.trace.iter().chain([&series.end]) {
+            writeln!(
+                writer,
+                "{} {} {} {}",
+                point.iteration,
+                point.nodes,
+                point.elapsed.as_micros(),
+                point.best
+            )?;
+        }
+    }
+    writer.flush()
+}
+
+// 最良値の推移を階段状の折れ線で重ねた SVG を作る
+pub fn convergence_svg(
+    series: &[ConvergenceSeries],
+    axis: ConvergenceAxis,
+) -> Result<String, String> {
+    const WIDTH: f64 = 640.0;
+    const HEIGHT: f64 = 400.0;
+    const MARGIN: f64 = 60.0;
+    const COLORS: [&str; 6] = [
+        "#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b",
+    ];
+
+    let points: Vec<&TracePoint> = series
+        .iter()
+        .flat_map(|series| series.trace.iter().chain([&series.end]))
+        .collect();
+    if series.iter().all(|series| series.trace.is_empty()) {
+        return Err("no results to plot".to_string());
+    }
+    let x_max = points
+        .iter()
+        .map(|point| axis.value(point))
+        .fold(0.0, f64::max)
+        .max(f64::MIN_POSITIVE);
+    let y_low = points.iter().map(|point| point.best).min().unwrap();
+    let y_high = points.iter().map(|point| point.best).max().unwrap();
+    let x = |point: &TracePoint| MARGIN + (WIDTH - 2.0 * MARGIN) * axis.value(point) / x_max;
+    let y = |best: ScoreType| {
+        let t = if y_high > y_low {
+            (best - y_low) as f64 / (y_high - y_low) as f64
+        } else {
+            0.5
+        };
+        HEIGHT - MARGIN - (HEIGHT - 2.0 * MARGIN) * t
+    };
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="12">"#,
+        WIDTH, HEIGHT
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<text x="{}" y="20" text-anchor="middle">best score {} - {} by {}</text>"#,
+        WIDTH / 2.0,
+        y_low,
+        y_high,
+        axis.label()
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<path d="M {m} {m} V {b} H {r}" fill="none" stroke="black"/>"#,
+        m = MARGIN,
+        b = HEIGHT - MARGIN,
+        r = WIDTH - MARGIN
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<text x="{}" y="{}" text-anchor="end">{:.0}</text>"#,
+        WIDTH - MARGIN,
+        HEIGHT - MARGIN + 16.0,
+        x_max
+    )
+    .unwrap();
+    for (best, anchor_y) in [(y_low, y(y_low)), (y_high, y(y_high))] {
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#,
+            MARGIN - 6.0,
+            anchor_y + 4.0,
+            best
+        )
+        .unwrap();
+    }
+    for (k, series) in series.iter().enumerate() {
+        let color = COLORS[k % COLORS.len()];
+        let mut path = String::new();
+        for (i, point) in series.trace.iter().enumerate() {
+            if i == 0 {
+                write!(path, "M {:.1} {:.1}", x(point), y(point.best)).unwrap();
+            } else {
+                // 次の更新までは前の最良値のまま
+                write!(path, " H {:.1} V {:.1}", x(point), y(point.best)).unwrap();
+            }
+        }
+        if !path.is_empty() {
+            write!(path, " H {:.1}", x(&series.end)).unwrap();
+            writeln!(
+                svg,
+                r#"<path d="{}" fill="none" stroke="{}" stroke-width="2"/>"#,
+                path, color
+            )
+            .unwrap();
+        }
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" fill="{}">{} ({})</text>"#,
+            MARGIN + 10.0,
+            MARGIN + 16.0 * k as f64,
+            color,
+            series.name,
+            series.end.best
+        )
+        .unwrap();
+    }
+    writeln!(svg, "</svg>").unwrap();
+    Ok(svg)
+}
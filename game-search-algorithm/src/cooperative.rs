@@ -0,0 +1,408 @@
+use crate::conformance::{encoded_hash, GameSpec};
+use crate::ids::{PlayerId, Seed};
+use crate::search::beam_search_action;
+use crate::simultaneous::PASS;
+use crate::{Coord, GameState, MazeParams, ScoreType, DX, DY};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+
+// 1人あたりの行動の数 (移動の 0..4 と PASS)
+const ACTIONS: usize = 5;
+
+// 2人のキャラクターが別々に動き、点を共有する迷路。同じマスに入ったときの点は1回だけ数える
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CooperativeMazeState {
+    points: Vec<Vec<ScoreType>>,
+    turn: usize,
+    characters: [Coord; 2],
+    pub game_score: ScoreType,
+    params: MazeParams,
+}
+
+// 2人の行動を1つの行動にまとめる。GameState の行動として search のアルゴリズムにそのまま渡せる
+pub fn joint_action(actions: [usize; 2]) -> usize {
+    actions[0] * ACTIONS + actions[1]
+}
+
+pub fn split_joint_action(action: usize) -> [usize; 2] {
+    [action / ACTIONS, action % ACTIONS]
+}
+
+impl CooperativeMazeState {
+    // 乱数を引く順番は SimultaneousMazeState と同じ (2人の初期位置、続いて点)
+    pub fn from_seed(seed: Seed, params: MazeParams) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        let mut cell = || (rng.gen_range(0..params.h), rng.gen_range(0..params.w));
+        let first = cell();
+        let mut second = first;
+        while second == first {
+            second = cell();
+        }
+        let mut points = vec![vec![0; params.w]; params.h];
+        for (j, row) in points.iter_mut().enumerate() {
+            for (i, point) in row.iter_mut().enumerate() {
+                if [first, second].contains(&(j, i)) {
+                    continue;
+                }
+                *point = rng.gen_range(0..10);
+            }
+        }
+        let coord = |(y, x): (usize, usize)| Coord {
+            y: y as i64,
+            x: x as i64,
+        };
+        Self {
+            points,
+            turn: 0,
+            characters: [coord(first), coord(second)],
+            game_score: 0,
+            params,
+        }
+    }
+
+    // 盤面の外に出る移動以外と PASS。相手と同じマスに入ってもよい
+    pub fn player_actions(&self, player: PlayerId) -> Vec<usize> {
+        let character = &self.characters[player.index()];
+        let mut actions: Vec<usize> = (0..4)
+            .filter(|&action| {
+                let y = character.y + DY[action];
+                let x = character.x + DX[action];
+                y >= 0 && y < self.params.h as i64 && x >= 0 && x < self.params.w as i64
+            })
+            .collect();
+        actions.push(PASS);
+        actions
+    }
+
+    pub fn advance_both(&mut self, actions: [usize; 2]) {
+        for (character, &action) in self.characters.iter_mut().zip(actions.iter()) {
+            if action == PASS {
+                continue;
+            }
+            character.y += DY[action];
+            character.x += DX[action];
+        }
+        // 同じマスなら1人目が取った時点で 0 になる
+        for character in &self.characters {
+            let point = &mut self.points[character.y as usize][character.x as usize];
+            self.game_score += *point;
+            *point = 0;
+        }
+        self.turn += 1;
+    }
+
+    // 相手を見ずに、隣のマスの点が最も高い行動 (どのマスも 0 なら PASS)
+    pub fn greedy_action(&self, player: PlayerId) -> usize {
+        let character = &self.characters[player.index()];
+        self.player_actions(player)
+            .into_iter()
+            .max_by_key(|&action| {
+                if action == PASS {
+                    return 0;
+                }
+                let y = (character.y + DY[action]) as usize;
+                let x = (character.x + DX[action]) as usize;
+                self.points[y][x]
+            })
+            .unwrap()
+    }
+}
+
+impl GameState for CooperativeMazeState {
+    // joint_action でまとめた2人の行動の組
+    fn legal_actions(&self) -> Vec<usize> {
+        let second = self.player_actions(PlayerId::SECOND);
+        self.player_actions(PlayerId::FIRST)
+            .into_iter()
+            .flat_map(|first| {
+                second
+                    .iter()
+                    .map(move |&second| joint_action([first, second]))
+            })
+            .collect()
+    }
+
+    fn advance(&mut self, action: usize) {
+        self.advance_both(split_joint_action(action));
+    }
+
+    fn is_done(&self) -> bool {
+        self.turn == self.params.end_turn || self.points.iter().flatten().all(|&point| point == 0)
+    }
+
+    fn evaluate_score(&mut self) -> ScoreType {
+        self.game_score
+    }
+}
+
+// 検査では既定の大きさの盤面を使う。encode は大きさも書くので、どの大きさの盤面も戻せる
+// h・w・終了ターン・手番・2人の y, x・得点を4バイトずつ、続いて点をマスごとに1バイト
+const COOPERATIVE_HEADER_LEN: usize = 4 * 9;
+
+impl GameSpec for CooperativeMazeState {
+    fn game_name() -> &'static str {
+        "cooperative maze"
+    }
+
+    fn from_seed(seed: Seed) -> Self {
+        CooperativeMazeState::from_seed(seed, MazeParams::default())
+    }
+
+    fn max_turns() -> usize {
+        MazeParams::default().end_turn
+    }
+
+    fn position_hash(&self) -> u64 {
+        encoded_hash(&self.encode())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let [first, second] = &self.characters;
+        let words = [
+            self.params.h,
+            self.params.w,
+            self.params.end_turn,
+            self.turn,
+            first.y as usize,
+            first.x as usize,
+            second.y as usize,
+            second.x as usize,
+            self.game_score as usize,
+        ];
+        let mut bytes: Vec<u8> = words
+            .iter()
+            .flat_map(|&word| (word as u32).to_le_bytes())
+            .collect();
+        bytes.extend(self.points.iter().flatten().map(|&point| point as u8));
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < COOPERATIVE_HEADER_LEN {
+            return Err(format!(
+                "encoded cooperative maze must be at least {} bytes, got {}",
+                COOPERATIVE_HEADER_LEN,
+                bytes.len()
+            ));
+        }
+        let word = |i: usize| {
+            let mut word = [0; 4];
+            word.copy_from_slice(&bytes[4 * i..4 * i + 4]);
+            u32::from_le_bytes(word) as usize
+        };
+        let params = MazeParams {
+            h: word(0),
+            w: word(1),
+            end_turn: word(2),
+        };
+        let turn = word(3);
+        if bytes.len() != COOPERATIVE_HEADER_LEN + params.h * params.w {
+            return Err(format!(
+                "encoded {}x{} cooperative maze must be {} bytes, got {}",
+                params.h,
+                params.w,
+                COOPERATIVE_HEADER_LEN + params.h * params.w,
+                bytes.len()
+            ));
+        }
+        if turn > params.end_turn {
+            return Err(format!(
+                "turn {} is past the last turn {}",
+                turn, params.end_turn
+            ));
+        }
+        let mut characters: [Coord; 2] = Default::default();
+        for (i, character) in characters.iter_mut().enumerate() {
+            let (y, x) = (word(4 + 2 * i), word(5 + 2 * i));
+            if y >= params.h || x >= params.w {
+                return Err(format!("character ({}, {}) is outside the board", y, x));
+            }
+            *character = Coord {
+                y: y as i64,
+                x: x as i64,
+            };
+        }
+        let points = bytes[COOPERATIVE_HEADER_LEN..]
+            .chunks(params.w.max(1))
+            .map(|row| row.iter().map(|&point| point as ScoreType).collect())
+            .collect();
+        Ok(CooperativeMazeState {
+            points,
+            turn,
+            characters,
+            game_score: word(8) as ScoreType,
+            params,
+        })
+    }
+}
+
+// キャラクターは A と B。同じマスにいれば A だけ描く
+impl Display for CooperativeMazeState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "turn:{} score:{}", self.turn, self.game_score)?;
+        for (y, row) in self.points.iter().enumerate() {
+            for (x, &point) in row.iter().enumerate() {
+                let here = |player: PlayerId| {
+                    let character = &self.characters[player.index()];
+                    character.y == y as i64 && character.x == x as i64
+                };
+                if here(PlayerId::FIRST) {
+                    write!(f, "A")?;
+                } else if here(PlayerId::SECOND) {
+                    write!(f, "B")?;
+                } else if point > 0 {
+                    write!(f, "{}", point)?;
+                } else {
+                    write!(f, ".")?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// 分散して計画するときに、相手がどう動くと仮定するか
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PartnerModel {
+    // その場に留まる
+    Still,
+    // greedy_action で動く
+    Greedy,
+}
+
+impl PartnerModel {
+    fn action(self, state: &CooperativeMazeState, partner: PlayerId) -> usize {
+        match self {
+            PartnerModel::Still => PASS,
+            PartnerModel::Greedy => state.greedy_action(partner),
+        }
+    }
+}
+
+impl Display for PartnerModel {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            PartnerModel::Still => write!(f, "still"),
+            PartnerModel::Greedy => write!(f, "greedy"),
+        }
+    }
+}
+
+// player だけが行動を選び、相手は model のとおりに動くと見なした一人用のゲーム
+#[derive(Clone, Debug)]
+struct SoloView {
+    state: CooperativeMazeState,
+    player: PlayerId,
+    model: PartnerModel,
+}
+
+impl GameState for SoloView {
+    fn legal_actions(&self) -> Vec<usize> {
+        self.state.player_actions(self.player)
+    }
+
+    fn advance(&mut self, action: usize) {
+        let partner = self.model.action(&self.state, self.player.opponent());
+        let mut actions = [action; 2];
+        actions[self.player.opponent().index()] = partner;
+        self.state.advance_both(actions);
+    }
+
+    fn is_done(&self) -> bool {
+        self.state.is_done()
+    }
+
+    fn evaluate_score(&mut self) -> ScoreType {
+        self.state.game_score
+    }
+}
+
+// 2人の手の決め方
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CooperativePolicy {
+    // 2人とも greedy_action
+    Greedy,
+    // 2人の行動の組をまとめてビームサーチする (中央で計画する)
+    Joint {
+        width: usize,
+        depth: usize,
+    },
+    // それぞれが相手を model と仮定して自分の行動だけをビームサーチする。相談はしない
+    Decentralized {
+        width: usize,
+        depth: usize,
+        model: PartnerModel,
+    },
+}
+
+impl Display for CooperativePolicy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            CooperativePolicy::Greedy => write!(f, "greedy"),
+            CooperativePolicy::Joint { width, depth } => {
+                write!(f, "joint(width={}, depth={})", width, depth)
+            }
+            CooperativePolicy::Decentralized {
+                width,
+                depth,
+                model,
+            } => write!(
+                f,
+                "decentralized(width={}, depth={}, partner={})",
+                width, depth, model
+            ),
+        }
+    }
+}
+
+impl CooperativePolicy {
+    pub fn select_actions(&self, state: &CooperativeMazeState) -> [usize; 2] {
+        match *self {
+            CooperativePolicy::Greedy => PlayerId::BOTH.map(|player| state.greedy_action(player)),
+            CooperativePolicy::Joint { width, depth } => {
+                split_joint_action(beam_search_action(state, width, depth))
+            }
+            CooperativePolicy::Decentralized {
+                width,
+                depth,
+                model,
+            } => PlayerId::BOTH.map(|player| {
+                let view = SoloView {
+                    state: state.clone(),
+                    player,
+                    model,
+                };
+                beam_search_action(&view, width, depth)
+            }),
+        }
+    }
+
+    pub fn play(&self, seed: Seed, params: MazeParams) -> ScoreType {
+        let mut state = CooperativeMazeState::from_seed(seed, params);
+        while !state.is_done() {
+            let actions = self.select_actions(&state);
+            state.advance_both(actions);
+        }
+        state.game_score
+    }
+}
+
+pub fn average_score(policy: CooperativePolicy, params: MazeParams, game_number: usize) -> f64 {
+    let total: ScoreType = Seed::first(game_number)
+        .map(|seed| policy.play(seed, params))
+        .sum();
+    total as f64 / game_number as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::assert_conformance;
+
+    #[test]
+    fn cooperative_maze_state_conforms() {
+        assert_conformance::<CooperativeMazeState>(0..100);
+    }
+}
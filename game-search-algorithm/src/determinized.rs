@@ -0,0 +1,174 @@
+use crate::budget::Budget;
+use crate::duct::{duct_root_visits, most_visited, step, DEFAULT_DUCT_EXPLORATION};
+use crate::ids::{PlayerId, Seed};
+use crate::simultaneous::{CollisionRule, GameResult, PlayerRules, SimultaneousMazeState};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+// 世界ごとの探索結果から1つの手を決める方法
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aggregation {
+    // 各世界で最も多くたどった手に1票ずつ入れ、票の最も多い手
+    MajorityVote,
+    // 全部の世界の根のたどった回数を手ごとに足し、最も多い手
+    SummedVisits,
+}
+
+impl Display for Aggregation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Aggregation::MajorityVote => write!(f, "vote"),
+            Aggregation::SummedVisits => write!(f, "visits"),
+        }
+    }
+}
+
+impl FromStr for Aggregation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vote" => Ok(Aggregation::MajorityVote),
+            "visits" => Ok(Aggregation::SummedVisits),
+            _ => Err(format!("unknown aggregation `{}` (vote or visits)", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeterminizationOptions {
+    // 見えないマスを埋めて作る世界の数
+    pub worlds: usize,
+    // 世界1つあたりの DUCT のたどる回数
+    pub playouts_per_world: usize,
+    pub aggregation: Aggregation,
+    pub exploration: f64,
+}
+
+impl Default for DeterminizationOptions {
+    fn default() -> Self {
+        Self {
+            worlds: 8,
+            playouts_per_world: 100,
+            aggregation: Aggregation::SummedVisits,
+            exploration: DEFAULT_DUCT_EXPLORATION,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeterminizedDecision {
+    pub action: usize,
+    // 世界ごとに最も多くたどった手
+    pub world_actions: Vec<usize>,
+    // 選んだ手と違う手を選んだ世界の割合。高ければ世界の数が足りない
+    pub disagreement: f64,
+}
+
+// player に見えている情報だけから世界を options.worlds 個作り、それぞれで DUCT を回してまとめる
+pub fn determinized_action(
+    state: &SimultaneousMazeState,
+    player: PlayerId,
+    options: &DeterminizationOptions,
+    rng: &mut ChaCha8Rng,
+) -> DeterminizedDecision {
+    let budget = Budget::iterations(options.playouts_per_world);
+    let mut world_actions = Vec::new();
+    // 合法手は見えない点に依らないので、どの世界でも同じ順に並ぶ
+    let mut totals: Vec<(usize, usize)> = state
+        .legal_actions(player)
+        .into_iter()
+        .map(|action| (action, 0))
+        .collect();
+    for _ in 0..options.worlds.max(1) {
+        let world = state.determinize(player, rng);
+        let (visits, _) = duct_root_visits(&world, player, &budget, options.exploration, rng);
+        let world_action = most_visited(&visits);
+        world_actions.push(world_action);
+        match options.aggregation {
+            Aggregation::MajorityVote => {
+                let k = totals.iter().position(|&(a, _)| a == world_action).unwrap();
+                totals[k].1 += 1;
+            }
+            Aggregation::SummedVisits => {
+                for (total, &(_, n)) in totals.iter_mut().zip(visits.iter()) {
+                    total.1 += n;
+                }
+            }
+        }
+    }
+    let action = most_visited(&totals);
+    let disagreeing = world_actions.iter().filter(|&&a| a != action).count();
+    DeterminizedDecision {
+        action,
+        disagreement: disagreeing as f64 / world_actions.len() as f64,
+        world_actions,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DeterminizationReport {
+    pub games: usize,
+    pub determinized_wins: usize,
+    pub greedy_wins: usize,
+    pub draws: usize,
+    pub decisions: usize,
+    pub total_disagreement: f64,
+    pub max_disagreement: f64,
+    // 半分を超える世界が、選んだ手と違う手を選んでいた意思決定の数
+    pub split_decisions: usize,
+}
+
+impl DeterminizationReport {
+    pub fn mean_disagreement(&self) -> f64 {
+        if self.decisions == 0 {
+            0.0
+        } else {
+            self.total_disagreement / self.decisions as f64
+        }
+    }
+}
+
+// 各盤面で determinized DUCT と貪欲法の先手・後手を入れ替えて2局ずつ指す
+// 貪欲法も自分に見えている点だけで選ぶ
+pub fn determinized_vs_greedy(
+    rule: CollisionRule,
+    player_rules: [PlayerRules; 2],
+    game_number: usize,
+    options: &DeterminizationOptions,
+) -> DeterminizationReport {
+    let mut report = DeterminizationReport::default();
+    for seed in Seed::first(game_number) {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        for determinized_player in PlayerId::BOTH {
+            let mut state = SimultaneousMazeState::from_seed(seed, rule, player_rules);
+            while !state.is_done() {
+                let actions = PlayerId::BOTH.map(|player| {
+                    if player != determinized_player {
+                        return state.greedy_action(player);
+                    }
+                    let decision = determinized_action(&state, player, options, &mut rng);
+                    report.decisions += 1;
+                    report.total_disagreement += decision.disagreement;
+                    report.max_disagreement = report.max_disagreement.max(decision.disagreement);
+                    if decision.disagreement > 0.5 {
+                        report.split_decisions += 1;
+                    }
+                    decision.action
+                });
+                step(&mut state, actions);
+            }
+            report.games += 1;
+            match state.result() {
+                GameResult::Win(player) if player == determinized_player => {
+                    report.determinized_wins += 1
+                }
+                GameResult::Win(_) => report.greedy_wins += 1,
+                GameResult::Draw => report.draws += 1,
+            }
+        }
+    }
+    report
+}
@@ -0,0 +1,176 @@
+use crate::best_first::upper_bound;
+use crate::packing::{from_hex, to_hex};
+use crate::provenance::Provenance;
+use crate::{GameState, MazeState, ScoreType};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+// 深さ優先分枝限定法の途中状態。これをそのまま書き出せば中断したところから再開できる
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DfbnbState {
+    pub root: MazeState,
+    // (最初の行動, 詰めた局面)
+    pub frontier: Vec<(usize, Vec<u8>)>,
+    pub incumbent: Option<(ScoreType, usize)>,
+    pub expanded: usize,
+    pub pruned: usize,
+}
+
+impl DfbnbState {
+    pub fn new(root: &MazeState) -> Self {
+        let mut frontier = Vec::new();
+        for action in root.legal_actions().into_iter().rev() {
+            let mut next_state = root.clone();
+            next_state.advance(action);
+            frontier.push((action, next_state.pack()));
+        }
+        Self {
+            root: root.clone(),
+            frontier,
+            incumbent: None,
+            expanded: 0,
+            pruned: 0,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frontier.is_empty()
+    }
+
+    // 最大 steps 個のノードを処理する
+    pub fn step(&mut self, steps: usize) {
+        for _ in 0..steps {
+            let Some((first_action, packed)) = self.frontier.pop() else {
+                return;
+            };
+            let state = MazeState::unpack(&packed).unwrap();
+            if let Some((best, _)) = self.incumbent {
+                if upper_bound(&state) <= best {
+                    self.pruned += 1;
+                    continue;
+                }
+            }
+            if state.is_done() {
+                self.incumbent = Some((state.game_score, first_action));
+                continue;
+            }
+            self.expanded += 1;
+            for action in state.legal_actions().into_iter().rev() {
+                let mut next_state = state.clone();
+                next_state.advance(action);
+                self.frontier.push((first_action, next_state.pack()));
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        // 書きかけのファイルで前のチェックポイントを壊さないように、一時ファイルに書いてから置き換える
+        let temporary = format!("{}.tmp", path);
+        let mut writer = BufWriter::new(fs::File::create(&temporary)?);
+        writeln!(writer, "dfbnb-checkpoint 1")?;
+        writeln!(writer, "{}", Provenance::collect(1))?;
+        writeln!(writer, "root {}", to_hex(&self.root.pack()))?;
+        match self.incumbent {
+            Some((score, action)) => writeln!(writer, "incumbent {} {}", score, action)?,
+            None => writeln!(writer, "incumbent -")?,
+        }
+        writeln!(writer, "expanded {}", self.expanded)?;
+        writeln!(writer, "pruned {}", self.pruned)?;
+        writeln!(writer, "frontier {}", self.frontier.len())?;
+        for (action, packed) in &self.frontier {
+            writeln!(writer, "{} {}", action, to_hex(packed))?;
+        }
+        writer.flush()?;
+        drop(writer);
+        fs::rename(temporary, path)
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let invalid = |message: String| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, message))
+        };
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines().filter(|line| !line.starts_with('#'));
+        let mut field = |name: &str| -> io::Result<String> {
+            let line = lines
+                .next()
+                .ok_or_else(|| invalid(format!("missing `{}`", name)))?;
+            line.strip_prefix(name)
+                .map(|rest| rest.trim().to_string())
+                .ok_or_else(|| invalid(format!("expected `{}`, got `{}`", name, line)))
+        };
+        if field("dfbnb-checkpoint")? != "1" {
+            return Err(invalid("unsupported checkpoint version".to_string()));
+        }
+        let parse_state = |hex: &str| {
+            from_hex(hex)
+                .and_then(|bytes| MazeState::unpack(&bytes))
+                .map_err(invalid)
+        };
+        let root = parse_state(&field("root")?)?;
+        let incumbent = match field("incumbent")?.as_str() {
+            "-" => None,
+            text => {
+                let (score, action) = text
+                    .split_once(' ')
+                    .ok_or_else(|| invalid(format!("invalid incumbent `{}`", text)))?;
+                Some((
+                    score
+                        .parse()
+                        .map_err(|_| invalid(format!("invalid score `{}`", score)))?,
+                    action
+                        .parse()
+                        .map_err(|_| invalid(format!("invalid action `{}`", action)))?,
+                ))
+            }
+        };
+        let number = |text: String| {
+            text.parse::<usize>()
+                .map_err(|_| invalid(format!("invalid number `{}`", text)))
+        };
+        let expanded = number(field("expanded")?)?;
+        let pruned = number(field("pruned")?)?;
+        let frontier_len = number(field("frontier")?)?;
+        let mut frontier = Vec::with_capacity(frontier_len);
+        for line in lines {
+            let (action, hex) = line
+                .split_once(' ')
+                .ok_or_else(|| invalid(format!("invalid frontier line `{}`", line)))?;
+            let action = action
+                .parse()
+                .map_err(|_| invalid(format!("invalid action `{}`", action)))?;
+            frontier.push((action, from_hex(hex).map_err(invalid)?));
+        }
+        if frontier.len() != frontier_len {
+            return Err(invalid("truncated frontier".to_string()));
+        }
+        Ok(Self {
+            root,
+            frontier,
+            incumbent,
+            expanded,
+            pruned,
+        })
+    }
+}
+
+// 探索を checkpoint_interval ノードごとに保存しながら進める。max_steps に達したら途中で止める
+pub fn solve_with_checkpoints(
+    solver: &mut DfbnbState,
+    checkpoint_path: &str,
+    checkpoint_interval: usize,
+    max_steps: Option<usize>,
+) -> io::Result<()> {
+    let mut steps = 0;
+    while !solver.is_finished() {
+        let chunk = match max_steps {
+            Some(max_steps) if steps >= max_steps => break,
+            Some(max_steps) => checkpoint_interval.min(max_steps - steps),
+            None => checkpoint_interval,
+        };
+        solver.step(chunk);
+        steps += chunk;
+        solver.save(checkpoint_path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use crate::agent::parse_agent;
+use crate::ids::Seed;
+use crate::{play_seed, ScoreType};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+// 1行1メッセージのテキストプロトコル
+//   worker -> coordinator: "ready" / "result <seed> <score>"
+//   coordinator -> worker: "task <seed> <agent spec>" / "done"
+
+struct Queue {
+    pending: VecDeque<u64>,
+    results: BTreeMap<u64, ScoreType>,
+    total: usize,
+    // 途中で切れたワーカーのエラー。結果と一緒に呼び出し元へ返す
+    worker_errors: Vec<io::Error>,
+}
+
+pub struct CoordinatorReport {
+    pub results: BTreeMap<u64, ScoreType>,
+    pub worker_errors: Vec<io::Error>,
+}
+
+fn serve_worker(
+    stream: TcpStream,
+    spec: &str,
+    queue: &Mutex<Queue>,
+    finished: &Condvar,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut assigned = None;
+    let result = (|| -> io::Result<()> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if let ["result", seed, score] = words.as_slice() {
+                let seed: u64 = seed.parse().unwrap_or(u64::MAX);
+                let score: ScoreType = score.parse().unwrap_or(0);
+                let mut queue = queue.lock().unwrap();
+                queue.results.insert(seed, score);
+                assigned = None;
+                if queue.results.len() == queue.total {
+                    finished.notify_all();
+                }
+            }
+            let next = queue.lock().unwrap().pending.pop_front();
+            match next {
+                Some(seed) => {
+                    assigned = Some(seed);
+                    writeln!(writer, "task {} {}", seed, spec)?;
+                }
+                None => {
+                    writeln!(writer, "done")?;
+                    return Ok(());
+                }
+            }
+        }
+    })();
+    // 途中で切断されたワーカーのタスクは他のワーカーに回す
+    if let Some(seed) = assigned {
+        queue.lock().unwrap().pending.push_back(seed);
+    }
+    result
+}
+
+// ポートを開いたコーディネーター。local_addr で実際のアドレスを知ってから run で待つ
+pub struct Coordinator {
+    listener: TcpListener,
+    spec: String,
+    seeds: std::ops::Range<u64>,
+}
+
+impl Coordinator {
+    pub fn bind(port: u16, spec: &str, seeds: std::ops::Range<u64>) -> io::Result<Self> {
+        parse_agent(spec).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(Self {
+            listener: TcpListener::bind(("0.0.0.0", port))?,
+            spec: spec.to_string(),
+            seeds,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    // 複数のワーカーにシードを配り、全ての結果がそろうまで待つ
+    pub fn run(self) -> CoordinatorReport {
+        let pending: VecDeque<u64> = self.seeds.collect();
+        let total = pending.len();
+        let shared = Arc::new((
+            Mutex::new(Queue {
+                pending,
+                results: BTreeMap::new(),
+                total,
+                worker_errors: Vec::new(),
+            }),
+            Condvar::new(),
+        ));
+
+        let spec = self.spec;
+        let listener = self.listener;
+        let acceptor = Arc::clone(&shared);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let shared = Arc::clone(&acceptor);
+                let spec = spec.clone();
+                thread::spawn(move || {
+                    let (queue, finished) = &*shared;
+                    if let Err(e) = serve_worker(stream, &spec, queue, finished) {
+                        queue.lock().unwrap().worker_errors.push(e);
+                    }
+                });
+            }
+        });
+
+        let (queue, finished) = &*shared;
+        let mut queue = queue.lock().unwrap();
+        while queue.results.len() < queue.total {
+            queue = finished.wait(queue).unwrap();
+        }
+        CoordinatorReport {
+            results: queue.results.clone(),
+            worker_errors: std::mem::take(&mut queue.worker_errors),
+        }
+    }
+}
+
+pub fn run_worker(address: &str) -> io::Result<usize> {
+    let stream = TcpStream::connect(address)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    writeln!(writer, "ready")?;
+    let mut completed = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(completed);
+        }
+        let line = line.trim();
+        if line == "done" {
+            return Ok(completed);
+        }
+        let rest = line
+            .strip_prefix("task ")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, line.to_string()))?;
+        let (seed, spec) = rest
+            .split_once(' ')
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, line.to_string()))?;
+        let seed: Seed = seed
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, line.to_string()))?;
+        let mut agent =
+            parse_agent(spec).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let score = play_seed(agent.as_mut(), seed);
+        writeln!(writer, "result {} {}", seed, score)?;
+        completed += 1;
+    }
+}
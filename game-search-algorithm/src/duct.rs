@@ -0,0 +1,236 @@
+use crate::budget::{Budget, BudgetMeter, Consumed};
+use crate::ids::{PlayerId, Seed};
+use crate::simultaneous::{CollisionRule, GameResult, PlayerRules, SimultaneousMazeState};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+// 報酬は勝ち 1、引き分け 0.5、負け 0 なので UCB1 の定数も 1 前後でよい
+pub const DEFAULT_DUCT_EXPLORATION: f64 = 1.0;
+
+// 先手から見た報酬。後手の報酬は 1 - これ
+fn reward(state: &SimultaneousMazeState) -> f64 {
+    match state.result() {
+        GameResult::Win(PlayerId::FIRST) => 1.0,
+        GameResult::Win(_) => 0.0,
+        GameResult::Draw => 0.5,
+    }
+}
+
+// 1ターン進める。追加の移動は木の中でも同じ局面になるように貪欲法で決める
+pub(crate) fn step(state: &mut SimultaneousMazeState, actions: [usize; 2]) {
+    state.advance(actions);
+    for player in state.extra_movers() {
+        let action = state.greedy_action(player);
+        state.advance_extra(player, action);
+    }
+}
+
+fn random_playout(
+    state: &SimultaneousMazeState,
+    rng: &mut impl Rng,
+    meter: &mut BudgetMeter,
+) -> f64 {
+    let mut state = state.clone();
+    while !state.is_done() {
+        let actions = PlayerId::BOTH.map(|player| state.random_action(player, rng));
+        state.advance(actions);
+        for player in state.extra_movers() {
+            let action = state.random_action(player, rng);
+            state.advance_extra(player, action);
+        }
+        meter.add_nodes(1);
+    }
+    reward(&state)
+}
+
+// プレイヤーごとの、自分の行動だけで分けた統計 (行動, 報酬の合計, 回数)
+#[derive(Clone, Debug)]
+struct ActionStats {
+    actions: Vec<(usize, f64, usize)>,
+}
+
+impl ActionStats {
+    fn new(actions: Vec<usize>) -> Self {
+        Self {
+            actions: actions.into_iter().map(|action| (action, 0.0, 0)).collect(),
+        }
+    }
+
+    // まだ試していない行動を先に、あとは UCB1 が最大の行動の位置
+    fn select(&self, visits: usize, exploration: f64) -> usize {
+        if let Some(k) = self.actions.iter().position(|&(_, _, n)| n == 0) {
+            return k;
+        }
+        let ucb1 = |&(_, total, n): &(usize, f64, usize)| {
+            total / n as f64 + exploration * ((visits as f64).ln() / n as f64).sqrt()
+        };
+        (0..self.actions.len())
+            .max_by(|&a, &b| ucb1(&self.actions[a]).total_cmp(&ucb1(&self.actions[b])))
+            .unwrap()
+    }
+
+    fn update(&mut self, k: usize, value: f64) {
+        self.actions[k].1 += value;
+        self.actions[k].2 += 1;
+    }
+
+    fn visits(&self) -> Vec<(usize, usize)> {
+        self.actions
+            .iter()
+            .map(|&(action, _, n)| (action, n))
+            .collect()
+    }
+}
+
+// 同じ回数なら先に並んでいる手
+pub(crate) fn most_visited(visits: &[(usize, usize)]) -> usize {
+    let mut best = 0;
+    for k in 0..visits.len() {
+        if visits[k].1 > visits[best].1 {
+            best = k;
+        }
+    }
+    visits[best].0
+}
+
+// Decoupled UCT の節点。2人がそれぞれ自分の統計だけを見て UCB1 で選び、組になった行動で子に進む
+struct Node {
+    state: SimultaneousMazeState,
+    visits: usize,
+    stats: [ActionStats; 2],
+    children: Vec<([usize; 2], Node)>,
+}
+
+impl Node {
+    fn new(state: SimultaneousMazeState) -> Self {
+        let stats = PlayerId::BOTH.map(|player| ActionStats::new(state.legal_actions(player)));
+        Self {
+            state,
+            visits: 0,
+            stats,
+            children: Vec::new(),
+        }
+    }
+
+    // 1回たどって先手から見た報酬を返す。初めて通る組の子はその場でプレイアウトする
+    fn evaluate(&mut self, exploration: f64, rng: &mut impl Rng, meter: &mut BudgetMeter) -> f64 {
+        if self.state.is_done() {
+            self.visits += 1;
+            return reward(&self.state);
+        }
+        let ks = [0, 1].map(|i| self.stats[i].select(self.visits, exploration));
+        let actions = [0, 1].map(|i| self.stats[i].actions[ks[i]].0);
+        let value = match self.children.iter_mut().find(|(a, _)| *a == actions) {
+            Some((_, child)) => child.evaluate(exploration, rng, meter),
+            None => {
+                let mut next_state = self.state.clone();
+                step(&mut next_state, actions);
+                meter.add_nodes(1);
+                let value = random_playout(&next_state, rng, meter);
+                let mut child = Node::new(next_state);
+                child.visits = 1;
+                self.children.push((actions, child));
+                value
+            }
+        };
+        self.visits += 1;
+        self.stats[0].update(ks[0], value);
+        self.stats[1].update(ks[1], 1.0 - value);
+        value
+    }
+}
+
+// player の行動を playout_number 回のたどりで選ぶ
+pub fn duct_action(
+    state: &SimultaneousMazeState,
+    player: PlayerId,
+    playout_number: usize,
+    exploration: f64,
+    rng: &mut impl Rng,
+) -> usize {
+    duct_action_with_budget(
+        state,
+        player,
+        &Budget::iterations(playout_number),
+        exploration,
+        rng,
+    )
+    .0
+}
+
+// budget の iterations はたどった回数、nodes は木に足した局面とプレイアウトで進めたターンの数
+pub fn duct_action_with_budget(
+    state: &SimultaneousMazeState,
+    player: PlayerId,
+    budget: &Budget,
+    exploration: f64,
+    rng: &mut impl Rng,
+) -> (usize, Consumed) {
+    let (visits, consumed) = duct_root_visits(state, player, budget, exploration, rng);
+    (most_visited(&visits), consumed)
+}
+
+// 探索したあとの player の根の (行動, たどった回数)
+pub fn duct_root_visits(
+    state: &SimultaneousMazeState,
+    player: PlayerId,
+    budget: &Budget,
+    exploration: f64,
+    rng: &mut impl Rng,
+) -> (Vec<(usize, usize)>, Consumed) {
+    assert!(!budget.is_unlimited(), "DUCT needs a finite budget");
+    let mut meter = BudgetMeter::new(*budget);
+    let mut root = Node::new(state.clone());
+    while meter.exhausted().is_none() {
+        root.evaluate(exploration, rng, &mut meter);
+        meter.add_iteration();
+    }
+    (root.stats[player.index()].visits(), meter.consumed())
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DuctSummary {
+    pub games: usize,
+    pub duct_wins: usize,
+    pub greedy_wins: usize,
+    pub draws: usize,
+}
+
+// 各盤面で DUCT と貪欲法の先手・後手を入れ替えて2局ずつ指す
+pub fn duct_vs_greedy(
+    rule: CollisionRule,
+    player_rules: [PlayerRules; 2],
+    game_number: usize,
+    playout_number: usize,
+) -> DuctSummary {
+    let mut summary = DuctSummary::default();
+    for seed in Seed::first(game_number) {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        for duct_player in PlayerId::BOTH {
+            let mut state = SimultaneousMazeState::from_seed(seed, rule, player_rules);
+            while !state.is_done() {
+                let actions = PlayerId::BOTH.map(|player| {
+                    if player == duct_player {
+                        duct_action(
+                            &state,
+                            player,
+                            playout_number,
+                            DEFAULT_DUCT_EXPLORATION,
+                            &mut rng,
+                        )
+                    } else {
+                        state.greedy_action(player)
+                    }
+                });
+                step(&mut state, actions);
+            }
+            summary.games += 1;
+            match state.result() {
+                GameResult::Win(player) if player == duct_player => summary.duct_wins += 1,
+                GameResult::Win(_) => summary.greedy_wins += 1,
+                GameResult::Draw => summary.draws += 1,
+            }
+        }
+    }
+    summary
+}
@@ -0,0 +1,207 @@
+use crate::agent::Agent;
+use crate::hooks::{self, Observer};
+use crate::ids::Seed;
+use crate::provenance::Provenance;
+use crate::{GameState, MazeState, ScoreType, Termination};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+// 1ゲームの記録。終わり方によって長さが変わる
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Episode {
+    pub seed: Seed,
+    pub actions: Vec<usize>,
+    // 各ターンに得た点
+    pub gains: Vec<ScoreType>,
+    pub score: ScoreType,
+    pub termination: Termination,
+}
+
+pub fn play_episode(agent: &mut dyn Agent, seed: Seed) -> Episode {
+    play_episode_with_observers(agent, seed, &mut [])
+}
+
+// EpisodeRecorder で記録しながら、ほかの observers にも同じ節目を知らせる
+pub fn play_episode_with_observers(
+    agent: &mut dyn Agent,
+    seed: Seed,
+    observers: &mut [&mut dyn Observer],
+) -> Episode {
+    let mut recorder = EpisodeRecorder::default();
+    let mut all: Vec<&mut dyn Observer> = vec![&mut recorder];
+    all.extend(
+        observers
+            .iter_mut()
+            .map(|observer| &mut **observer as &mut dyn Observer),
+    );
+    let state = hooks::play(agent, seed, &mut all);
+    Episode {
+        seed,
+        actions: recorder.actions,
+        gains: recorder.gains,
+        score: state.game_score,
+        termination: state.termination().unwrap(),
+    }
+}
+
+// 指した手と、その手で得た点を記録する。得た点は次の手番 (か終局) で確定する
+#[derive(Clone, Debug, Default)]
+pub struct EpisodeRecorder {
+    pub actions: Vec<usize>,
+    pub gains: Vec<ScoreType>,
+    score_before: ScoreType,
+}
+
+impl EpisodeRecorder {
+    fn settle(&mut self, state: &MazeState) {
+        if self.gains.len() < self.actions.len() {
+            self.gains.push(state.game_score - self.score_before);
+        }
+    }
+}
+
+impl Observer for EpisodeRecorder {
+    fn on_turn_start(&mut self, state: &MazeState) {
+        self.settle(state);
+    }
+
+    fn on_action_chosen(&mut self, state: &MazeState, action: usize) {
+        self.actions.push(action);
+        self.score_before = state.game_score;
+    }
+
+    fn on_game_end(&mut self, state: &MazeState) {
+        self.settle(state);
+    }
+}
+
+// 記録した行動列を初めから指し直す。終局のあとに行動が残っていたり、途中で終わっていなければエラー
+pub fn replay(seed: Seed, actions: &[usize]) -> Result<MazeState, String> {
+    replay_from(MazeState::from_seed(seed), actions).map_err(|e| format!("seed {}: {}", seed, e))
+}
+
+// 与えた局面から行動列を指し直す。盤面の大きさを変えた局面や scenario の局面でも使える
+pub fn replay_from(mut state: MazeState, actions: &[usize]) -> Result<MazeState, String> {
+    for (turn, &action) in actions.iter().enumerate() {
+        if state.is_done() {
+            return Err(format!(
+                "game ended at turn {} but {} actions remain",
+                turn,
+                actions.len() - turn
+            ));
+        }
+        if !state.legal_actions().contains(&action) {
+            return Err(format!("illegal action {} at turn {}", action, turn));
+        }
+        state.advance(action);
+    }
+    if !state.is_done() {
+        return Err(format!(
+            "replay stopped at turn {} before the game ended",
+            state.turn
+        ));
+    }
+    Ok(state)
+}
+
+// 1行に "シード 行動..." を書く
+pub fn save_replays(path: &str, episodes: &[Episode]) -> io::Result<()> {
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    writeln!(writer, "{}", Provenance::collect(1))?;
+    for episode in episodes {
+        write!(writer, "{}", episode.seed)?;
+        for action in &episode.actions {
+            write!(writer, " {}", action)?;
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()
+}
+
+pub fn load_replays(path: &str) -> io::Result<Vec<(Seed, Vec<usize>)>> {
+    let invalid = |line: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid replay line `{}`", line),
+        )
+    };
+    let mut replays = Vec::new();
+    for line in fs::read_to_string(path)?.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let seed = words
+            .next()
+            .and_then(|seed| seed.parse().ok())
+            .ok_or_else(|| invalid(line))?;
+        let actions = words
+            .map(|action| action.parse().map_err(|_| invalid(line)))
+            .collect::<io::Result<Vec<usize>>>()?;
+        replays.push((seed, actions));
+    }
+    Ok(replays)
+}
+
+// ターンごとの集計。そのターンまで続いたゲームだけで平均する
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EpisodeStats {
+    pub games: usize,
+    pub total_score: ScoreType,
+    // games_by_turn[t] は t ターン目を指したゲームの数
+    pub games_by_turn: Vec<usize>,
+    pub gains_by_turn: Vec<ScoreType>,
+    pub terminations: BTreeMap<Termination, usize>,
+}
+
+impl EpisodeStats {
+    pub fn add(&mut self, episode: &Episode) {
+        self.games += 1;
+        self.total_score += episode.score;
+        if self.games_by_turn.len() < episode.gains.len() {
+            self.games_by_turn.resize(episode.gains.len(), 0);
+            self.gains_by_turn.resize(episode.gains.len(), 0);
+        }
+        for (turn, &gain) in episode.gains.iter().enumerate() {
+            self.games_by_turn[turn] += 1;
+            self.gains_by_turn[turn] += gain;
+        }
+        *self.terminations.entry(episode.termination).or_insert(0) += 1;
+    }
+
+    pub fn mean_length(&self) -> f64 {
+        self.games_by_turn.iter().sum::<usize>() as f64 / self.games.max(1) as f64
+    }
+}
+
+impl Display for EpisodeStats {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "games: {}, mean score: {:.3}, mean length: {:.3}",
+            self.games,
+            self.total_score as f64 / self.games.max(1) as f64,
+            self.mean_length()
+        )?;
+        for (termination, count) in &self.terminations {
+            write!(f, "\n  {:?}: {}", termination, count)?;
+        }
+        for (turn, (&games, &gains)) in self
+            .games_by_turn
+            .iter()
+            .zip(self.gains_by_turn.iter())
+            .enumerate()
+        {
+            write!(
+                f,
+                "\nturn {}: {} games, mean gain {:.3}",
+                turn,
+                games,
+                gains as f64 / games as f64
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,352 @@
+use crate::agent::{depth_or_remaining, describe_depth, Agent};
+use crate::search::{
+    beam_search_action_with_evaluator, chokudai_search_action_with_evaluator, greedy_action,
+};
+use crate::stats;
+use crate::{GameState, MazeState, ScoreType};
+use std::collections::BinaryHeap;
+
+pub trait Evaluator: Send + Sync {
+    fn name(&self) -> String;
+    fn evaluate(&self, state: &MazeState) -> ScoreType;
+}
+
+// これまでに得たスコアそのもの (MazeState::evaluate_score と同じ)
+pub struct GameScoreEvaluator;
+
+impl Evaluator for GameScoreEvaluator {
+    fn name(&self) -> String {
+        "game_score".to_string()
+    }
+
+    fn evaluate(&self, state: &MazeState) -> ScoreType {
+        state.game_score
+    }
+}
+
+// 残りターン内に到達できる最も高い点を足す
+pub struct NearestPointEvaluator;
+
+impl Evaluator for NearestPointEvaluator {
+    fn name(&self) -> String {
+        "nearest_point".to_string()
+    }
+
+    fn evaluate(&self, state: &MazeState) -> ScoreType {
+        let remaining_turns = state.remaining_turns() as i64;
+        let mut best_point = 0;
+        for (y, row) in state.points.iter().enumerate() {
+            for (x, &point) in row.iter().enumerate() {
+                let distance =
+                    (y as i64 - state.character.y).abs() + (x as i64 - state.character.x).abs();
+                if distance <= remaining_turns {
+                    best_point = best_point.max(point);
+                }
+            }
+        }
+        state.game_score + best_point
+    }
+}
+
+// 残りターン数ぶんの上位の点の半分を見込みとして足す
+pub struct RemainingPotentialEvaluator;
+
+impl Evaluator for RemainingPotentialEvaluator {
+    fn name(&self) -> String {
+        "remaining_potential".to_string()
+    }
+
+    fn evaluate(&self, state: &MazeState) -> ScoreType {
+        let mut points: Vec<ScoreType> = state.points.iter().flatten().copied().collect();
+        points.sort_unstable_by(|a, b| b.cmp(a));
+        let potential: ScoreType = points.iter().take(state.remaining_turns()).sum();
+        state.game_score + potential / 2
+    }
+}
+
+// parse_evaluator が作れる評価関数の名前
+pub const EVALUATOR_NAMES: &[&str] = &["game_score", "nearest_point", "remaining_potential"];
+
+// 名前から評価関数を作る。名前は各評価関数の name() と同じ
+pub fn parse_evaluator(name: &str) -> Result<Box<dyn Evaluator>, String> {
+    match name {
+        "game_score" => Ok(Box::new(GameScoreEvaluator)),
+        "nearest_point" => Ok(Box::new(NearestPointEvaluator)),
+        "remaining_potential" => Ok(Box::new(RemainingPotentialEvaluator)),
+        _ => Err(format!("unknown evaluator `{}`", name)),
+    }
+}
+
+// evaluator の値で状態を並べるビームサーチ
+pub fn evaluator_beam_search_action(
+    state: &MazeState,
+    beam_width: usize,
+    beam_depth: usize,
+    evaluator: &dyn Evaluator,
+) -> usize {
+    beam_search_action_with_evaluator(state, beam_width, beam_depth, &|state| {
+        evaluator.evaluate(state)
+    })
+}
+
+// evaluator の値で各層の状態を並べる Chokudai サーチ
+pub fn evaluator_chokudai_search_action(
+    state: &MazeState,
+    beam_width: usize,
+    beam_depth: usize,
+    beam_number: usize,
+    evaluator: &dyn Evaluator,
+) -> usize {
+    chokudai_search_action_with_evaluator(state, beam_width, beam_depth, beam_number, &|state| {
+        evaluator.evaluate(state)
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EnsembleScore {
+    pub mean: f64,
+    // 各評価関数の値の標準偏差
+    pub disagreement: f64,
+}
+
+pub struct EnsembleEvaluator {
+    pub members: Vec<Box<dyn Evaluator>>,
+}
+
+impl EnsembleEvaluator {
+    pub fn evaluate_with_disagreement(&self, state: &MazeState) -> EnsembleScore {
+        let values: Vec<f64> = self
+            .members
+            .iter()
+            .map(|member| member.evaluate(state) as f64)
+            .collect();
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+        EnsembleScore {
+            mean,
+            disagreement: variance.sqrt(),
+        }
+    }
+}
+
+impl Evaluator for EnsembleEvaluator {
+    fn name(&self) -> String {
+        let members: Vec<String> = self.members.iter().map(|member| member.name()).collect();
+        format!("ensemble([{}])", members.join(", "))
+    }
+
+    fn evaluate(&self, state: &MazeState) -> ScoreType {
+        self.evaluate_with_disagreement(state).mean.round() as ScoreType
+    }
+}
+
+struct Candidate {
+    state: MazeState,
+    disagreement: f64,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.state == other.state
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.state.cmp(&other.state)
+    }
+}
+
+// 評価関数同士の意見が割れている状態はビーム幅の外でも追加で展開するビームサーチ
+// 評価した局面の数と意見の割れ、追加で展開した数は stats に知らせる
+pub fn ensemble_beam_search_action(
+    state: &MazeState,
+    beam_width: usize,
+    beam_depth: usize,
+    ensemble: &EnsembleEvaluator,
+    disagreement_threshold: f64,
+) -> usize {
+    // 終局した局面は展開できないので、合法手を返すだけにする。幅や深さが 0 でも1層目は展開する
+    if state.is_done() {
+        return greedy_action(state);
+    }
+    let (beam_width, beam_depth) = (beam_width.max(1), beam_depth.max(1));
+    let mut now_beam = BinaryHeap::new();
+    let mut best_state: Option<MazeState> = None;
+
+    now_beam.push(Candidate {
+        state: state.clone(),
+        disagreement: 0.0,
+    });
+    for t in 0..beam_depth {
+        let mut next_beam = BinaryHeap::new();
+        let mut expanded = 0;
+        let mut extra = 0;
+        while let Some(candidate) = now_beam.pop() {
+            if expanded >= beam_width {
+                if candidate.disagreement <= disagreement_threshold || extra >= beam_width {
+                    continue;
+                }
+                extra += 1;
+            }
+            expanded += 1;
+            for action in candidate.state.legal_actions() {
+                let mut next_state = candidate.state.clone();
+                next_state.advance(action);
+                let score = ensemble.evaluate_with_disagreement(&next_state);
+                next_state.evaluated_score = score.mean.round() as ScoreType;
+                if t == 0 {
+                    next_state.first_action = Some(action);
+                }
+                stats::record_evaluation(score.disagreement);
+                next_beam.push(Candidate {
+                    state: next_state,
+                    disagreement: score.disagreement,
+                });
+            }
+        }
+        stats::record_extra_expansions(extra);
+
+        now_beam = next_beam;
+        best_state = now_beam.peek().map(|candidate| candidate.state.clone());
+
+        if best_state.as_ref().map_or(true, |state| state.is_done()) {
+            break;
+        }
+    }
+    best_state
+        .and_then(|best| best.first_action)
+        .unwrap_or_else(|| greedy_action(state))
+}
+
+// evaluator の値で並べるビームサーチで指すエージェント
+pub struct EvaluatorBeamAgent {
+    pub beam_width: usize,
+    pub beam_depth: usize,
+    pub evaluator: Box<dyn Evaluator>,
+}
+
+impl Agent for EvaluatorBeamAgent {
+    fn name(&self) -> String {
+        format!(
+            "beam(width={}, depth={}, evaluator={})",
+            self.beam_width,
+            self.beam_depth,
+            self.evaluator.name()
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        evaluator_beam_search_action(
+            state,
+            self.beam_width,
+            self.beam_depth,
+            self.evaluator.as_ref(),
+        )
+    }
+}
+
+// evaluator の値で並べる Chokudai サーチで指すエージェント
+pub struct EvaluatorChokudaiAgent {
+    pub beam_width: usize,
+    pub beam_depth: usize,
+    pub beam_number: usize,
+    pub evaluator: Box<dyn Evaluator>,
+}
+
+impl Agent for EvaluatorChokudaiAgent {
+    fn name(&self) -> String {
+        format!(
+            "chokudai(width={}, depth={}, number={}, evaluator={})",
+            self.beam_width,
+            self.beam_depth,
+            self.beam_number,
+            self.evaluator.name()
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        evaluator_chokudai_search_action(
+            state,
+            self.beam_width,
+            self.beam_depth,
+            self.beam_number,
+            self.evaluator.as_ref(),
+        )
+    }
+}
+
+// beam_depth が None なら局面の残りターンを深さにする
+pub struct EnsembleBeamAgent {
+    pub beam_width: usize,
+    pub beam_depth: Option<usize>,
+    pub ensemble: EnsembleEvaluator,
+    pub disagreement_threshold: f64,
+}
+
+impl EnsembleBeamAgent {
+    pub fn new(beam_width: usize, beam_depth: Option<usize>, disagreement_threshold: f64) -> Self {
+        Self {
+            beam_width,
+            beam_depth,
+            ensemble: EnsembleEvaluator {
+                members: vec![
+                    Box::new(GameScoreEvaluator),
+                    Box::new(NearestPointEvaluator),
+                    Box::new(RemainingPotentialEvaluator),
+                ],
+            },
+            disagreement_threshold,
+        }
+    }
+}
+
+impl Agent for EnsembleBeamAgent {
+    fn name(&self) -> String {
+        format!(
+            "ensemble_beam(width={}, depth={}, threshold={}, {})",
+            self.beam_width,
+            describe_depth(self.beam_depth),
+            self.disagreement_threshold,
+            self.ensemble.name()
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        ensemble_beam_search_action(
+            state,
+            self.beam_width,
+            depth_or_remaining(self.beam_depth, state),
+            &self.ensemble,
+            self.disagreement_threshold,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::Seed;
+
+    #[test]
+    fn ensemble_beam_returns_a_legal_action_without_children() {
+        let agent = EnsembleBeamAgent::new(2, None, 1.0);
+        let mut state = MazeState::from_seed(Seed(0));
+        while !state.is_done() {
+            let action = ensemble_beam_search_action(&state, 2, 0, &agent.ensemble, 1.0);
+            assert!(state.legal_actions().contains(&action));
+            state.advance(action);
+        }
+        let action = ensemble_beam_search_action(&state, 2, 3, &agent.ensemble, 1.0);
+        assert!(state.legal_actions().contains(&action));
+    }
+}
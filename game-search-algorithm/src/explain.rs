@@ -0,0 +1,140 @@
+use crate::agent::describe_beam_budget;
+use crate::budget::Budget;
+use crate::search;
+use crate::{GameState, MazeState, ScoreType};
+use std::fmt::{self, Display, Formatter};
+
+// 1手の選び方の説明
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Explanation {
+    pub chosen: usize,
+    // 根の各行動と、その行動から探索した評価値 (良い順)
+    pub alternatives: Vec<(usize, ScoreType)>,
+    // 選んだ手の評価値の内訳 (評価関数の名前, 値)
+    pub terms: Vec<(String, ScoreType)>,
+    // ビームサーチの budget。iterations は深さで、何も指定しなければ終局まで読む
+    pub budget: Budget,
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "chose {} with {}",
+            self.chosen,
+            describe_beam_budget(&self.budget)
+        )?;
+        for (action, score) in &self.alternatives {
+            let mark = if *action == self.chosen { "*" } else { " " };
+            write!(f, "\n {} action {}: {}", mark, action, score)?;
+        }
+        for (name, value) in &self.terms {
+            write!(f, "\n   {} = {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl MazeState {
+    // 最初の手を first_action に固定して、残りを同じ予算のビームサーチで読んだ評価値
+    pub fn beam_search_value_with_first_action(
+        &self,
+        first_action: usize,
+        beam_width: usize,
+        budget: Budget,
+    ) -> ScoreType {
+        let mut child = self.clone();
+        child.advance(first_action);
+        child.evaluate_score();
+        if child.is_done() || budget.iterations == Some(1) {
+            return child.evaluated_score;
+        }
+        // 最初の手で1層使ったので、深さを1つ減らして読む
+        let budget = Budget {
+            iterations: budget.iterations.map(|depth| depth - 1),
+            ..budget
+        };
+        search::beam_search_with_budget(&child, beam_width, &budget)
+            .0
+            .score
+    }
+
+    pub fn explain_beam_search(
+        &self,
+        chosen: usize,
+        beam_width: usize,
+        budget: Budget,
+    ) -> Explanation {
+        let mut alternatives: Vec<(usize, ScoreType)> = self
+            .legal_actions()
+            .into_iter()
+            .map(|action| {
+                let value = self.beam_search_value_with_first_action(action, beam_width, budget);
+                (action, value)
+            })
+            .collect();
+        alternatives.sort_by_key(|&(action, value)| (std::cmp::Reverse(value), action));
+        let mut child = self.clone();
+        child.advance(chosen);
+        Explanation {
+            chosen,
+            alternatives,
+            terms: vec![("game_score".to_string(), child.game_score)],
+            budget,
+        }
+    }
+}
+
+// 指定された手と探索が選ぶ手の比較
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Counterfactual {
+    pub suggested: usize,
+    pub suggested_value: ScoreType,
+    pub preferred: usize,
+    pub preferred_value: ScoreType,
+}
+
+impl Counterfactual {
+    pub fn loss(&self) -> ScoreType {
+        self.preferred_value - self.suggested_value
+    }
+}
+
+impl Display for Counterfactual {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "suggested {}: {}, preferred {}: {} (loss {})",
+            self.suggested,
+            self.suggested_value,
+            self.preferred,
+            self.preferred_value,
+            self.loss()
+        )
+    }
+}
+
+impl MazeState {
+    // 探索が選ぶ手も同じく最初の手を固定した読みで評価し、両者を同じ物差しで比べる
+    pub fn counterfactual(
+        &self,
+        suggested: usize,
+        beam_width: usize,
+        budget: Budget,
+    ) -> Result<Counterfactual, String> {
+        if !self.legal_actions().contains(&suggested) {
+            return Err(format!("action {} is not legal here", suggested));
+        }
+        let preferred = search::beam_search_with_budget(self, beam_width, &budget)
+            .0
+            .first_action;
+        Ok(Counterfactual {
+            suggested,
+            suggested_value: self
+                .beam_search_value_with_first_action(suggested, beam_width, budget),
+            preferred,
+            preferred_value: self
+                .beam_search_value_with_first_action(preferred, beam_width, budget),
+        })
+    }
+}
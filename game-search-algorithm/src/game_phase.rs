@@ -0,0 +1,256 @@
+use crate::agent::Agent;
+use crate::evaluator::{evaluator_beam_search_action, parse_evaluator, Evaluator};
+use crate::{MazeState, ScoreType, END_TURN};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamePhase {
+    Opening,
+    Midgame,
+    Endgame,
+}
+
+impl GamePhase {
+    pub const ALL: [GamePhase; 3] = [GamePhase::Opening, GamePhase::Midgame, GamePhase::Endgame];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl Display for GamePhase {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let name = match self {
+            GamePhase::Opening => "opening",
+            GamePhase::Midgame => "midgame",
+            GamePhase::Endgame => "endgame",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// 局面がどれだけ進んだかを 0 から 1 で測るもの
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    // 経過ターン / 盤面のターン数
+    Turn,
+    // 取った点 / (取った点 + 盤面に残っている点)。点が残っていなければ 1
+    Points,
+}
+
+// 進み具合が opening 未満なら序盤、endgame 以上なら終盤、その間は中盤
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhaseDetector {
+    pub progress: Progress,
+    pub opening: f64,
+    pub endgame: f64,
+}
+
+impl Default for PhaseDetector {
+    fn default() -> Self {
+        Self {
+            progress: Progress::Turn,
+            opening: 0.3,
+            endgame: 0.7,
+        }
+    }
+}
+
+impl PhaseDetector {
+    pub fn progress(&self, state: &MazeState) -> f64 {
+        match self.progress {
+            Progress::Turn => state.turn as f64 / state.params.end_turn as f64,
+            Progress::Points => {
+                let remaining: ScoreType = state.points.iter().flatten().sum();
+                let total = state.game_score + remaining;
+                if total == 0 {
+                    1.0
+                } else {
+                    state.game_score as f64 / total as f64
+                }
+            }
+        }
+    }
+
+    pub fn phase(&self, state: &MazeState) -> GamePhase {
+        let progress = self.progress(state);
+        if progress >= self.endgame {
+            GamePhase::Endgame
+        } else if progress < self.opening {
+            GamePhase::Opening
+        } else {
+            GamePhase::Midgame
+        }
+    }
+}
+
+// ある局面の段階で使う評価関数とビームサーチのパラメータ
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PhaseSettings {
+    pub evaluator: String,
+    pub beam_width: usize,
+    pub beam_depth: usize,
+}
+
+// 段階の分け方と、段階ごとの設定。文字列で書ける
+// "progress=turn opening=0.3 endgame=0.7 opening.evaluator=remaining_potential endgame.depth=4"
+// 書かなかった段階の値は既定値 (evaluator=game_score width=2 depth=END_TURN) のまま
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhasedConfig {
+    pub detector: PhaseDetector,
+    pub phases: [PhaseSettings; 3],
+}
+
+impl Default for PhasedConfig {
+    fn default() -> Self {
+        let settings = PhaseSettings {
+            evaluator: "game_score".to_string(),
+            beam_width: 2,
+            beam_depth: END_TURN,
+        };
+        Self {
+            detector: PhaseDetector::default(),
+            phases: [settings.clone(), settings.clone(), settings],
+        }
+    }
+}
+
+impl PhasedConfig {
+    // 序盤は陣地 (残りの点の見込み)、中盤は近くの点、終盤は最後まで読んで得点を数える
+    pub fn territory_then_exact() -> Self {
+        let mut config = Self::default();
+        config.phases[GamePhase::Opening.index()].evaluator = "remaining_potential".to_string();
+        config.phases[GamePhase::Midgame.index()].evaluator = "nearest_point".to_string();
+        config
+    }
+
+    pub fn settings(&self, phase: GamePhase) -> &PhaseSettings {
+        &self.phases[phase.index()]
+    }
+}
+
+impl Display for PhasedConfig {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let progress = match self.detector.progress {
+            Progress::Turn => "turn",
+            Progress::Points => "points",
+        };
+        write!(
+            f,
+            "progress={} opening={} endgame={}",
+            progress, self.detector.opening, self.detector.endgame
+        )?;
+        for phase in GamePhase::ALL {
+            let settings = self.settings(phase);
+            write!(
+                f,
+                " {0}.evaluator={1} {0}.width={2} {0}.depth={3}",
+                phase, settings.evaluator, settings.beam_width, settings.beam_depth
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PhasedConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = Self::default();
+        for word in s.split_whitespace() {
+            let (key, value) = word
+                .split_once('=')
+                .ok_or(format!("expected key=value, got `{}`", word))?;
+            let fraction = || {
+                value
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid fraction `{}` for `{}`", value, key))
+            };
+            let number = || {
+                value
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid number `{}` for `{}`", value, key))
+            };
+            match key {
+                "progress" => {
+                    config.detector.progress = match value {
+                        "turn" => Progress::Turn,
+                        "points" => Progress::Points,
+                        _ => return Err(format!("unknown progress `{}`", value)),
+                    }
+                }
+                "opening" => config.detector.opening = fraction()?,
+                "endgame" => config.detector.endgame = fraction()?,
+                _ => {
+                    let (phase, field) = key
+                        .split_once('.')
+                        .ok_or(format!("unknown key `{}`", key))?;
+                    let phase = GamePhase::ALL
+                        .into_iter()
+                        .find(|p| p.to_string() == phase)
+                        .ok_or(format!("unknown phase `{}`", phase))?;
+                    let settings = &mut config.phases[phase.index()];
+                    match field {
+                        "evaluator" => {
+                            parse_evaluator(value)?;
+                            settings.evaluator = value.to_string();
+                        }
+                        "width" => settings.beam_width = number()?,
+                        "depth" => settings.beam_depth = number()?,
+                        _ => return Err(format!("unknown key `{}`", key)),
+                    }
+                }
+            }
+        }
+        if config.detector.opening > config.detector.endgame {
+            return Err(format!(
+                "opening ({}) must not exceed endgame ({})",
+                config.detector.opening, config.detector.endgame
+            ));
+        }
+        Ok(config)
+    }
+}
+
+// 局面の段階を見て、その段階の評価関数とパラメータでビームサーチする
+pub struct PhasedBeamAgent {
+    pub config: PhasedConfig,
+    evaluators: [Box<dyn Evaluator>; 3],
+    // 段階ごとに選んだ手の数
+    pub decisions: [usize; 3],
+}
+
+impl PhasedBeamAgent {
+    pub fn new(config: PhasedConfig) -> Result<Self, String> {
+        let [opening, midgame, endgame] = &config.phases;
+        let evaluators = [
+            parse_evaluator(&opening.evaluator)?,
+            parse_evaluator(&midgame.evaluator)?,
+            parse_evaluator(&endgame.evaluator)?,
+        ];
+        Ok(Self {
+            config,
+            evaluators,
+            decisions: [0; 3],
+        })
+    }
+}
+
+impl Agent for PhasedBeamAgent {
+    fn name(&self) -> String {
+        format!("phased_beam({})", self.config)
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        let phase = self.config.detector.phase(state);
+        let settings = self.config.settings(phase);
+        self.decisions[phase.index()] += 1;
+        evaluator_beam_search_action(
+            state,
+            settings.beam_width,
+            settings.beam_depth,
+            self.evaluators[phase.index()].as_ref(),
+        )
+    }
+}
@@ -0,0 +1,160 @@
+use crate::agent::Agent;
+use crate::ids::Seed;
+use crate::{GameState, MazeState};
+use std::cell::RefCell;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+// 対局と探索の節目で呼ばれる。使わないものは既定の空の実装のままでよい
+// 記録・ログ・進み具合の表示は、対局のループを書き換えずにこれを実装して play に渡す
+pub trait Observer {
+    // 手を選ぶ前の局面
+    fn on_turn_start(&mut self, _state: &MazeState) {}
+    // 選んだ手と、それを指す前の局面
+    fn on_action_chosen(&mut self, _state: &MazeState, _action: usize) {}
+    fn on_game_end(&mut self, _state: &MazeState) {}
+    // 手を選ぶ間に、探索が BudgetMeter で外側のループを1回まわるたび (反復回数とそこまでのノード数)
+    // 探索の途中ではなく、その手の on_action_chosen の直前にまとめて届く
+    fn on_search_iteration(&mut self, _iterations: usize, _nodes: usize) {}
+}
+
+thread_local! {
+    // 対局中のスレッドで探索から届いた反復をためておく。None なら誰も聞いていない
+    static SEARCH_ITERATIONS: RefCell<Option<Vec<(usize, usize)>>> = const { RefCell::new(None) };
+}
+
+// BudgetMeter::add_iteration から呼ぶ
+pub(crate) fn notify_search_iteration(iterations: usize, nodes: usize) {
+    SEARCH_ITERATIONS.with(|events| {
+        if let Some(events) = events.borrow_mut().as_mut() {
+            events.push((iterations, nodes));
+        }
+    });
+}
+
+// seed の盤面で agent に最後まで指させ、節目ごとに observers へ知らせる。終局の局面を返す
+pub fn play(agent: &mut dyn Agent, seed: Seed, observers: &mut [&mut dyn Observer]) -> MazeState {
+    play_from(agent, MazeState::from_seed(seed), observers)
+}
+
+// 与えた局面から終局まで指す。盤面の大きさを変えた局面でも使える
+pub fn play_from(
+    agent: &mut dyn Agent,
+    mut state: MazeState,
+    observers: &mut [&mut dyn Observer],
+) -> MazeState {
+    while !state.is_done() {
+        for observer in observers.iter_mut() {
+            observer.on_turn_start(&state);
+        }
+        SEARCH_ITERATIONS.with(|events| *events.borrow_mut() = Some(Vec::new()));
+        let action = agent.select_action(&state);
+        let events = SEARCH_ITERATIONS.with(|events| events.borrow_mut().take());
+        for observer in observers.iter_mut() {
+            for &(iterations, nodes) in events.iter().flatten() {
+                observer.on_search_iteration(iterations, nodes);
+            }
+            observer.on_action_chosen(&state, action);
+        }
+        state.advance(action);
+    }
+    for observer in observers.iter_mut() {
+        observer.on_game_end(&state);
+    }
+    state
+}
+
+// 1手ごとに手番・選んだ手・探索の反復回数を書く
+pub struct Logger<W: Write> {
+    writer: W,
+    iterations: usize,
+    nodes: usize,
+}
+
+impl<W: Write> Logger<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            iterations: 0,
+            nodes: 0,
+        }
+    }
+}
+
+impl<W: Write> Observer for Logger<W> {
+    fn on_search_iteration(&mut self, iterations: usize, nodes: usize) {
+        self.iterations = iterations;
+        self.nodes = nodes;
+    }
+
+    fn on_action_chosen(&mut self, state: &MazeState, action: usize) {
+        writeln!(
+            self.writer,
+            "turn {}: action {} ({} iterations, {} nodes)",
+            state.turn(),
+            action,
+            self.iterations,
+            self.nodes
+        )
+        .unwrap();
+        self.iterations = 0;
+        self.nodes = 0;
+    }
+
+    fn on_game_end(&mut self, state: &MazeState) {
+        writeln!(self.writer, "game end: score {}", state.game_score).unwrap();
+    }
+}
+
+// 終わった対局の数を "games 3/100" のように同じ行に書き直して writer に書く
+pub struct ProgressBar<W: Write> {
+    writer: W,
+    done: usize,
+    total: usize,
+}
+
+impl<W: Write> ProgressBar<W> {
+    pub fn new(total: usize, writer: W) -> Self {
+        Self {
+            writer,
+            done: 0,
+            total,
+        }
+    }
+}
+
+impl<W: Write> Observer for ProgressBar<W> {
+    fn on_game_end(&mut self, _state: &MazeState) {
+        self.done += 1;
+        write!(self.writer, "\rgames {}/{}", self.done, self.total).unwrap();
+        if self.done == self.total {
+            writeln!(self.writer).unwrap();
+        }
+        self.writer.flush().unwrap();
+    }
+}
+
+// 1手ごとに手を選ぶのにかかった時間を記録する
+#[derive(Clone, Debug, Default)]
+pub struct MoveTimer {
+    started: Option<Instant>,
+    pub times: Vec<Duration>,
+}
+
+impl MoveTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Observer for MoveTimer {
+    fn on_turn_start(&mut self, _state: &MazeState) {
+        self.started = Some(Instant::now());
+    }
+
+    fn on_action_chosen(&mut self, _state: &MazeState, _action: usize) {
+        if let Some(started) = self.started.take() {
+            self.times.push(started.elapsed());
+        }
+    }
+}
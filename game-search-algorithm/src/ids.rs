@@ -0,0 +1,76 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+// 盤面を作る乱数の種。対局数や反復回数と取り違えないように、ただの整数とは型を分ける
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Seed(pub u64);
+
+impl Seed {
+    // 0 から数えた最初の game_number 個の種
+    pub fn first(game_number: usize) -> impl Iterator<Item = Seed> {
+        (0..game_number as u64).map(Seed)
+    }
+}
+
+impl Display for Seed {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Seed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse()
+            .map(Seed)
+            .map_err(|_| format!("invalid seed `{}`", s))
+    }
+}
+
+// 多人数ゲームのプレイヤー。0 が先手
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PlayerId(pub u8);
+
+impl PlayerId {
+    pub const FIRST: PlayerId = PlayerId(0);
+    pub const SECOND: PlayerId = PlayerId(1);
+    pub const BOTH: [PlayerId; 2] = [PlayerId::FIRST, PlayerId::SECOND];
+
+    // プレイヤーごとの配列を引くための添字
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    // 2人のゲームでの相手
+    pub fn opponent(self) -> PlayerId {
+        PlayerId(1 - self.0)
+    }
+}
+
+impl Display for PlayerId {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// 0 から数えたターン。ゲームの始めが Turn(0)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Turn(pub u32);
+
+impl Turn {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn next(self) -> Turn {
+        Turn(self.0 + 1)
+    }
+}
+
+impl Display for Turn {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -0,0 +1,332 @@
+use crate::provenance::Provenance;
+use crate::ScoreType;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+// 外部のツールとも共有できる最適化問題のインスタンス。1行目が種類と大きさ、続く行がデータ
+// '#' で始まる行と空行は読み飛ばす。例:
+//   tsp 3            knapsack 2 10      placement 2 3 2
+//   0 0              4 7                1 2 3
+//   3 4              6 9                4 5 6
+//   6 0
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Instance {
+    // 都市の座標。巡回路の長さはユークリッド距離を整数に丸めて足す
+    Tsp {
+        cities: Vec<(i64, i64)>,
+    },
+    // (重さ, 価値)
+    Knapsack {
+        capacity: ScoreType,
+        items: Vec<(ScoreType, ScoreType)>,
+    },
+    // 盤面に k 個の駒を別々のマスに置き、置いたマスの点の合計を最大にする
+    Placement {
+        points: Vec<Vec<ScoreType>>,
+        pieces: usize,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InstanceKind {
+    Tsp {
+        cities: usize,
+        size: i64,
+    },
+    Knapsack {
+        items: usize,
+        max_weight: ScoreType,
+        max_value: ScoreType,
+    },
+    Placement {
+        h: usize,
+        w: usize,
+        pieces: usize,
+        max_point: ScoreType,
+    },
+}
+
+impl InstanceKind {
+    // "tsp cities=20 size=100" のような文字列から作る。省略したパラメータは既定値
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut words = spec.split_whitespace();
+        let kind = words.next().ok_or("empty instance spec")?;
+        let mut params = Vec::new();
+        for word in words {
+            let (key, value) = word
+                .split_once('=')
+                .ok_or(format!("expected key=value, got `{}`", word))?;
+            let value: i64 = value
+                .parse()
+                .map_err(|_| format!("invalid number `{}` for `{}`", value, key))?;
+            params.push((key, value));
+        }
+        let known: &[&str] = match kind {
+            "tsp" => &["cities", "size"],
+            "knapsack" => &["items", "max_weight", "max_value"],
+            "placement" => &["h", "w", "pieces", "max_point"],
+            _ => {
+                return Err(format!(
+                    "unknown instance `{}` (tsp, knapsack, placement)",
+                    kind
+                ))
+            }
+        };
+        if let Some((key, _)) = params.iter().find(|(key, _)| !known.contains(key)) {
+            return Err(format!("`{}` does not take `{}`", kind, key));
+        }
+        let param = |key: &str, default: i64| {
+            params
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map_or(default, |&(_, v)| v)
+        };
+        Ok(match kind {
+            "tsp" => InstanceKind::Tsp {
+                cities: param("cities", 20) as usize,
+                size: param("size", 100),
+            },
+            "knapsack" => InstanceKind::Knapsack {
+                items: param("items", 20) as usize,
+                max_weight: param("max_weight", 10),
+                max_value: param("max_value", 10),
+            },
+            _ => InstanceKind::Placement {
+                h: param("h", 5) as usize,
+                w: param("w", 5) as usize,
+                pieces: param("pieces", 3) as usize,
+                max_point: param("max_point", 9),
+            },
+        })
+    }
+}
+
+impl Instance {
+    // 同じ kind と seed からは同じインスタンスを作る
+    pub fn generate(kind: InstanceKind, seed: u64) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        match kind {
+            InstanceKind::Tsp { cities, size } => Instance::Tsp {
+                cities: (0..cities)
+                    .map(|_| (rng.gen_range(0..size), rng.gen_range(0..size)))
+                    .collect(),
+            },
+            InstanceKind::Knapsack {
+                items,
+                max_weight,
+                max_value,
+            } => {
+                let items: Vec<(ScoreType, ScoreType)> = (0..items)
+                    .map(|_| (rng.gen_range(1..=max_weight), rng.gen_range(1..=max_value)))
+                    .collect();
+                // 全部は入らず、1つも入らないこともない程度の容量にする
+                let capacity = items.iter().map(|&(weight, _)| weight).sum::<ScoreType>() / 2;
+                Instance::Knapsack { capacity, items }
+            }
+            InstanceKind::Placement {
+                h,
+                w,
+                pieces,
+                max_point,
+            } => Instance::Placement {
+                points: (0..h)
+                    .map(|_| (0..w).map(|_| rng.gen_range(0..=max_point)).collect())
+                    .collect(),
+                pieces,
+            },
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        let header = lines.next().ok_or("empty instance")?;
+        let numbers = |line: &str| {
+            line.split_whitespace()
+                .map(|word| {
+                    word.parse::<i64>()
+                        .map_err(|_| format!("invalid number `{}` in `{}`", word, line))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let pair = |line: &str| match numbers(line)?[..] {
+            [a, b] => Ok((a, b)),
+            _ => Err(format!("expected two numbers, got `{}`", line)),
+        };
+        let (kind, sizes) = header.split_once(' ').unwrap_or((header, ""));
+        let sizes = numbers(sizes)?;
+        let instance = match (kind, &sizes[..]) {
+            ("tsp", &[n]) => Instance::Tsp {
+                cities: lines
+                    .by_ref()
+                    .take(n as usize)
+                    .map(pair)
+                    .collect::<Result<_, _>>()?,
+            },
+            ("knapsack", &[n, capacity]) => Instance::Knapsack {
+                capacity,
+                items: lines
+                    .by_ref()
+                    .take(n as usize)
+                    .map(pair)
+                    .collect::<Result<_, _>>()?,
+            },
+            ("placement", &[h, w, pieces]) => {
+                let points: Vec<Vec<ScoreType>> = lines
+                    .by_ref()
+                    .take(h as usize)
+                    .map(numbers)
+                    .collect::<Result<_, _>>()?;
+                if let Some(row) = points.iter().find(|row| row.len() != w as usize) {
+                    return Err(format!("expected {} points in a row, got {}", w, row.len()));
+                }
+                if pieces as usize > (h * w) as usize {
+                    return Err(format!("{} pieces do not fit on {}x{}", pieces, h, w));
+                }
+                Instance::Placement {
+                    points,
+                    pieces: pieces as usize,
+                }
+            }
+            _ => return Err(format!("invalid instance header `{}`", header)),
+        };
+        if instance.len() != sizes[0] as usize {
+            return Err(format!(
+                "header says {} but the file has {}",
+                sizes[0],
+                instance.len()
+            ));
+        }
+        if let Some(line) = lines.next() {
+            return Err(format!("unexpected line `{}`", line));
+        }
+        Ok(instance)
+    }
+
+    // ヘッダの最初の数 (都市数, 品物数, 盤面の行数)
+    fn len(&self) -> usize {
+        match self {
+            Instance::Tsp { cities } => cities.len(),
+            Instance::Knapsack { items, .. } => items.len(),
+            Instance::Placement { points, .. } => points.len(),
+        }
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        Instance::parse(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        writeln!(writer, "{}", Provenance::collect(1))?;
+        write!(writer, "{}", self)?;
+        writer.flush()
+    }
+
+    // 解も1行のテキストで受け取り、他のツールの出した解をそのまま採点できるようにする
+    //   tsp: 訪れる順の都市番号 "0 2 1" (最後に最初の都市へ戻る)。長さなので小さいほど良い
+    //   knapsack: 入れる品物の番号 "0 3"
+    //   placement: 駒を置くマス "y,x y,x"
+    pub fn score(&self, solution: &str) -> Result<ScoreType, String> {
+        let indices = |n: usize| {
+            let indices = solution
+                .split_whitespace()
+                .map(|word| {
+                    word.parse::<usize>()
+                        .ok()
+                        .filter(|&i| i < n)
+                        .ok_or(format!("invalid index `{}`", word))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut sorted = indices.clone();
+            sorted.sort_unstable();
+            sorted.dedup();
+            if sorted.len() != indices.len() {
+                return Err("an index appears twice".to_string());
+            }
+            Ok(indices)
+        };
+        match self {
+            Instance::Tsp { cities } => {
+                let tour = indices(cities.len())?;
+                if tour.len() != cities.len() {
+                    return Err(format!(
+                        "tour visits {} of {} cities",
+                        tour.len(),
+                        cities.len()
+                    ));
+                }
+                Ok((0..tour.len())
+                    .map(|i| {
+                        let (a, b) = (cities[tour[i]], cities[tour[(i + 1) % tour.len()]]);
+                        (((a.0 - b.0).pow(2) + (a.1 - b.1).pow(2)) as f64)
+                            .sqrt()
+                            .round() as ScoreType
+                    })
+                    .sum())
+            }
+            Instance::Knapsack { capacity, items } => {
+                let chosen = indices(items.len())?;
+                let weight: ScoreType = chosen.iter().map(|&i| items[i].0).sum();
+                if weight > *capacity {
+                    return Err(format!("weight {} exceeds capacity {}", weight, capacity));
+                }
+                Ok(chosen.iter().map(|&i| items[i].1).sum())
+            }
+            Instance::Placement { points, pieces } => {
+                let mut cells = Vec::new();
+                for word in solution.split_whitespace() {
+                    let cell = word
+                        .split_once(',')
+                        .and_then(|(y, x)| {
+                            Some((y.parse::<usize>().ok()?, x.parse::<usize>().ok()?))
+                        })
+                        .filter(|&(y, x)| y < points.len() && x < points[0].len())
+                        .ok_or(format!("invalid cell `{}`", word))?;
+                    if cells.contains(&cell) {
+                        return Err(format!("cell `{}` is used twice", word));
+                    }
+                    cells.push(cell);
+                }
+                if cells.len() != *pieces {
+                    return Err(format!("placed {} of {} pieces", cells.len(), pieces));
+                }
+                Ok(cells.iter().map(|&(y, x)| points[y][x]).sum())
+            }
+        }
+    }
+}
+
+impl Display for Instance {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Instance::Tsp { cities } => {
+                writeln!(f, "tsp {}", cities.len())?;
+                for (x, y) in cities {
+                    writeln!(f, "{} {}", x, y)?;
+                }
+            }
+            Instance::Knapsack { capacity, items } => {
+                writeln!(f, "knapsack {} {}", items.len(), capacity)?;
+                for (weight, value) in items {
+                    writeln!(f, "{} {}", weight, value)?;
+                }
+            }
+            Instance::Placement { points, pieces } => {
+                let w = points.first().map_or(0, Vec::len);
+                writeln!(f, "placement {} {} {}", points.len(), w, pieces)?;
+                for row in points {
+                    let row: Vec<String> = row.iter().map(ScoreType::to_string).collect();
+                    writeln!(f, "{}", row.join(" "))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
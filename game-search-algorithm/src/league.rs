@@ -0,0 +1,154 @@
+use crate::ids::{PlayerId, Seed};
+use crate::simultaneous::{CollisionRule, GameResult, PlayerRules, SimultaneousMazeState};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+// 同時手番の迷路で、行動の特徴量の重み付き和が最大の手を選ぶ方策
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinearPolicy {
+    pub weights: [f64; 3],
+}
+
+impl LinearPolicy {
+    pub fn action(&self, state: &SimultaneousMazeState, player: PlayerId) -> usize {
+        let mut best = (f64::NEG_INFINITY, 0);
+        for action in state.legal_actions(player) {
+            let features = state.action_features(player, action);
+            let score: f64 = features.iter().zip(&self.weights).map(|(f, w)| f * w).sum();
+            if score > best.0 {
+                best = (score, action);
+            }
+        }
+        best.1
+    }
+}
+
+// players[0] から見た勝ち点 (勝ち 1, 引き分け 0.5)
+pub fn play_match(players: [&LinearPolicy; 2], seed: Seed) -> f64 {
+    let mut state =
+        SimultaneousMazeState::from_seed(seed, CollisionRule::Split, [PlayerRules::default(); 2]);
+    while !state.is_done() {
+        let actions = [
+            players[0].action(&state, PlayerId::FIRST),
+            players[1].action(&state, PlayerId::SECOND),
+        ];
+        state.advance(actions);
+    }
+    match state.result() {
+        GameResult::Win(PlayerId::FIRST) => 1.0,
+        GameResult::Win(_) => 0.0,
+        GameResult::Draw => 0.5,
+    }
+}
+
+// 先手と後手を入れ替えて両方指した平均の勝ち点
+pub fn match_score(policy: &LinearPolicy, opponent: &LinearPolicy, seeds: &[Seed]) -> f64 {
+    let total: f64 = seeds
+        .iter()
+        .map(|&seed| {
+            play_match([policy, opponent], seed) + 1.0 - play_match([opponent, policy], seed)
+        })
+        .sum();
+    total / (2 * seeds.len()) as f64
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeagueMember {
+    pub name: String,
+    pub policy: LinearPolicy,
+    pub rating: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct League {
+    pub members: Vec<LeagueMember>,
+}
+
+const ELO_K: f64 = 16.0;
+
+fn elo_update(rating: &mut f64, opponent_rating: &mut f64, score: f64) {
+    let expected = 1.0 / (1.0 + 10f64.powf((*opponent_rating - *rating) / 400.0));
+    *rating += ELO_K * (score - expected);
+    *opponent_rating -= ELO_K * (score - expected);
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeagueOptions {
+    pub generations: usize,
+    // 候補1つを評価するときに当てる相手の数と、相手ごとの盤面数
+    pub opponents_per_generation: usize,
+    pub seeds_per_opponent: usize,
+    // この世代数ごとに今の方策を固定してリーグに加える
+    pub snapshot_interval: usize,
+    pub mutation: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeagueProgress {
+    pub generation: usize,
+    pub league_size: usize,
+    pub rating: f64,
+    pub policy: LinearPolicy,
+    // 最初のメンバー (貪欲法と同じ重み) に対する勝ち点
+    pub score_against_first: f64,
+}
+
+// 今の方策を少しずらした候補を、リーグから選んだ過去の版と戦わせて強いほうを残す
+pub fn league_training(
+    options: &LeagueOptions,
+    rng: &mut ChaCha8Rng,
+) -> (League, Vec<LeagueProgress>) {
+    let first = LinearPolicy {
+        weights: [1.0, 0.0, 0.0],
+    };
+    let mut league = League {
+        members: vec![LeagueMember {
+            name: "v0".to_string(),
+            policy: first,
+            rating: 1500.0,
+        }],
+    };
+    let mut current = first;
+    let mut rating = 1500.0;
+    let mut progress = Vec::new();
+    let mut next_seed = 0;
+    for generation in 1..=options.generations {
+        let mut candidate = current;
+        for weight in &mut candidate.weights {
+            *weight += rng.gen_range(-options.mutation..options.mutation);
+        }
+        let mut current_total = 0.0;
+        let mut candidate_total = 0.0;
+        for _ in 0..options.opponents_per_generation {
+            let opponent = rng.gen_range(0..league.members.len());
+            let seeds: Vec<Seed> = (next_seed..next_seed + options.seeds_per_opponent as u64)
+                .map(Seed)
+                .collect();
+            next_seed += options.seeds_per_opponent as u64;
+            let member = &mut league.members[opponent];
+            let score = match_score(&current, &member.policy, &seeds);
+            elo_update(&mut rating, &mut member.rating, score);
+            current_total += score;
+            candidate_total += match_score(&candidate, &member.policy, &seeds);
+        }
+        if candidate_total > current_total {
+            current = candidate;
+        }
+        if generation % options.snapshot_interval == 0 {
+            league.members.push(LeagueMember {
+                name: format!("v{}", generation),
+                policy: current,
+                rating,
+            });
+            let seeds: Vec<Seed> = Seed::first(100).collect();
+            progress.push(LeagueProgress {
+                generation,
+                league_size: league.members.len(),
+                rating,
+                policy: current,
+                score_against_first: match_score(&current, &first, &seeds),
+            });
+        }
+    }
+    (league, progress)
+}
@@ -0,0 +1,458 @@
+pub mod adversarial;
+pub mod agent;
+pub mod alternate;
+pub mod archive;
+pub mod async_agent;
+pub mod auction;
+pub mod audit;
+pub mod auto_move;
+pub mod belief;
+pub mod best_first;
+pub mod blunder;
+pub mod budget;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod conformance;
+pub mod connect_four;
+pub mod constraints;
+#[cfg(feature = "plot")]
+pub mod convergence;
+pub mod cooperative;
+pub mod determinized;
+pub mod dfbnb;
+#[cfg(feature = "server")]
+pub mod distributed;
+pub mod duct;
+pub mod episode;
+pub mod evaluator;
+pub mod explain;
+pub mod game_phase;
+pub mod hooks;
+pub mod ids;
+pub mod instances;
+pub mod league;
+pub mod limits;
+pub mod local_search;
+pub mod macro_action;
+pub mod manifest;
+pub mod mcts;
+pub mod mutation;
+pub mod nested;
+pub mod packing;
+pub mod patch;
+pub mod pattern;
+pub mod phases;
+pub mod policy_iteration;
+pub mod pool;
+pub mod portfolio;
+pub mod prior;
+pub mod provenance;
+pub mod pruning;
+#[cfg(feature = "serde")]
+pub mod record;
+pub mod repro;
+pub mod rhea;
+pub mod scenario;
+pub mod scheduler;
+pub mod search;
+pub mod selector;
+pub mod sequential_halving;
+pub mod simultaneous;
+pub mod soa_beam;
+pub mod soak;
+pub mod softmax_beam;
+pub mod solver;
+pub mod stats;
+pub mod suite;
+pub mod symmetry;
+pub mod td;
+pub mod tempering;
+pub mod test_positions;
+pub mod tournament;
+pub mod tuning;
+pub mod viewer;
+pub mod wall_maze;
+pub mod warm_start;
+pub mod zobrist_hash;
+pub mod zoo;
+
+use agent::Agent;
+use ids::{Seed, Turn};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+pub type ScoreType = i64;
+pub const INF: ScoreType = ScoreType::MAX;
+
+pub const H: usize = 3;
+pub const W: usize = 4;
+pub const END_TURN: usize = 4;
+pub const DX: [i64; 4] = [1, -1, 0, 0];
+pub const DY: [i64; 4] = [0, 0, 1, -1];
+
+// 盤面の大きさとターン数。既定値は H, W, END_TURN
+// 詰めた形式 (packing)、パターンの表、Zobrist の表、盤面の対称性は既定の大きさでしか使えない
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MazeParams {
+    pub h: usize,
+    pub w: usize,
+    pub end_turn: usize,
+}
+
+impl Default for MazeParams {
+    fn default() -> Self {
+        Self {
+            h: H,
+            w: W,
+            end_turn: END_TURN,
+        }
+    }
+}
+
+impl Display for MazeParams {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "h={} w={} end_turn={}", self.h, self.w, self.end_turn)
+    }
+}
+
+// "h=30 w=30 end_turn=100" のような文字列から読む。書かなかった値は既定値
+impl FromStr for MazeParams {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut params = MazeParams::default();
+        for word in s.split_whitespace() {
+            let (key, value) = word
+                .split_once('=')
+                .ok_or(format!("expected key=value, got `{}`", word))?;
+            let value: usize = value
+                .parse()
+                .map_err(|_| format!("invalid number `{}` for `{}`", value, key))?;
+            match key {
+                "h" => params.h = value,
+                "w" => params.w = value,
+                "end_turn" => params.end_turn = value,
+                _ => return Err(format!("unknown maze parameter `{}`", key)),
+            }
+        }
+        if params.h == 0 || params.w == 0 || params.h * params.w < 2 {
+            return Err(format!(
+                "a {}x{} board has no room to move",
+                params.h, params.w
+            ));
+        }
+        Ok(params)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Coord {
+    y: i64,
+    x: i64,
+}
+
+// ゲームが終わった理由
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Termination {
+    TurnLimit,
+    // 盤面の点をすべて取り終えた (END_TURN より前に終わることがある)
+    AllPointsCollected,
+}
+
+// 探索アルゴリズムが前提にする一人用ゲームの操作。search のアルゴリズムはこれだけで動く
+pub trait GameState: Clone {
+    fn legal_actions(&self) -> Vec<usize>;
+    fn advance(&mut self, action: usize);
+    fn is_done(&self) -> bool;
+    // 局面の評価値を計算して返す。大きいほど良い
+    fn evaluate_score(&mut self) -> ScoreType;
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MazeState {
+    points: Vec<Vec<ScoreType>>,
+    turn: usize,
+    character: Coord,
+    game_score: ScoreType,
+    evaluated_score: ScoreType,
+    first_action: Option<usize>,
+    // 直前の行動。枝刈りで来た道を戻る手を見分けるのに使う
+    last_action: Option<usize>,
+    params: MazeParams,
+}
+
+impl MazeState {
+    pub fn from_seed(seed: Seed) -> Self {
+        Self::from_seed_with_params(seed, MazeParams::default())
+    }
+
+    // 既定の大きさなら from_seed と同じ盤面になる
+    pub fn from_seed_with_params(seed: Seed, params: MazeParams) -> Self {
+        Self::from_rng(&mut ChaCha8Rng::seed_from_u64(seed.0), params)
+    }
+
+    // 盤面を作る乱数を差し替える。from_seed_with_params はシードから作った ChaCha8Rng を渡す
+    pub fn from_rng<R: Rng>(rng: &mut R, params: MazeParams) -> Self {
+        let y = rng.gen_range(0..params.h);
+        let x = rng.gen_range(0..params.w);
+
+        let mut points = vec![vec![0; params.w]; params.h];
+
+        for (j, row) in points.iter_mut().enumerate() {
+            for (i, point) in row.iter_mut().enumerate() {
+                if j == y && i == x {
+                    continue;
+                }
+                *point = rng.gen_range(0..10);
+            }
+        }
+
+        Self::from_layout(points, (y, x), params)
+    }
+
+    // 点の配置とキャラクターの位置 (y, x) を直接与えて始めの局面を作る。params の大きさと合わなければ panic
+    pub fn from_layout(
+        points: Vec<Vec<ScoreType>>,
+        character: (usize, usize),
+        params: MazeParams,
+    ) -> Self {
+        assert!(
+            points.len() == params.h && points.iter().all(|row| row.len() == params.w),
+            "points do not match {}x{}",
+            params.h,
+            params.w
+        );
+        assert!(character.0 < params.h && character.1 < params.w);
+        Self {
+            points,
+            turn: 0,
+            character: Coord {
+                y: character.0 as i64,
+                x: character.1 as i64,
+            },
+            game_score: 0,
+            evaluated_score: 0,
+            first_action: None,
+            last_action: None,
+            params,
+        }
+    }
+
+    // empty_percent % のマスの点を 0 にする。盤面とは別の乱数の列を使うので、点の残るマスは from_seed と同じ値
+    pub fn with_empty_cells(mut self, seed: Seed, empty_percent: u32) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        rng.set_stream(1);
+        for point in self.points.iter_mut().flatten() {
+            if rng.gen_range(0..100) < empty_percent {
+                *point = 0;
+            }
+        }
+        self
+    }
+
+    pub fn turn(&self) -> Turn {
+        Turn(self.turn as u32)
+    }
+
+    pub fn params(&self) -> MazeParams {
+        self.params
+    }
+
+    pub fn remaining_turns(&self) -> usize {
+        self.params.end_turn - self.turn
+    }
+
+    fn termination(&self) -> Option<Termination> {
+        if self.turn == self.params.end_turn {
+            Some(Termination::TurnLimit)
+        } else if self.points.iter().flatten().all(|&point| point == 0) {
+            Some(Termination::AllPointsCollected)
+        } else {
+            None
+        }
+    }
+
+    // 一様ランダムな合法手。乱数は呼び出し側が持ち、1つの seed から再現できるようにする
+    pub fn random_action(&self, rng: &mut impl Rng) -> usize {
+        let legal_actions = self.legal_actions();
+        legal_actions[rng.gen_range(0..legal_actions.len())]
+    }
+}
+
+impl GameState for MazeState {
+    fn legal_actions(&self) -> Vec<usize> {
+        let mut actions = Vec::new();
+        for action in 0..4 {
+            let y = self.character.y + DY[action];
+            let x = self.character.x + DX[action];
+            if y >= 0 && y < self.params.h as i64 && x >= 0 && x < self.params.w as i64 {
+                actions.push(action);
+            }
+        }
+        actions
+    }
+
+    fn advance(&mut self, action: usize) {
+        self.character.y += DY[action];
+        self.character.x += DX[action];
+        let point = &mut self.points[self.character.y as usize][self.character.x as usize];
+        if *point > 0 {
+            self.game_score += *point;
+            *point = 0;
+        }
+        self.turn += 1;
+        self.last_action = Some(action);
+    }
+
+    fn is_done(&self) -> bool {
+        self.termination().is_some()
+    }
+
+    fn evaluate_score(&mut self) -> ScoreType {
+        self.evaluated_score = self.game_score;
+        self.evaluated_score
+    }
+}
+
+impl PartialOrd for MazeState {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MazeState {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.evaluated_score.cmp(&other.evaluated_score)
+    }
+}
+
+impl Display for MazeState {
+    // `f` is a buffer, and this method must write the formatted string into it.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "turn:{}", self.turn)?;
+        writeln!(f, "score:{}", self.game_score)?;
+        let (y, x) = (self.character.y as usize, self.character.x as usize);
+        for j in 0..self.params.h {
+            for i in 0..self.params.w {
+                if j == y && i == x {
+                    write!(f, "@")?;
+                } else if self.points[j][i] > 0 {
+                    write!(f, "{}", self.points[j][i])?;
+                } else {
+                    write!(f, ".")?;
+                }
+            }
+            writeln!(f)?;
+        }
+        writeln!(f)
+    }
+}
+
+// 既定の大きさの盤面で1ゲーム指したスコア
+pub fn play_seed(agent: &mut dyn Agent, seed: Seed) -> ScoreType {
+    play_seed_with_params(agent, seed, MazeParams::default())
+}
+
+// 盤面の大きさとターン数を変えて1ゲーム指したスコア
+// 指した手を新しい盤面で指し直してスコアを確かめる。食い違えば対局の局面が壊れているので panic する
+pub fn play_seed_with_params(agent: &mut dyn Agent, seed: Seed, params: MazeParams) -> ScoreType {
+    let fresh = || MazeState::from_seed_with_params(seed, params);
+    let mut recorder = audit::ActionRecorder::default();
+    let score = hooks::play_from(agent, fresh(), &mut [&mut recorder]).game_score;
+    audit::audit_score(fresh(), &recorder.actions, score)
+        .unwrap_or_else(|e| panic!("score audit failed on seed {}: {}", seed, e));
+    score
+}
+
+pub fn test_ai_score(agent: &mut dyn Agent, game_number: usize) -> f64 {
+    let mut total_score = 0;
+    for seed in Seed::first(game_number) {
+        total_score += play_seed(agent, seed);
+    }
+    total_score as f64 / game_number as f64
+}
+
+// 複数ゲームのスコアの平均と標準偏差
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ScoreSummary {
+    pub games: usize,
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+impl ScoreSummary {
+    pub fn new(scores: &[ScoreType]) -> Self {
+        let n = scores.len().max(1) as f64;
+        let mean = scores.iter().sum::<ScoreType>() as f64 / n;
+        let variance = scores
+            .iter()
+            .map(|&score| (score as f64 - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        Self {
+            games: scores.len(),
+            mean,
+            stddev: variance.sqrt(),
+        }
+    }
+}
+
+impl Display for ScoreSummary {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "mean {:.3}, stddev {:.3} over {} games",
+            self.mean, self.stddev, self.games
+        )
+    }
+}
+
+// seeds の盤面を threads 本のスレッドで分けて指し、seeds の順にスコアを返す
+// エージェントはゲームごとに make_agent で作り直すので、スレッド数や割り振りによらず同じ結果になる
+pub fn play_seeds_parallel<F>(
+    make_agent: F,
+    seeds: &[Seed],
+    params: MazeParams,
+    threads: usize,
+) -> Vec<ScoreType>
+where
+    F: Fn() -> Box<dyn Agent> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let scores = Mutex::new(vec![0; seeds.len()]);
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&seed) = seeds.get(index) else {
+                    return;
+                };
+                let score = play_seed_with_params(make_agent().as_mut(), seed, params);
+                scores.lock().unwrap()[index] = score;
+            });
+        }
+    });
+    scores.into_inner().unwrap()
+}
+
+// test_ai_score を threads 本のスレッドで並列に行う。エージェントはゲームごとに作り直す
+pub fn test_ai_score_parallel<F>(make_agent: F, game_number: usize, threads: usize) -> ScoreSummary
+where
+    F: Fn() -> Box<dyn Agent> + Sync,
+{
+    let seeds: Vec<Seed> = Seed::first(game_number).collect();
+    ScoreSummary::new(&play_seeds_parallel(
+        make_agent,
+        &seeds,
+        MazeParams::default(),
+        threads,
+    ))
+}
@@ -0,0 +1,225 @@
+use crate::agent::{Agent, Capabilities};
+use crate::search;
+use crate::{GameState, MazeState, ScoreType, H, W};
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    pub time: Option<Duration>,
+    pub max_nodes: Option<usize>,
+    // 状態1個あたりのおおよそのバイト数から、同時に持てるノード数の上限に換算する
+    pub max_memory_bytes: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViolationKind {
+    Time,
+    Nodes,
+    Memory,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub turn: usize,
+    pub kind: ViolationKind,
+    pub elapsed: Duration,
+    pub nodes: usize,
+}
+
+pub fn approximate_state_bytes() -> usize {
+    std::mem::size_of::<MazeState>()
+        + std::mem::size_of::<Vec<ScoreType>>() * H
+        + std::mem::size_of::<ScoreType>() * H * W
+}
+
+// 1回の意思決定の間、時間とノード数を数えて上限を超えたかを判定する
+pub struct ResourceGuard {
+    limits: ResourceLimits,
+    start: Instant,
+    pub nodes: usize,
+    pub live_nodes: usize,
+    pub violation: Option<ViolationKind>,
+}
+
+impl ResourceGuard {
+    pub fn new(limits: ResourceLimits) -> Self {
+        Self {
+            limits,
+            start: Instant::now(),
+            nodes: 0,
+            live_nodes: 0,
+            violation: None,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    // 上限を超えていれば true を返し、最初に超えた種類を記録する
+    pub fn exceeded(&mut self) -> bool {
+        if self.violation.is_some() {
+            return true;
+        }
+        if let Some(time) = self.limits.time {
+            if self.elapsed() >= time {
+                self.violation = Some(ViolationKind::Time);
+            }
+        }
+        if let Some(max_nodes) = self.limits.max_nodes {
+            if self.nodes >= max_nodes {
+                self.violation = Some(ViolationKind::Nodes);
+            }
+        }
+        if let Some(max_memory_bytes) = self.limits.max_memory_bytes {
+            if self.live_nodes * approximate_state_bytes() >= max_memory_bytes {
+                self.violation = Some(ViolationKind::Memory);
+            }
+        }
+        self.violation.is_some()
+    }
+}
+
+// 展開のたびに上限を確認し、超えたらその時点で最良の状態の最初の行動を返すビームサーチ
+// 1層も展開できなかったときは貪欲法の手を返す
+pub fn beam_search_action_with_limits(
+    state: &MazeState,
+    beam_width: usize,
+    beam_depth: usize,
+    guard: &mut ResourceGuard,
+) -> usize {
+    let mut now_beam = BinaryHeap::new();
+    let mut best_action = None;
+
+    now_beam.push(state.clone());
+    'search: for t in 0..beam_depth {
+        let mut next_beam: BinaryHeap<MazeState> = BinaryHeap::new();
+        for _ in 0..beam_width {
+            if let Some(now_state) = now_beam.pop() {
+                for action in now_state.legal_actions() {
+                    if guard.exceeded() {
+                        // 途中まで展開した層でも、得点は前の層以上なのでその最良を使う
+                        if let Some(best) = next_beam.peek() {
+                            best_action = best.first_action;
+                        }
+                        break 'search;
+                    }
+                    let mut next_state = now_state.clone();
+                    next_state.advance(action);
+                    next_state.evaluate_score();
+                    if t == 0 {
+                        next_state.first_action = Some(action);
+                    }
+                    next_beam.push(next_state);
+                    guard.nodes += 1;
+                    guard.live_nodes = now_beam.len() + next_beam.len();
+                }
+            }
+        }
+
+        now_beam = next_beam;
+        let best_state = now_beam.peek().unwrap();
+        best_action = best_state.first_action;
+        if best_state.is_done() {
+            break;
+        }
+    }
+    best_action.unwrap_or_else(|| search::greedy_action(state))
+}
+
+pub struct LimitedBeamAgent {
+    pub beam_width: usize,
+    pub beam_depth: usize,
+    pub limits: ResourceLimits,
+    pub violations: Vec<Violation>,
+}
+
+impl LimitedBeamAgent {
+    pub fn new(beam_width: usize, beam_depth: usize, limits: ResourceLimits) -> Self {
+        Self {
+            beam_width,
+            beam_depth,
+            limits,
+            violations: Vec::new(),
+        }
+    }
+}
+
+impl Agent for LimitedBeamAgent {
+    fn name(&self) -> String {
+        format!(
+            "limited_beam(width={}, depth={}, {:?})",
+            self.beam_width, self.beam_depth, self.limits
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        let mut guard = ResourceGuard::new(self.limits);
+        let action =
+            beam_search_action_with_limits(state, self.beam_width, self.beam_depth, &mut guard);
+        if let Some(kind) = guard.violation {
+            self.violations.push(Violation {
+                turn: state.turn,
+                kind,
+                elapsed: guard.elapsed(),
+                nodes: guard.nodes,
+            });
+        }
+        action
+    }
+
+    fn set_time_limit(&mut self, time: Option<Duration>) {
+        self.limits.time = time;
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            time_limit: true,
+            // 時間で打ち切ると手がマシンの速さに依存する
+            deterministic: self.limits.time.is_none(),
+            ..Capabilities::SEARCH
+        }
+    }
+}
+
+// 途中で止められないエージェント向けに、意思決定後に時間を測って違反を記録するだけのラッパー
+pub struct LimitedAgent {
+    pub inner: Box<dyn Agent>,
+    pub time: Duration,
+    pub violations: Vec<Violation>,
+}
+
+impl Agent for LimitedAgent {
+    fn name(&self) -> String {
+        format!("limited({}, time={:?})", self.inner.name(), self.time)
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        self.select_action_with_score(state).0
+    }
+
+    fn select_action_with_score(&mut self, state: &MazeState) -> (usize, Option<ScoreType>) {
+        let start = Instant::now();
+        let decision = self.inner.select_action_with_score(state);
+        let elapsed = start.elapsed();
+        if elapsed > self.time {
+            self.violations.push(Violation {
+                turn: state.turn,
+                kind: ViolationKind::Time,
+                elapsed,
+                nodes: 0,
+            });
+        }
+        decision
+    }
+
+    fn set_time_limit(&mut self, time: Option<Duration>) {
+        self.time = time.unwrap_or(Duration::MAX);
+    }
+
+    // 時間を測って記録するだけなので、打ち切りには対応しない
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+}
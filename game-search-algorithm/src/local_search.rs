@@ -0,0 +1,547 @@
+use crate::budget::{Budget, BudgetLimit, BudgetMeter, Consumed};
+use crate::{GameState, MazeState, ScoreType, END_TURN};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+
+// 局所探索の打ち切り条件。指定したもののうち最初に満たしたもので止める
+// budget の iterations は反復回数、nodes は評価した解の数
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StoppingCriteria {
+    pub budget: Budget,
+    // この回数続けて最良値が更新されなければ止める
+    pub no_improvement: Option<usize>,
+    pub target_score: Option<ScoreType>,
+}
+
+impl StoppingCriteria {
+    pub fn iterations(iterations: usize) -> Self {
+        Self {
+            budget: Budget::iterations(iterations),
+            no_improvement: None,
+            target_score: None,
+        }
+    }
+}
+
+impl Default for StoppingCriteria {
+    fn default() -> Self {
+        Self::iterations(1000)
+    }
+}
+
+impl Display for StoppingCriteria {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut conditions = Vec::new();
+        if !self.budget.is_unlimited() {
+            conditions.push(self.budget.to_string());
+        }
+        if let Some(patience) = self.no_improvement {
+            conditions.push(format!("no_improvement={}", patience));
+        }
+        if let Some(target) = self.target_score {
+            conditions.push(format!("target={}", target));
+        }
+        write!(f, "{}", conditions.join(" "))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    Budget(BudgetLimit),
+    NoImprovement,
+    TargetScore,
+}
+
+impl Display for StopReason {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            StopReason::Budget(BudgetLimit::Iterations) => write!(f, "max iterations"),
+            StopReason::Budget(BudgetLimit::Nodes) => write!(f, "node budget"),
+            StopReason::Budget(BudgetLimit::Time) => write!(f, "time"),
+            StopReason::Budget(BudgetLimit::Cancelled) => write!(f, "cancel"),
+            StopReason::NoImprovement => write!(f, "no improvement"),
+            StopReason::TargetScore => write!(f, "target score"),
+        }
+    }
+}
+
+// 最良値が更新された時点の記録。収束の速さを比べるのに使う
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TracePoint {
+    pub iteration: usize,
+    pub nodes: usize,
+    pub elapsed: Duration,
+    pub best: ScoreType,
+}
+
+// 1回の探索の間、反復回数と最良値の推移を数えて打ち切りを判定する
+pub struct Stopper {
+    criteria: StoppingCriteria,
+    meter: BudgetMeter,
+    best: Option<ScoreType>,
+    stale: usize,
+    // 最良値が更新されるたびに1点足す
+    pub trace: Vec<TracePoint>,
+}
+
+impl Stopper {
+    pub fn new(criteria: StoppingCriteria) -> Self {
+        Self {
+            criteria,
+            meter: BudgetMeter::new(criteria.budget),
+            best: None,
+            stale: 0,
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn iterations(&self) -> usize {
+        self.meter.iterations
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.meter.elapsed()
+    }
+
+    // 解を評価するたびに呼ぶ
+    pub fn add_nodes(&mut self, nodes: usize) {
+        self.meter.add_nodes(nodes);
+    }
+
+    pub fn consumed(&self) -> Consumed {
+        self.meter.consumed()
+    }
+
+    // 1反復を終えたときにその時点の最良値を渡す。止めるべきならその理由を返す
+    pub fn step(&mut self, best: ScoreType) -> Option<StopReason> {
+        self.meter.add_iteration();
+        if self.best.map_or(true, |previous| best > previous) {
+            self.best = Some(best);
+            self.stale = 0;
+            self.trace.push(TracePoint {
+                iteration: self.meter.iterations,
+                nodes: self.meter.nodes,
+                elapsed: self.meter.elapsed(),
+                best,
+            });
+        } else {
+            self.stale += 1;
+        }
+        let criteria = &self.criteria;
+        if criteria.target_score.map_or(false, |target| best >= target) {
+            Some(StopReason::TargetScore)
+        } else if let Some(limit) = self.meter.exhausted() {
+            Some(StopReason::Budget(limit))
+        } else if criteria
+            .no_improvement
+            .map_or(false, |patience| self.stale >= patience)
+        {
+            Some(StopReason::NoImprovement)
+        } else {
+            None
+        }
+    }
+}
+
+// 1局分の行動列。遺伝子 g は各局面で legal_actions[g % 合法手数] と読む (RHEA と同じ)
+pub type Plan = Vec<usize>;
+
+pub fn plan_score(state: &MazeState, plan: &Plan) -> ScoreType {
+    let mut state = state.clone();
+    for &gene in plan {
+        if state.is_done() {
+            break;
+        }
+        let legal_actions = state.legal_actions();
+        state.advance(legal_actions[gene % legal_actions.len()]);
+    }
+    state.game_score
+}
+
+// plan が実際に指す手の列。ゲームが終われば残りの遺伝子は読まない
+pub fn decode_plan(state: &MazeState, plan: &[usize]) -> Vec<usize> {
+    let mut state = state.clone();
+    let mut actions = Vec::new();
+    for &gene in plan {
+        if state.is_done() {
+            break;
+        }
+        let legal_actions = state.legal_actions();
+        let action = legal_actions[gene % legal_actions.len()];
+        state.advance(action);
+        actions.push(action);
+    }
+    actions
+}
+
+// 実際に指す手が食い違うターンの数。遺伝子が違っても同じ手になるなら同じ解と見なす
+pub fn plan_distance(state: &MazeState, a: &Plan, b: &Plan) -> usize {
+    let (a, b) = (decode_plan(state, a), decode_plan(state, b));
+    let differing = a.iter().zip(&b).filter(|(x, y)| x != y).count();
+    differing + a.len().abs_diff(b.len())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LocalSearchMethod {
+    HillClimbing,
+    // 悪くなる変更も exp(差 / 温度) の確率で受け入れる。温度は毎反復 cooling 倍にする
+    Annealing { temperature: f64, cooling: f64 },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalSearchOutcome {
+    pub plan: Plan,
+    pub score: ScoreType,
+    pub reason: StopReason,
+    pub consumed: Consumed,
+    pub trace: Vec<TracePoint>,
+}
+
+// 行動列の近傍。どれも行動列の長さを変えない
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Neighborhood {
+    // 1つの遺伝子を書き換える
+    Mutate,
+    // 2つの遺伝子を入れ替える
+    Swap,
+    // 1つの遺伝子を取り除いて後ろを前に詰め、末尾に新しい遺伝子を足す
+    Shift,
+}
+
+impl Neighborhood {
+    pub fn apply(self, plan: &mut Plan, rng: &mut ChaCha8Rng) {
+        match self {
+            Neighborhood::Mutate => {
+                let i = rng.gen_range(0..plan.len());
+                plan[i] = rng.gen_range(0..4);
+            }
+            Neighborhood::Swap => {
+                let i = rng.gen_range(0..plan.len());
+                let j = rng.gen_range(0..plan.len());
+                plan.swap(i, j);
+            }
+            Neighborhood::Shift => {
+                let i = rng.gen_range(0..plan.len());
+                plan.remove(i);
+                plan.push(rng.gen_range(0..4));
+            }
+        }
+    }
+}
+
+impl FromStr for Neighborhood {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mutate" => Ok(Neighborhood::Mutate),
+            "swap" => Ok(Neighborhood::Swap),
+            "shift" => Ok(Neighborhood::Shift),
+            _ => Err(format!(
+                "unknown neighborhood `{}` (mutate, swap, shift)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Neighborhood {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Neighborhood::Mutate => write!(f, "mutate"),
+            Neighborhood::Swap => write!(f, "swap"),
+            Neighborhood::Shift => write!(f, "shift"),
+        }
+    }
+}
+
+// 複数の近傍のどれを次に試すか
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OperatorSelection {
+    Uniform,
+    // 直近の改善率に比例したルーレットで選ぶ。改善率は decay で指数移動平均をとり、
+    // 重みが min_weight を下回った近傍も時々は試して、局面が変われば戻ってこられるようにする
+    Roulette { decay: f64, min_weight: f64 },
+}
+
+impl OperatorSelection {
+    fn choose(&self, stats: &[OperatorStats], rng: &mut ChaCha8Rng) -> usize {
+        // 近傍が1つなら乱数を使わない (近傍を1つしか渡さない呼び出しの乱数列を変えない)
+        if stats.len() == 1 {
+            return 0;
+        }
+        match *self {
+            OperatorSelection::Uniform => rng.gen_range(0..stats.len()),
+            OperatorSelection::Roulette { min_weight, .. } => {
+                let weights: Vec<f64> = stats.iter().map(|s| s.weight.max(min_weight)).collect();
+                let mut r = rng.gen::<f64>() * weights.iter().sum::<f64>();
+                for (k, weight) in weights.iter().enumerate() {
+                    if r < *weight {
+                        return k;
+                    }
+                    r -= weight;
+                }
+                stats.len() - 1
+            }
+        }
+    }
+
+    fn update(&self, stats: &mut OperatorStats, improved: bool) {
+        if let OperatorSelection::Roulette { decay, .. } = *self {
+            let reward = if improved { 1.0 } else { 0.0 };
+            stats.weight = decay * stats.weight + (1.0 - decay) * reward;
+        }
+    }
+}
+
+// 近傍ごとの試行回数と結果
+#[derive(Clone, Debug, PartialEq)]
+pub struct OperatorStats {
+    pub operator: Neighborhood,
+    pub applied: usize,
+    pub accepted: usize,
+    // 今の行動列より良くなった回数
+    pub improved: usize,
+    // ルーレットでの重み。最初はどの近傍も試されるよう 1 から始める
+    pub weight: f64,
+}
+
+impl OperatorStats {
+    pub fn new(operator: Neighborhood) -> Self {
+        Self {
+            operator,
+            applied: 0,
+            accepted: 0,
+            improved: 0,
+            weight: 1.0,
+        }
+    }
+
+    pub fn acceptance_rate(&self) -> f64 {
+        self.accepted as f64 / self.applied.max(1) as f64
+    }
+
+    pub fn improvement_rate(&self) -> f64 {
+        self.improved as f64 / self.applied.max(1) as f64
+    }
+
+    // 別の探索の回数を足し込む。重みは後の探索のものを残す
+    pub fn add(&mut self, other: &OperatorStats) {
+        self.applied += other.applied;
+        self.accepted += other.accepted;
+        self.improved += other.improved;
+        self.weight = other.weight;
+    }
+}
+
+impl Display for OperatorStats {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: applied {}, accepted {} ({:.1}%), improved {} ({:.1}%), weight {:.3}",
+            self.operator,
+            self.applied,
+            self.accepted,
+            100.0 * self.acceptance_rate(),
+            self.improved,
+            100.0 * self.improvement_rate(),
+            self.weight
+        )
+    }
+}
+
+// 1つの遺伝子を書き換える近傍で行動列を改善する
+pub fn local_search(
+    state: &MazeState,
+    initial: Plan,
+    method: LocalSearchMethod,
+    criteria: StoppingCriteria,
+    rng: &mut ChaCha8Rng,
+) -> LocalSearchOutcome {
+    local_search_with_operators(
+        state,
+        initial,
+        method,
+        criteria,
+        &[Neighborhood::Mutate],
+        OperatorSelection::Uniform,
+        rng,
+    )
+    .0
+}
+
+// 反復ごとに operators から selection で1つ選んで近傍を作る。近傍ごとの統計も返す
+pub fn local_search_with_operators(
+    state: &MazeState,
+    initial: Plan,
+    method: LocalSearchMethod,
+    criteria: StoppingCriteria,
+    operators: &[Neighborhood],
+    selection: OperatorSelection,
+    rng: &mut ChaCha8Rng,
+) -> (LocalSearchOutcome, Vec<OperatorStats>) {
+    local_search_with_objective(
+        initial,
+        method,
+        criteria,
+        operators,
+        selection,
+        &mut |plan, _| plan_score(state, plan),
+        rng,
+    )
+}
+
+// 得点の代わりに objective を最大化する。objective は評価の前に行動列を直してもよい (制約の修復など)
+pub fn local_search_with_objective(
+    initial: Plan,
+    method: LocalSearchMethod,
+    criteria: StoppingCriteria,
+    operators: &[Neighborhood],
+    selection: OperatorSelection,
+    objective: &mut dyn FnMut(&mut Plan, &mut ChaCha8Rng) -> ScoreType,
+    rng: &mut ChaCha8Rng,
+) -> (LocalSearchOutcome, Vec<OperatorStats>) {
+    let mut stats: Vec<OperatorStats> = operators.iter().map(|&o| OperatorStats::new(o)).collect();
+    let mut stopper = Stopper::new(criteria);
+    let mut current = initial;
+    let mut current_score = objective(&mut current, rng);
+    stopper.add_nodes(1);
+    let mut best = (current_score, current.clone());
+    let mut temperature = match method {
+        LocalSearchMethod::HillClimbing => 0.0,
+        LocalSearchMethod::Annealing { temperature, .. } => temperature,
+    };
+    loop {
+        let k = selection.choose(&stats, rng);
+        let mut candidate = current.clone();
+        stats[k].operator.apply(&mut candidate, rng);
+        let score = objective(&mut candidate, rng);
+        stopper.add_nodes(1);
+        let accept = score >= current_score
+            || (temperature > 0.0
+                && rng.gen::<f64>() < ((score - current_score) as f64 / temperature).exp());
+        let improved = score > current_score;
+        stats[k].applied += 1;
+        stats[k].accepted += accept as usize;
+        stats[k].improved += improved as usize;
+        selection.update(&mut stats[k], improved);
+        if accept {
+            current = candidate;
+            current_score = score;
+            if current_score > best.0 {
+                best = (current_score, current.clone());
+            }
+        }
+        if let LocalSearchMethod::Annealing { cooling, .. } = method {
+            temperature *= cooling;
+        }
+        if let Some(reason) = stopper.step(best.0) {
+            let outcome = LocalSearchOutcome {
+                plan: best.1,
+                score: best.0,
+                reason,
+                consumed: stopper.consumed(),
+                trace: stopper.trace,
+            };
+            return (outcome, stats);
+        }
+    }
+}
+
+pub fn random_plan(rng: &mut ChaCha8Rng) -> Plan {
+    (0..END_TURN).map(|_| rng.gen_range(0..4)).collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RestartStrategy {
+    // 毎回ランダムな行動列から始め直す
+    Random,
+    // 最良の行動列の perturbation 個の遺伝子を書き換えてから局所探索し、悪くならなければ移る
+    BasinHopping { perturbation: usize },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RestartStats {
+    // 各回の局所探索が見つけた最良値。長さが実行回数
+    pub best_per_restart: Vec<ScoreType>,
+    // 全体の最良値を更新した回の番号
+    pub improving_restarts: Vec<usize>,
+    pub iterations: usize,
+}
+
+// 任意の最適化 optimize を繰り返し呼ぶ。outer で実行回数や目標値を決め、最良の結果と統計を返す
+// outer の iterations は実行回数、nodes は全回で評価した解の数の合計
+// 返す結果の reason と consumed は outer のもの。warm_start を渡せば最初の回はそこから始める
+pub fn with_restarts<F>(
+    state: &MazeState,
+    strategy: RestartStrategy,
+    outer: StoppingCriteria,
+    warm_start: Option<Plan>,
+    rng: &mut ChaCha8Rng,
+    mut optimize: F,
+) -> (LocalSearchOutcome, RestartStats)
+where
+    F: FnMut(&MazeState, Plan, &mut ChaCha8Rng) -> LocalSearchOutcome,
+{
+    let mut stopper = Stopper::new(outer);
+    let mut stats = RestartStats::default();
+    let mut best: Option<LocalSearchOutcome> = None;
+    let mut warm_start = warm_start;
+    // 各回の推移を通しの反復回数と経過時間に直してつなぐ
+    let mut trace = Vec::new();
+    loop {
+        let initial = match warm_start.take() {
+            Some(plan) => plan,
+            None => match (strategy, &best) {
+                (RestartStrategy::BasinHopping { perturbation }, Some(best)) => {
+                    let mut plan = best.plan.clone();
+                    for _ in 0..perturbation {
+                        let i = rng.gen_range(0..plan.len());
+                        plan[i] = rng.gen_range(0..4);
+                    }
+                    plan
+                }
+                _ => random_plan(rng),
+            },
+        };
+        let offset = stopper.consumed();
+        let outcome = optimize(state, initial, rng);
+        for point in &outcome.trace {
+            if best.as_ref().map_or(true, |best| point.best > best.score) {
+                trace.push(TracePoint {
+                    iteration: stats.iterations + point.iteration,
+                    nodes: offset.nodes + point.nodes,
+                    elapsed: offset.time + point.elapsed,
+                    best: point.best,
+                });
+            }
+        }
+        stats.iterations += outcome.consumed.iterations;
+        stopper.add_nodes(outcome.consumed.nodes);
+        if best
+            .as_ref()
+            .map_or(true, |best| outcome.score > best.score)
+        {
+            stats.improving_restarts.push(stats.best_per_restart.len());
+        }
+        stats.best_per_restart.push(outcome.score);
+        // 同点なら新しい方に移り、盆地の間を渡れるようにする
+        if best
+            .as_ref()
+            .map_or(true, |best| outcome.score >= best.score)
+        {
+            best = Some(outcome);
+        }
+        let best_score = best.as_ref().unwrap().score;
+        if let Some(reason) = stopper.step(best_score) {
+            let mut best = best.unwrap();
+            best.reason = reason;
+            best.consumed = stopper.consumed();
+            best.trace = trace;
+            return (best, stats);
+        }
+    }
+}
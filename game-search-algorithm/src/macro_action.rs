@@ -0,0 +1,118 @@
+use crate::agent::Agent;
+use crate::{GameState, MazeState};
+use std::collections::BinaryHeap;
+
+// 点のあるマスまで最短路で進む複合手。path の長さだけターンを消費する
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MacroAction {
+    pub target: (usize, usize),
+    pub path: Vec<usize>,
+}
+
+fn steps(from: i64, to: i64, forward: usize, backward: usize) -> Vec<usize> {
+    let action = if to > from { forward } else { backward };
+    vec![action; (to - from).unsigned_abs() as usize]
+}
+
+impl MazeState {
+    // 残りターン内に届く点のマスごとに、縦を先に進む経路と横を先に進む経路を作る
+    pub fn macro_actions(&self) -> Vec<MacroAction> {
+        let remaining_turns = self.remaining_turns();
+        let mut macros = Vec::new();
+        for (y, row) in self.points.iter().enumerate() {
+            for (x, &point) in row.iter().enumerate() {
+                if point == 0 {
+                    continue;
+                }
+                // DY[2] = 1, DY[3] = -1, DX[0] = 1, DX[1] = -1
+                let vertical = steps(self.character.y, y as i64, 2, 3);
+                let horizontal = steps(self.character.x, x as i64, 0, 1);
+                if vertical.len() + horizontal.len() > remaining_turns {
+                    continue;
+                }
+                let vertical_first = [vertical.clone(), horizontal.clone()].concat();
+                let horizontal_first = [horizontal, vertical].concat();
+                let distinct = vertical_first != horizontal_first;
+                macros.push(MacroAction {
+                    target: (y, x),
+                    path: vertical_first,
+                });
+                if distinct {
+                    macros.push(MacroAction {
+                        target: (y, x),
+                        path: horizontal_first,
+                    });
+                }
+            }
+        }
+        macros
+    }
+
+    pub fn advance_macro(&mut self, macro_action: &MacroAction) {
+        for &action in &macro_action.path {
+            debug_assert!(self.legal_actions().contains(&action));
+            self.advance(action);
+        }
+    }
+}
+
+// 複合手を1手として展開するビームサーチ。各状態はそれぞれのターン数まで進んでいる
+// 届く点がなくなった状態はそれ以上点が増えないので、そこで打ち切る
+pub fn macro_beam_search_action(state: &MazeState, beam_width: usize) -> usize {
+    let mut now_beam = BinaryHeap::new();
+    let mut best_state: Option<MazeState> = None;
+
+    now_beam.push(state.clone());
+    while !now_beam.is_empty() {
+        let mut next_beam = BinaryHeap::new();
+        for _ in 0..beam_width {
+            let Some(now_state) = now_beam.pop() else {
+                break;
+            };
+            let macros = if now_state.is_done() {
+                Vec::new()
+            } else {
+                now_state.macro_actions()
+            };
+            if macros.is_empty() {
+                if now_state.first_action.is_some()
+                    && best_state
+                        .as_ref()
+                        .map_or(true, |best| now_state.game_score > best.game_score)
+                {
+                    best_state = Some(now_state);
+                }
+                continue;
+            }
+            for macro_action in macros {
+                let mut next_state = now_state.clone();
+                next_state.advance_macro(&macro_action);
+                next_state.evaluate_score();
+                if next_state.first_action.is_none() {
+                    next_state.first_action = Some(macro_action.path[0]);
+                }
+                next_beam.push(next_state);
+            }
+        }
+        now_beam = next_beam;
+    }
+    // 届く点が1つもなければ何を指しても同じ
+    best_state.map_or_else(
+        || state.legal_actions()[0],
+        |best| best.first_action.unwrap(),
+    )
+}
+
+pub struct MacroBeamAgent {
+    pub beam_width: usize,
+}
+
+impl Agent for MacroBeamAgent {
+    fn name(&self) -> String {
+        format!("macro_beam(width={})", self.beam_width)
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        macro_beam_search_action(state, self.beam_width)
+    }
+}
@@ -1,219 +1,2700 @@
+use game_search_algorithm::agent::{Agent, BeamSearchAgent, GreedyAgent, RandomAgent};
+use game_search_algorithm::alternate::TwoPlayerGame;
+use game_search_algorithm::budget::Budget;
+use game_search_algorithm::evaluator::EnsembleBeamAgent;
+use game_search_algorithm::ids::Seed;
+use game_search_algorithm::nested::{NestedMonteCarloAgent, NestedRolloutPolicyAgent};
+use game_search_algorithm::pool::PooledBeamAgent;
+use game_search_algorithm::portfolio::{PortfolioAgent, PortfolioRule};
+use game_search_algorithm::prior::GreedyPrior;
+use game_search_algorithm::rhea::RheaAgent;
+use game_search_algorithm::selector::SelectorAgent;
+use game_search_algorithm::sequential_halving::{RootPolicy, SequentialHalvingAgent};
+use game_search_algorithm::softmax_beam::SoftmaxBeamAgent;
+use game_search_algorithm::solver::{CacheOptions, CachedAgent, KeyWidth, SolverCache};
+use game_search_algorithm::{
+    adversarial, agent, alternate, archive, async_agent, auction, audit, auto_move, belief,
+    best_first, blunder, budget, conformance, connect_four, constraints, convergence, cooperative,
+    determinized, dfbnb, distributed, duct, episode, evaluator, game_phase, hooks, instances,
+    league, limits, local_search, macro_action, manifest, mcts, nested, packing, patch, pattern,
+    phases, play_seed_with_params, play_seeds_parallel, policy_iteration, pool, prior, provenance,
+    pruning, record, repro, scenario, scheduler, search, selector, sequential_halving,
+    simultaneous, soa_beam, soak, softmax_beam, solver, stats, suite, symmetry, td, tempering,
+    test_ai_score, test_positions, tournament, tuning, viewer, wall_maze, warm_start, zobrist_hash,
+    zoo, GameState, MazeParams, MazeState, ScoreSummary, ScoreType, END_TURN,
+};
 use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
-use std::{
-    collections::BinaryHeap,
-    fmt::{self, Display, Formatter},
-};
 
-type ScoreType = i64;
-const INF: ScoreType = ScoreType::MAX;
+#[allow(dead_code)]
+fn play_game(seed: Seed) {
+    let mut state = MazeState::from_seed(seed);
+    while !state.is_done() {
+        // state.advance(state.random_action(&mut rng)); // ランダム行動
+        state.advance(search::greedy_action(&state)); // 貪欲法
+        println!("{}", state);
+    }
+}
 
-const H: usize = 3;
-const W: usize = 4;
-const END_TURN: usize = 4;
-const DX: [i64; 4] = [1, -1, 0, 0];
-const DY: [i64; 4] = [0, 0, 1, -1];
+fn run_tournament<G: TwoPlayerGame + 'static>(
+    new_game: &dyn Fn(Seed) -> G,
+    a: &str,
+    b: &str,
+    seed_number: usize,
+) -> Result<tournament::TournamentReport, String> {
+    let mut a = tournament::parse_two_player_ai(a)?;
+    let mut b = tournament::parse_two_player_ai(b)?;
+    tournament::play_tournament(new_game, &mut a, &mut b, seed_number)
+}
 
-#[derive(PartialEq, Eq, Clone, Debug)]
-struct Coord {
-    y: i64,
-    x: i64,
+fn default_portfolio(rule: PortfolioRule, parallel: bool) -> PortfolioAgent {
+    PortfolioAgent::new(
+        vec![
+            Box::new(GreedyAgent),
+            Box::new(BeamSearchAgent::new(2, Budget::iterations(END_TURN))),
+            Box::new(BeamSearchAgent::new(5, Budget::nodes(64))),
+        ],
+        rule,
+        parallel,
+    )
 }
 
-impl Default for Coord {
-    fn default() -> Self {
-        Self { y: 0, x: 0 }
+fn run_determinism_audit(game_number: usize) {
+    let factories: Vec<Box<dyn Fn() -> Box<dyn Agent>>> = vec![
+        Box::new(|| Box::new(RandomAgent::new(0))),
+        Box::new(|| Box::new(GreedyAgent)),
+        Box::new(|| Box::new(BeamSearchAgent::new(2, Budget::iterations(END_TURN)))),
+        Box::new(|| Box::new(BeamSearchAgent::new(2, Budget::nodes(32)))),
+        Box::new(|| Box::new(default_portfolio(PortfolioRule::Vote, true))),
+        Box::new(|| Box::new(default_portfolio(PortfolioRule::BestScore, true))),
+        Box::new(|| Box::new(SelectorAgent::from_rules(selector::DEFAULT_RULES).unwrap())),
+        Box::new(|| Box::new(EnsembleBeamAgent::new(2, None, 1.0))),
+        Box::new(|| {
+            Box::new(SoftmaxBeamAgent::new(
+                2,
+                Budget::iterations(END_TURN),
+                2.0,
+                0.5,
+                0,
+            ))
+        }),
+        Box::new(|| Box::new(NestedMonteCarloAgent::new(2, 0))),
+        Box::new(|| Box::new(mcts::PrimitiveMonteCarloAgent::new(100, 0))),
+        Box::new(|| Box::new(mcts::MctsAgent::new(100, 0))),
+        Box::new(|| Box::new(NestedRolloutPolicyAgent::new(2, 10, 0))),
+        Box::new(|| Box::new(RheaAgent::new(END_TURN, 10, Budget::iterations(6), 0.2, 0))),
+        Box::new(|| {
+            Box::new(SequentialHalvingAgent::new(
+                RootPolicy::SequentialHalving,
+                100,
+                0,
+            ))
+        }),
+        Box::new(|| Box::new(PooledBeamAgent::new(2, Budget::iterations(END_TURN)))),
+        Box::new(|| {
+            Box::new(limits::LimitedBeamAgent::new(
+                5,
+                END_TURN,
+                limits::ResourceLimits {
+                    max_nodes: Some(20),
+                    ..limits::ResourceLimits::default()
+                },
+            ))
+        }),
+        Box::new(|| Box::new(SequentialHalvingAgent::new(RootPolicy::Shot, 100, 0))),
+        Box::new(|| {
+            let mut agent = SequentialHalvingAgent::new(RootPolicy::Shot, 100, 0);
+            agent.prior = Box::new(GreedyPrior { temperature: 2.0 });
+            Box::new(agent)
+        }),
+        Box::new(|| Box::new(macro_action::MacroBeamAgent { beam_width: 3 })),
+        Box::new(|| Box::new(pruning::PrunedBeamAgent::new(2, END_TURN))),
+    ];
+    for make_agent in factories {
+        let agent = make_agent();
+        let name = agent.name();
+        // 決定的と名乗らないエージェントは比べても意味がない
+        if !agent.capabilities().deterministic {
+            println!("{}: skipped (not deterministic)", name);
+            continue;
+        }
+        match audit::audit_all(make_agent, game_number) {
+            Ok(()) => println!("{}: ok", name),
+            Err(divergence) => {
+                eprintln!("{}", divergence);
+                std::process::exit(1);
+            }
+        }
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Debug)]
-struct MazeState {
-    points: Vec<Vec<ScoreType>>,
-    turn: usize,
-    character: Coord,
-    game_score: ScoreType,
-    evaluated_score: ScoreType,
-    first_action: Option<usize>,
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == name)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
 }
 
-impl MazeState {
-    fn from_seed(seed: u64) -> Self {
-        let mut rng = ChaCha8Rng::seed_from_u64(seed);
-
-        let y = rng.gen_range(0..H);
-        let x = rng.gen_range(0..W);
+// --name の値を読む。読めなければエラーを出して終わる
+fn parsed_option<T: std::str::FromStr>(args: &[String], name: &str) -> Option<T> {
+    option_value(args, name).map(|value| parse_or_exit(value, name))
+}
 
-        let mut points = vec![vec![0; W]; H];
+// 読めない値はメッセージを出して終了コード 1 で終わる
+fn parse_or_exit<T: std::str::FromStr>(value: &str, name: &str) -> T {
+    value.trim().parse().unwrap_or_else(|_| {
+        eprintln!("invalid value `{}` for {}", value, name);
+        std::process::exit(1);
+    })
+}
 
-        for j in 0..H {
-            for i in 0..W {
-                if j == y && i == x {
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("--list-agents") => {
+            for agent_kind in agent::AGENT_KINDS {
+                println!("{}", agent_kind);
+            }
+        }
+        Some("analyze") => {
+            // analyze [seed] [--agent SPEC] [--suggest 0,2,1] [--width W] 1手ごとに盤面と手の説明を出す
+            // --suggest を渡すと、その手を順に指して幅 W のビームサーチの選ぶ手と比べる (尽きたら探索の手を指す)
+            let seed: Seed = args
+                .get(2)
+                .filter(|a| !a.starts_with("--"))
+                .map_or(Seed(0), |s| parse_or_exit(s, "seed"));
+            let spec = option_value(&args, "--agent").unwrap_or("beam width=2 depth=4");
+            let mut agent = agent::parse_agent(spec).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let suggestions: Vec<usize> =
+                option_value(&args, "--suggest").map_or(Vec::new(), |s| {
+                    s.split(',')
+                        .map(|a| parse_or_exit(a, "--suggest"))
+                        .collect()
+                });
+            let beam_width = parsed_option(&args, "--width").unwrap_or(2);
+            let mut state = MazeState::from_seed(seed);
+            while !state.is_done() {
+                print!("{}", state);
+                if let Some(&suggested) = suggestions.get(state.turn().index()) {
+                    match state.counterfactual(suggested, beam_width, Budget::iterations(END_TURN))
+                    {
+                        Ok(counterfactual) => println!("{}\n", counterfactual),
+                        Err(e) => {
+                            eprintln!("turn {}: {}", state.turn(), e);
+                            std::process::exit(1);
+                        }
+                    }
+                    state.advance(suggested);
                     continue;
                 }
-                points[j][i] = rng.gen_range(0..10);
+                let action = agent.select_action(&state);
+                match agent.explain_last_decision() {
+                    Some(explanation) => println!("{}\n", explanation),
+                    None => println!("chose {} ({} gives no explanation)\n", action, agent.name()),
+                }
+                state.advance(action);
             }
+            print!("{}", state);
         }
-
-        Self {
-            points,
-            turn: 0,
-            character: Coord {
-                y: y as i64,
-                x: x as i64,
-            },
-            game_score: 0,
-            evaluated_score: 0,
-            first_action: None,
+        Some("blunders") => {
+            // blunders [replay_path] [--agent SPEC] [--games N] [--width W] [--margin M] [--extract path]
+            // 記録を渡さなければ --agent でその場で対局して調べる
+            let replays = match args.get(2).filter(|a| !a.starts_with("--")) {
+                Some(path) => episode::load_replays(path).unwrap_or_else(|e| {
+                    eprintln!("{}: {}", path, e);
+                    std::process::exit(1);
+                }),
+                None => {
+                    let spec = option_value(&args, "--agent").unwrap_or("greedy");
+                    let game_number = parsed_option(&args, "--games").unwrap_or(100);
+                    let mut agent = agent::parse_agent(spec).unwrap_or_else(|e| {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    });
+                    Seed::first(game_number)
+                        .map(|seed| (seed, episode::play_episode(agent.as_mut(), seed).actions))
+                        .collect()
+                }
+            };
+            let beam_width = parsed_option(&args, "--width").unwrap_or(10);
+            let margin = parsed_option(&args, "--margin").unwrap_or(1);
+            let mut blunder_number = 0;
+            let mut total_loss = 0;
+            let mut decisions = 0;
+            let mut found = Vec::new();
+            for (seed, actions) in &replays {
+                decisions += actions.len();
+                let blunders = blunder::find_blunders(*seed, actions, beam_width, margin)
+                    .unwrap_or_else(|e| {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    });
+                for blunder in blunders {
+                    println!("{}", blunder);
+                    print!("{}", blunder.state);
+                    blunder_number += 1;
+                    total_loss += blunder.best_value - blunder.played_value;
+                    found.push(blunder);
+                }
+            }
+            println!(
+                "{} blunders in {} decisions, total loss {}",
+                blunder_number, decisions, total_loss
+            );
+            // --extract を渡すと悪手の局面を最善手つきのテスト局面集として書き出す
+            if let Some(path) = option_value(&args, "--extract") {
+                let positions = test_positions::from_blunders(&found);
+                test_positions::save(path, &positions).unwrap_or_else(|e| {
+                    eprintln!("{}: {}", path, e);
+                    std::process::exit(1);
+                });
+                println!("{} test positions written to {}", positions.len(), path);
+            }
         }
-    }
-
-    fn is_done(&self) -> bool {
-        self.turn == END_TURN
-    }
-
-    fn advance(&mut self, action: usize) {
-        self.character.y += DY[action];
-        self.character.x += DX[action];
-        let point = &mut self.points[self.character.y as usize][self.character.x as usize];
-        if *point > 0 {
-            self.game_score += *point;
-            *point = 0;
-        }
-        self.turn += 1;
-    }
-
-    fn legal_actions(&self) -> Vec<usize> {
-        let mut actions = Vec::new();
-        for action in 0..4 {
-            let y = self.character.y + DY[action];
-            let x = self.character.x + DX[action];
-            if y >= 0 && y < H as i64 && x >= 0 && x < W as i64 {
-                actions.push(action);
+        Some("solve-suite") => {
+            // solve-suite path [--agent SPEC]
+            let path = args.get(2).map_or("positions.txt", String::as_str);
+            let positions = test_positions::load(path).unwrap_or_else(|e| {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            });
+            let spec = option_value(&args, "--agent").unwrap_or("beam width=2 depth=4");
+            let mut agent = agent::parse_agent(spec).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let (solved, missed) = test_positions::solve_suite(agent.as_mut(), &positions);
+            for position in missed {
+                println!("missed: {}", position.id);
             }
+            println!(
+                "{}: {}/{} ({:.1}%)",
+                agent.name(),
+                solved,
+                positions.len(),
+                100.0 * solved as f64 / positions.len().max(1) as f64
+            );
         }
-        actions
-    }
-
-    #[allow(dead_code)]
-    fn random_action(&self) -> usize {
-        let legal_actions = self.legal_actions();
-        let mut rng = ChaCha8Rng::seed_from_u64(0);
-        legal_actions[rng.gen_range(0..legal_actions.len())]
-    }
-
-    fn evaluate_score(&mut self) {
-        self.evaluated_score = self.game_score;
-    }
-
-    fn greedy_action(&self) -> usize {
-        let legal_actions = self.legal_actions();
-        let mut best_score = -INF;
-        let mut best_action = None;
-        for action in legal_actions {
-            let mut now_state = self.clone();
-            now_state.advance(action);
-            now_state.evaluate_score();
-            if now_state.evaluated_score > best_score {
-                best_score = now_state.evaluated_score;
-                best_action = Some(action);
-            }
-        }
-        best_action.unwrap()
-    }
-
-    fn beam_search_action(&self, beam_width: usize, beam_depth: usize) -> usize {
-        let mut now_beam = BinaryHeap::new();
-        let mut best_state = None;
-
-        now_beam.push(self.clone());
-        for t in 0..beam_depth {
-            let mut next_beam = BinaryHeap::new();
-            for _ in 0..beam_width {
-                if let Some(now_state) = now_beam.pop() {
-                    let legal_actions = now_state.legal_actions();
-                    for action in legal_actions {
-                        let mut next_state = now_state.clone();
-                        next_state.advance(action);
-                        next_state.evaluate_score();
-                        if t == 0 {
-                            next_state.first_action = Some(action);
+        Some("td-train") => {
+            // td-train [path] [--evaluator pattern|linear] [--games N] [--epsilon E] [--lambda L] [--rate R] [--interval N]
+            // この迷路では探索を多めにしたほうが強くなる
+            let path = args.get(2).filter(|a| !a.starts_with("--"));
+            let options = td::TdOptions {
+                games: parsed_option(&args, "--games").unwrap_or(20000),
+                epsilon: parsed_option(&args, "--epsilon").unwrap_or(0.5),
+                lambda: parsed_option(&args, "--lambda").unwrap_or(0.5),
+                learning_rate: parsed_option(&args, "--rate").unwrap_or(0.1),
+                checkpoint_interval: parsed_option(&args, "--interval").unwrap_or(2000),
+                checkpoint_path: path.cloned(),
+            };
+            let mut value: Box<dyn td::ValueFunction> =
+                match option_value(&args, "--evaluator").unwrap_or("pattern") {
+                    "pattern" => Box::new(pattern::PatternTable::new(
+                        pattern::PatternTable::default_tuples(),
+                    )),
+                    "linear" => Box::new(td::LinearValue { weights: [0.0; 5] }),
+                    other => {
+                        eprintln!("unknown evaluator `{}` (pattern, linear)", other);
+                        std::process::exit(1);
+                    }
+                };
+            // 学習で動かない比較相手
+            let baselines: Vec<Box<dyn Agent>> = vec![
+                Box::new(GreedyAgent),
+                Box::new(BeamSearchAgent::new(2, Budget::iterations(END_TURN))),
+            ];
+            for mut baseline in baselines {
+                println!(
+                    "{}: {}",
+                    baseline.name(),
+                    test_ai_score(baseline.as_mut(), 100)
+                );
+            }
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let reports = td::td_lambda_train(value.as_mut(), &options, &mut rng).unwrap();
+            for report in reports {
+                println!(
+                    "{} games: {:.3} (previous checkpoint {:.3})",
+                    report.games, report.score, report.previous_score
+                );
+            }
+            println!("{}", value.name());
+        }
+        Some("value-play") => {
+            let path = args.get(2).map_or("pattern_table.txt", String::as_str);
+            let value = td::load_value(path).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let mut agent = td::ValueAgent { value };
+            println!("{}", test_ai_score(&mut agent, 100));
+        }
+        Some("league") => {
+            // league [--generations N] [--interval N]
+            let options = league::LeagueOptions {
+                generations: parsed_option(&args, "--generations").unwrap_or(200),
+                opponents_per_generation: 4,
+                seeds_per_opponent: 10,
+                snapshot_interval: parsed_option(&args, "--interval").unwrap_or(20),
+                mutation: 0.5,
+            };
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let (league, progress) = league::league_training(&options, &mut rng);
+            for p in progress {
+                let weights: Vec<String> = p
+                    .policy
+                    .weights
+                    .iter()
+                    .map(|w| format!("{:.2}", w))
+                    .collect();
+                println!(
+                    "generation {}: league {}, rating {:.0}, vs v0 {:.3}, weights [{}]",
+                    p.generation,
+                    p.league_size,
+                    p.rating,
+                    p.score_against_first,
+                    weights.join(", ")
+                );
+            }
+            for member in league.members {
+                println!("{}: {:.0}", member.name, member.rating);
+            }
+        }
+        Some("tune") => {
+            // tune [--candidates "beam width=1;beam width=2"] [--budget N] [--journal path]
+            let specs: Vec<String> = option_value(&args, "--candidates")
+                .unwrap_or("greedy;beam width=1;beam width=2;beam width=3;beam width=5")
+                .split(';')
+                .map(|spec| spec.trim().to_string())
+                .collect();
+            let budget = parsed_option(&args, "--budget").unwrap_or(500);
+            let mut journal = option_value(&args, "--journal").map(|path| {
+                tuning::Journal::open(path).unwrap_or_else(|e| {
+                    eprintln!("{}: {}", path, e);
+                    std::process::exit(1);
+                })
+            });
+            let stats =
+                tuning::bandit_tune(&specs, budget, 10, journal.as_mut()).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+            for candidate in stats {
+                println!(
+                    "{}: {:.3} ± {:.3} ({} games)",
+                    candidate.spec,
+                    candidate.mean(),
+                    candidate.half_width(),
+                    candidate.scores.len()
+                );
+            }
+        }
+        Some("tune-report") => {
+            let path = args.get(2).map_or("tuning_journal.txt", |s| s.as_str());
+            let journal = tuning::Journal::open(path).unwrap_or_else(|e| {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            });
+            let candidates = journal.candidates();
+            for candidate in &candidates {
+                println!(
+                    "{}: {:.3} ± {:.3} ({} games)",
+                    candidate.spec,
+                    candidate.mean(),
+                    candidate.half_width(),
+                    candidate.scores.len()
+                );
+            }
+            println!("parameter importance (share of score variance):");
+            for importance in tuning::parameter_importance(&candidates) {
+                println!(
+                    "  {}: {:.1}% ({} levels)",
+                    importance.parameter,
+                    importance.explained * 100.0,
+                    importance.levels
+                );
+            }
+        }
+        Some("tune-heatmap") => {
+            // tune-heatmap journal [--x width] [--y depth] [--out heatmap.svg]
+            let path = args.get(2).map_or("tuning_journal.txt", |s| s.as_str());
+            let journal = tuning::Journal::open(path).unwrap_or_else(|e| {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            });
+            let x = option_value(&args, "--x").unwrap_or("width");
+            let y = option_value(&args, "--y").unwrap_or("depth");
+            let out = option_value(&args, "--out").unwrap_or("heatmap.svg");
+            let svg = tuning::score_heatmap_svg(&journal.candidates(), x, y).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            std::fs::write(out, svg).unwrap();
+            println!("wrote {}", out);
+        }
+        Some("manifest-run") => {
+            // manifest-run manifest [--out results]
+            let path = args.get(2).map_or("manifest.txt", |s| s.as_str());
+            let out = option_value(&args, "--out").unwrap_or("manifest_results.txt");
+            let exit = |e: String| -> ! {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            };
+            let manifest = manifest::Manifest::load(path).unwrap_or_else(|e| exit(e));
+            let result = manifest::run_manifest(&manifest).unwrap_or_else(|e| exit(e));
+            result.save(out).unwrap();
+            println!(
+                "manifest {:016x}: {} games -> {}",
+                result.hash,
+                result.games.len(),
+                out
+            );
+        }
+        Some("verify") => {
+            // verify manifest results [--sample 20] [--seed 0]
+            let path = args.get(2).map_or("manifest.txt", |s| s.as_str());
+            let results = args.get(3).map_or("manifest_results.txt", |s| s.as_str());
+            let sample = parsed_option(&args, "--sample").unwrap_or(20);
+            let seed = parsed_option(&args, "--seed").unwrap_or(0);
+            let exit = |e: String| -> ! {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            };
+            let manifest = manifest::Manifest::load(path).unwrap_or_else(|e| exit(e));
+            let result =
+                manifest::ManifestResult::load(results).unwrap_or_else(|e| exit(e.to_string()));
+            let mismatches = manifest::verify_manifest(&manifest, &result, sample, seed)
+                .unwrap_or_else(|e| exit(e));
+            for m in &mismatches {
+                println!(
+                    "mismatch: {} seed {}: recorded {} replayed {}",
+                    m.spec, m.seed, m.recorded, m.replayed
+                );
+            }
+            if !mismatches.is_empty() {
+                std::process::exit(1);
+            }
+            println!(
+                "{} sampled games agree with manifest {:016x}",
+                sample.min(result.games.len()),
+                result.hash
+            );
+        }
+        Some("local-search") => {
+            // local-search [seed] [--method hill|anneal] [--iterations 1000] [--nodes N] [--time-ms N] [--patience N] [--target S]
+            //   [--restarts N] [--perturb K] (--perturb を付けるとランダム再開の代わりに basin hopping)
+            //   [--operators mutate,swap,shift] [--adaptive] (--adaptive で近傍を直近の改善率のルーレットで選ぶ)
+            //   [--forbid "y,x y,x"] [--max-uses action=N] [--handling penalty|repair] [--penalty W]
+            //   [--archive K] [--min-distance D] [--export path] (評価した解から互いに D 手以上違う上位 K 個を残す)
+            //   [--init path] [--save path] (--init で前回 --save した解から始める)
+            let seed: Seed = args.get(2).and_then(|s| s.parse().ok()).unwrap_or_default();
+            let method = match option_value(&args, "--method").unwrap_or("hill") {
+                "hill" => local_search::LocalSearchMethod::HillClimbing,
+                "anneal" => local_search::LocalSearchMethod::Annealing {
+                    temperature: 2.0,
+                    cooling: 0.99,
+                },
+                method => {
+                    eprintln!("unknown method `{}`", method);
+                    std::process::exit(1);
+                }
+            };
+            let number = |name: &str| parsed_option::<usize>(&args, name);
+            let criteria = local_search::StoppingCriteria {
+                budget: budget::Budget {
+                    time: number("--time-ms").map(|ms| std::time::Duration::from_millis(ms as u64)),
+                    nodes: number("--nodes"),
+                    iterations: number("--iterations").or(Some(1000)),
+                },
+                no_improvement: number("--patience"),
+                target_score: number("--target").map(|n| n as ScoreType),
+            };
+            let operators: Vec<local_search::Neighborhood> = option_value(&args, "--operators")
+                .unwrap_or("mutate")
+                .split(',')
+                .map(|name| {
+                    name.trim().parse().unwrap_or_else(|e| {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    })
+                })
+                .collect();
+            let selection = if args.iter().any(|a| a == "--adaptive") {
+                local_search::OperatorSelection::Roulette {
+                    decay: 0.9,
+                    min_weight: 0.05,
+                }
+            } else {
+                local_search::OperatorSelection::Uniform
+            };
+            let mut constraints: Vec<Box<dyn constraints::Constraint>> = Vec::new();
+            if let Some(cells) = option_value(&args, "--forbid") {
+                let cells = cells
+                    .split_whitespace()
+                    .map(|cell| {
+                        let (y, x) = cell.split_once(',').unwrap_or_else(|| {
+                            eprintln!("invalid value `{}` for --forbid", cell);
+                            std::process::exit(1);
+                        });
+                        (parse_or_exit(y, "--forbid"), parse_or_exit(x, "--forbid"))
+                    })
+                    .collect();
+                constraints.push(Box::new(constraints::ForbiddenCells { cells }));
+            }
+            if let Some(budget) = option_value(&args, "--max-uses") {
+                let (action, max_uses) = budget.split_once('=').unwrap_or_else(|| {
+                    eprintln!("invalid value `{}` for --max-uses", budget);
+                    std::process::exit(1);
+                });
+                constraints.push(Box::new(constraints::ActionBudget {
+                    action: parse_or_exit(action, "--max-uses"),
+                    max_uses: parse_or_exit(max_uses, "--max-uses"),
+                }));
+            }
+            let weight = number("--penalty").map_or(10, |w| w as ScoreType);
+            let handling = match option_value(&args, "--handling").unwrap_or("penalty") {
+                "penalty" => constraints::ConstraintHandling::Penalty { weight },
+                "repair" => constraints::ConstraintHandling::Repair { weight },
+                handling => {
+                    eprintln!(
+                        "unknown constraint handling `{}` (penalty, repair)",
+                        handling
+                    );
+                    std::process::exit(1);
+                }
+            };
+            let constraint_set = (!constraints.is_empty()).then(|| constraints::ConstraintSet {
+                constraints,
+                handling,
+            });
+            let state = MazeState::from_seed(seed);
+            let mut archive = number("--archive").map(|capacity| {
+                let state = state.clone();
+                archive::SolutionArchive::new(
+                    capacity,
+                    number("--min-distance").unwrap_or(1),
+                    move |a, b| local_search::plan_distance(&state, a, b),
+                )
+            });
+            let mut run = |initial: local_search::Plan, rng: &mut ChaCha8Rng| {
+                let mut objective = |plan: &mut local_search::Plan, rng: &mut ChaCha8Rng| {
+                    let score = match &constraint_set {
+                        Some(set) => set.objective(&state, plan, rng),
+                        None => local_search::plan_score(&state, plan),
+                    };
+                    if let Some(archive) = archive.as_mut() {
+                        archive.insert(score, plan);
+                    }
+                    score
+                };
+                local_search::local_search_with_objective(
+                    initial,
+                    method,
+                    criteria,
+                    &operators,
+                    selection,
+                    &mut objective,
+                    rng,
+                )
+            };
+            let report_archive = |archive: Option<archive::SolutionArchive<local_search::Plan>>| {
+                let Some(archive) = archive else { return };
+                let describe = |plan: &local_search::Plan| {
+                    let actions: Vec<String> = local_search::decode_plan(&state, plan)
+                        .iter()
+                        .map(usize::to_string)
+                        .collect();
+                    actions.join(",")
+                };
+                for (score, plan) in archive.entries() {
+                    println!("archive: {} {}", score, describe(plan));
+                }
+                if let Some(path) = option_value(&args, "--export") {
+                    archive.save(path, describe).unwrap();
+                    println!("{} solutions written to {}", archive.len(), path);
+                }
+            };
+            let report_constraints = |plan: &local_search::Plan| {
+                if let Some(set) = &constraint_set {
+                    println!(
+                        "{}: game score {}, violation {}",
+                        set,
+                        local_search::plan_score(&state, plan),
+                        set.violation(&state, plan)
+                    );
+                }
+            };
+            let warm_plan = option_value(&args, "--init").map(|path| {
+                warm_start::Solution::load(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|solution| solution.to_plan(seed, &state, constraint_set.as_ref()))
+                    .unwrap_or_else(|e| {
+                        eprintln!("{}: {}", path, e);
+                        std::process::exit(1);
+                    })
+            });
+            let save = |plan: &local_search::Plan| {
+                if let Some(path) = option_value(&args, "--save") {
+                    warm_start::Solution::from_plan(seed, &state, plan)
+                        .save(path)
+                        .unwrap();
+                    println!("solution written to {}", path);
+                }
+            };
+            let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+            if let Some(restarts) = number("--restarts") {
+                let strategy = match number("--perturb") {
+                    Some(perturbation) => {
+                        local_search::RestartStrategy::BasinHopping { perturbation }
+                    }
+                    None => local_search::RestartStrategy::Random,
+                };
+                let mut operator_stats: Vec<local_search::OperatorStats> = operators
+                    .iter()
+                    .map(|&o| local_search::OperatorStats::new(o))
+                    .collect();
+                let outer = local_search::StoppingCriteria {
+                    target_score: criteria.target_score,
+                    ..local_search::StoppingCriteria::iterations(restarts + 1)
+                };
+                let (outcome, stats) = local_search::with_restarts(
+                    &state,
+                    strategy,
+                    outer,
+                    warm_plan,
+                    &mut rng,
+                    |_, initial, rng| {
+                        let (outcome, stats) = run(initial, rng);
+                        for (total, run) in operator_stats.iter_mut().zip(&stats) {
+                            total.add(run);
                         }
-                        next_beam.push(next_state);
+                        outcome
+                    },
+                );
+                println!(
+                    "seed {}: score {} (optimal {}) after {} runs ({} iterations), stopped by {}",
+                    seed,
+                    outcome.score,
+                    solver::exhaustive_search(&state).0,
+                    stats.best_per_restart.len(),
+                    stats.iterations,
+                    outcome.reason
+                );
+                println!("consumed: {}", outcome.consumed);
+                println!("best per run: {:?}", stats.best_per_restart);
+                println!("improving runs: {:?}", stats.improving_restarts);
+                report_constraints(&outcome.plan);
+                save(&outcome.plan);
+                report_archive(archive);
+                for stats in &operator_stats {
+                    println!("{}", stats);
+                }
+                return;
+            }
+            let initial = warm_plan.unwrap_or_else(|| local_search::random_plan(&mut rng));
+            let (outcome, operator_stats) = run(initial, &mut rng);
+            println!(
+                "seed {}: score {} (optimal {}) after {} iterations, stopped by {} [{}]",
+                seed,
+                outcome.score,
+                solver::exhaustive_search(&state).0,
+                outcome.consumed.iterations,
+                outcome.reason,
+                criteria
+            );
+            println!("consumed: {}", outcome.consumed);
+            report_constraints(&outcome.plan);
+            save(&outcome.plan);
+            report_archive(archive);
+            for stats in &operator_stats {
+                println!("{}", stats);
+            }
+        }
+        Some("convergence") => {
+            // convergence [seed] [--nodes 1000] [--axis nodes|iterations|time] [--out convergence.svg] [--trace path]
+            // 同じ盤面で山登り・焼きなまし・ランダム再開・レプリカ交換法を、評価する解の数をそろえて動かし、最良値の推移を重ねて描く
+            let seed: Seed = args.get(2).and_then(|s| s.parse().ok()).unwrap_or_default();
+            let nodes = parsed_option(&args, "--nodes").unwrap_or(1000);
+            let axis: convergence::ConvergenceAxis = option_value(&args, "--axis")
+                .unwrap_or("nodes")
+                .parse()
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+            let state = MazeState::from_seed(seed);
+            let criteria = local_search::StoppingCriteria {
+                budget: budget::Budget::nodes(nodes),
+                no_improvement: None,
+                target_score: None,
+            };
+            let hill = local_search::LocalSearchMethod::HillClimbing;
+            let anneal = local_search::LocalSearchMethod::Annealing {
+                temperature: 2.0,
+                cooling: 0.99,
+            };
+            let tempering_options = tempering::TemperingOptions::default();
+            let run = |name: &str, rng: &mut ChaCha8Rng| match name {
+                "hill" | "anneal" => {
+                    let method = if name == "hill" { hill } else { anneal };
+                    let initial = local_search::random_plan(rng);
+                    local_search::local_search(&state, initial, method, criteria, rng)
+                }
+                "restarts" => {
+                    // 1回 100 反復の山登りを、全体で評価した解の数が nodes になるまで繰り返す
+                    local_search::with_restarts(
+                        &state,
+                        local_search::RestartStrategy::Random,
+                        criteria,
+                        None,
+                        rng,
+                        |state, initial, rng| {
+                            let inner = local_search::StoppingCriteria::iterations(100);
+                            local_search::local_search(state, initial, hill, inner, rng)
+                        },
+                    )
+                    .0
+                }
+                _ => tempering::parallel_tempering(&state, &tempering_options, criteria, rng).0,
+            };
+            let series: Vec<convergence::ConvergenceSeries> =
+                ["hill", "anneal", "restarts", "tempering"]
+                    .iter()
+                    .map(|name| {
+                        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+                        let outcome = run(name, &mut rng);
+                        let series = convergence::ConvergenceSeries::new(name, &outcome);
+                        let last = series.trace.last().unwrap();
+                        println!(
+                            "{}: best {} reached after {} nodes ({:?}); consumed {}",
+                            name, outcome.score, last.nodes, last.elapsed, outcome.consumed
+                        );
+                        series
+                    })
+                    .collect();
+            let out = option_value(&args, "--out").unwrap_or("convergence.svg");
+            std::fs::write(out, convergence::convergence_svg(&series, axis).unwrap()).unwrap();
+            println!("plot written to {}", out);
+            if let Some(path) = option_value(&args, "--trace") {
+                convergence::save_traces(path, &series).unwrap();
+                println!("traces written to {}", path);
+            }
+        }
+        Some("budget") => {
+            // budget [seed] [--budget "time_ms=5 nodes=1000 iterations=50"] [--width 3]
+            // 同じ budget をビームサーチ・MCTS・原始モンテカルロ・Chokudai サーチ・softmax ビーム・逐次半減法・SHOT
+            // NMCS・NRPA・プールつきビーム・SoA ビーム・局所探索・反復深化に渡し、使った分を並べる
+            let seed: Seed = args
+                .get(2)
+                .filter(|a| !a.starts_with("--"))
+                .map_or(Seed(0), |s| parse_or_exit(s, "seed"));
+            let budget = budget::Budget::parse(
+                option_value(&args, "--budget").unwrap_or("nodes=1000 iterations=50"),
+            )
+            .unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let beam_width = parsed_option(&args, "--width").unwrap_or(3);
+            let state = MazeState::from_seed(seed);
+            println!("budget: {}", budget);
+            let (best, consumed) = search::beam_search_with_budget(&state, beam_width, &budget);
+            println!(
+                "beam(width={}): action {}, consumed {}",
+                beam_width, best.first_action, consumed
+            );
+            let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+            let (action, consumed) =
+                mcts::mcts_action_with_budget(&state, &budget, mcts::DEFAULT_EXPLORATION, &mut rng);
+            println!("mcts: action {}, consumed {}", action, consumed);
+            let (action, consumed) =
+                mcts::primitive_montecarlo_action_with_budget(&state, &budget, &mut rng);
+            println!("montecarlo: action {}, consumed {}", action, consumed);
+            let (action, consumed) =
+                search::chokudai_search_with_budget(&state, 1, END_TURN, &budget);
+            println!(
+                "chokudai(width=1): action {}, consumed {}",
+                action, consumed
+            );
+            let (action, consumed) = softmax_beam::softmax_beam_search_with_budget(
+                &state, beam_width, &budget, 2.0, 0.5, &mut rng,
+            );
+            println!(
+                "softmax_beam(width={}): action {}, consumed {}",
+                beam_width, action, consumed
+            );
+            let (action, consumed) = sequential_halving::sequential_halving_with_budget(
+                &state,
+                &budget,
+                &prior::UniformPrior,
+                &mut rng,
+            );
+            println!(
+                "sequential_halving: action {}, consumed {}",
+                action, consumed
+            );
+            let (action, consumed) = sequential_halving::shot_with_budget(
+                &state,
+                &budget,
+                &prior::UniformPrior,
+                &mut rng,
+            );
+            println!("shot: action {}, consumed {}", action, consumed);
+            let ((_, sequence), consumed) =
+                nested::nested_monte_carlo_search_with_budget(&state, 2, &budget, &mut rng);
+            println!(
+                "nmcs(level=2): action {}, consumed {}",
+                sequence[0], consumed
+            );
+            let ((_, sequence), consumed) = nested::nested_rollout_policy_adaptation_with_budget(
+                &state,
+                2,
+                10,
+                &budget,
+                &Default::default(),
+                &mut rng,
+            );
+            println!(
+                "nrpa(level=2, iterations=10): action {}, consumed {}",
+                sequence[0], consumed
+            );
+            let (action, consumed) = pool::pooled_beam_search_with_budget(
+                &state,
+                beam_width,
+                &budget,
+                &mut pool::StatePool::default(),
+            );
+            println!(
+                "pooled_beam(width={}): action {}, consumed {}",
+                beam_width, action, consumed
+            );
+            let (action, consumed) =
+                soa_beam::soa_beam_search_with_budget(&state, beam_width, &budget);
+            println!(
+                "soa_beam(width={}): action {}, consumed {}",
+                beam_width, action, consumed
+            );
+            let criteria = local_search::StoppingCriteria {
+                budget,
+                no_improvement: None,
+                target_score: None,
+            };
+            let initial = local_search::random_plan(&mut rng);
+            let outcome = local_search::local_search(
+                &state,
+                initial,
+                local_search::LocalSearchMethod::HillClimbing,
+                criteria,
+                &mut rng,
+            );
+            println!(
+                "hill climbing: score {}, consumed {}",
+                outcome.score, outcome.consumed
+            );
+            let alternate_state = alternate::AlternateMazeState::from_seed(seed);
+            let (action, consumed) =
+                alternate::iterative_deepening_action_with_budget(&alternate_state, &budget);
+            println!(
+                "iterative deepening (alternate maze): action {}, consumed {}",
+                action, consumed
+            );
+        }
+        Some("auto-move") => {
+            // auto-move [--games N] [--budget "iterations=10000"] [--temp 500]
+            // 配置だけを決める迷路で、ランダム配置・山登り・焼きなましの平均得点を比べる
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let budget = budget::Budget::parse(
+                option_value(&args, "--budget").unwrap_or("iterations=10000"),
+            )
+            .unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let temperature: f64 = parsed_option(&args, "--temp").unwrap_or(500.0);
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let random = auto_move::average_score(game_number, &mut |state| {
+                auto_move::random_placement(state, &mut rng)
+            });
+            println!("random: {:.2}", random);
+            let hill = auto_move::average_score(game_number, &mut |state| {
+                auto_move::hill_climb(state, &budget, &mut rng).0
+            });
+            println!("hill climbing ({}): {:.2}", budget, hill);
+            let annealing = auto_move::average_score(game_number, &mut |state| {
+                auto_move::simulated_annealing(state, &budget, temperature, 10.0, &mut rng).0
+            });
+            println!(
+                "simulated annealing ({}, temperature {} -> 10): {:.2}",
+                budget, temperature, annealing
+            );
+        }
+        Some("anneal-compare") => {
+            // anneal-compare [--games N] [--iterations 1000] [--start-temp 500] [--end-temp 10]
+            // 配置だけを決める迷路の各盤面で、同じ初期配置から山登りと焼きなましを同じ回数動かして比べる
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let number_of_iterations = parsed_option(&args, "--iterations").unwrap_or(1000);
+            let start_temperature: f64 = parsed_option(&args, "--start-temp").unwrap_or(500.0);
+            let end_temperature: f64 = parsed_option(&args, "--end-temp").unwrap_or(10.0);
+            println!(
+                "{}",
+                auto_move::compare_optimizers(
+                    game_number,
+                    number_of_iterations,
+                    start_temperature,
+                    end_temperature
+                )
+            );
+        }
+        Some("tempering") => {
+            // tempering [seed] [--chains 4] [--min-temp T] [--max-temp T] [--interval 10] [--rounds 100] [--sequential]
+            let seed: Seed = args.get(2).and_then(|s| s.parse().ok()).unwrap_or_default();
+            let defaults = tempering::TemperingOptions::default();
+            let options = tempering::TemperingOptions {
+                chains: parsed_option(&args, "--chains").unwrap_or(defaults.chains),
+                min_temperature: parsed_option(&args, "--min-temp")
+                    .unwrap_or(defaults.min_temperature),
+                max_temperature: parsed_option(&args, "--max-temp")
+                    .unwrap_or(defaults.max_temperature),
+                exchange_interval: parsed_option(&args, "--interval")
+                    .unwrap_or(defaults.exchange_interval),
+                parallel: !args.iter().any(|a| a == "--sequential"),
+            };
+            let criteria = local_search::StoppingCriteria::iterations(
+                parsed_option(&args, "--rounds").unwrap_or(100),
+            );
+            let state = MazeState::from_seed(seed);
+            let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+            let (outcome, stats) =
+                tempering::parallel_tempering(&state, &options, criteria, &mut rng);
+            println!(
+                "seed {}: score {} (optimal {}) after {} rounds ({} evaluated solutions), stopped by {}",
+                seed,
+                outcome.score,
+                solver::exhaustive_search(&state).0,
+                stats.rounds,
+                outcome.consumed.nodes,
+                outcome.reason
+            );
+            let temperatures = options.temperatures();
+            for (k, (attempts, accepts)) in stats
+                .exchange_attempts
+                .iter()
+                .zip(&stats.exchange_accepts)
+                .enumerate()
+            {
+                println!(
+                    "T={:.3} <-> T={:.3}: {}/{} exchanges",
+                    temperatures[k],
+                    temperatures[k + 1],
+                    accepts,
+                    attempts
+                );
+            }
+        }
+        Some("audit") => run_determinism_audit(100),
+        Some("score-audit") => {
+            // score-audit [--agents "greedy;beam width=2 depth=4;pooled"] [--games N]
+            // 対局で指した手を新しい盤面で指し直し、報告されたスコアと同じかを確かめる
+            // play_seed などベンチマークの対局はいつも確かめて、食い違えば panic する。これは食い違いを全部並べる
+            let specs =
+                option_value(&args, "--agents").unwrap_or("greedy;beam width=2 depth=4;pooled");
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let mut mismatches = 0;
+            for spec in specs.split(';') {
+                let mut agent = agent::parse_agent(spec.trim()).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+                let mut failed = 0;
+                for seed in Seed::first(game_number) {
+                    let mut recorder = audit::ActionRecorder::default();
+                    let mut state = hooks::play(agent.as_mut(), seed, &mut [&mut recorder]);
+                    if let Err(e) = audit::audit_score(
+                        MazeState::from_seed(seed),
+                        &recorder.actions,
+                        state.evaluate_score(),
+                    ) {
+                        eprintln!("{} seed {}: {}", agent.name(), seed, e);
+                        failed += 1;
                     }
                 }
+                println!(
+                    "{}: {} games replayed, {} score mismatches",
+                    agent.name(),
+                    game_number,
+                    failed
+                );
+                mismatches += failed;
             }
-
-            now_beam = next_beam;
-            best_state = now_beam.peek();
-
-            if best_state.unwrap().is_done() {
-                break;
+            if mismatches > 0 {
+                std::process::exit(1);
             }
         }
-        best_state.unwrap().first_action.unwrap()
-    }
-}
-
-impl PartialOrd for MazeState {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl Ord for MazeState {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.evaluated_score.cmp(&other.evaluated_score)
-    }
-}
-
-impl Display for MazeState {
-    // `f` is a buffer, and this method must write the formatted string into it.
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        writeln!(f, "turn:{}", self.turn)?;
-        writeln!(f, "score:{}", self.game_score)?;
-        let (y, x) = (self.character.y as usize, self.character.x as usize);
-        for j in 0..H {
-            for i in 0..W {
-                if j == y && i == x {
-                    write!(f, "@")?;
-                } else if self.points[j][i] > 0 {
-                    write!(f, "{}", self.points[j][i])?;
+        Some("conformance") => {
+            let results = [
+                ("maze", conformance::check_conformance::<MazeState>(0..100)),
+                (
+                    "wall maze",
+                    conformance::check_conformance::<wall_maze::WallMazeState>(0..100),
+                ),
+                (
+                    "cooperative maze",
+                    conformance::check_conformance::<cooperative::CooperativeMazeState>(0..100),
+                ),
+            ];
+            let mut failed = false;
+            for (name, failures) in &results {
+                for failure in failures {
+                    eprintln!("{}", failure);
+                }
+                if failures.is_empty() {
+                    println!("{}: ok", name);
+                }
+                failed |= !failures.is_empty();
+            }
+            if failed {
+                std::process::exit(1);
+            }
+        }
+        Some("ensemble") => {
+            let mut agent = EnsembleBeamAgent::new(2, None, 1.0);
+            let (score, search_stats) = stats::collect(|| test_ai_score(&mut agent, 100));
+            println!("{}", score);
+            println!(
+                "evaluations: {}, mean disagreement: {:.3}, max disagreement: {:.3}, extra expansions: {}",
+                search_stats.evaluations.unwrap_or(0),
+                search_stats.mean_disagreement().unwrap_or(0.0),
+                search_stats.max_disagreement.unwrap_or(0.0),
+                search_stats.extra_expansions.unwrap_or(0)
+            );
+        }
+        Some("phased") => {
+            // phased [--config "progress=turn opening=0.3 endgame=0.7 opening.evaluator=remaining_potential ..."] [--games N]
+            // 局面の段階ごとに評価関数を切り替えるビームサーチと、1つの評価関数だけのビームサーチを比べる
+            // --config がなければ序盤 remaining_potential、中盤 nearest_point、終盤 game_score
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let config = match option_value(&args, "--config") {
+                Some(spec) => spec.parse().unwrap_or_else(|e: String| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }),
+                None => game_phase::PhasedConfig::territory_then_exact(),
+            };
+            let mut agent = game_phase::PhasedBeamAgent::new(config.clone()).unwrap();
+            println!(
+                "{}: {}",
+                agent.name(),
+                test_ai_score(&mut agent, game_number)
+            );
+            let decisions: Vec<String> = game_phase::GamePhase::ALL
+                .iter()
+                .map(|phase| format!("{} {}", phase, agent.decisions[phase.index()]))
+                .collect();
+            println!("decisions: {}", decisions.join(", "));
+            for evaluator in evaluator::EVALUATOR_NAMES {
+                let mut single = config.clone();
+                for settings in single.phases.iter_mut() {
+                    settings.evaluator = evaluator.to_string();
+                }
+                let mut agent = game_phase::PhasedBeamAgent::new(single).unwrap();
+                println!(
+                    "{} only: {}",
+                    evaluator,
+                    test_ai_score(&mut agent, game_number)
+                );
+            }
+        }
+        Some("evaluators") => {
+            // evaluators [--scenario maze::dense_small] [--games N] [--width W] [--depth D] [--number N]
+            // 評価関数ごとに、その値で並べるビームサーチと Chokudai サーチの平均スコアを出す
+            let found = option_value(&args, "--scenario")
+                .map_or(
+                    Ok(scenario::MazeScenario::custom(MazeParams::default())),
+                    scenario::find_maze_scenario,
+                )
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let beam_width = parsed_option(&args, "--width").unwrap_or(2);
+            let beam_depth = parsed_option(&args, "--depth").unwrap_or(found.params.end_turn);
+            let beam_number = parsed_option(&args, "--number").unwrap_or(2);
+            for name in evaluator::EVALUATOR_NAMES {
+                let agents: [Box<dyn Agent>; 2] = [
+                    Box::new(evaluator::EvaluatorBeamAgent {
+                        beam_width,
+                        beam_depth,
+                        evaluator: evaluator::parse_evaluator(name).unwrap(),
+                    }),
+                    Box::new(evaluator::EvaluatorChokudaiAgent {
+                        beam_width,
+                        beam_depth,
+                        beam_number,
+                        evaluator: evaluator::parse_evaluator(name).unwrap(),
+                    }),
+                ];
+                for mut agent in agents {
+                    let total: ScoreType = Seed::first(game_number)
+                        .map(|seed| found.play(agent.as_mut(), seed))
+                        .sum();
+                    println!(
+                        "{} on {}: {:.3}",
+                        agent.name(),
+                        found.name,
+                        total as f64 / game_number as f64
+                    );
+                }
+            }
+        }
+        Some("zobrist") => {
+            // zobrist [--games N] [--width W] [--depth D]
+            // 同じ局面を飛ばすビームサーチと普通のビームサーチを同じ幅で比べる
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let beam_width = parsed_option(&args, "--width").unwrap_or(2);
+            let beam_depth = parsed_option(&args, "--depth").unwrap_or(END_TURN);
+            let mut beam = BeamSearchAgent::new(beam_width, Budget::iterations(beam_depth));
+            println!("{}: {}", beam.name(), test_ai_score(&mut beam, game_number));
+            let mut dedup = zobrist_hash::DedupBeamAgent::new(beam_width, Some(beam_depth));
+            println!(
+                "{}: {}",
+                dedup.name(),
+                test_ai_score(&mut dedup, game_number)
+            );
+            println!(
+                "duplicates skipped: {} ({:.2} per decision)",
+                dedup.duplicates,
+                dedup.duplicates as f64 / (game_number * END_TURN) as f64
+            );
+        }
+        Some("wall-maze") => {
+            // wall-maze [--games N] [--width W] [--depth D] [--show]
+            // 壁のある 7x7 の迷路で、スコアだけの評価と最寄りの点までの BFS 距離を加えた評価のビームサーチを比べる
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let beam_width = parsed_option(&args, "--width").unwrap_or(100);
+            let beam_depth = parsed_option(&args, "--depth").unwrap_or(10);
+            if args.iter().any(|a| a == "--show") {
+                print!(
+                    "{}",
+                    wall_maze::WallMazeState::from_seed(
+                        Seed(0),
+                        wall_maze::WallEvaluation::BfsDistance
+                    )
+                );
+            }
+            for evaluation in [
+                wall_maze::WallEvaluation::Score,
+                wall_maze::WallEvaluation::BfsDistance,
+            ] {
+                println!(
+                    "{:?}: {} (beam width={}, depth={})",
+                    evaluation,
+                    wall_maze::average_score(evaluation, beam_width, beam_depth, game_number),
+                    beam_width,
+                    beam_depth
+                );
+            }
+        }
+        Some("scenarios") => {
+            // 名前付きの盤面の一覧。manifest の game 行や scenario コマンドにこの名前を渡す
+            for scenario in scenario::scenarios() {
+                println!("{:<20} {}", scenario.name(), scenario.description());
+            }
+        }
+        Some("scenario") => {
+            // scenario NAME [--agents "greedy;beam width=2 depth=4"] [--games N] [--width W] [--depth D] [--show]
+            // 迷路ならエージェントごとの平均スコア、壁のある迷路なら2つの評価のビームサーチの平均スコアを出す
+            let Some(found) = args.get(2).and_then(|name| scenario::find_scenario(name)) else {
+                eprintln!("usage: scenario NAME (see `scenarios`)");
+                std::process::exit(1);
+            };
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let show = args.iter().any(|a| a == "--show");
+            match found {
+                scenario::Scenario::Maze(maze) => {
+                    if show {
+                        println!("{}", maze.state(Seed(0)));
+                    }
+                    let specs =
+                        option_value(&args, "--agents").unwrap_or("greedy;beam width=2 depth=4");
+                    for spec in specs.split(';') {
+                        let mut agent = agent::parse_agent(spec.trim()).unwrap_or_else(|e| {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        });
+                        let scores: Vec<ScoreType> = Seed::first(game_number)
+                            .map(|seed| maze.play(agent.as_mut(), seed))
+                            .collect();
+                        println!(
+                            "{} on {}: {:.3}",
+                            agent.name(),
+                            maze.name,
+                            scores.iter().sum::<ScoreType>() as f64 / game_number as f64
+                        );
+                    }
+                }
+                scenario::Scenario::WallMaze(wall) => {
+                    let beam_width = parsed_option(&args, "--width").unwrap_or(100);
+                    let beam_depth = parsed_option(&args, "--depth").unwrap_or(10);
+                    if show {
+                        print!(
+                            "{}",
+                            wall.state(Seed(0), wall_maze::WallEvaluation::BfsDistance)
+                        );
+                    }
+                    for evaluation in [
+                        wall_maze::WallEvaluation::Score,
+                        wall_maze::WallEvaluation::BfsDistance,
+                    ] {
+                        println!(
+                            "{:?} on {}: {} (beam width={}, depth={})",
+                            evaluation,
+                            wall.name,
+                            wall_maze::average_score_from(
+                                &|seed| wall.state(seed, evaluation),
+                                beam_width,
+                                beam_depth,
+                                game_number
+                            ),
+                            beam_width,
+                            beam_depth
+                        );
+                    }
+                }
+            }
+        }
+        Some("policy-iteration") => {
+            let (policy, history) = policy_iteration::policy_iteration(5, 100, 100);
+            for (iteration, step) in history.iter().enumerate() {
+                println!(
+                    "iteration {}: score {:.3} {}",
+                    iteration, step.score, step.policy
+                );
+            }
+            println!("{:?}", policy.weights);
+        }
+        Some("self-play-export") => {
+            // self-play-export [path] [--games N] [--augment]
+            let path = args.get(2).map_or("self_play.txt", String::as_str);
+            let game_number: u64 = parsed_option(&args, "--games").unwrap_or(100);
+            let mut agent = BeamSearchAgent::new(2, Budget::iterations(END_TURN));
+            let mut samples = policy_iteration::collect_self_play(&mut agent, 0..game_number);
+            let played = samples.len();
+            if args.iter().any(|a| a == "--augment") {
+                samples = symmetry::augment(&samples);
+            }
+            policy_iteration::export_samples(path, &samples).unwrap();
+            println!(
+                "{} samples from {} decisions written to {}",
+                samples.len(),
+                played,
+                path
+            );
+        }
+        Some("cache-build") => {
+            // cache-build [path] [max_remaining_turns] [--128] [--verify]
+            let flags: Vec<&str> = args[2..]
+                .iter()
+                .map(String::as_str)
+                .filter(|a| a.starts_with("--"))
+                .collect();
+            let positional: Vec<&str> = args[2..]
+                .iter()
+                .map(String::as_str)
+                .filter(|a| !a.starts_with("--"))
+                .collect();
+            let path = positional.first().copied().unwrap_or("solver_cache.txt");
+            let max_remaining_turns = positional
+                .get(1)
+                .map_or(2, |n| parse_or_exit(n, "max_remaining_turns"));
+            let mut cache = SolverCache::new(CacheOptions {
+                key_width: if flags.contains(&"--128") {
+                    KeyWidth::Bits128
                 } else {
-                    write!(f, ".")?;
+                    KeyWidth::Bits64
+                },
+                verify: flags.contains(&"--verify"),
+            });
+            for i in 0..100 {
+                cache.solve_endgames(&MazeState::from_seed(Seed(i)), max_remaining_turns);
+            }
+            cache.save(path).unwrap();
+            println!("{} positions solved into {}", cache.len(), path);
+        }
+        Some("cache-play") => {
+            let path = args
+                .get(2)
+                .map(String::as_str)
+                .unwrap_or("solver_cache.txt");
+            let cache = SolverCache::load(path).unwrap_or_else(|e| {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            });
+            let mut agent = CachedAgent::new(Box::new(GreedyAgent), cache);
+            let (score, search_stats) = stats::collect(|| test_ai_score(&mut agent, 100));
+            println!("{}", score);
+            let or_dash = |value: Option<usize>| value.map_or("-".to_string(), |v| v.to_string());
+            println!(
+                "probes: {}, hits: {}, verified hits: {}, collisions: {}",
+                or_dash(search_stats.table_probes),
+                or_dash(search_stats.table_hits),
+                agent.cache.stats.verified_hits,
+                or_dash(search_stats.hash_collisions)
+            );
+        }
+        Some("pool-profile") => {
+            let (beam_width, beam_depth) = (5, END_TURN);
+            let mut agent = PooledBeamAgent::new(beam_width, Budget::iterations(beam_depth));
+            let score = test_ai_score(&mut agent, 100);
+            println!("score: {}", score);
+            // プールなしのビームサーチでは取得のたびに状態を確保する
+            let stats = agent.pool.stats;
+            println!(
+                "allocations without pool: {}",
+                stats.allocations + stats.reuses
+            );
+            println!("allocations with pool: {}", stats.allocations);
+        }
+        Some("soa-bench") => {
+            // soa-bench [WIDTH] [--maze "h=3 w=4 end_turn=4"] [--budget "nodes=1000"]
+            // --budget を渡さなければ終局まで読む
+            let beam_width = args
+                .get(2)
+                .filter(|arg| !arg.starts_with("--"))
+                .map_or(100_000, |n| parse_or_exit(n, "WIDTH"));
+            let params: MazeParams = option_value(&args, "--maze")
+                .unwrap_or("")
+                .parse()
+                .unwrap_or_else(|e: String| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+            let budget = option_value(&args, "--budget").map_or(Budget::default(), |spec| {
+                Budget::parse(spec).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                })
+            });
+            for result in soa_beam::benchmark(beam_width, &budget, params, 100) {
+                println!(
+                    "{}: width={} score={:.3} time={}ms",
+                    result.name,
+                    beam_width,
+                    result.score,
+                    result.elapsed.as_millis()
+                );
+            }
+        }
+        Some("plan") => {
+            // plan [--seed N] [--width W] [--depth D]
+            // ビームサーチの最良の局面までの手の列を出し、指し直して同じスコアになるかを確かめる
+            let seed = Seed(parsed_option(&args, "--seed").unwrap_or(0));
+            let beam_width = parsed_option(&args, "--width").unwrap_or(2);
+            let beam_depth = parsed_option(&args, "--depth").unwrap_or(END_TURN);
+            let state = MazeState::from_seed(seed);
+            let plan = search::beam_search_plan(&state, beam_width, beam_depth);
+            println!(
+                "seed {}: plan {:?}, score {}",
+                seed, plan.actions, plan.score
+            );
+            let mut replayed = state.clone();
+            for &action in &plan.actions {
+                replayed.advance(action);
+            }
+            println!(
+                "replayed score {}, first action {} (beam_search_action {})",
+                replayed.evaluate_score(),
+                plan.actions[0],
+                search::beam_search_action(&state, beam_width, beam_depth)
+            );
+        }
+        Some("best-first") => {
+            // best-first [memory_limit]: memory_limit を指定するとオープンリストをディスクに溢れさせる
+            let memory_limit: Option<usize> = args.get(2).map(|n| parse_or_exit(n, "memory_limit"));
+            let mut total_score = 0;
+            let mut expanded = 0;
+            let mut io_stats = best_first::IoStats::default();
+            for i in 0..100 {
+                let state = MazeState::from_seed(Seed(i));
+                let result = match memory_limit {
+                    Some(limit) => {
+                        let mut open_list = best_first::ExternalOpenList::new(limit).unwrap();
+                        let result = best_first::best_first_solve(&state, &mut open_list);
+                        let stats = open_list.stats;
+                        io_stats.runs_written += stats.runs_written;
+                        io_stats.nodes_written += stats.nodes_written;
+                        io_stats.nodes_read += stats.nodes_read;
+                        io_stats.bytes_written += stats.bytes_written;
+                        io_stats.bytes_read += stats.bytes_read;
+                        io_stats.max_in_memory = io_stats.max_in_memory.max(stats.max_in_memory);
+                        result
+                    }
+                    None => best_first::best_first_solve(
+                        &state,
+                        &mut best_first::MemoryOpenList::default(),
+                    ),
                 }
+                .unwrap();
+                assert_eq!(result.score, solver::exhaustive_search(&state).0);
+                total_score += result.score;
+                expanded += result.expanded;
+            }
+            println!("optimal score: {}", total_score as f64 / 100.0);
+            println!("expanded: {}", expanded);
+            if memory_limit.is_some() {
+                println!(
+                    "runs written: {}, nodes written: {}, nodes read: {}, bytes written: {}, bytes read: {}, max in memory: {}",
+                    io_stats.runs_written,
+                    io_stats.nodes_written,
+                    io_stats.nodes_read,
+                    io_stats.bytes_written,
+                    io_stats.bytes_read,
+                    io_stats.max_in_memory
+                );
             }
-            writeln!(f)?;
         }
-        writeln!(f)
-    }
-}
-
-#[allow(dead_code)]
-fn play_game(seed: u64) {
-    let mut state = MazeState::from_seed(seed);
-    while !state.is_done() {
-        // state.advance(state.random_action()); // ランダム行動
-        state.advance(state.greedy_action()); // 貪欲法
-        println!("{}", state);
-    }
-}
-
-fn test_ai_score(game_number: usize) -> f64 {
-    let mut total_score = 0;
-    for i in 0..game_number {
-        let mut state = MazeState::from_seed(i as u64);
-        while !state.is_done() {
-            // state.advance(state.random_action()); // ランダム行動
-            // state.advance(state.greedy_action()); // 貪欲法
-            state.advance(state.beam_search_action(2, END_TURN)); // ビームサーチ
-        }
-        total_score += state.game_score;
+        Some("solve") => {
+            // solve [--seed S] [--checkpoint path] [--interval N] [--max-steps N] [--resume]
+            let seed = parsed_option(&args, "--seed").unwrap_or(Seed(0));
+            let path = option_value(&args, "--checkpoint").unwrap_or("dfbnb_checkpoint.txt");
+            let interval = parsed_option(&args, "--interval").unwrap_or(1000);
+            let max_steps = parsed_option(&args, "--max-steps");
+            let mut solver = if args.iter().any(|a| a == "--resume") {
+                dfbnb::DfbnbState::load(path).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                })
+            } else {
+                dfbnb::DfbnbState::new(&MazeState::from_seed(seed))
+            };
+            dfbnb::solve_with_checkpoints(&mut solver, path, interval, max_steps).unwrap();
+            let status = if solver.is_finished() {
+                "optimal"
+            } else {
+                "interrupted"
+            };
+            match solver.incumbent {
+                Some((score, action)) => {
+                    println!("{}: score {} first action {}", status, score, action)
+                }
+                None => println!("{}: no solution yet", status),
+            }
+            println!(
+                "expanded: {}, pruned: {}, frontier: {}",
+                solver.expanded,
+                solver.pruned,
+                solver.frontier.len()
+            );
+        }
+        Some("bench-serve") => {
+            // bench-serve [--port P] [--agent SPEC] [--games N]
+            let port = parsed_option(&args, "--port").unwrap_or(7878);
+            let spec = option_value(&args, "--agent").unwrap_or("beam width=2 depth=4");
+            let game_number: u64 = parsed_option(&args, "--games").unwrap_or(100);
+            let coordinator = distributed::Coordinator::bind(port, spec, 0..game_number)
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+            match coordinator.local_addr() {
+                Ok(address) => println!("coordinator listening on {}", address),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            let report = coordinator.run();
+            for e in &report.worker_errors {
+                eprintln!("worker error: {}", e);
+            }
+            let results = report.results;
+            println!("{}", provenance::Provenance::collect(1));
+            for (seed, score) in &results {
+                println!("{} {}", seed, score);
+            }
+            let total: ScoreType = results.values().sum();
+            println!("{}: {}", spec, total as f64 / results.len() as f64);
+        }
+        Some("bench-worker") => {
+            // bench-worker [--addr host:port]
+            let address = option_value(&args, "--addr").unwrap_or("127.0.0.1:7878");
+            let completed = distributed::run_worker(address).unwrap_or_else(|e| {
+                eprintln!("{}: {}", address, e);
+                std::process::exit(1);
+            });
+            println!("completed {} tasks", completed);
+        }
+        Some("matrix") => {
+            // matrix [--agents "greedy;beam width=2 depth=4"] [--games N] [--threads N] [--timeout-ms T] [--retries R]
+            //        [--move-time-ms T] 1手の持ち時間を伝え、考慮時間の分布と持ち時間を超えた手を出す
+            //        [--repro-dir DIR] 探索が panic したか合法でない手を返した対局の材料を DIR に書く (既定は repro)
+            let specs: Vec<String> = option_value(&args, "--agents")
+                .unwrap_or("random;greedy;beam width=2 depth=4")
+                .split(';')
+                .map(|spec| spec.trim().to_string())
+                .collect();
+            let game_number: u64 = parsed_option(&args, "--games").unwrap_or(100);
+            let options = scheduler::SchedulerOptions {
+                threads: parsed_option(&args, "--threads").unwrap_or(4),
+                timeout: std::time::Duration::from_millis(
+                    parsed_option(&args, "--timeout-ms").unwrap_or(10_000),
+                ),
+                retries: parsed_option(&args, "--retries").unwrap_or(1),
+                move_time: parsed_option(&args, "--move-time-ms")
+                    .map(std::time::Duration::from_millis),
+            };
+            println!("{}", provenance::Provenance::collect(options.threads));
+            let outcomes = scheduler::run_tasks(scheduler::matrix(&specs, 0..game_number), options);
+            let repro_dir = option_value(&args, "--repro-dir").unwrap_or("repro");
+            for outcome in &outcomes {
+                let Some(bundle) = &outcome.repro else {
+                    continue;
+                };
+                let name: String = outcome
+                    .task
+                    .spec
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                    .collect();
+                let path = format!("{}/{}-seed{}.repro", repro_dir, name, outcome.task.seed);
+                std::fs::create_dir_all(repro_dir).unwrap();
+                bundle.save(&path).unwrap();
+                eprintln!("wrote {} (run `repro {}` to replay it)", path, path);
+            }
+            for spec in &specs {
+                let mut scores = Vec::new();
+                let mut failures = 0;
+                let mut timeouts = 0;
+                let mut retried = 0;
+                let mut move_times = Vec::new();
+                for outcome in outcomes.iter().filter(|o| &o.task.spec == spec) {
+                    retried += outcome.attempts - 1;
+                    move_times.extend_from_slice(&outcome.move_times);
+                    match &outcome.result {
+                        scheduler::TaskResult::Score(score) => scores.push(*score),
+                        scheduler::TaskResult::TimedOut => {
+                            timeouts += 1;
+                            eprintln!("{} seed {}: timed out", spec, outcome.task.seed);
+                        }
+                        result => {
+                            failures += 1;
+                            eprintln!("{} seed {}: {:?}", spec, outcome.task.seed, result);
+                        }
+                    }
+                }
+                let mean = scores.iter().sum::<ScoreType>() as f64 / scores.len().max(1) as f64;
+                println!(
+                    "{}: {:.3} ({} games, {} failed, {} timed out, {} retries), {}",
+                    spec,
+                    mean,
+                    scores.len(),
+                    failures,
+                    timeouts,
+                    retried,
+                    scheduler::TimeUsage::new(&move_times, options.move_time)
+                );
+            }
+        }
+        Some("adversarial") => {
+            // adversarial [--agent "beam width=2 depth=4"] [--seeds N] [--iterations N] [--maze "h=3 w=4 end_turn=4"] [--out hard_instances.txt]
+            // 盤面の配置を山登りで書き換えて、--agent が全探索の厳密解に届かない盤面を集める
+            let exit = |e: String| -> ! {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            };
+            let spec = option_value(&args, "--agent").unwrap_or("beam width=2 depth=4");
+            let seed_number = parsed_option(&args, "--seeds").unwrap_or(20);
+            let iterations = parsed_option(&args, "--iterations").unwrap_or(200);
+            let params: MazeParams = option_value(&args, "--maze")
+                .unwrap_or("")
+                .parse()
+                .unwrap_or_else(|e| exit(e));
+            let out = option_value(&args, "--out").unwrap_or("hard_instances.txt");
+            agent::parse_agent(spec).unwrap_or_else(|e| exit(e));
+            let make_agent = || agent::parse_agent(spec).unwrap();
+            let mut instances = Vec::new();
+            let mut random_regret = 0;
+            for seed in Seed::first(seed_number) {
+                let random = adversarial::BoardLayout::from_seed(seed, params);
+                random_regret += adversarial::measure_gap(&make_agent, &random).regret();
+                let instance =
+                    adversarial::mine_hard_instance(&make_agent, seed, params, iterations)
+                        .unwrap_or_else(|e| exit(e));
+                println!(
+                    "seed {}: optimal {}, agent {}, regret {}",
+                    seed,
+                    instance.gap.optimal,
+                    instance.gap.agent,
+                    instance.gap.regret()
+                );
+                instances.push(instance);
+            }
+            adversarial::save_corpus(out, &make_agent().name(), &instances).unwrap();
+            let mined_regret: ScoreType = instances.iter().map(|i| i.gap.regret()).sum();
+            println!(
+                "mean regret: {:.3} on random boards, {:.3} on mined boards -> {}",
+                random_regret as f64 / seed_number as f64,
+                mined_regret as f64 / seed_number as f64,
+                out
+            );
+        }
+        Some("adversarial-eval") => {
+            // adversarial-eval [PATH] [--agents "greedy;beam width=2 depth=4;mcts"]
+            // adversarial で集めた盤面で、エージェントごとに厳密解との平均の差を出す
+            let path = args.get(2).map_or("hard_instances.txt", |s| s.as_str());
+            let exit = |e: String| -> ! {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            };
+            let layouts = adversarial::load_corpus(path).unwrap_or_else(|e| exit(e));
+            let specs =
+                option_value(&args, "--agents").unwrap_or("greedy;beam width=2 depth=4;mcts");
+            for spec in specs.split(';') {
+                let spec = spec.trim();
+                agent::parse_agent(spec).unwrap_or_else(|e| exit(e));
+                let make_agent = || agent::parse_agent(spec).unwrap();
+                let gaps: Vec<adversarial::Gap> = layouts
+                    .iter()
+                    .map(|layout| adversarial::measure_gap(&make_agent, layout))
+                    .collect();
+                let n = gaps.len().max(1) as f64;
+                println!(
+                    "{}: mean score {:.3}, mean regret {:.3}, optimal on {}/{} boards",
+                    make_agent().name(),
+                    gaps.iter().map(|gap| gap.agent).sum::<ScoreType>() as f64 / n,
+                    gaps.iter().map(|gap| gap.regret()).sum::<ScoreType>() as f64 / n,
+                    gaps.iter().filter(|gap| gap.regret() == 0).count(),
+                    gaps.len()
+                );
+            }
+        }
+        Some("record") => {
+            // record [--agent "beam width=2 depth=4"] [--seed S] [--maze "h=5 w=5 end_turn=12"] [--out game.json]
+            // 1ゲームを指して、シード・盤面の大きさ・手の列を JSON で残す。record-show で局面を並べて見直す
+            let exit = |e: String| -> ! {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            };
+            let spec = option_value(&args, "--agent").unwrap_or("beam width=2 depth=4");
+            let seed = Seed(parsed_option(&args, "--seed").unwrap_or(0));
+            let params: MazeParams = option_value(&args, "--maze")
+                .unwrap_or("")
+                .parse()
+                .unwrap_or_else(|e| exit(e));
+            let out = option_value(&args, "--out").unwrap_or("game.json");
+            let mut agent = agent::parse_agent(spec).unwrap_or_else(|e| exit(e));
+            let game = record::GameRecord::play(agent.as_mut(), seed, params);
+            game.save(out).unwrap();
+            println!(
+                "{} on seed {} ({}): score {} in {} actions -> {}",
+                game.agent,
+                game.seed,
+                game.params,
+                game.score,
+                game.actions.len(),
+                out
+            );
+        }
+        Some("record-show") => {
+            // record-show PATH [--turn T]: 記録を指し直し、各手番の局面と指した手を出す。--turn ならその手番だけ
+            let path = args.get(2).map_or("game.json", |s| s.as_str());
+            let exit = |e: String| -> ! {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            };
+            let game = record::GameRecord::load(path).unwrap_or_else(|e| exit(e.to_string()));
+            let states = game
+                .states()
+                .unwrap_or_else(|e| exit(format!("{}: {}", path, e)));
+            let turn: Option<usize> = parsed_option(&args, "--turn");
+            println!("{}", game.provenance);
+            println!("{} on seed {} ({})", game.agent, game.seed, game.params);
+            for (t, state) in states.iter().enumerate() {
+                if turn.map_or(false, |turn| turn != t) {
+                    continue;
+                }
+                print!("{}", state);
+                match game.actions.get(t) {
+                    Some(action) => println!("action {}\n", action),
+                    None => println!("final score {}", game.score),
+                }
+            }
+        }
+        Some("view") => {
+            // view [PATH] [--plain]: record で残した対局を1手ずつ進めたり戻したりして見る
+            // --plain なら画面を消さず、キャラクターを @ で描く (端末でないところに流すとき)
+            let path = args
+                .get(2)
+                .filter(|arg| !arg.starts_with("--"))
+                .map_or("game.json", |s| s.as_str());
+            let exit = |e: String| -> ! {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            };
+            let game = record::GameRecord::load(path).unwrap_or_else(|e| exit(e.to_string()));
+            let frames = game
+                .states()
+                .unwrap_or_else(|e| exit(format!("{}: {}", path, e)));
+            let ansi = !args.iter().any(|a| a == "--plain");
+            viewer::step_through(
+                &frames,
+                &game.actions,
+                &mut std::io::stdin().lock(),
+                &mut std::io::stdout(),
+                ansi,
+            )
+            .unwrap();
+        }
+        Some("repro") => {
+            // repro PATH [--no-time-limit]
+            // matrix が書いた材料から失敗した意思決定をこのスレッドでやり直す。panic はそのまま落ちるのでデバッガで追える
+            // --no-time-limit で記録した持ち時間を外す (ブレークポイントで止めても探索が打ち切られない)
+            let Some(path) = args.get(2) else {
+                eprintln!("usage: repro PATH [--no-time-limit]");
+                std::process::exit(1);
+            };
+            let bundle = repro::ReproBundle::load(path).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            println!(
+                "{} seed {} after {} actions: {}",
+                bundle.spec,
+                bundle.seed,
+                bundle.actions.len(),
+                bundle.failure
+            );
+            let with_time_limit = !args.iter().any(|arg| arg == "--no-time-limit");
+            match repro::reproduce(&bundle, with_time_limit) {
+                Ok(outcome) => {
+                    if outcome.divergences > 0 {
+                        println!(
+                            "{} of {} earlier decisions differed from the recording",
+                            outcome.divergences,
+                            bundle.actions.len()
+                        );
+                    }
+                    if outcome.legal {
+                        println!("did not reproduce: chose legal action {}", outcome.action);
+                    } else {
+                        println!("reproduced: illegal action {}", outcome.action);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("soak") => {
+            // soak [--agent SPEC] [--duration-s S] [--sample-s S] [--max-growth-mb MB] [--max-drift R]
+            // S 秒のあいだ対局を続け、常駐メモリ・エージェントが持ち続けている状態の数・1手あたりの時間を --sample-s ごとに出す
+            // 最初の標本からメモリが MB 以上増えるか、1手あたりの時間が最初の R 倍を超えたら終了コード 1 で終わる
+            let spec = option_value(&args, "--agent").unwrap_or("pooled width=2");
+            let defaults = soak::SoakOptions::default();
+            let seconds = |name: &str, default: std::time::Duration| {
+                parsed_option(&args, name).map_or(default, std::time::Duration::from_secs_f64)
+            };
+            let options = soak::SoakOptions {
+                duration: seconds("--duration-s", defaults.duration),
+                sample_every: seconds("--sample-s", defaults.sample_every),
+                max_memory_growth_bytes: parsed_option::<usize>(&args, "--max-growth-mb")
+                    .map_or(defaults.max_memory_growth_bytes, |mb| mb << 20),
+                max_latency_drift: parsed_option(&args, "--max-drift")
+                    .unwrap_or(defaults.max_latency_drift),
+            };
+            let mut agent = agent::parse_agent(spec).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            println!("{}", provenance::Provenance::collect(1));
+            println!("soak {} for {:?}", agent.name(), options.duration);
+            let report = soak::run_soak(agent.as_mut(), &options, &mut |sample| {
+                println!("{}", sample)
+            });
+            if report.passed() {
+                println!("passed");
+            } else {
+                for failure in &report.failures {
+                    println!("failed: {}", failure);
+                }
+                std::process::exit(1);
+            }
+        }
+        Some("stats") => {
+            // stats [--agents "greedy;beam width=2 depth=4;mcts playouts=100"] [--games N]
+            // エージェントごとに、1手あたりの展開した局面・複製した局面・読んだ深さ・時間を出す。数えない探索の項目は -
+            // 表を引く探索は、引いた回数・見つかった回数・照合で見つかった衝突の数も出す
+            let specs = option_value(&args, "--agents")
+                .unwrap_or("greedy;beam width=2 depth=4;chokudai;montecarlo;mcts");
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            for spec in specs.split(';') {
+                let inner = agent::parse_agent(spec.trim()).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+                let mut agent = stats::StatsAgent::new(inner);
+                let score = test_ai_score(&mut agent, game_number);
+                let (total, decisions) = (agent.total, agent.decisions);
+                let per_decision = |value: Option<usize>| {
+                    value.map_or("-".to_string(), |v| {
+                        format!("{:.1}", v as f64 / decisions as f64)
+                    })
+                };
+                println!(
+                    "{}: mean score {:.3}, per decision: expanded {}, cloned {}, time {:?}; max depth {}",
+                    agent.name(),
+                    score,
+                    per_decision(total.nodes_expanded),
+                    per_decision(total.states_cloned),
+                    total.elapsed / decisions.max(1) as u32,
+                    total.max_depth.map_or("-".to_string(), |depth| depth.to_string())
+                );
+                if let Some(probes) = total.table_probes {
+                    println!(
+                        "  table: probes {}, hits {}, collisions {}",
+                        probes,
+                        total.table_hits.unwrap_or(0),
+                        total
+                            .hash_collisions
+                            .map_or("-".to_string(), |collisions| collisions.to_string())
+                    );
+                }
+            }
+        }
+        Some("limits") => {
+            // limits [--width W] [--time-ms T] [--max-nodes N] [--max-memory B] [--agent SPEC]
+            let beam_width = parsed_option(&args, "--width").unwrap_or(100);
+            let limits = limits::ResourceLimits {
+                time: parsed_option(&args, "--time-ms").map(std::time::Duration::from_millis),
+                max_nodes: parsed_option(&args, "--max-nodes"),
+                max_memory_bytes: parsed_option(&args, "--max-memory"),
+            };
+            // --agent を渡すと探索を止められないので、制限時間を超えたかを記録するだけになる
+            let violations = match option_value(&args, "--agent") {
+                Some(spec) => {
+                    let mut agent = limits::LimitedAgent {
+                        inner: agent::parse_agent(spec).unwrap_or_else(|e| {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        }),
+                        time: limits.time.unwrap_or(std::time::Duration::MAX),
+                        violations: Vec::new(),
+                    };
+                    println!("{}", test_ai_score(&mut agent, 100));
+                    agent.violations
+                }
+                None => {
+                    let mut agent = limits::LimitedBeamAgent::new(beam_width, END_TURN, limits);
+                    println!("{}", test_ai_score(&mut agent, 100));
+                    agent.violations
+                }
+            };
+            println!("violations: {}", violations.len());
+            for kind in [
+                limits::ViolationKind::Time,
+                limits::ViolationKind::Nodes,
+                limits::ViolationKind::Memory,
+            ] {
+                let count = violations.iter().filter(|v| v.kind == kind).count();
+                println!("  {:?}: {}", kind, count);
+            }
+        }
+        Some("suite") => {
+            // suite [name] [--agent SPEC]... 名前を省略するとスイートの一覧を出す
+            let Some(suite) = args.get(2).and_then(|name| suite::find_suite(name)) else {
+                for suite in suite::suites() {
+                    println!("{:<18} {}", suite.name, suite.description);
+                }
+                return;
+            };
+            let specs: Vec<&str> = args
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i > 0 && args[i - 1] == "--agent")
+                .map(|(_, spec)| spec.as_str())
+                .collect();
+            let specs = if specs.is_empty() {
+                vec!["random", "greedy", "beam width=3"]
+            } else {
+                specs
+            };
+            println!("{}", provenance::Provenance::collect(1));
+            for spec in specs {
+                match suite::run_suite(&suite, spec) {
+                    Ok(result) => println!("{}", result.report_line()),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        Some("alternate") => {
+            // alternate [--games N] [--depth D | --time MS] 先後を入れ替えながら MiniMax とランダムを対戦させる
+            // --time を指定すると深さの代わりに1手あたりの時間で反復深化する
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let depth = parsed_option(&args, "--depth").unwrap_or(END_TURN);
+            let time_ms: Option<u64> = parsed_option(&args, "--time");
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let rate = alternate::win_rate(
+                &mut |state| match time_ms {
+                    Some(time_ms) => alternate::iterative_deepening_action(state, time_ms),
+                    None => alternate::minimax_action(state, depth),
+                },
+                &mut |state| state.random_action(&mut rng),
+                game_number,
+            );
+            let name = match time_ms {
+                Some(time_ms) => format!("iterative deepening(time_ms={})", time_ms),
+                None => format!("minimax(depth={})", depth),
+            };
+            println!(
+                "{} vs random: win rate {:.3} over {} games",
+                name, rate, game_number
+            );
+        }
+        Some("min-regret") => {
+            // min-regret [--games N] [--depth D]
+            // 相手の最善の応手だけを見て手を選ぶ基準のエージェントを、ランダム・貪欲法・深さ D の Alpha-Beta と対戦させる
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let depth = parsed_option(&args, "--depth").unwrap_or(4);
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let mut min_regret =
+                |state: &alternate::AlternateMazeState| alternate::min_regret_action(state);
+            let report = |name: &str, rate: f64| {
+                println!(
+                    "min-regret vs {}: win rate {:.3} over {} games",
+                    name, rate, game_number
+                )
+            };
+            let rate = alternate::win_rate(
+                &mut min_regret,
+                &mut |state| state.random_action(&mut rng),
+                game_number,
+            );
+            report("random", rate);
+            let rate = alternate::win_rate(
+                &mut min_regret,
+                &mut |state| alternate::minimax_action(state, 1),
+                game_number,
+            );
+            report("greedy", rate);
+            let rate = alternate::win_rate(
+                &mut min_regret,
+                &mut |state| alternate::alpha_beta_action(state, depth),
+                game_number,
+            );
+            report(&format!("alpha-beta(depth={})", depth), rate);
+        }
+        Some("thunder") => {
+            // thunder [--games N] [--playouts N | --time MS] [--depth D]
+            // 先後を入れ替えながら Thunder 探索をランダムと深さ D の Alpha-Beta と対戦させる
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let playout_number = parsed_option(&args, "--playouts").unwrap_or(300);
+            let time_ms: Option<u64> = parsed_option(&args, "--time");
+            let depth = parsed_option(&args, "--depth").unwrap_or(2);
+            let mut thunder = |state: &alternate::AlternateMazeState| match time_ms {
+                Some(time_ms) => {
+                    alternate::thunder_search_action_with_time_threshold(state, time_ms)
+                }
+                None => alternate::thunder_search_action(state, playout_number),
+            };
+            let name = match time_ms {
+                Some(time_ms) => format!("thunder(time_ms={})", time_ms),
+                None => format!("thunder(playouts={})", playout_number),
+            };
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let rate = alternate::win_rate(
+                &mut thunder,
+                &mut |state| state.random_action(&mut rng),
+                game_number,
+            );
+            println!(
+                "{} vs random: win rate {:.3} over {} games",
+                name, rate, game_number
+            );
+            let rate = alternate::win_rate(
+                &mut thunder,
+                &mut |state| alternate::alpha_beta_action(state, depth),
+                game_number,
+            );
+            println!(
+                "{} vs alpha-beta(depth={}): win rate {:.3} over {} games",
+                name, depth, rate, game_number
+            );
+        }
+        Some("alpha-beta") => {
+            // alpha-beta [--games N] [--depth D] Alpha-Beta が minimax と同じ手を選ぶかを確かめ、読んだ局面数を比べる
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let depth = parsed_option(&args, "--depth").unwrap_or(END_TURN);
+            match alternate::check_alpha_beta(game_number, depth) {
+                Ok(report) => println!(
+                    "{} positions agree: minimax {} nodes, alpha-beta {} nodes ({:.1}%), ordered alpha-beta {} nodes ({:.1}%)",
+                    report.positions,
+                    report.minimax_nodes,
+                    report.alpha_beta_nodes,
+                    100.0 * report.alpha_beta_nodes as f64 / report.minimax_nodes.max(1) as f64,
+                    report.ordered_nodes,
+                    100.0 * report.ordered_nodes as f64 / report.minimax_nodes.max(1) as f64
+                ),
+                Err(divergence) => {
+                    eprintln!("{}", divergence);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("connect-four") => {
+            // connect-four [--games N] [--depth D] [--playouts N]
+            // 四目並べで Alpha-Beta・MCTS・Thunder をランダムと、また互いに先後を入れ替えて対戦させる
+            let game_number = parsed_option(&args, "--games").unwrap_or(20);
+            let depth = parsed_option(&args, "--depth").unwrap_or(4);
+            let playout_number = parsed_option(&args, "--playouts").unwrap_or(1000);
+            let new_game = &connect_four::ConnectFourState::from_seed;
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let mut mcts_rng = ChaCha8Rng::seed_from_u64(1);
+            let alpha_beta_name = format!("alpha-beta(depth={})", depth);
+            let mcts_name = format!("mcts(playouts={})", playout_number);
+            let thunder_name = format!("thunder(playouts={})", playout_number);
+            let mut alpha_beta =
+                |state: &connect_four::ConnectFourState| alternate::alpha_beta_action(state, depth);
+            let mut thunder = |state: &connect_four::ConnectFourState| {
+                alternate::thunder_search_action(state, playout_number)
+            };
+            let mut mcts = |state: &connect_four::ConnectFourState| {
+                alternate::mcts_action(state, playout_number, &mut mcts_rng)
+            };
+            let mut random = |state: &connect_four::ConnectFourState| state.random_action(&mut rng);
+            let report = |name: &str, opponent: &str, rate: f64| {
+                println!(
+                    "{} vs {}: win rate {:.3} over {} games",
+                    name, opponent, rate, game_number
+                )
+            };
+            let rate =
+                alternate::win_rate_with(new_game, &mut alpha_beta, &mut random, game_number);
+            report(&alpha_beta_name, "random", rate);
+            let rate = alternate::win_rate_with(new_game, &mut mcts, &mut random, game_number);
+            report(&mcts_name, "random", rate);
+            let rate = alternate::win_rate_with(new_game, &mut thunder, &mut random, game_number);
+            report(&thunder_name, "random", rate);
+            let rate = alternate::win_rate_with(new_game, &mut alpha_beta, &mut mcts, game_number);
+            report(&alpha_beta_name, &mcts_name, rate);
+            let rate = alternate::win_rate_with(new_game, &mut thunder, &mut mcts, game_number);
+            report(&thunder_name, &mcts_name, rate);
+            let rate =
+                alternate::win_rate_with(new_game, &mut alpha_beta, &mut thunder, game_number);
+            report(&alpha_beta_name, &thunder_name, rate);
+        }
+        Some("tournament") => {
+            // tournament [--game maze|connect-four] [--a "alphabeta depth=4"] [--b "mcts playouts=1000"] [--seeds N]
+            // 2人ゲームで a と b を N 個の盤面で先後を入れ替えて2局ずつ対戦させ、a から見た勝ち・引き分け・負けを出す
+            // AI の種類: random seed / minregret / minimax depth / alphabeta depth / deepening time_ms / thunder playouts / mcts playouts seed
+            let a = option_value(&args, "--a").unwrap_or("alphabeta depth=4");
+            let b = option_value(&args, "--b").unwrap_or("mcts playouts=1000");
+            let seed_number = parsed_option(&args, "--seeds").unwrap_or(50);
+            let report = match option_value(&args, "--game").unwrap_or("maze") {
+                "maze" => {
+                    run_tournament(&alternate::AlternateMazeState::from_seed, a, b, seed_number)
+                }
+                "connect-four" => run_tournament(
+                    &connect_four::ConnectFourBitState::from_seed,
+                    a,
+                    b,
+                    seed_number,
+                ),
+                game => Err(format!("unknown game `{}` (maze or connect-four)", game)),
+            };
+            match report {
+                Ok(report) => println!("{}", report),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("connect-four-bench") => {
+            // connect-four-bench [--playouts N] [--games N]
+            // 2つの四目並べの実装が --games 局のランダムな対局で食い違わないことを確かめ、ランダムプレイアウトの速さを比べる
+            let playout_number = parsed_option(&args, "--playouts").unwrap_or(100_000);
+            let game_number = parsed_option(&args, "--games").unwrap_or(1000);
+            match connect_four::check_bitboard(game_number) {
+                Ok(positions) => println!("{} positions agree", positions),
+                Err(divergence) => {
+                    eprintln!("{}", divergence);
+                    std::process::exit(1);
+                }
+            }
+            let array = connect_four::measure_playouts(
+                &connect_four::ConnectFourState::from_seed,
+                playout_number,
+            );
+            let bitboard = connect_four::measure_playouts(
+                &connect_four::ConnectFourBitState::from_seed,
+                playout_number,
+            );
+            for (name, throughput) in [("array", array), ("bitboard", bitboard)] {
+                println!(
+                    "{}: {} playouts ({} turns) in {:?}, {:.0} playouts/s",
+                    name,
+                    throughput.playouts,
+                    throughput.turns,
+                    throughput.elapsed,
+                    throughput.playouts_per_second()
+                );
+            }
+            println!(
+                "speedup: {:.2}x",
+                bitboard.playouts_per_second() / array.playouts_per_second()
+            );
+        }
+        Some("instance") => {
+            // instance generate "tsp cities=20 size=100" PATH [--seed S]
+            // instance check PATH [--solution "0 2 1"] 読み込めるかを確かめ、解があれば採点する
+            let path = args.get(3).unwrap_or_else(|| {
+                eprintln!("usage: instance generate SPEC PATH | instance check PATH");
+                std::process::exit(1);
+            });
+            match args.get(2).map(String::as_str) {
+                Some("generate") => {
+                    let kind = instances::InstanceKind::parse(path).unwrap_or_else(|e| {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    });
+                    let output = args.get(4).expect("instance generate needs an output path");
+                    let seed = parsed_option(&args, "--seed").unwrap_or(0);
+                    instances::Instance::generate(kind, seed)
+                        .save(output)
+                        .expect("failed to write instance");
+                }
+                Some("check") => {
+                    let instance = instances::Instance::load(path).unwrap_or_else(|e| {
+                        eprintln!("{}: {}", path, e);
+                        std::process::exit(1);
+                    });
+                    print!("{}", instance);
+                    if let Some(solution) = option_value(&args, "--solution") {
+                        match instance.score(solution) {
+                            Ok(score) => println!("score {}", score),
+                            Err(e) => {
+                                eprintln!("invalid solution: {}", e);
+                                std::process::exit(1);
+                            }
+                        }
+                    }
+                }
+                _ => eprintln!("usage: instance generate SPEC PATH | instance check PATH"),
+            }
+        }
+        Some("simultaneous") => {
+            // simultaneous [--collision split|priority0|priority1|neither] [--games N]
+            //   [--rules0 "start=y,x pass=K extra=K vision=R"] [--rules1 ...] [--save-replay PREFIX]
+            // --save-replay を指定すると最初の盤面の対局を全部見える形とプレイヤーごとの見え方で書く
+            let rule = option_value(&args, "--collision")
+                .unwrap_or("split")
+                .parse()
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let player_rules = ["--rules0", "--rules1"].map(|name| {
+                option_value(&args, name)
+                    .unwrap_or("")
+                    .parse()
+                    .unwrap_or_else(|e| {
+                        eprintln!("{}: {}", name, e);
+                        std::process::exit(1);
+                    })
+            });
+            let summary = simultaneous::greedy_vs_random(rule, player_rules, game_number);
+            if let Some(prefix) = option_value(&args, "--save-replay") {
+                let frames = simultaneous::record_greedy_vs_random(Seed(0), rule, player_rules);
+                for path in simultaneous::save_replay_views(prefix, &frames).unwrap() {
+                    println!("replay written to {}", path);
+                }
+            }
+            println!(
+                "collision={} greedy wins: {}, random wins: {}, draws: {}, collisions: {}, scores: {:?}",
+                rule, summary.wins[0], summary.wins[1], summary.draws, summary.collisions, summary.total_scores
+            );
+        }
+        Some("cooperative") => {
+            // cooperative [--maze "h=5 w=5 end_turn=10"] [--games N] [--width W] [--depth D] [--show]
+            // 点を共有する2人の迷路で、2人とも貪欲法、行動の組をまとめたビームサーチ、相手を仮定した分散の計画を比べる
+            let params: MazeParams = option_value(&args, "--maze")
+                .unwrap_or("h=5 w=5 end_turn=10")
+                .parse()
+                .unwrap_or_else(|e: String| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let width = parsed_option(&args, "--width").unwrap_or(10);
+            let depth = parsed_option(&args, "--depth").unwrap_or(4);
+            if args.iter().any(|a| a == "--show") {
+                print!(
+                    "{}",
+                    cooperative::CooperativeMazeState::from_seed(Seed(0), params)
+                );
+            }
+            let policies = [
+                cooperative::CooperativePolicy::Greedy,
+                cooperative::CooperativePolicy::Joint { width, depth },
+                cooperative::CooperativePolicy::Decentralized {
+                    width,
+                    depth,
+                    model: cooperative::PartnerModel::Still,
+                },
+                cooperative::CooperativePolicy::Decentralized {
+                    width,
+                    depth,
+                    model: cooperative::PartnerModel::Greedy,
+                },
+            ];
+            for policy in policies {
+                println!(
+                    "{}: {:.3} ({})",
+                    policy,
+                    cooperative::average_score(policy, params, game_number),
+                    params
+                );
+            }
+        }
+        Some("auction") => {
+            // auction [--maze "h=5 w=5 end_turn=10"] [--games N] [--playouts 200] [--levels 0,1,2,4] [--show]
+            // 毎ターン入札で先に動く権利を決める迷路で、入札の候補の細かさを変えたモンテカルロ法を
+            // 入札しない貪欲法と、取り合うマスにだけ入札する方法と、先手・後手を入れ替えて対戦させる
+            let params: MazeParams = option_value(&args, "--maze")
+                .unwrap_or("h=5 w=5 end_turn=10")
+                .parse()
+                .unwrap_or_else(|e: String| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let playouts = parsed_option(&args, "--playouts").unwrap_or(200);
+            let levels: Vec<usize> = option_value(&args, "--levels")
+                .unwrap_or("0,1,2,4")
+                .split(',')
+                .map(|n| parse_or_exit(n, "--levels"))
+                .collect();
+            if args.iter().any(|a| a == "--show") {
+                print!("{}", auction::AuctionMazeState::from_seed(Seed(0), params));
+            }
+            let mut pairs = vec![(
+                auction::AuctionPolicy::Contest,
+                auction::AuctionPolicy::Greedy,
+            )];
+            for levels in levels {
+                let policy = auction::AuctionPolicy::MonteCarlo {
+                    scale: auction::BidScale { levels },
+                    playouts,
+                };
+                pairs.push((policy, auction::AuctionPolicy::Greedy));
+                pairs.push((policy, auction::AuctionPolicy::Contest));
+            }
+            for (policy, opponent) in pairs {
+                let as_first = auction::compare([policy, opponent], params, game_number);
+                let as_second = auction::compare([opponent, policy], params, game_number);
+                let games = 2 * game_number;
+                let wins = as_first.wins[0] + as_second.wins[1];
+                let draws = as_first.draws + as_second.draws;
+                let score = as_first.total_scores[0] + as_second.total_scores[1];
+                let paid = as_first.total_paid[0] + as_second.total_paid[1];
+                let opponent_paid = as_first.total_paid[1] + as_second.total_paid[0];
+                println!(
+                    "{} vs {}: win rate {:.3} ({} wins, {} draws, {} games), average score {:.2}, average paid {:.2} vs {:.2}",
+                    policy,
+                    opponent,
+                    (wins as f64 + 0.5 * draws as f64) / games as f64,
+                    wins,
+                    draws,
+                    games,
+                    score as f64 / games as f64,
+                    paid as f64 / games as f64,
+                    opponent_paid as f64 / games as f64
+                );
+            }
+        }
+        Some("duct") => {
+            // duct [--playouts 300] [--games N] [--collision split|priority0|priority1|neither]
+            // 同時手番の迷路で Decoupled UCT と貪欲法を、先手・後手を入れ替えて対戦させる
+            let rule = option_value(&args, "--collision")
+                .unwrap_or("split")
+                .parse()
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let playout_number = parsed_option(&args, "--playouts").unwrap_or(300);
+            let summary =
+                duct::duct_vs_greedy(rule, Default::default(), game_number, playout_number);
+            println!(
+                "collision={} duct(playouts={}) wins: {}, greedy wins: {}, draws: {} over {} games",
+                rule,
+                playout_number,
+                summary.duct_wins,
+                summary.greedy_wins,
+                summary.draws,
+                summary.games
+            );
+        }
+        Some("belief") => {
+            // belief [--bonus B] [--vision R] [--games N]
+            // 視界を R に狭めた同時手番の迷路で、見えないマスの点の確率を持って指す貪欲法と、見えないマスを 0 とみなす貪欲法を対戦させる
+            // 情報量のボーナスなし (期待値だけ) と --bonus B の2通りを出す
+            // R >= 1 なら移動先は必ず見えているので、ボーナスなしは貪欲法と同じ手になる
+            let bonus: f64 = parsed_option(&args, "--bonus").unwrap_or(0.5);
+            let vision: usize = parsed_option(&args, "--vision").unwrap_or(1);
+            let game_number = parsed_option(&args, "--games").unwrap_or(50);
+            let player_rules = [simultaneous::PlayerRules {
+                vision: Some(vision),
+                ..Default::default()
+            }; 2];
+            for information_bonus in [0.0, bonus] {
+                let report = belief::belief_vs_greedy(
+                    simultaneous::CollisionRule::Split,
+                    player_rules,
+                    game_number,
+                    information_bonus,
+                );
+                println!(
+                    "belief(bonus={}) vs greedy, vision={}: {} wins, {} losses, {} draws over {} games, mean score {:.2} vs {:.2}",
+                    information_bonus,
+                    vision,
+                    report.belief_wins,
+                    report.greedy_wins,
+                    report.draws,
+                    report.games,
+                    report.belief_score as f64 / report.games as f64,
+                    report.greedy_score as f64 / report.games as f64
+                );
+            }
+        }
+        Some("determinized") => {
+            // determinized [--worlds N] [--playouts N] [--aggregate vote|visits] [--vision R] [--games N]
+            // 両者の視界を R に狭めた同時手番の迷路で、見えない点を埋めた世界ごとに DUCT を回すエージェントと貪欲法を対戦させる
+            // 世界ごとの手がよく食い違うなら --worlds を増やす
+            let defaults = determinized::DeterminizationOptions::default();
+            let options = determinized::DeterminizationOptions {
+                worlds: parsed_option(&args, "--worlds").unwrap_or(defaults.worlds),
+                playouts_per_world: parsed_option(&args, "--playouts")
+                    .unwrap_or(defaults.playouts_per_world),
+                aggregation: option_value(&args, "--aggregate").map_or(defaults.aggregation, |a| {
+                    a.parse().unwrap_or_else(|e| {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    })
+                }),
+                ..defaults
+            };
+            let vision: usize = parsed_option(&args, "--vision").unwrap_or(1);
+            let game_number = parsed_option(&args, "--games").unwrap_or(50);
+            let player_rules = [simultaneous::PlayerRules {
+                vision: Some(vision),
+                ..Default::default()
+            }; 2];
+            let report = determinized::determinized_vs_greedy(
+                simultaneous::CollisionRule::Split,
+                player_rules,
+                game_number,
+                &options,
+            );
+            println!(
+                "determinized(worlds={}, playouts={}, aggregate={}) vs greedy, vision={}: {} wins, {} losses, {} draws over {} games",
+                options.worlds,
+                options.playouts_per_world,
+                options.aggregation,
+                vision,
+                report.determinized_wins,
+                report.greedy_wins,
+                report.draws,
+                report.games
+            );
+            println!(
+                "world disagreement: mean {:.3}, max {:.3}, {} of {} decisions split",
+                report.mean_disagreement(),
+                report.max_disagreement,
+                report.split_decisions,
+                report.decisions
+            );
+        }
+        Some("async") => {
+            // async [--agent SPEC] [--games N] [--threads 2] [--cancel-after-ms T]
+            // 探索をプールのスレッドで動かして Future で待つ。--cancel-after-ms を渡すと各手の探索を T ms で止め、
+            // 止められた手は貪欲法で指す
+            let spec = option_value(&args, "--agent").unwrap_or("beam width=2 depth=4");
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let threads = parsed_option(&args, "--threads").unwrap_or(2);
+            let cancel_after: Option<u64> = parsed_option(&args, "--cancel-after-ms");
+            let agent = agent::parse_agent(spec).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let pool = std::sync::Arc::new(async_agent::BlockingPool::new(threads));
+            let agent = async_agent::AsyncAgent::new(agent, pool);
+            let mut total_score = 0;
+            let mut cancelled = 0;
+            let mut decisions = 0;
+            for seed in Seed::first(game_number) {
+                let mut state = MazeState::from_seed(seed);
+                while !state.is_done() {
+                    let token = async_agent::CancelToken::new();
+                    if let Some(ms) = cancel_after {
+                        let token = token.clone();
+                        std::thread::spawn(move || {
+                            std::thread::sleep(std::time::Duration::from_millis(ms));
+                            token.cancel();
+                        });
+                    }
+                    let action = match async_agent::block_on(agent.select_action(&state, token)) {
+                        Ok(action) => action,
+                        Err(async_agent::TaskError::Cancelled) => {
+                            cancelled += 1;
+                            search::greedy_action(&state)
+                        }
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            std::process::exit(1);
+                        }
+                    };
+                    decisions += 1;
+                    state.advance(action);
+                }
+                total_score += state.evaluate_score();
+            }
+            println!(
+                "{}: mean score {:.3}, {} of {} decisions cancelled",
+                agent.name(),
+                total_score as f64 / game_number as f64,
+                cancelled,
+                decisions
+            );
+        }
+        Some("episodes") => {
+            // episodes [--agent SPEC] [--games N] [--save replay_path] [--save-patches path] [--log] [--progress]
+            // 指し直すときに1手ごとの差分が手番・位置・得点・入ったマスだけかも確かめる
+            // --log で1手ごとの手と探索の反復回数を、--progress で終わった対局の数を標準エラーに出す
+            let spec = option_value(&args, "--agent").unwrap_or("beam width=2 depth=4");
+            let game_number = parsed_option(&args, "--games").unwrap_or(100);
+            let mut agent = agent::parse_agent(spec).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let mut logger = args
+                .iter()
+                .any(|a| a == "--log")
+                .then(|| hooks::Logger::new(std::io::stderr()));
+            let mut progress = args
+                .iter()
+                .any(|a| a == "--progress")
+                .then(|| hooks::ProgressBar::new(game_number, std::io::stderr()));
+            let mut stats = episode::EpisodeStats::default();
+            let mut episodes = Vec::new();
+            let mut patch_replays = Vec::new();
+            for seed in Seed::first(game_number) {
+                let mut patches = patch::PatchRecorder::default();
+                let mut observers: Vec<&mut dyn hooks::Observer> = vec![&mut patches];
+                if let Some(logger) = logger.as_mut() {
+                    observers.push(logger);
+                }
+                if let Some(progress) = progress.as_mut() {
+                    observers.push(progress);
+                }
+                let episode =
+                    episode::play_episode_with_observers(agent.as_mut(), seed, &mut observers);
+                episode::replay(seed, &episode.actions).unwrap();
+                if let Some(e) = patches.errors.first() {
+                    eprintln!("seed {}: {}", seed, e);
+                    std::process::exit(1);
+                }
+                patch_replays.push((seed, patches.patches));
+                stats.add(&episode);
+                episodes.push(episode);
+            }
+            println!("{}", stats);
+            if let Some(path) = option_value(&args, "--save") {
+                episode::save_replays(path, &episodes).unwrap();
+            }
+            if let Some(path) = option_value(&args, "--save-patches") {
+                patch::save_patch_replays(path, &patch_replays).unwrap();
+            }
+        }
+        Some("patches") => {
+            // patches <path>: episodes --save-patches で書いた差分を初期局面に当てて、各ゲームの最後の局面を出す
+            let path = args.get(2).map_or("patches.txt", |p| p.as_str());
+            let replays = patch::load_patch_replays(path).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            for (seed, patches) in &replays {
+                let states = patch::replay_patches(*seed, patches);
+                println!("seed {} after {} patches:", seed, patches.len());
+                print!("{}", states.last().unwrap());
+            }
+        }
+        Some("phases") => {
+            // phases [--seeds 0,1,2] [--time-ms T] [--width W]
+            let seeds = option_value(&args, "--seeds")
+                .unwrap_or("0,1,2")
+                .split(',')
+                .map(|seed| parse_or_exit(seed, "--seeds"))
+                .collect();
+            let total_time =
+                std::time::Duration::from_millis(parsed_option(&args, "--time-ms").unwrap_or(30));
+            let beam_width = parsed_option(&args, "--width").unwrap_or(100);
+            let mut agent = limits::LimitedBeamAgent::new(
+                beam_width,
+                END_TURN,
+                limits::ResourceLimits::default(),
+            );
+            if !agent.capabilities().time_limit {
+                eprintln!(
+                    "warning: {} ignores time limits; the shared clock only counts overtime",
+                    agent.name()
+                );
+            }
+            let result =
+                phases::play_phases(&mut agent, &phases::MultiPhaseGame { seeds, total_time });
+            for phase in &result.phases {
+                println!(
+                    "seed {}: +{} in {:?}",
+                    phase.seed, phase.gained, phase.elapsed
+                );
+            }
+            println!(
+                "score: {}, elapsed: {:?}, overtime decisions: {}",
+                result.score, result.elapsed, result.overtime_decisions
+            );
+        }
+        Some("pruning") => {
+            // pruning [width]
+            let beam_width = args.get(2).map_or(2, |w| parse_or_exit(w, "width"));
+            let mut agent = pruning::PrunedBeamAgent::new(beam_width, END_TURN);
+            println!("{}", test_ai_score(&mut agent, 100));
+            println!(
+                "decisions: {}, expanded: {}, pruned: {}, changed decisions: {}",
+                agent.stats.decisions,
+                agent.stats.expanded_actions,
+                agent.stats.pruned_actions,
+                agent.stats.changed_decisions
+            );
+        }
+        Some("zoo") => {
+            println!("{}", provenance::Provenance::collect(1));
+            let checks = zoo::verify_zoo();
+            for check in &checks {
+                println!(
+                    "{:<12} {:>7.3} (expected {:>7.3}) {} - {}",
+                    check.name,
+                    check.score,
+                    check.expected_score,
+                    if check.drifted() { "DRIFTED" } else { "ok" },
+                    check.description
+                );
+            }
+            let drifted: Vec<&str> = checks
+                .iter()
+                .filter(|check| check.drifted())
+                .map(|check| check.name)
+                .collect();
+            if !drifted.is_empty() {
+                eprintln!("baseline scores changed: {}", drifted.join(", "));
+                std::process::exit(1);
+            }
+        }
+        Some("pack-stats") => {
+            let sizes = packing::PackedSizes::measure();
+            println!("MazeState: {} bytes", sizes.unpacked);
+            println!(
+                "packed: {} bytes ({} without score)",
+                sizes.packed, sizes.packed_position
+            );
+            println!("ratio: {:.1}x", sizes.ratio());
+        }
+        Some("selector") => {
+            let selector = match args.get(2) {
+                Some(path) => SelectorAgent::from_file(path),
+                None => SelectorAgent::from_rules(selector::DEFAULT_RULES),
+            };
+            let mut agent = selector.unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            println!("{}", test_ai_score(&mut agent, 100));
+            for (name, count) in agent.usage() {
+                println!("{}: {}", name, count);
+            }
+        }
+        Some(command) if !command.starts_with("--") => {
+            eprintln!("unknown command `{}`", command);
+            std::process::exit(1);
+        }
+        _ => {
+            // [--algo random|greedy|beam|chokudai|mcts|...] [--games N] [--seed S] [--width W] [--depth D] ...
+            // --algo のエージェントでシード S, S+1, ... の N 個の盤面を指した平均スコアを出す
+            // --h H --w W --end-turn T で盤面の大きさとターン数を変える (既定は 3x4 で 4 ターン)
+            // --threads N で盤面を N 本のスレッドに分けて指す。エージェントは盤面ごとに作り直す
+            // ほかの --key value はエージェントのパラメータ key=value になる (--time-ms は time_ms)
+            // 使えるエージェントとパラメータは --list-agents で出る
+            let algo = option_value(&args, "--algo").unwrap_or("beam");
+            let game_number: u64 = parsed_option(&args, "--games").unwrap_or(100);
+            let first_seed: u64 = parsed_option(&args, "--seed").unwrap_or(0);
+            let threads: Option<usize> = parsed_option(&args, "--threads");
+            let mut spec = algo.to_string();
+            let mut maze = String::new();
+            let mut rest = args[1..].iter();
+            while let Some(arg) = rest.next() {
+                let Some(key) = arg.strip_prefix("--") else {
+                    eprintln!("expected --key value, got `{}`", arg);
+                    std::process::exit(1);
+                };
+                let Some(value) = rest.next() else {
+                    eprintln!("missing value for `{}`", arg);
+                    std::process::exit(1);
+                };
+                let word = format!(" {}={}", key.replace('-', "_"), value);
+                if ["h", "w", "end-turn"].contains(&key) {
+                    maze.push_str(&word);
+                } else if !["algo", "games", "seed", "threads"].contains(&key) {
+                    spec.push_str(&word);
+                }
+            }
+            let params: MazeParams = maze.parse().unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let mut agent = agent::parse_agent(&spec).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            let seeds = first_seed..first_seed + game_number;
+            let scores: Vec<ScoreType> = match threads {
+                None => seeds
+                    .clone()
+                    .map(|seed| play_seed_with_params(agent.as_mut(), Seed(seed), params))
+                    .collect(),
+                Some(threads) => {
+                    let seeds: Vec<Seed> = seeds.clone().map(Seed).collect();
+                    let make_agent = || agent::parse_agent(&spec).unwrap();
+                    play_seeds_parallel(make_agent, &seeds, params, threads)
+                }
+            };
+            let summary = ScoreSummary::new(&scores);
+            println!(
+                "{}: {} (stddev {:.3}) over {} games (seeds {}..{})",
+                agent.name(),
+                summary.mean,
+                summary.stddev,
+                game_number,
+                seeds.start,
+                seeds.end
+            );
+        }
     }
-    total_score as f64 / game_number as f64
-}
-
-fn main() {
-    // play_game(121321);
-    let score = test_ai_score(100);
-    println!("{}", score);
 }
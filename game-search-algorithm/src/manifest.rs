@@ -0,0 +1,251 @@
+use crate::agent::{describe_beam_budget, parse_agent_with_budget};
+use crate::budget::Budget;
+use crate::ids::Seed;
+use crate::provenance::Provenance;
+use crate::scenario::{find_maze_scenario, MazeScenario};
+use crate::ScoreType;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+
+// 実験の条件をすべて書いたファイル。例:
+//   game maze h=3 w=4 end_turn=4
+// game は大きさを書くか、scenario の名前 (game maze::sparse_large) を書く
+//   seeds 0..100
+//   budget nodes=64
+//   agent greedy
+//   agent beam width=2
+// '#' で始まる行と空行は読み飛ばす。budget を省略すると depth=end (局面の残りターンまで読む)
+// budget は depth=D (D は数か end) と nodes=N を並べて書ける (両方なら先に使い切った方で止める)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Manifest {
+    pub game: MazeScenario,
+    pub seeds: Range<u64>,
+    // budget を書かなかったビームサーチの予算。iterations は深さ
+    pub budget: Budget,
+    pub agents: Vec<String>,
+}
+
+fn game_line(game: &MazeScenario) -> String {
+    if *game == MazeScenario::custom(game.params) {
+        format!("game maze {}", game.params)
+    } else {
+        format!("game {}", game.name)
+    }
+}
+
+fn parse_game(text: &str) -> Result<MazeScenario, String> {
+    match text.strip_prefix("maze ") {
+        Some(params) => Ok(MazeScenario::custom(params.parse()?)),
+        None => find_maze_scenario(text),
+    }
+}
+
+// 正規化した形で書き出す。ハッシュはこの文字列から取る
+impl Display for Manifest {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "{}", game_line(&self.game))?;
+        writeln!(f, "seeds {}..{}", self.seeds.start, self.seeds.end)?;
+        writeln!(
+            f,
+            "budget {}",
+            describe_beam_budget(&self.budget).replace(", ", " ")
+        )?;
+        for agent in &self.agents {
+            writeln!(f, "agent {}", agent)?;
+        }
+        Ok(())
+    }
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut game = None;
+        let mut seeds = None;
+        let mut budget = Budget::default();
+        let mut agents = Vec::new();
+        let number = |text: &str| {
+            text.parse::<u64>()
+                .map_err(|_| format!("invalid number `{}`", text))
+        };
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+            // 余分な空白で別のハッシュにならないように、単語の間を空白1つにそろえる
+            let rest = rest.split_whitespace().collect::<Vec<_>>().join(" ");
+            match key {
+                "game" => game = Some(parse_game(&rest)?),
+                "seeds" => {
+                    let (start, end) = rest
+                        .split_once("..")
+                        .ok_or(format!("expected seeds start..end, got `{}`", rest))?;
+                    seeds = Some(number(start)?..number(end)?);
+                }
+                "budget" => {
+                    if rest.is_empty() {
+                        return Err("empty `budget` line".to_string());
+                    }
+                    budget = Budget::default();
+                    for word in rest.split_whitespace() {
+                        match word.split_once('=') {
+                            Some(("depth", "end")) => budget.iterations = None,
+                            Some(("depth", depth)) => {
+                                budget.iterations = Some(number(depth)? as usize)
+                            }
+                            Some(("nodes", nodes)) => budget.nodes = Some(number(nodes)? as usize),
+                            _ => return Err(format!("invalid budget `{}`", rest)),
+                        }
+                    }
+                }
+                "agent" => {
+                    parse_agent_with_budget(&rest, budget)?;
+                    agents.push(rest);
+                }
+                _ => return Err(format!("unknown manifest line `{}`", line)),
+            }
+        }
+        let game = game.ok_or("missing `game` line")?;
+        if agents.is_empty() {
+            return Err("no `agent` lines".to_string());
+        }
+        Ok(Self {
+            game,
+            seeds: seeds.ok_or("missing `seeds` line")?,
+            budget,
+            agents,
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // 正規化した条件の FNV-1a ハッシュ
+    pub fn hash(&self) -> u64 {
+        const OFFSET: u64 = 0xcbf29ce484222325;
+        const PRIME: u64 = 0x100000001b3;
+        let mut hash = OFFSET;
+        for byte in self.to_string().bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(PRIME);
+        }
+        hash
+    }
+
+    fn play(&self, spec: &str, seed: Seed) -> Result<ScoreType, String> {
+        let mut agent = parse_agent_with_budget(spec, self.budget)?;
+        Ok(self.game.play(agent.as_mut(), seed))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManifestResult {
+    pub hash: u64,
+    // (エージェント, シード, 得点)
+    pub games: Vec<(String, Seed, ScoreType)>,
+}
+
+impl ManifestResult {
+    // 1行目にマニフェストのハッシュ、以降 "シード<TAB>得点<TAB>エージェント" を書く
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        writeln!(writer, "{}", Provenance::collect(1))?;
+        writeln!(writer, "manifest {:016x}", self.hash)?;
+        for (spec, seed, score) in &self.games {
+            writeln!(writer, "{}\t{}\t{}", seed, score, spec)?;
+        }
+        writer.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: invalid result line `{}`", path, line),
+            )
+        };
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines().filter(|line| !line.starts_with('#'));
+        let header = lines.next().unwrap_or_default();
+        let hash = header
+            .strip_prefix("manifest ")
+            .and_then(|hash| u64::from_str_radix(hash, 16).ok())
+            .ok_or_else(|| invalid(header))?;
+        let mut games = Vec::new();
+        for line in lines {
+            let fields: Vec<&str> = line.splitn(3, '\t').collect();
+            let [seed, score, spec] = fields[..] else {
+                return Err(invalid(line));
+            };
+            let seed = seed.parse().map_err(|_| invalid(line))?;
+            let score = score.parse().map_err(|_| invalid(line))?;
+            games.push((spec.to_string(), seed, score));
+        }
+        Ok(Self { hash, games })
+    }
+}
+
+pub fn run_manifest(manifest: &Manifest) -> Result<ManifestResult, String> {
+    let mut games = Vec::new();
+    for spec in &manifest.agents {
+        let mut agent = parse_agent_with_budget(spec, manifest.budget)?;
+        for seed in manifest.seeds.clone().map(Seed) {
+            games.push((spec.clone(), seed, manifest.game.play(agent.as_mut(), seed)));
+        }
+    }
+    Ok(ManifestResult {
+        hash: manifest.hash(),
+        games,
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub spec: String,
+    pub seed: Seed,
+    pub recorded: ScoreType,
+    pub replayed: ScoreType,
+}
+
+// 結果が同じ条件から出たものかをハッシュで確かめ、sample 局を選んで指し直して得点を比べる
+pub fn verify_manifest(
+    manifest: &Manifest,
+    result: &ManifestResult,
+    sample: usize,
+    seed: u64,
+) -> Result<Vec<Mismatch>, String> {
+    if result.hash != manifest.hash() {
+        return Err(format!(
+            "result was produced by manifest {:016x}, not {:016x}",
+            result.hash,
+            manifest.hash()
+        ));
+    }
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut mismatches = Vec::new();
+    for (spec, seed, recorded) in result.games.choose_multiple(&mut rng, sample) {
+        if !manifest.agents.contains(spec) || !manifest.seeds.contains(&seed.0) {
+            return Err(format!(
+                "`{}` on seed {} is not in the manifest",
+                spec, seed
+            ));
+        }
+        let replayed = manifest.play(spec, *seed)?;
+        if replayed != *recorded {
+            mismatches.push(Mismatch {
+                spec: spec.clone(),
+                seed: *seed,
+                recorded: *recorded,
+                replayed,
+            });
+        }
+    }
+    Ok(mismatches)
+}
@@ -0,0 +1,445 @@
+use crate::agent::Agent;
+use crate::budget::{Budget, BudgetMeter, Consumed};
+use crate::prior::{GreedyPrior, PriorPolicy};
+use crate::sequential_halving::{halve, HalvingArm, RootPolicy};
+use crate::stats;
+use crate::{GameState, MazeState, ScoreType};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+// UCB1 の探索項の係数。平均得点と同じ単位なので、1局の得点の幅に合わせておく
+pub const DEFAULT_EXPLORATION: f64 = 10.0;
+// この回数訪れた葉を展開する
+const EXPAND_THRESHOLD: usize = 10;
+
+// 終局まで一様ランダムに指した評価値。進めた局面の数を meter に足す
+fn random_playout<S: GameState>(
+    state: &S,
+    rng: &mut impl Rng,
+    meter: &mut BudgetMeter,
+) -> ScoreType {
+    stats::record_clones(1);
+    let mut state = state.clone();
+    while !state.is_done() {
+        let legal_actions = state.legal_actions();
+        state.advance(legal_actions[rng.gen_range(0..legal_actions.len())]);
+        meter.add_nodes(1);
+    }
+    state.evaluate_score()
+}
+
+// 各合法手のあとに playout_number / 合法手数 回ずつランダムに指し、平均評価値が最大の手を選ぶ
+// 木を作らない素朴なモンテカルロ法。同じ平均なら先に並んでいる手
+pub fn primitive_montecarlo_action<S: GameState>(
+    state: &S,
+    playout_number: usize,
+    rng: &mut impl Rng,
+) -> usize {
+    primitive_montecarlo_action_with_budget(state, &Budget::iterations(playout_number), rng).0
+}
+
+// budget の iterations はプレイアウトの回数、nodes は進めた局面の数
+// どの手も1回はプレイアウトするまでは budget を使い切っても続ける
+pub fn primitive_montecarlo_action_with_budget<S: GameState>(
+    state: &S,
+    budget: &Budget,
+    rng: &mut impl Rng,
+) -> (usize, Consumed) {
+    let mut meter = BudgetMeter::new(*budget);
+    let legal_actions = state.legal_actions();
+    let mut totals = vec![(0, 0); legal_actions.len()];
+    for i in 0.. {
+        if i >= legal_actions.len() && meter.exhausted().is_some() {
+            break;
+        }
+        let k = i % legal_actions.len();
+        stats::record_clones(1);
+        let mut next_state = state.clone();
+        next_state.advance(legal_actions[k]);
+        meter.add_nodes(1);
+        totals[k].0 += random_playout(&next_state, rng, &mut meter);
+        totals[k].1 += 1;
+        meter.add_iteration();
+    }
+    let mean = |(total, count): (ScoreType, usize)| total as f64 / count as f64;
+    let mut best = 0;
+    for k in 0..legal_actions.len() {
+        if mean(totals[k]) > mean(totals[best]) {
+            best = k;
+        }
+    }
+    (legal_actions[best], meter.consumed())
+}
+
+pub struct PrimitiveMonteCarloAgent {
+    pub playout_number: usize,
+    rng: ChaCha8Rng,
+}
+
+impl PrimitiveMonteCarloAgent {
+    pub fn new(playout_number: usize, seed: u64) -> Self {
+        Self {
+            playout_number,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Agent for PrimitiveMonteCarloAgent {
+    fn name(&self) -> String {
+        format!("montecarlo(playouts={})", self.playout_number)
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        primitive_montecarlo_action(state, self.playout_number, &mut self.rng)
+    }
+}
+
+struct Node<S> {
+    state: S,
+    total_score: ScoreType,
+    visits: usize,
+    children: Vec<(usize, Node<S>)>,
+    // 親の局面でこの手を指す事前確率。PUCT で選ぶときだけ使う
+    prior: f64,
+}
+
+impl<S: GameState> Node<S> {
+    fn new(state: S) -> Self {
+        Self {
+            state,
+            total_score: 0,
+            visits: 0,
+            children: Vec::new(),
+            prior: 1.0,
+        }
+    }
+
+    fn mean(&self) -> f64 {
+        self.total_score as f64 / self.visits as f64
+    }
+
+    // prior があれば子に事前確率をつけておく
+    fn expand(&mut self, prior: Option<&dyn PriorPolicy<S>>, meter: &mut BudgetMeter) {
+        stats::record_expansion();
+        let priors = match prior {
+            Some(prior) => prior.priors(&self.state),
+            None => self
+                .state
+                .legal_actions()
+                .into_iter()
+                .map(|action| (action, 1.0))
+                .collect(),
+        };
+        for (action, p) in priors {
+            stats::record_clones(1);
+            let mut next_state = self.state.clone();
+            next_state.advance(action);
+            meter.add_nodes(1);
+            let mut child = Node::new(next_state);
+            child.prior = p;
+            self.children.push((action, child));
+        }
+    }
+
+    // 選択・展開・プレイアウトを1回行い、得た評価値を自分に足して返す (逆伝播)
+    // prior がなければ UCB1、あれば PUCT で子を選ぶ
+    fn evaluate(
+        &mut self,
+        exploration: f64,
+        prior: Option<&dyn PriorPolicy<S>>,
+        rng: &mut impl Rng,
+        meter: &mut BudgetMeter,
+    ) -> ScoreType {
+        let score = if self.state.is_done() {
+            self.state.clone().evaluate_score()
+        } else if self.children.is_empty() {
+            let score = random_playout(&self.state, rng, meter);
+            if self.visits + 1 == EXPAND_THRESHOLD {
+                self.expand(prior, meter);
+            }
+            score
+        } else {
+            let i = match prior {
+                Some(_) => self.select_puct(exploration),
+                None => self.select(exploration),
+            };
+            self.children[i].1.evaluate(exploration, prior, rng, meter)
+        };
+        self.total_score += score;
+        self.visits += 1;
+        score
+    }
+
+    // まだ訪れていない子を先に、あとは UCB1 が最大の子を選ぶ
+    fn select(&self, exploration: f64) -> usize {
+        if let Some(i) = self
+            .children
+            .iter()
+            .position(|(_, child)| child.visits == 0)
+        {
+            return i;
+        }
+        let log_visits = (self.visits as f64).ln();
+        let ucb1 = |child: &Node<S>| {
+            child.mean() + exploration * (log_visits / child.visits as f64).sqrt()
+        };
+        let mut best = 0;
+        for (i, (_, child)) in self.children.iter().enumerate() {
+            if ucb1(child) > ucb1(&self.children[best].1) {
+                best = i;
+            }
+        }
+        best
+    }
+
+    // 平均 + exploration * 事前確率 * sqrt(親の訪問回数) / (1 + 子の訪問回数) が最大の子
+    // 訪れていない子の平均は親の平均で代える。同じ値なら先に並んでいる子
+    fn select_puct(&self, exploration: f64) -> usize {
+        let parent_mean = if self.visits == 0 { 0.0 } else { self.mean() };
+        let sqrt_visits = (self.visits as f64).sqrt();
+        let puct = |child: &Node<S>| {
+            let mean = if child.visits == 0 {
+                parent_mean
+            } else {
+                child.mean()
+            };
+            mean + exploration * child.prior * sqrt_visits / (1 + child.visits) as f64
+        };
+        let mut best = 0;
+        for (i, (_, child)) in self.children.iter().enumerate() {
+            if puct(child) > puct(&self.children[best].1) {
+                best = i;
+            }
+        }
+        best
+    }
+
+    // 逐次半減法 (sequential_halving::halve) で budget 回のプレイアウトを子に配る
+    // shot なら子の中でも再帰的に半減し (SHOT)、そうでなければ子より下は evaluate で選ぶ
+    // 戻り値は (合計評価値, プレイアウト回数)
+    fn halving(
+        &mut self,
+        budget: usize,
+        shot: bool,
+        exploration: f64,
+        prior: Option<&dyn PriorPolicy<S>>,
+        rng: &mut impl Rng,
+        meter: &mut BudgetMeter,
+    ) -> (ScoreType, usize) {
+        if self.state.is_done() || budget <= 1 {
+            return (self.evaluate(exploration, prior, rng, meter), 1);
+        }
+        if self.children.is_empty() {
+            self.expand(prior, meter);
+        }
+        let search = |child: &mut Node<S>, playouts: usize| {
+            if shot {
+                child.halving(playouts, true, exploration, prior, rng, meter)
+            } else {
+                let total = (0..playouts)
+                    .map(|_| child.evaluate(exploration, prior, rng, meter))
+                    .sum();
+                (total, playouts)
+            }
+        };
+        let (total, used) = halve(&mut self.children, budget, search);
+        self.total_score += total;
+        self.visits += used;
+        (total, used)
+    }
+
+    // 最も多く訪れた手。同数なら先に並んでいる手
+    fn most_visited_action(&self) -> usize {
+        let mut best = &self.children[0];
+        for child in &self.children {
+            if child.1.visits > best.1.visits {
+                best = child;
+            }
+        }
+        best.0
+    }
+
+    // 半減で残った手。最後の半減で訪問回数が並ぶことがあるので、同数なら平均の高い手
+    fn halving_action(&self) -> usize {
+        let mut best = &self.children[0];
+        for child in &self.children {
+            if child.1.visits > best.1.visits
+                || (child.1.visits == best.1.visits && child.1.mean() > best.1.mean())
+            {
+                best = child;
+            }
+        }
+        best.0
+    }
+}
+
+impl<S: GameState> HalvingArm for Node<S> {
+    fn mean(&self) -> f64 {
+        Node::mean(self)
+    }
+}
+
+pub fn mcts_action<S: GameState>(state: &S, playout_number: usize, rng: &mut impl Rng) -> usize {
+    mcts_action_with_exploration(state, playout_number, DEFAULT_EXPLORATION, rng)
+}
+
+pub fn mcts_action_with_exploration<S: GameState>(
+    state: &S,
+    playout_number: usize,
+    exploration: f64,
+    rng: &mut impl Rng,
+) -> usize {
+    mcts_search(state, playout_number, exploration, None, rng)
+}
+
+// 子を UCB1 の代わりに prior の事前確率を使う PUCT で選ぶ MCTS
+pub fn puct_action<S: GameState>(
+    state: &S,
+    playout_number: usize,
+    exploration: f64,
+    prior: &dyn PriorPolicy<S>,
+    rng: &mut impl Rng,
+) -> usize {
+    mcts_search(state, playout_number, exploration, Some(prior), rng)
+}
+
+// 根の手へのプレイアウトの配分を UCB1 (PUCT) の代わりに root_policy で決める MCTS
+// 逐次半減法なら根の子より下は UCB1 (PUCT) の木、SHOT なら木全体で半減を繰り返す
+pub fn mcts_action_with_root_policy<S: GameState>(
+    state: &S,
+    playout_number: usize,
+    exploration: f64,
+    root_policy: RootPolicy,
+    prior: Option<&dyn PriorPolicy<S>>,
+    rng: &mut impl Rng,
+) -> usize {
+    let mut root = Node::new(state.clone());
+    let mut meter = BudgetMeter::new(Budget::default());
+    root.expand(prior, &mut meter);
+    let shot = root_policy == RootPolicy::Shot;
+    root.halving(
+        playout_number.max(2),
+        shot,
+        exploration,
+        prior,
+        rng,
+        &mut meter,
+    );
+    root.halving_action()
+}
+
+fn mcts_search<S: GameState>(
+    state: &S,
+    playout_number: usize,
+    exploration: f64,
+    prior: Option<&dyn PriorPolicy<S>>,
+    rng: &mut impl Rng,
+) -> usize {
+    let mut root = Node::new(state.clone());
+    let mut meter = BudgetMeter::new(Budget::default());
+    root.expand(prior, &mut meter);
+    for _ in 0..playout_number {
+        root.evaluate(exploration, prior, rng, &mut meter);
+    }
+    root.most_visited_action()
+}
+
+// budget の iterations はプレイアウトの回数、nodes は木に足した局面とプレイアウトで進めた局面の数
+// 使い切るまで続けるので、budget は1つ以上の条件を持たなければならない
+pub fn mcts_action_with_budget<S: GameState>(
+    state: &S,
+    budget: &Budget,
+    exploration: f64,
+    rng: &mut impl Rng,
+) -> (usize, Consumed) {
+    assert!(!budget.is_unlimited(), "MCTS needs a finite budget");
+    let mut meter = BudgetMeter::new(*budget);
+    let mut root = Node::new(state.clone());
+    root.expand(None, &mut meter);
+    while meter.exhausted().is_none() {
+        root.evaluate(exploration, None, rng, &mut meter);
+        meter.add_iteration();
+    }
+    (root.most_visited_action(), meter.consumed())
+}
+
+pub struct MctsAgent {
+    pub playout_number: usize,
+    pub exploration: f64,
+    // あれば PUCT で子を選ぶ
+    pub prior: Option<Box<dyn PriorPolicy>>,
+    // あれば根の手へのプレイアウトの配分を UCB1 (PUCT) の代わりにこれで決める
+    pub root_policy: Option<RootPolicy>,
+    rng: ChaCha8Rng,
+}
+
+impl MctsAgent {
+    pub fn new(playout_number: usize, seed: u64) -> Self {
+        Self {
+            playout_number,
+            exploration: DEFAULT_EXPLORATION,
+            prior: None,
+            root_policy: None,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    // 1手先の評価値の softmax を事前確率にした PUCT
+    pub fn puct(playout_number: usize, seed: u64) -> Self {
+        Self {
+            prior: Some(Box::new(GreedyPrior { temperature: 2.0 })),
+            ..Self::new(playout_number, seed)
+        }
+    }
+}
+
+impl Agent for MctsAgent {
+    fn name(&self) -> String {
+        let mut name = match &self.prior {
+            None => format!(
+                "mcts(playouts={}, exploration={}",
+                self.playout_number, self.exploration
+            ),
+            Some(prior) => format!(
+                "puct(playouts={}, exploration={}, prior={}",
+                self.playout_number,
+                self.exploration,
+                prior.name()
+            ),
+        };
+        match self.root_policy {
+            None => {}
+            Some(RootPolicy::SequentialHalving) => name.push_str(", root=halving"),
+            Some(RootPolicy::Shot) => name.push_str(", root=shot"),
+        }
+        name.push(')');
+        name
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        match (self.root_policy, &self.prior) {
+            (Some(root_policy), prior) => mcts_action_with_root_policy(
+                state,
+                self.playout_number,
+                self.exploration,
+                root_policy,
+                prior.as_deref(),
+                &mut self.rng,
+            ),
+            (None, None) => mcts_action_with_exploration(
+                state,
+                self.playout_number,
+                self.exploration,
+                &mut self.rng,
+            ),
+            (None, Some(prior)) => puct_action(
+                state,
+                self.playout_number,
+                self.exploration,
+                prior.as_ref(),
+                &mut self.rng,
+            ),
+        }
+    }
+}
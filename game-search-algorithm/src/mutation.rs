@@ -0,0 +1,92 @@
+use crate::budget::{Budget, BudgetMeter, Consumed};
+use crate::ScoreType;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+// 配置を決めるタイプのゲームで、解を少しだけ変えて近傍を作る操作
+// これを実装すれば hill_climb と simulated_annealing がそのまま使える
+pub trait Mutation: Clone {
+    // 解の一部をランダムに変える
+    fn mutate(&mut self, rng: &mut ChaCha8Rng);
+    // 今の解の得点。大きいほど良い
+    fn score(&self) -> ScoreType;
+}
+
+// state から始めて1か所ずつ変え、得点が下がらなければ移る。number_of_iterations 回変えたら止める
+pub fn hill_climb<S: Mutation>(state: &S, number_of_iterations: usize, rng: &mut ChaCha8Rng) -> S {
+    simulated_annealing(state, number_of_iterations, 0.0, 0.0, rng)
+}
+
+// number_of_iterations 回変えたら止める焼きなまし
+pub fn simulated_annealing<S: Mutation>(
+    state: &S,
+    number_of_iterations: usize,
+    start_temperature: f64,
+    end_temperature: f64,
+    rng: &mut ChaCha8Rng,
+) -> S {
+    simulated_annealing_with_budget(
+        state,
+        &Budget::iterations(number_of_iterations),
+        start_temperature,
+        end_temperature,
+        rng,
+    )
+    .0
+}
+
+// 温度のスケジュール。進んだ割合 progress (0 から 1) に応じて start から end へ線形に下げる
+pub fn temperature(start_temperature: f64, end_temperature: f64, progress: f64) -> f64 {
+    start_temperature + (end_temperature - start_temperature) * progress
+}
+
+// 得点が delta だけ変わる解に移る確率。良くなるなら必ず移り、温度 0 なら悪くなる解には移らない
+pub fn acceptance_probability(delta: ScoreType, temperature: f64) -> f64 {
+    if delta >= 0 {
+        1.0
+    } else if temperature > 0.0 {
+        (delta as f64 / temperature).exp()
+    } else {
+        0.0
+    }
+}
+
+// 山登りと同じ近傍で、悪くなる解にも acceptance_probability の確率で移る
+// 温度は budget を使った割合に応じて temperature で下げる
+// budget の iterations は変えた回数、nodes は score を呼んだ回数
+pub fn simulated_annealing_with_budget<S: Mutation>(
+    state: &S,
+    budget: &Budget,
+    start_temperature: f64,
+    end_temperature: f64,
+    rng: &mut ChaCha8Rng,
+) -> (S, Consumed) {
+    assert!(
+        !budget.is_unlimited(),
+        "mutation search needs a finite budget"
+    );
+    let mut meter = BudgetMeter::new(*budget);
+    let mut current = state.clone();
+    let mut current_score = current.score();
+    meter.add_nodes(1);
+    let mut best = (current_score, current.clone());
+    while meter.exhausted().is_none() {
+        let mut candidate = current.clone();
+        candidate.mutate(rng);
+        let score = candidate.score();
+        meter.add_nodes(1);
+        let temperature = temperature(start_temperature, end_temperature, meter.progress());
+        let delta = score - current_score;
+        let accept = delta >= 0
+            || (temperature > 0.0 && rng.gen::<f64>() < acceptance_probability(delta, temperature));
+        if accept {
+            current = candidate;
+            current_score = score;
+            if current_score > best.0 {
+                best = (current_score, current.clone());
+            }
+        }
+        meter.add_iteration();
+    }
+    (best.1, meter.consumed())
+}
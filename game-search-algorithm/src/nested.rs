@@ -0,0 +1,331 @@
+use crate::agent::{Agent, Capabilities};
+use crate::budget::{Budget, BudgetMeter, Consumed};
+use crate::{GameState, MazeState, ScoreType};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashMap;
+use std::time::Duration;
+
+// 進めた局面の数を meter の nodes に、プレイアウト1回を iterations に足す
+fn random_playout(
+    state: &MazeState,
+    rng: &mut impl Rng,
+    meter: &mut BudgetMeter,
+) -> (ScoreType, Vec<usize>) {
+    let mut state = state.clone();
+    let mut sequence = Vec::new();
+    while !state.is_done() {
+        let action = state.random_action(rng);
+        state.advance(action);
+        sequence.push(action);
+    }
+    meter.add_nodes(sequence.len());
+    meter.add_iteration();
+    (state.game_score, sequence)
+}
+
+// Nested Monte Carlo Search
+// レベル n では各手をレベル n-1 で評価し、これまでに見つけた最良の手順に沿って進む
+pub fn nested_monte_carlo_search(
+    state: &MazeState,
+    level: usize,
+    rng: &mut impl Rng,
+) -> (ScoreType, Vec<usize>) {
+    nested_monte_carlo_search_with_budget(state, level, &Budget::default(), rng).0
+}
+
+// budget の iterations はプレイアウトの回数、nodes はプレイアウトで進めた局面の数
+// 使い切ったら、それまでに見つけた最良の手順をそのまま返す。手順が1つ見つかるまでは止めない
+pub fn nested_monte_carlo_search_with_budget(
+    state: &MazeState,
+    level: usize,
+    budget: &Budget,
+    rng: &mut impl Rng,
+) -> ((ScoreType, Vec<usize>), Consumed) {
+    let mut meter = BudgetMeter::new(*budget);
+    let result = nested_monte_carlo(state, level, rng, &mut meter);
+    (result, meter.consumed())
+}
+
+fn nested_monte_carlo(
+    state: &MazeState,
+    level: usize,
+    rng: &mut impl Rng,
+    meter: &mut BudgetMeter,
+) -> (ScoreType, Vec<usize>) {
+    if level == 0 || state.is_done() {
+        return random_playout(state, rng, meter);
+    }
+    let mut state = state.clone();
+    let mut played = Vec::new();
+    let mut best_score = -1;
+    let mut best_sequence = Vec::new();
+    while !state.is_done() {
+        for action in state.legal_actions() {
+            if !best_sequence.is_empty() && meter.exhausted().is_some() {
+                return (best_score, best_sequence);
+            }
+            let mut next_state = state.clone();
+            next_state.advance(action);
+            let (score, sequence) = nested_monte_carlo(&next_state, level - 1, rng, meter);
+            if score > best_score {
+                best_score = score;
+                best_sequence = played.clone();
+                best_sequence.push(action);
+                best_sequence.extend(sequence);
+            }
+        }
+        let action = best_sequence[played.len()];
+        state.advance(action);
+        played.push(action);
+    }
+    (best_score, best_sequence)
+}
+
+// budget の iterations はプレイアウトの回数。指定しなければ level の探索を最後まで行う
+pub struct NestedMonteCarloAgent {
+    pub level: usize,
+    pub budget: Budget,
+    // set_time_limit で受け取った1手の時間
+    time_limit: Option<Duration>,
+    rng: ChaCha8Rng,
+}
+
+impl NestedMonteCarloAgent {
+    pub fn new(level: usize, seed: u64) -> Self {
+        Self::with_budget(level, Budget::default(), seed)
+    }
+
+    pub fn with_budget(level: usize, budget: Budget, seed: u64) -> Self {
+        Self {
+            level,
+            budget,
+            time_limit: None,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+// 予算を指定していれば名前に足す
+fn name_with_budget(name: String, budget: &Budget, iterations: &str) -> String {
+    if budget.is_unlimited() {
+        format!("{})", name)
+    } else {
+        format!("{}, {})", name, budget.describe(iterations))
+    }
+}
+
+impl Agent for NestedMonteCarloAgent {
+    fn name(&self) -> String {
+        name_with_budget(
+            format!("nmcs(level={}", self.level),
+            &self.budget,
+            "playouts",
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        self.select_action_with_score(state).0
+    }
+
+    fn select_action_with_score(&mut self, state: &MazeState) -> (usize, Option<ScoreType>) {
+        let ((score, sequence), _) = nested_monte_carlo_search_with_budget(
+            state,
+            self.level.max(1),
+            &self.budget.with_time_limit(self.time_limit),
+            &mut self.rng,
+        );
+        (sequence[0], Some(score))
+    }
+
+    fn set_time_limit(&mut self, time: Option<Duration>) {
+        self.time_limit = time;
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::budgeted(&self.budget, self.time_limit)
+    }
+}
+
+// 手の符号: (キャラクターの位置, 行動)
+type MoveCode = (i64, i64, usize);
+type Policy = HashMap<MoveCode, f64>;
+
+fn code(state: &MazeState, action: usize) -> MoveCode {
+    (state.character.y, state.character.x, action)
+}
+
+fn policy_playout(
+    state: &MazeState,
+    policy: &Policy,
+    rng: &mut impl Rng,
+    meter: &mut BudgetMeter,
+) -> (ScoreType, Vec<usize>) {
+    let mut state = state.clone();
+    let mut sequence = Vec::new();
+    while !state.is_done() {
+        let legal_actions = state.legal_actions();
+        let weights: Vec<f64> = legal_actions
+            .iter()
+            .map(|&action| policy.get(&code(&state, action)).unwrap_or(&0.0).exp())
+            .collect();
+        let total: f64 = weights.iter().sum();
+        let mut r = rng.gen::<f64>() * total;
+        let mut action = *legal_actions.last().unwrap();
+        for (&a, weight) in legal_actions.iter().zip(&weights) {
+            if r < *weight {
+                action = a;
+                break;
+            }
+            r -= weight;
+        }
+        state.advance(action);
+        sequence.push(action);
+    }
+    meter.add_nodes(sequence.len());
+    meter.add_iteration();
+    (state.game_score, sequence)
+}
+
+// 最良手順の手の重みを上げ、各局面の手の重みを方策確率に比例して下げる
+fn adapt(state: &MazeState, sequence: &[usize], policy: &Policy, alpha: f64) -> Policy {
+    let mut new_policy = policy.clone();
+    let mut state = state.clone();
+    for &best_action in sequence {
+        let legal_actions = state.legal_actions();
+        let weights: Vec<f64> = legal_actions
+            .iter()
+            .map(|&action| policy.get(&code(&state, action)).unwrap_or(&0.0).exp())
+            .collect();
+        let total: f64 = weights.iter().sum();
+        *new_policy.entry(code(&state, best_action)).or_insert(0.0) += alpha;
+        for (&action, weight) in legal_actions.iter().zip(&weights) {
+            *new_policy.entry(code(&state, action)).or_insert(0.0) -= alpha * weight / total;
+        }
+        state.advance(best_action);
+    }
+    new_policy
+}
+
+// Nested Rollout Policy Adaptation
+pub fn nested_rollout_policy_adaptation(
+    state: &MazeState,
+    level: usize,
+    iterations: usize,
+    policy: &Policy,
+    rng: &mut impl Rng,
+) -> (ScoreType, Vec<usize>) {
+    nested_rollout_policy_adaptation_with_budget(
+        state,
+        level,
+        iterations,
+        &Budget::default(),
+        policy,
+        rng,
+    )
+    .0
+}
+
+// budget の iterations はプレイアウトの回数、nodes はプレイアウトで進めた局面の数
+// (引数の iterations は各レベルで方策を更新する回数で、budget とは別)
+// 使い切ったら、それまでに見つけた最良の手順をそのまま返す。手順が1つ見つかるまでは止めない
+pub fn nested_rollout_policy_adaptation_with_budget(
+    state: &MazeState,
+    level: usize,
+    iterations: usize,
+    budget: &Budget,
+    policy: &Policy,
+    rng: &mut impl Rng,
+) -> ((ScoreType, Vec<usize>), Consumed) {
+    let mut meter = BudgetMeter::new(*budget);
+    let result = nrpa(state, level, iterations, policy, rng, &mut meter);
+    (result, meter.consumed())
+}
+
+fn nrpa(
+    state: &MazeState,
+    level: usize,
+    iterations: usize,
+    policy: &Policy,
+    rng: &mut impl Rng,
+    meter: &mut BudgetMeter,
+) -> (ScoreType, Vec<usize>) {
+    if level == 0 || state.is_done() {
+        return policy_playout(state, policy, rng, meter);
+    }
+    let mut policy = policy.clone();
+    let mut best_score = -1;
+    let mut best_sequence = Vec::new();
+    for _ in 0..iterations {
+        if !best_sequence.is_empty() && meter.exhausted().is_some() {
+            break;
+        }
+        let (score, sequence) = nrpa(state, level - 1, iterations, &policy, rng, meter);
+        if score >= best_score {
+            best_score = score;
+            best_sequence = sequence;
+        }
+        policy = adapt(state, &best_sequence, &policy, 1.0);
+    }
+    (best_score, best_sequence)
+}
+
+// budget の iterations はプレイアウトの回数。指定しなければ level と iterations の探索を最後まで行う
+pub struct NestedRolloutPolicyAgent {
+    pub level: usize,
+    pub iterations: usize,
+    pub budget: Budget,
+    // set_time_limit で受け取った1手の時間
+    time_limit: Option<Duration>,
+    rng: ChaCha8Rng,
+}
+
+impl NestedRolloutPolicyAgent {
+    pub fn new(level: usize, iterations: usize, seed: u64) -> Self {
+        Self::with_budget(level, iterations, Budget::default(), seed)
+    }
+
+    pub fn with_budget(level: usize, iterations: usize, budget: Budget, seed: u64) -> Self {
+        Self {
+            level,
+            iterations,
+            budget,
+            time_limit: None,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Agent for NestedRolloutPolicyAgent {
+    fn name(&self) -> String {
+        name_with_budget(
+            format!("nrpa(level={}, iterations={}", self.level, self.iterations),
+            &self.budget,
+            "playouts",
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        self.select_action_with_score(state).0
+    }
+
+    fn select_action_with_score(&mut self, state: &MazeState) -> (usize, Option<ScoreType>) {
+        let ((score, sequence), _) = nested_rollout_policy_adaptation_with_budget(
+            state,
+            self.level.max(1),
+            self.iterations,
+            &self.budget.with_time_limit(self.time_limit),
+            &Policy::new(),
+            &mut self.rng,
+        );
+        (sequence[0], Some(score))
+    }
+
+    fn set_time_limit(&mut self, time: Option<Duration>) {
+        self.time_limit = time;
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::budgeted(&self.budget, self.time_limit)
+    }
+}
@@ -0,0 +1,159 @@
+use crate::ids::Seed;
+use crate::{Coord, MazeParams, MazeState, ScoreType, H, W};
+
+const CELL_BYTES: usize = (H * W + 1) / 2;
+pub const PACKED_POSITION_LEN: usize = CELL_BYTES + 3;
+pub const PACKED_LEN: usize = PACKED_POSITION_LEN + 4;
+
+impl MazeState {
+    // 盤面を1マス4ビットで詰め、キャラクターの座標とターンを1バイトずつ続ける (得点は含まない)
+    // 既定でない大きさの盤面ではその大きさに合わせた長さになり、unpack_with_params で読む
+    // 座標とターンは 255 までしか書けないので、それより大きな盤面や長いゲームは詰められない
+    pub fn pack_position(&self) -> Vec<u8> {
+        debug_assert!(
+            self.params.h <= 256 && self.params.w <= 256 && self.params.end_turn <= 255,
+            "{} is too large to pack",
+            self.params
+        );
+        let mut bytes = vec![0; (self.params.h * self.params.w + 1) / 2];
+        for (i, &point) in self.points.iter().flatten().enumerate() {
+            debug_assert!((0..16).contains(&point));
+            bytes[i / 2] |= (point as u8) << (4 * (i % 2));
+        }
+        bytes.push(self.character.y as u8);
+        bytes.push(self.character.x as u8);
+        bytes.push(self.turn as u8);
+        bytes
+    }
+
+    // pack_position に得点を4バイトで続けたもの。探索用の evaluated_score と first_action は捨てる
+    pub fn pack(&self) -> Vec<u8> {
+        let mut bytes = self.pack_position();
+        bytes.extend_from_slice(&(self.game_score as u32).to_le_bytes());
+        bytes
+    }
+
+    // 既定の大きさの盤面を pack したものを読む
+    pub fn unpack(bytes: &[u8]) -> Result<MazeState, String> {
+        Self::unpack_with_params(bytes, MazeParams::default())
+    }
+
+    // params の大きさの盤面を pack したものを読む。大きさは書かれていないので呼び出し側が渡す
+    pub fn unpack_with_params(bytes: &[u8], params: MazeParams) -> Result<MazeState, String> {
+        let cell_bytes = (params.h * params.w + 1) / 2;
+        let packed_len = cell_bytes + 3 + 4;
+        if bytes.len() != packed_len {
+            return Err(format!(
+                "packed {}x{} state must be {} bytes, got {}",
+                params.h,
+                params.w,
+                packed_len,
+                bytes.len()
+            ));
+        }
+        let mut points = vec![vec![0; params.w]; params.h];
+        for (i, point) in points.iter_mut().flatten().enumerate() {
+            *point = ((bytes[i / 2] >> (4 * (i % 2))) & 0xf) as ScoreType;
+        }
+        let (y, x, turn) = (
+            bytes[cell_bytes],
+            bytes[cell_bytes + 1],
+            bytes[cell_bytes + 2],
+        );
+        if y as usize >= params.h || x as usize >= params.w {
+            return Err(format!("character ({}, {}) is outside the board", y, x));
+        }
+        if turn as usize > params.end_turn {
+            return Err(format!(
+                "turn {} is past the last turn {}",
+                turn, params.end_turn
+            ));
+        }
+        let mut score = [0; 4];
+        score.copy_from_slice(&bytes[cell_bytes + 3..]);
+        Ok(MazeState {
+            points,
+            turn: turn as usize,
+            character: Coord {
+                y: y as i64,
+                x: x as i64,
+            },
+            game_score: u32::from_le_bytes(score) as ScoreType,
+            evaluated_score: 0,
+            first_action: None,
+            last_action: None,
+            params,
+        })
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(text: &str) -> Result<Vec<u8>, String> {
+    // 2バイトずつ切り出すので、マルチバイト文字の途中で切らないように先に弾く
+    if !text.is_ascii() {
+        return Err(format!("invalid hex `{}`", text));
+    }
+    if text.len() % 2 != 0 {
+        return Err(format!("odd-length hex `{}`", text));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| format!("invalid hex `{}`", text))
+        })
+        .collect()
+}
+
+// 通常の状態と詰めた状態の1個あたりのバイト数
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackedSizes {
+    pub unpacked: usize,
+    pub packed: usize,
+    pub packed_position: usize,
+}
+
+impl PackedSizes {
+    pub fn measure() -> Self {
+        let state = MazeState::from_seed(Seed(0));
+        assert_eq!(MazeState::unpack(&state.pack()).unwrap(), state);
+        let heap =
+            std::mem::size_of::<Vec<ScoreType>>() * H + std::mem::size_of::<ScoreType>() * H * W;
+        Self {
+            unpacked: std::mem::size_of::<MazeState>() + heap,
+            packed: PACKED_LEN,
+            packed_position: PACKED_POSITION_LEN,
+        }
+    }
+
+    pub fn ratio(&self) -> f64 {
+        self.unpacked as f64 / self.packed as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameState;
+
+    #[test]
+    fn unpack_with_params_reads_other_board_sizes() {
+        let params = MazeParams {
+            h: 7,
+            w: 11,
+            end_turn: 30,
+        };
+        let mut state = MazeState::from_seed_with_params(Seed(2), params);
+        while !state.is_done() {
+            let packed = state.pack();
+            let unpacked = MazeState::unpack_with_params(&packed, params).unwrap();
+            assert_eq!(unpacked.params(), params);
+            assert_eq!(unpacked.pack(), packed);
+            assert!(MazeState::unpack(&packed).is_err());
+            let action = state.legal_actions()[0];
+            state.advance(action);
+        }
+    }
+}
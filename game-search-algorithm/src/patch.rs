@@ -0,0 +1,406 @@
+use crate::hooks::Observer;
+use crate::ids::{Seed, Turn};
+use crate::provenance::Provenance;
+use crate::{Coord, GameState, MazeParams, MazeState, ScoreType};
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::str::FromStr;
+
+// 2つの局面の差分。変わったフィールドだけを持ち、変わらなかったものは None にする
+// 盤面は変わったマスの (y, x, 新しい点) だけを持つ
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StatePatch {
+    // 差分を当てる盤面の大きさ。変わったフィールドには数えない
+    pub params: MazeParams,
+    pub turn: Option<Turn>,
+    pub character: Option<(i64, i64)>,
+    pub game_score: Option<ScoreType>,
+    pub evaluated_score: Option<ScoreType>,
+    pub first_action: Option<Option<usize>>,
+    pub last_action: Option<Option<usize>>,
+    pub cells: Vec<(usize, usize, ScoreType)>,
+}
+
+// a から b への差分。apply(a, diff(a, b)) == b になる
+pub fn diff(a: &MazeState, b: &MazeState) -> StatePatch {
+    fn changed<T: PartialEq>(before: T, after: T) -> Option<T> {
+        (before != after).then_some(after)
+    }
+    let mut cells = Vec::new();
+    for (j, (row_a, row_b)) in a.points.iter().zip(b.points.iter()).enumerate() {
+        for (i, (&point_a, &point_b)) in row_a.iter().zip(row_b.iter()).enumerate() {
+            if point_a != point_b {
+                cells.push((j, i, point_b));
+            }
+        }
+    }
+    StatePatch {
+        params: b.params(),
+        turn: changed(a.turn(), b.turn()),
+        character: changed(
+            (a.character.y, a.character.x),
+            (b.character.y, b.character.x),
+        ),
+        game_score: changed(a.game_score, b.game_score),
+        evaluated_score: changed(a.evaluated_score, b.evaluated_score),
+        first_action: changed(a.first_action, b.first_action),
+        last_action: changed(a.last_action, b.last_action),
+        cells,
+    }
+}
+
+impl MazeState {
+    pub fn apply(&mut self, patch: &StatePatch) {
+        debug_assert_eq!(self.params, patch.params);
+        if let Some(turn) = patch.turn {
+            self.turn = turn.index();
+        }
+        if let Some((y, x)) = patch.character {
+            self.character = Coord { y, x };
+        }
+        if let Some(game_score) = patch.game_score {
+            self.game_score = game_score;
+        }
+        if let Some(evaluated_score) = patch.evaluated_score {
+            self.evaluated_score = evaluated_score;
+        }
+        if let Some(first_action) = patch.first_action {
+            self.first_action = first_action;
+        }
+        if let Some(last_action) = patch.last_action {
+            self.last_action = last_action;
+        }
+        for &(y, x, point) in &patch.cells {
+            self.points[y][x] = point;
+        }
+    }
+}
+
+impl StatePatch {
+    pub fn is_empty(&self) -> bool {
+        *self
+            == StatePatch {
+                params: self.params,
+                ..StatePatch::default()
+            }
+    }
+
+    // 変わったフィールドの名前。テキスト形式のキーと同じ
+    pub fn changed_fields(&self) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.turn.is_some() {
+            fields.push("turn");
+        }
+        if self.character.is_some() {
+            fields.push("at");
+        }
+        if self.game_score.is_some() {
+            fields.push("score");
+        }
+        if self.evaluated_score.is_some() {
+            fields.push("eval");
+        }
+        if self.first_action.is_some() {
+            fields.push("first");
+        }
+        if self.last_action.is_some() {
+            fields.push("last");
+        }
+        if !self.cells.is_empty() {
+            fields.push("cell");
+        }
+        fields
+    }
+}
+
+// "turn=1 at=0,2 score=7 last=0 cell=0,2:0" の形。行動がないことは "-" で書く
+// 既定の大きさでない盤面では先頭に "h=3 w=4 end_turn=4" を付ける
+impl Display for StatePatch {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let action = |action: Option<usize>| action.map_or("-".to_string(), |a| a.to_string());
+        let mut words = Vec::new();
+        if self.params != MazeParams::default() {
+            words.push(self.params.to_string());
+        }
+        if let Some(turn) = self.turn {
+            words.push(format!("turn={}", turn));
+        }
+        if let Some((y, x)) = self.character {
+            words.push(format!("at={},{}", y, x));
+        }
+        if let Some(game_score) = self.game_score {
+            words.push(format!("score={}", game_score));
+        }
+        if let Some(evaluated_score) = self.evaluated_score {
+            words.push(format!("eval={}", evaluated_score));
+        }
+        if let Some(first_action) = self.first_action {
+            words.push(format!("first={}", action(first_action)));
+        }
+        if let Some(last_action) = self.last_action {
+            words.push(format!("last={}", action(last_action)));
+        }
+        for (y, x, point) in &self.cells {
+            words.push(format!("cell={},{}:{}", y, x, point));
+        }
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+impl FromStr for StatePatch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn number<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+            value
+                .parse()
+                .map_err(|_| format!("invalid value `{}` for `{}`", value, key))
+        }
+        fn pair<T: FromStr>(key: &str, value: &str) -> Result<(T, T), String> {
+            let (y, x) = value
+                .split_once(',')
+                .ok_or(format!("expected y,x for `{}`, got `{}`", key, value))?;
+            Ok((number(key, y)?, number(key, x)?))
+        }
+        fn action(key: &str, value: &str) -> Result<Option<usize>, String> {
+            match value {
+                "-" => Ok(None),
+                _ => number(key, value).map(Some),
+            }
+        }
+
+        let mut patch = StatePatch::default();
+        let mut params_words = Vec::new();
+        for word in s.split_whitespace() {
+            let (key, value) = word
+                .split_once('=')
+                .ok_or(format!("expected key=value, got `{}`", word))?;
+            match key {
+                "h" | "w" | "end_turn" => params_words.push(word),
+                "turn" => patch.turn = Some(Turn(number(key, value)?)),
+                "at" => patch.character = Some(pair(key, value)?),
+                "score" => patch.game_score = Some(number(key, value)?),
+                "eval" => patch.evaluated_score = Some(number(key, value)?),
+                "first" => patch.first_action = Some(action(key, value)?),
+                "last" => patch.last_action = Some(action(key, value)?),
+                "cell" => {
+                    let (at, point) = value
+                        .split_once(':')
+                        .ok_or(format!("expected y,x:point for `cell`, got `{}`", value))?;
+                    let (y, x) = pair(key, at)?;
+                    patch.cells.push((y, x, number(key, point)?));
+                }
+                _ => return Err(format!("unknown field `{}`", key)),
+            }
+        }
+        // 大きさは行のどこに書かれていてもよいので、読み終えてから位置を確かめる
+        patch.params = params_words.join(" ").parse()?;
+        let (h, w) = (patch.params.h, patch.params.w);
+        if let Some((y, x)) = patch.character {
+            if y < 0 || x < 0 || y as usize >= h || x as usize >= w {
+                return Err(format!("at {},{} is outside the {}x{} board", y, x, h, w));
+            }
+        }
+        if let Some(&(y, x, _)) = patch.cells.iter().find(|&&(y, x, _)| y >= h || x >= w) {
+            return Err(format!("cell {},{} is outside the {}x{} board", y, x, h, w));
+        }
+        Ok(patch)
+    }
+}
+
+// 1手進めたときの差分が、手番・位置・得点・直前の行動と、入ったマス1つだけで済んでいるかを確かめる
+pub fn check_advance(state: &MazeState, action: usize) -> Result<StatePatch, String> {
+    let mut next = state.clone();
+    next.advance(action);
+    let patch = diff(state, &next);
+    let unexpected: Vec<&str> = patch
+        .changed_fields()
+        .into_iter()
+        .filter(|field| !["turn", "at", "score", "last", "cell"].contains(field))
+        .collect();
+    if !unexpected.is_empty() {
+        return Err(format!(
+            "turn {}: action {} changed {}",
+            state.turn(),
+            action,
+            unexpected.join(", ")
+        ));
+    }
+    if patch.cells.len() > 1 {
+        return Err(format!(
+            "turn {}: action {} changed {} cells",
+            state.turn(),
+            action,
+            patch.cells.len()
+        ));
+    }
+    let mut patched = state.clone();
+    patched.apply(&patch);
+    if patched != next {
+        return Err(format!(
+            "turn {}: applying `{}` does not reproduce the next state",
+            state.turn(),
+            patch
+        ));
+    }
+    Ok(patch)
+}
+
+// 1手ごとに check_advance で差分を取って記録する
+#[derive(Clone, Debug, Default)]
+pub struct PatchRecorder {
+    pub patches: Vec<StatePatch>,
+    pub errors: Vec<String>,
+}
+
+impl Observer for PatchRecorder {
+    fn on_action_chosen(&mut self, state: &MazeState, action: usize) {
+        match check_advance(state, action) {
+            Ok(patch) => self.patches.push(patch),
+            Err(e) => self.errors.push(e),
+        }
+    }
+}
+
+// 盤面全体は初期局面 (シード) から作り直せるので、リプレイには1手ごとの差分だけを残す
+// "seed S" の行に続けて、1手ごとに "patch ..." の行を書く
+pub fn save_patch_replays(path: &str, replays: &[(Seed, Vec<StatePatch>)]) -> io::Result<()> {
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    writeln!(writer, "{}", Provenance::collect(1))?;
+    for (seed, patches) in replays {
+        writeln!(writer, "seed {}", seed)?;
+        for patch in patches {
+            writeln!(writer, "patch {}", patch)?;
+        }
+    }
+    writer.flush()
+}
+
+pub fn load_patch_replays(path: &str) -> io::Result<Vec<(Seed, Vec<StatePatch>)>> {
+    let invalid = |line: &str, e: String| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid patch replay line `{}`: {}", line, e),
+        )
+    };
+    let mut replays: Vec<(Seed, Vec<StatePatch>)> = Vec::new();
+    for line in fs::read_to_string(path)?.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        match line.split_once(' ') {
+            Some(("seed", seed)) => {
+                let seed = seed.trim().parse().map_err(|e| invalid(line, e))?;
+                replays.push((seed, Vec::new()));
+            }
+            Some(("patch", patch)) => {
+                let patch = patch.parse().map_err(|e| invalid(line, e))?;
+                replays
+                    .last_mut()
+                    .ok_or_else(|| invalid(line, "patch before any seed".to_string()))?
+                    .1
+                    .push(patch);
+            }
+            _ => return Err(invalid(line, "expected `seed` or `patch`".to_string())),
+        }
+    }
+    Ok(replays)
+}
+
+// シードの初期局面に差分を順に当てて、各手のあとの局面を返す。盤面の大きさは差分に書かれたものを使う
+pub fn replay_patches(seed: Seed, patches: &[StatePatch]) -> Vec<MazeState> {
+    let params = patches
+        .first()
+        .map_or(MazeParams::default(), |patch| patch.params);
+    let mut state = MazeState::from_seed_with_params(seed, params);
+    let mut states = vec![state.clone()];
+    for patch in patches {
+        state.apply(patch);
+        states.push(state.clone());
+    }
+    states
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DX, DY};
+
+    #[test]
+    fn advance_changes_only_the_intended_fields() {
+        for seed in Seed::first(20) {
+            let mut state = MazeState::from_seed(seed);
+            let mut i = 0;
+            while !state.is_done() {
+                let legal_actions = state.legal_actions();
+                let action = legal_actions[i % legal_actions.len()];
+                let (y, x) = (
+                    (state.character.y + DY[action]) as usize,
+                    (state.character.x + DX[action]) as usize,
+                );
+                let point = state.points[y][x];
+                let mut next = state.clone();
+                next.advance(action);
+                let patch = diff(&state, &next);
+
+                let mut expected = vec!["turn", "at"];
+                if point > 0 {
+                    expected.push("score");
+                }
+                if state.last_action != Some(action) {
+                    expected.push("last");
+                }
+                if point > 0 {
+                    expected.push("cell");
+                }
+                assert_eq!(patch.changed_fields(), expected, "seed {}", seed);
+                if point > 0 {
+                    assert_eq!(patch.cells, vec![(y, x, 0)]);
+                }
+                assert_eq!(check_advance(&state, action), Ok(patch));
+                state = next;
+                i += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn patch_round_trips_through_text_and_apply() {
+        let state = MazeState::from_seed(Seed(0));
+        let mut next = state.clone();
+        for _ in 0..2 {
+            let action = next.legal_actions()[0];
+            next.advance(action);
+        }
+        let patch = diff(&state, &next);
+        assert_eq!(patch.to_string().parse::<StatePatch>(), Ok(patch.clone()));
+        let mut patched = state.clone();
+        patched.apply(&patch);
+        assert_eq!(patched, next);
+        assert!(diff(&next, &next).is_empty());
+    }
+
+    #[test]
+    fn patches_carry_the_board_size() {
+        let params = MazeParams {
+            h: 3,
+            w: 8,
+            end_turn: 6,
+        };
+        let seed = Seed(1);
+        let mut state = MazeState::from_seed_with_params(seed, params);
+        let mut patches = Vec::new();
+        while !state.is_done() {
+            let action = *state.legal_actions().last().unwrap();
+            let patch = check_advance(&state, action).unwrap();
+            assert_eq!(patch.to_string().parse::<StatePatch>(), Ok(patch.clone()));
+            patches.push(patch);
+            state.advance(action);
+        }
+        assert_eq!(replay_patches(seed, &patches).last(), Some(&state));
+        assert!("h=3 w=8 cell=2,7:0".parse::<StatePatch>().is_ok());
+        assert!("h=3 w=8 cell=3,0:0".parse::<StatePatch>().is_err());
+        assert!("at=0,6 w=6".parse::<StatePatch>().is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::evaluator::Evaluator;
+use crate::provenance::Provenance;
+use crate::td::ValueFunction;
+use crate::{GameState, MazeState, ScoreType, END_TURN, H, W};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+// 1マスの状態: 点を 0, 1..=3, 4..=6, 7..=9 に分けたものと、盤の外
+// 点をそのまま使うと組の状態が多すぎて、学習中にほとんどの重みが一度も更新されない
+const CELL_STATES: usize = 5;
+
+// n-tuple network。キャラクターから見た相対位置のマスの組ごとに、
+// その組の状態の組み合わせ (と残りターン数) に重みを持つ
+// 局面の価値はこれから得られる点の見込みで、全部の組の重みの和
+#[derive(Clone, Debug, PartialEq)]
+pub struct PatternTable {
+    pub tuples: Vec<Vec<(i64, i64)>>,
+    // weights[組][残りターン数 * 組の状態数 + 組の状態]
+    weights: Vec<Vec<f64>>,
+}
+
+impl PatternTable {
+    pub fn new(tuples: Vec<Vec<(i64, i64)>>) -> Self {
+        let weights = tuples
+            .iter()
+            .map(|tuple| vec![0.0; (END_TURN + 1) * CELL_STATES.pow(tuple.len() as u32)])
+            .collect();
+        Self { tuples, weights }
+    }
+
+    // 上下左右それぞれの向きに広がる4マス
+    pub fn default_tuples() -> Vec<Vec<(i64, i64)>> {
+        vec![
+            vec![(0, 1), (0, 2), (1, 1), (-1, 1)],
+            vec![(0, -1), (0, -2), (1, -1), (-1, -1)],
+            vec![(1, 0), (2, 0), (1, 1), (1, -1)],
+            vec![(-1, 0), (-2, 0), (-1, 1), (-1, -1)],
+        ]
+    }
+
+    fn cell_state(state: &MazeState, dy: i64, dx: i64) -> usize {
+        let (y, x) = (state.character.y + dy, state.character.x + dx);
+        if y < 0 || y >= H as i64 || x < 0 || x >= W as i64 {
+            CELL_STATES - 1
+        } else {
+            (state.points[y as usize][x as usize] as usize + 2) / 3
+        }
+    }
+
+    fn indices(&self, state: &MazeState) -> Vec<usize> {
+        let remaining_turns = END_TURN - state.turn;
+        self.tuples
+            .iter()
+            .map(|tuple| {
+                let index = tuple.iter().fold(0, |index, &(y, x)| {
+                    index * CELL_STATES + Self::cell_state(state, y, x)
+                });
+                remaining_turns * CELL_STATES.pow(tuple.len() as u32) + index
+            })
+            .collect()
+    }
+
+    pub fn value(&self, state: &MazeState) -> f64 {
+        if state.is_done() {
+            return 0.0;
+        }
+        self.indices(state)
+            .into_iter()
+            .zip(&self.weights)
+            .map(|(index, weights)| weights[index])
+            .sum()
+    }
+
+    // value(state) を target に近づける
+    pub fn update(&mut self, state: &MazeState, target: f64, learning_rate: f64) {
+        let error = target - self.value(state);
+        let step = learning_rate * error / self.tuples.len() as f64;
+        for (index, weights) in self.indices(state).into_iter().zip(&mut self.weights) {
+            weights[index] += step;
+        }
+    }
+
+    // 1行目に組の定義、以降に 0 でない重みを "組 位置 重み" で書く
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        writeln!(writer, "{}", Provenance::collect(1))?;
+        let tuples: Vec<String> = self
+            .tuples
+            .iter()
+            .map(|tuple| {
+                let cells: Vec<String> =
+                    tuple.iter().map(|(y, x)| format!("{},{}", y, x)).collect();
+                cells.join(" ")
+            })
+            .collect();
+        writeln!(writer, "tuples {}", tuples.join(" | "))?;
+        for (i, weights) in self.weights.iter().enumerate() {
+            for (index, &weight) in weights.iter().enumerate() {
+                if weight != 0.0 {
+                    writeln!(writer, "{} {} {}", i, index, weight)?;
+                }
+            }
+        }
+        writer.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let invalid = |message: String| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, message))
+        };
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines().filter(|line| !line.starts_with('#'));
+        let header = lines
+            .next()
+            .and_then(|line| line.strip_prefix("tuples "))
+            .ok_or_else(|| invalid("missing `tuples`".to_string()))?;
+        let parse_cell = |cell: &str| -> Option<(i64, i64)> {
+            let (dy, dx) = cell.split_once(',')?;
+            Some((dy.parse().ok()?, dx.parse().ok()?))
+        };
+        let tuples = header
+            .split(" | ")
+            .map(|tuple| {
+                tuple
+                    .split_whitespace()
+                    .map(|cell| {
+                        parse_cell(cell).ok_or_else(|| invalid(format!("invalid cell `{}`", cell)))
+                    })
+                    .collect::<io::Result<Vec<_>>>()
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut table = Self::new(tuples);
+        for line in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let entry = match words[..] {
+                [tuple, index, weight] => tuple
+                    .parse::<usize>()
+                    .ok()
+                    .zip(index.parse::<usize>().ok())
+                    .zip(weight.parse::<f64>().ok()),
+                _ => None,
+            };
+            let ((tuple, index), weight) =
+                entry.ok_or_else(|| invalid(format!("invalid weight `{}`", line)))?;
+            let slot = table
+                .weights
+                .get_mut(tuple)
+                .and_then(|weights| weights.get_mut(index))
+                .ok_or_else(|| invalid(format!("weight out of range `{}`", line)))?;
+            *slot = weight;
+        }
+        Ok(table)
+    }
+}
+
+impl Evaluator for PatternTable {
+    fn name(&self) -> String {
+        ValueFunction::name(self)
+    }
+
+    fn evaluate(&self, state: &MazeState) -> ScoreType {
+        state.game_score + self.value(state).round() as ScoreType
+    }
+}
@@ -0,0 +1,82 @@
+use crate::agent::Agent;
+use crate::ids::Seed;
+use crate::{GameState, MazeState, ScoreType, END_TURN};
+use std::time::{Duration, Instant};
+
+// 複数の盤面を続けて遊び、点は次の盤面へ持ち越す。持ち時間は全体で共有する
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiPhaseGame {
+    pub seeds: Vec<u64>,
+    pub total_time: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PhaseResult {
+    pub seed: Seed,
+    // この盤面で増えた点
+    pub gained: ScoreType,
+    pub elapsed: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiPhaseResult {
+    pub phases: Vec<PhaseResult>,
+    pub score: ScoreType,
+    pub elapsed: Duration,
+    // 持ち時間を使い切ったあとに指した手の数
+    pub overtime_decisions: usize,
+}
+
+// 残りの持ち時間を残りの手数 (早く終わる盤面があるので上限) で割って1手に配る
+struct PhaseClock {
+    deadline: Instant,
+    remaining_decisions: usize,
+}
+
+impl PhaseClock {
+    fn allowance(&self) -> Option<Duration> {
+        let remaining = self.deadline.checked_duration_since(Instant::now())?;
+        Some(remaining / self.remaining_decisions.max(1) as u32)
+    }
+}
+
+pub fn play_phases(agent: &mut dyn Agent, game: &MultiPhaseGame) -> MultiPhaseResult {
+    let start = Instant::now();
+    let mut clock = PhaseClock {
+        deadline: start + game.total_time,
+        remaining_decisions: game.seeds.len() * END_TURN,
+    };
+    let mut phases = Vec::new();
+    let mut score = 0;
+    let mut overtime_decisions = 0;
+    for &seed in &game.seeds {
+        let phase_start = Instant::now();
+        let mut state = MazeState::from_seed(Seed(seed));
+        state.game_score = score;
+        let mut turns = 0;
+        while !state.is_done() {
+            let allowance = clock.allowance().unwrap_or_else(|| {
+                overtime_decisions += 1;
+                Duration::ZERO
+            });
+            agent.set_time_limit(Some(allowance));
+            state.advance(agent.select_action(&state));
+            clock.remaining_decisions -= 1;
+            turns += 1;
+        }
+        // 早く終わった盤面の残りの手数は以降の盤面に回す
+        clock.remaining_decisions -= END_TURN - turns;
+        phases.push(PhaseResult {
+            seed: Seed(seed),
+            gained: state.game_score - score,
+            elapsed: phase_start.elapsed(),
+        });
+        score = state.game_score;
+    }
+    MultiPhaseResult {
+        phases,
+        score,
+        elapsed: start.elapsed(),
+        overtime_decisions,
+    }
+}
@@ -0,0 +1,191 @@
+use crate::agent::Agent;
+use crate::ids::Seed;
+use crate::packing::to_hex;
+use crate::prior::{softmax, PriorPolicy};
+use crate::provenance::Provenance;
+use crate::sequential_halving::{RootPolicy, SequentialHalvingAgent};
+use crate::symmetry::augment;
+use crate::{test_ai_score, GameState, MazeState};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+const FEATURE_NUMBER: usize = 4;
+type Features = [f64; FEATURE_NUMBER];
+
+// 行動ごとの手作り特徴量
+// [その手で取れる点, 移動先から残りターン内に届く最大の点, 最も近い点への距離が縮むか, 盤の端に向かうか]
+fn action_features(state: &MazeState, action: usize) -> Features {
+    let mut next_state = state.clone();
+    next_state.advance(action);
+    let gained = (next_state.game_score - state.game_score) as f64;
+
+    let remaining_turns = next_state.remaining_turns() as i64;
+    let mut reachable_best = 0;
+    let mut nearest_before = i64::MAX;
+    let mut nearest_after = i64::MAX;
+    for (y, row) in next_state.points.iter().enumerate() {
+        for (x, &point) in row.iter().enumerate() {
+            if point == 0 {
+                continue;
+            }
+            let distance =
+                |c: &MazeState| (y as i64 - c.character.y).abs() + (x as i64 - c.character.x).abs();
+            if distance(&next_state) <= remaining_turns {
+                reachable_best = reachable_best.max(point);
+            }
+            nearest_before = nearest_before.min(distance(state));
+            nearest_after = nearest_after.min(distance(&next_state));
+        }
+    }
+    let approaching = if nearest_after < nearest_before {
+        1.0
+    } else {
+        0.0
+    };
+    let c = &next_state.character;
+    let at_edge = if c.y == 0
+        || c.y == next_state.params.h as i64 - 1
+        || c.x == 0
+        || c.x == next_state.params.w as i64 - 1
+    {
+        1.0
+    } else {
+        0.0
+    };
+    [gained, reachable_best as f64, approaching, at_edge]
+}
+
+// 特徴量の線形和の softmax で手を選ぶプレイアウト方策
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinearSoftmaxPolicy {
+    pub weights: Features,
+}
+
+impl LinearSoftmaxPolicy {
+    fn logits(&self, state: &MazeState) -> Vec<(usize, Features, f64)> {
+        state
+            .legal_actions()
+            .into_iter()
+            .map(|action| {
+                let features = action_features(state, action);
+                let logit = features.iter().zip(&self.weights).map(|(f, w)| f * w).sum();
+                (action, features, logit)
+            })
+            .collect()
+    }
+}
+
+impl PriorPolicy for LinearSoftmaxPolicy {
+    fn name(&self) -> String {
+        let weights: Vec<String> = self.weights.iter().map(|w| format!("{:.2}", w)).collect();
+        format!("linear_softmax([{}])", weights.join(", "))
+    }
+
+    fn priors(&self, state: &MazeState) -> Vec<(usize, f64)> {
+        let logits: Vec<(usize, f64)> = self
+            .logits(state)
+            .into_iter()
+            .map(|(action, _, logit)| (action, logit))
+            .collect();
+        softmax(&logits, 1.0)
+    }
+}
+
+// 自己対戦で探索が選んだ手
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sample {
+    pub state: MazeState,
+    pub action: usize,
+}
+
+pub fn collect_self_play(agent: &mut dyn Agent, seeds: std::ops::Range<u64>) -> Vec<Sample> {
+    let mut samples = Vec::new();
+    for seed in seeds.map(Seed) {
+        let mut state = MazeState::from_seed(seed);
+        while !state.is_done() {
+            let action = agent.select_action(&state);
+            samples.push(Sample {
+                state: state.clone(),
+                action,
+            });
+            state.advance(action);
+        }
+    }
+    samples
+}
+
+// 探索の選んだ手の対数尤度を勾配上昇で最大化する
+pub fn fit(
+    policy: &mut LinearSoftmaxPolicy,
+    samples: &[Sample],
+    epochs: usize,
+    learning_rate: f64,
+) {
+    for _ in 0..epochs {
+        let mut gradient = [0.0; FEATURE_NUMBER];
+        for sample in samples {
+            let logits = policy.logits(&sample.state);
+            let probabilities = softmax(
+                &logits
+                    .iter()
+                    .map(|&(action, _, logit)| (action, logit))
+                    .collect::<Vec<_>>(),
+                1.0,
+            );
+            for ((action, features, _), (_, p)) in logits.iter().zip(probabilities) {
+                let target = if *action == sample.action { 1.0 } else { 0.0 };
+                for (g, f) in gradient.iter_mut().zip(features) {
+                    *g += (target - p) * f;
+                }
+            }
+        }
+        for (w, g) in policy.weights.iter_mut().zip(gradient) {
+            *w += learning_rate * g / samples.len() as f64;
+        }
+    }
+}
+
+// 1回の方策反復で、学習し直す前の方策を評価した結果
+#[derive(Clone, Debug, PartialEq)]
+pub struct PolicyIterationStep {
+    pub score: f64,
+    // 評価した方策の名前
+    pub policy: String,
+}
+
+// 方策をプレイアウトに使う SHOT で自己対戦し、その手を真似るように方策を学習し直すことを繰り返す
+// 最後の方策と、反復ごとの評価を返す
+pub fn policy_iteration(
+    iterations: usize,
+    game_number: usize,
+    playout_number: usize,
+) -> (LinearSoftmaxPolicy, Vec<PolicyIterationStep>) {
+    let mut policy = LinearSoftmaxPolicy {
+        weights: [0.0; FEATURE_NUMBER],
+    };
+    let mut history = Vec::new();
+    for iteration in 0..iterations {
+        let mut agent = SequentialHalvingAgent::new(RootPolicy::Shot, playout_number, 0);
+        agent.prior = Box::new(policy.clone());
+        history.push(PolicyIterationStep {
+            score: test_ai_score(&mut agent, game_number),
+            policy: policy.name(),
+        });
+
+        // 評価に使った盤面とは別のシードで学習データを作る
+        let seeds = (game_number * (iteration + 1)) as u64..(game_number * (iteration + 2)) as u64;
+        let samples = augment(&collect_self_play(&mut agent, seeds));
+        fit(&mut policy, &samples, 200, 0.1);
+    }
+    (policy, history)
+}
+
+// 1行に "局面 行動" を書く。局面は packing の16進表記
+pub fn export_samples(path: &str, samples: &[Sample]) -> io::Result<()> {
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    writeln!(writer, "{}", Provenance::collect(1))?;
+    for sample in samples {
+        writeln!(writer, "{} {}", to_hex(&sample.state.pack()), sample.action)?;
+    }
+    writer.flush()
+}
@@ -0,0 +1,167 @@
+use crate::agent::{describe_beam_budget, Agent, Capabilities};
+use crate::budget::{Budget, BudgetMeter, Consumed};
+use crate::{GameState, MazeState};
+use std::collections::BinaryHeap;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    // 新しく確保した状態の数
+    pub allocations: usize,
+    // 解放済みの状態を使い回した数
+    pub reuses: usize,
+}
+
+// 意思決定ごとに使い捨てる状態を再利用するプール
+// 手を決めたら reset で全て返却し、次の意思決定では確保済みの盤面をそのまま上書きして使う
+#[derive(Default)]
+pub struct StatePool {
+    free: Vec<MazeState>,
+    pub stats: PoolStats,
+}
+
+impl StatePool {
+    pub fn acquire(&mut self, source: &MazeState) -> MazeState {
+        match self.free.pop() {
+            Some(mut state) => {
+                self.stats.reuses += 1;
+                // Vec::clone_from は確保済みの領域を再利用する
+                state.points.clone_from(&source.points);
+                state.turn = source.turn;
+                state.character = source.character.clone();
+                state.game_score = source.game_score;
+                state.evaluated_score = source.evaluated_score;
+                state.first_action = source.first_action;
+                state.last_action = source.last_action;
+                state.params = source.params;
+                state
+            }
+            None => {
+                self.stats.allocations += 1;
+                source.clone()
+            }
+        }
+    }
+
+    // 返却されて次に使い回せる状態の数
+    pub fn free_len(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn release(&mut self, state: MazeState) {
+        self.free.push(state);
+    }
+
+    pub fn reset<I: IntoIterator<Item = MazeState>>(&mut self, states: I) {
+        self.free.extend(states);
+    }
+}
+
+// search::beam_search_action と同じ探索を、プールから取った状態で行う
+pub fn pooled_beam_search_action(
+    state: &MazeState,
+    beam_width: usize,
+    beam_depth: usize,
+    pool: &mut StatePool,
+) -> usize {
+    pooled_beam_search_with_budget(state, beam_width, &Budget::iterations(beam_depth), pool).0
+}
+
+// search::beam_search_with_budget と同じく、budget の iterations は深さ、nodes は展開した子の数
+// 1層目は budget に関係なく展開するので必ず手を返す
+pub fn pooled_beam_search_with_budget(
+    state: &MazeState,
+    beam_width: usize,
+    budget: &Budget,
+    pool: &mut StatePool,
+) -> (usize, Consumed) {
+    let mut meter = BudgetMeter::new(*budget);
+    let mut now_beam = BinaryHeap::new();
+    let mut best_action = None;
+
+    now_beam.push(pool.acquire(state));
+    for t in 0.. {
+        let mut next_beam = BinaryHeap::new();
+        for _ in 0..beam_width {
+            if t > 0 && meter.exhausted().is_some() {
+                break;
+            }
+            if let Some(now_state) = now_beam.pop() {
+                let legal_actions = now_state.legal_actions();
+                meter.add_nodes(legal_actions.len());
+                for action in legal_actions {
+                    let mut next_state = pool.acquire(&now_state);
+                    next_state.advance(action);
+                    next_state.evaluate_score();
+                    if t == 0 {
+                        next_state.first_action = Some(action);
+                    }
+                    next_beam.push(next_state);
+                }
+                pool.release(now_state);
+            }
+        }
+        pool.reset(now_beam.drain());
+
+        if next_beam.is_empty() {
+            break;
+        }
+        now_beam = next_beam;
+        meter.add_iteration();
+        let best_state = now_beam.peek().unwrap();
+        best_action = best_state.first_action;
+
+        if meter.exhausted().is_some() || best_state.is_done() {
+            break;
+        }
+    }
+    pool.reset(now_beam);
+    (best_action.unwrap(), meter.consumed())
+}
+
+// budget の iterations は深さ。何も指定しなければ終局まで読む
+pub struct PooledBeamAgent {
+    pub beam_width: usize,
+    pub budget: Budget,
+    pub pool: StatePool,
+    // set_time_limit で受け取った1手の時間
+    time_limit: Option<Duration>,
+}
+
+impl PooledBeamAgent {
+    pub fn new(beam_width: usize, budget: Budget) -> Self {
+        Self {
+            beam_width,
+            budget,
+            pool: StatePool::default(),
+            time_limit: None,
+        }
+    }
+}
+
+impl Agent for PooledBeamAgent {
+    fn name(&self) -> String {
+        format!(
+            "pooled_beam(width={}, {})",
+            self.beam_width,
+            describe_beam_budget(&self.budget)
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        let budget = self.budget.with_time_limit(self.time_limit);
+        pooled_beam_search_with_budget(state, self.beam_width, &budget, &mut self.pool).0
+    }
+
+    fn set_time_limit(&mut self, time: Option<Duration>) {
+        self.time_limit = time;
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::budgeted(&self.budget, self.time_limit)
+    }
+
+    fn retained_states(&self) -> Option<usize> {
+        Some(self.pool.free_len())
+    }
+}
@@ -0,0 +1,200 @@
+use crate::agent::{Agent, Capabilities};
+use crate::{MazeState, ScoreType};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PortfolioRule {
+    // 最も多くのメンバーが選んだ行動 (同数なら先に並んでいるメンバーを優先)
+    Vote,
+    // 自己申告の評価値が最も高いメンバーの行動
+    BestScore,
+}
+
+// 複数のアルゴリズムを同じ局面で走らせ、その結果から行動を決める
+pub struct PortfolioAgent {
+    pub members: Vec<Box<dyn Agent>>,
+    pub rule: PortfolioRule,
+    pub parallel: bool,
+    // set_time_limit で受け取った1手の時間
+    time_limit: Option<Duration>,
+}
+
+impl PortfolioAgent {
+    pub fn new(members: Vec<Box<dyn Agent>>, rule: PortfolioRule, parallel: bool) -> Self {
+        Self {
+            members,
+            rule,
+            parallel,
+            time_limit: None,
+        }
+    }
+
+    fn run_members(&mut self, state: &MazeState) -> Vec<(usize, Option<ScoreType>)> {
+        // 順に走らせるときは1手の時間を分け合う。各メンバーには残り時間を残りの人数で割った分を渡すので
+        // 早く終わったメンバーの余りは後のメンバーが使う
+        if !self.parallel {
+            let start = Instant::now();
+            let member_number = self.members.len();
+            let time_limit = self.time_limit;
+            return self
+                .members
+                .iter_mut()
+                .enumerate()
+                .map(|(i, member)| {
+                    if let Some(limit) = time_limit {
+                        let remaining = limit.saturating_sub(start.elapsed());
+                        member.set_time_limit(Some(remaining / (member_number - i) as u32));
+                    }
+                    member.select_action_with_score(state)
+                })
+                .collect();
+        }
+        thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .members
+                .iter_mut()
+                .map(|member| scope.spawn(move || member.select_action_with_score(state)))
+                .collect();
+            // 結果はメンバーの並び順で集めるので、スレッドの終了順には依存しない
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+
+    // 評価値を申告したメンバーのうち最も高い (行動, 評価値)。同じなら先に並んでいるメンバー
+    fn best_scored<'a>(
+        decisions: impl Iterator<Item = &'a (usize, Option<ScoreType>)>,
+    ) -> Option<(usize, ScoreType)> {
+        decisions
+            .filter_map(|&(action, score)| score.map(|score| (action, score)))
+            .fold(None, |best, (action, score)| match best {
+                Some((_, best_score)) if best_score >= score => best,
+                _ => Some((action, score)),
+            })
+    }
+
+    fn vote(decisions: &[(usize, Option<ScoreType>)]) -> usize {
+        let mut best_action = decisions[0].0;
+        let mut best_count = 0;
+        for &(action, _) in decisions {
+            let count = decisions.iter().filter(|&&(a, _)| a == action).count();
+            if count > best_count {
+                best_count = count;
+                best_action = action;
+            }
+        }
+        best_action
+    }
+}
+
+impl Agent for PortfolioAgent {
+    fn name(&self) -> String {
+        let members: Vec<String> = self.members.iter().map(|member| member.name()).collect();
+        format!("portfolio({:?}, [{}])", self.rule, members.join(", "))
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        self.select_action_with_score(state).0
+    }
+
+    // 並列なら全員に同じ時間を渡す。順に走らせるなら run_members で1手ごとに分ける
+    fn set_time_limit(&mut self, time: Option<Duration>) {
+        self.time_limit = time;
+        if self.parallel || time.is_none() {
+            for member in &mut self.members {
+                member.set_time_limit(time);
+            }
+        }
+    }
+
+    // 全員が時間の制限に従えるときだけ従える。決定的なのは全員が決定的で、時間で止まるメンバーがいないとき
+    fn capabilities(&self) -> Capabilities {
+        let members: Vec<Capabilities> = self
+            .members
+            .iter()
+            .map(|member| member.capabilities())
+            .collect();
+        let time_bound = self.time_limit.is_some() && members.iter().any(|c| c.time_limit);
+        Capabilities {
+            time_limit: members.iter().all(|c| c.time_limit),
+            deterministic: !time_bound && members.iter().all(|c| c.deterministic),
+            ..Capabilities::SEARCH
+        }
+    }
+
+    // 返す評価値は選んだ行動のもの。投票で決めたなら、その行動を選んだメンバーの評価値のうち最も高いもの
+    fn select_action_with_score(&mut self, state: &MazeState) -> (usize, Option<ScoreType>) {
+        let decisions = self.run_members(state);
+        match (self.rule, Self::best_scored(decisions.iter())) {
+            (PortfolioRule::BestScore, Some((action, score))) => (action, Some(score)),
+            _ => {
+                let action = Self::vote(&decisions);
+                let voters = decisions.iter().filter(|&&(a, _)| a == action);
+                (action, Self::best_scored(voters).map(|(_, score)| score))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::BeamSearchAgent;
+    use crate::budget::Budget;
+    use crate::ids::Seed;
+    use crate::GameState;
+    use std::sync::{Arc, Mutex};
+
+    // 受け取った時間の制限を覚えておき、最初の合法手を返す
+    struct Recorder(Arc<Mutex<Vec<Option<Duration>>>>);
+
+    impl Agent for Recorder {
+        fn name(&self) -> String {
+            "recorder".to_string()
+        }
+
+        fn select_action(&mut self, state: &MazeState) -> usize {
+            state.legal_actions()[0]
+        }
+
+        fn set_time_limit(&mut self, time: Option<Duration>) {
+            self.0.lock().unwrap().push(time);
+        }
+    }
+
+    #[test]
+    fn sequential_members_share_the_time_limit() {
+        let limits = Arc::new(Mutex::new(Vec::new()));
+        let members: Vec<Box<dyn Agent>> = (0..4)
+            .map(|_| Box::new(Recorder(Arc::clone(&limits))) as Box<dyn Agent>)
+            .collect();
+        let mut agent = PortfolioAgent::new(members, PortfolioRule::Vote, false);
+        agent.set_time_limit(Some(Duration::from_millis(400)));
+        agent.select_action(&MazeState::from_seed(Seed(0)));
+        let limits = limits.lock().unwrap();
+        assert_eq!(limits.len(), 4);
+        // i 番目のメンバーは残りの 4 - i 人で残り時間を分ける
+        for (i, limit) in limits.iter().enumerate() {
+            assert!(limit.map_or(false, |t| t <= Duration::from_millis(400) / (4 - i) as u32));
+        }
+    }
+
+    #[test]
+    fn capabilities_combine_the_members() {
+        let mut agent = PortfolioAgent::new(
+            vec![
+                Box::new(BeamSearchAgent::new(2, Budget::nodes(64))),
+                Box::new(BeamSearchAgent::new(3, Budget::nodes(64))),
+            ],
+            PortfolioRule::BestScore,
+            true,
+        );
+        assert!(agent.capabilities().time_limit);
+        assert!(agent.capabilities().deterministic);
+        agent.set_time_limit(Some(Duration::from_millis(5)));
+        assert!(!agent.capabilities().deterministic);
+    }
+}
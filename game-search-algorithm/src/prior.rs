@@ -0,0 +1,111 @@
+use crate::alternate::TwoPlayerGame;
+use crate::{GameState, MazeState};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+// 各合法手の事前確率を返す。探索はこれをプレイアウトの方策、MCTS の PUCT、Alpha-Beta の手の並べ替えに使う
+pub trait PriorPolicy<S = MazeState>: Send {
+    fn name(&self) -> String;
+    fn priors(&self, state: &S) -> Vec<(usize, f64)>;
+}
+
+pub struct UniformPrior;
+
+impl<S: GameState> PriorPolicy<S> for UniformPrior {
+    fn name(&self) -> String {
+        "uniform".to_string()
+    }
+
+    fn priors(&self, state: &S) -> Vec<(usize, f64)> {
+        let legal_actions = state.legal_actions();
+        let p = 1.0 / legal_actions.len() as f64;
+        legal_actions
+            .into_iter()
+            .map(|action| (action, p))
+            .collect()
+    }
+}
+
+// 1手先の評価値の softmax
+pub struct GreedyPrior {
+    pub temperature: f64,
+}
+
+impl<S: GameState> PriorPolicy<S> for GreedyPrior {
+    fn name(&self) -> String {
+        format!("greedy(temperature={})", self.temperature)
+    }
+
+    fn priors(&self, state: &S) -> Vec<(usize, f64)> {
+        let scores: Vec<(usize, f64)> = state
+            .legal_actions()
+            .into_iter()
+            .map(|action| {
+                let mut next_state = state.clone();
+                next_state.advance(action);
+                (action, next_state.evaluate_score() as f64)
+            })
+            .collect();
+        softmax(&scores, self.temperature)
+    }
+}
+
+// 2人ゲームで、手番のプレイヤーから見た1手先の評価値の softmax
+// 指したあとの evaluate_score は相手から見た値なので、符号を反転して使う
+pub struct TwoPlayerGreedyPrior {
+    pub temperature: f64,
+}
+
+impl<G: TwoPlayerGame> PriorPolicy<G> for TwoPlayerGreedyPrior {
+    fn name(&self) -> String {
+        format!("greedy(temperature={})", self.temperature)
+    }
+
+    fn priors(&self, state: &G) -> Vec<(usize, f64)> {
+        let scores: Vec<(usize, f64)> = state
+            .legal_actions()
+            .into_iter()
+            .map(|action| {
+                let mut next_state = state.clone();
+                next_state.advance(action);
+                (action, -next_state.evaluate_score() as f64)
+            })
+            .collect();
+        softmax(&scores, self.temperature)
+    }
+}
+
+pub fn softmax(scores: &[(usize, f64)], temperature: f64) -> Vec<(usize, f64)> {
+    let max_score = scores
+        .iter()
+        .map(|&(_, s)| s)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let weights: Vec<f64> = scores
+        .iter()
+        .map(|&(_, s)| ((s - max_score) / temperature.max(1e-9)).exp())
+        .collect();
+    let total: f64 = weights.iter().sum();
+    scores
+        .iter()
+        .zip(weights)
+        .map(|(&(action, _), w)| (action, w / total))
+        .collect()
+}
+
+pub fn sample_action(priors: &[(usize, f64)], rng: &mut ChaCha8Rng) -> usize {
+    let mut r = rng.gen::<f64>();
+    for &(action, p) in priors {
+        if r < p {
+            return action;
+        }
+        r -= p;
+    }
+    priors.last().unwrap().0
+}
+
+// 事前確率の高い順に並べた合法手 (同率なら元の順序)
+pub fn ordered_actions<S>(state: &S, prior: &dyn PriorPolicy<S>) -> Vec<usize> {
+    let mut priors = prior.priors(state);
+    priors.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    priors.into_iter().map(|(action, _)| action).collect()
+}
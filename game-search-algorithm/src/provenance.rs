@@ -0,0 +1,65 @@
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+
+// 結果がどのビルド・どのマシンで出たものかを後から確認するための情報
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Provenance {
+    pub crate_version: &'static str,
+    pub git_commit: &'static str,
+    pub features: &'static str,
+    pub profile: &'static str,
+    pub threads: usize,
+    pub cpu_model: String,
+}
+
+impl Provenance {
+    // threads には実験で使ったスレッド数を渡す
+    pub fn collect(threads: usize) -> Self {
+        Self {
+            crate_version: env!("CARGO_PKG_VERSION"),
+            git_commit: env!("GIT_COMMIT"),
+            features: env!("ENABLED_FEATURES"),
+            profile: if cfg!(debug_assertions) {
+                "debug"
+            } else {
+                "release"
+            },
+            threads,
+            cpu_model: cpu_model(),
+        }
+    }
+}
+
+fn cpu_model() -> String {
+    fs::read_to_string("/proc/cpuinfo")
+        .ok()
+        .and_then(|cpuinfo| {
+            cpuinfo
+                .lines()
+                .find(|line| line.starts_with("model name"))
+                .and_then(|line| line.split_once(':'))
+                .map(|(_, model)| model.trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+// 結果ファイルではコメント行として先頭に置く
+impl Display for Provenance {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let features = if self.features.is_empty() {
+            "-"
+        } else {
+            self.features
+        };
+        write!(
+            f,
+            "# provenance version={} commit={} features={} profile={} threads={} cpu=\"{}\"",
+            self.crate_version,
+            self.git_commit,
+            features,
+            self.profile,
+            self.threads,
+            self.cpu_model
+        )
+    }
+}
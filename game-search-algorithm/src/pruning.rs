@@ -0,0 +1,128 @@
+use crate::agent::Agent;
+use crate::{GameState, MazeState};
+use std::collections::BinaryHeap;
+
+impl MazeState {
+    // 明らかに悪い手を展開前に落とす。すべて落ちるときは元の手をそのまま返す
+    // 今は来た道をすぐに戻る手だけを落とす (戻った先の点はもう取ってある)
+    pub fn prune_actions(&self, actions: Vec<usize>) -> Vec<usize> {
+        let Some(last_action) = self.last_action else {
+            return actions;
+        };
+        // 0 と 1、2 と 3 が逆向き
+        let back = last_action ^ 1;
+        let pruned: Vec<usize> = actions
+            .iter()
+            .copied()
+            .filter(|&action| action != back)
+            .collect();
+        if pruned.is_empty() {
+            actions
+        } else {
+            pruned
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PruningStats {
+    pub decisions: usize,
+    pub expanded_actions: usize,
+    pub pruned_actions: usize,
+    // 枝刈りしない探索と選んだ手が変わった回数
+    pub changed_decisions: usize,
+}
+
+fn beam_search_action(
+    state: &MazeState,
+    beam_width: usize,
+    beam_depth: usize,
+    prune: bool,
+    stats: &mut PruningStats,
+) -> usize {
+    let mut now_beam = BinaryHeap::new();
+    let mut best_state = None;
+
+    now_beam.push(state.clone());
+    for t in 0..beam_depth {
+        let mut next_beam = BinaryHeap::new();
+        for _ in 0..beam_width {
+            if let Some(now_state) = now_beam.pop() {
+                let legal_actions = now_state.legal_actions();
+                let actions = if prune {
+                    now_state.prune_actions(legal_actions.clone())
+                } else {
+                    legal_actions.clone()
+                };
+                stats.pruned_actions += legal_actions.len() - actions.len();
+                stats.expanded_actions += actions.len();
+                for action in actions {
+                    let mut next_state = now_state.clone();
+                    next_state.advance(action);
+                    next_state.evaluate_score();
+                    if t == 0 {
+                        next_state.first_action = Some(action);
+                    }
+                    next_beam.push(next_state);
+                }
+            }
+        }
+
+        now_beam = next_beam;
+        best_state = now_beam.peek();
+
+        if best_state.unwrap().is_done() {
+            break;
+        }
+    }
+    best_state.unwrap().first_action.unwrap()
+}
+
+// 枝刈り付きのビームサーチ。比較のため枝刈りなしの探索も毎回走らせて統計を取る
+pub struct PrunedBeamAgent {
+    pub beam_width: usize,
+    pub beam_depth: usize,
+    pub stats: PruningStats,
+}
+
+impl PrunedBeamAgent {
+    pub fn new(beam_width: usize, beam_depth: usize) -> Self {
+        Self {
+            beam_width,
+            beam_depth,
+            stats: PruningStats::default(),
+        }
+    }
+}
+
+impl Agent for PrunedBeamAgent {
+    fn name(&self) -> String {
+        format!(
+            "pruned_beam(width={}, depth={})",
+            self.beam_width, self.beam_depth
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        let action = beam_search_action(
+            state,
+            self.beam_width,
+            self.beam_depth,
+            true,
+            &mut self.stats,
+        );
+        let mut unpruned_stats = PruningStats::default();
+        let unpruned_action = beam_search_action(
+            state,
+            self.beam_width,
+            self.beam_depth,
+            false,
+            &mut unpruned_stats,
+        );
+        self.stats.decisions += 1;
+        if action != unpruned_action {
+            self.stats.changed_decisions += 1;
+        }
+        action
+    }
+}
@@ -0,0 +1,78 @@
+use crate::agent::Agent;
+use crate::audit::ActionRecorder;
+use crate::hooks;
+use crate::ids::Seed;
+use crate::provenance::Provenance;
+use crate::{GameState, MazeParams, MazeState, ScoreType};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+// 1ゲームをあとから同じ局面で調べ直すための記録。盤面は seed と params から作り直す
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameRecord {
+    // 記録したビルド (Provenance の1行)
+    pub provenance: String,
+    pub agent: String,
+    pub seed: Seed,
+    pub params: MazeParams,
+    pub actions: Vec<usize>,
+    pub score: ScoreType,
+}
+
+impl GameRecord {
+    // agent で seed の盤面を終局まで指して記録する
+    pub fn play(agent: &mut dyn Agent, seed: Seed, params: MazeParams) -> Self {
+        let mut recorder = ActionRecorder::default();
+        let state = MazeState::from_seed_with_params(seed, params);
+        let state = hooks::play_from(agent, state, &mut [&mut recorder]);
+        Self {
+            provenance: Provenance::collect(1)
+                .to_string()
+                .trim_start_matches("# ")
+                .to_string(),
+            agent: agent.name(),
+            seed,
+            params,
+            actions: recorder.actions,
+            score: state.game_score,
+        }
+    }
+
+    pub fn initial_state(&self) -> MazeState {
+        MazeState::from_seed_with_params(self.seed, self.params)
+    }
+
+    // 各手を指す前の局面と終局の局面 (actions.len() + 1 個)。合法でない手があればエラー
+    pub fn states(&self) -> Result<Vec<MazeState>, String> {
+        let mut state = self.initial_state();
+        let mut states = vec![state.clone()];
+        for (turn, &action) in self.actions.iter().enumerate() {
+            if state.is_done() || !state.legal_actions().contains(&action) {
+                return Err(format!("illegal action {} at turn {}", action, turn));
+            }
+            state.advance(action);
+            states.push(state.clone());
+        }
+        if state.game_score != self.score {
+            return Err(format!(
+                "recorded score {} but replaying the actions scores {}",
+                self.score, state.game_score
+            ));
+        }
+        Ok(states)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))
+    }
+}
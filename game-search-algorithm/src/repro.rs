@@ -0,0 +1,268 @@
+use crate::agent::{parse_agent, Agent};
+use crate::hooks::{self, Observer};
+use crate::ids::Seed;
+use crate::packing::{from_hex, to_hex};
+use crate::provenance::Provenance;
+use crate::{GameState, MazeParams, MazeState};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+// 探索が panic したか合法でない手を返した意思決定を、あとで同じ条件でやり直すための材料
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReproBundle {
+    pub spec: String,
+    pub seed: Seed,
+    // 盤面の大きさ。seed と合わせて初期局面を作り直す
+    pub params: MazeParams,
+    // 失敗した意思決定より前に指した手
+    // エージェントの乱数や木は、この手数だけ select_action を呼んだところまで進んでいる
+    pub actions: Vec<usize>,
+    pub time_limit: Option<Duration>,
+    // 失敗した局面を pack したもの。actions を指し直した局面と照らし合わせる
+    pub state: Vec<u8>,
+    pub failure: String,
+}
+
+// 中のエージェントの手を見張る。最初に失敗したところを覚え、それ以降は中のエージェントを呼ばずに合法手を返す
+struct Referee {
+    inner: Box<dyn Agent>,
+    actions: Vec<usize>,
+    failure: Option<(MazeState, String)>,
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+impl Agent for Referee {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        if self.failure.is_some() {
+            return state.legal_actions()[0];
+        }
+        let inner = &mut self.inner;
+        let failure = match panic::catch_unwind(AssertUnwindSafe(|| inner.select_action(state))) {
+            Ok(action) if state.legal_actions().contains(&action) => {
+                self.actions.push(action);
+                return action;
+            }
+            Ok(action) => format!("illegal action {}", action),
+            Err(payload) => format!("panicked: {}", panic_message(payload.as_ref())),
+        };
+        self.failure = Some((state.clone(), failure));
+        state.legal_actions()[0]
+    }
+}
+
+// spec から作った inner で seed と params の盤面を終局まで指す。失敗したらそこまでの材料を返す
+pub fn refereed_play(
+    mut inner: Box<dyn Agent>,
+    spec: &str,
+    seed: Seed,
+    params: MazeParams,
+    time_limit: Option<Duration>,
+    observers: &mut [&mut dyn Observer],
+) -> Result<MazeState, Box<ReproBundle>> {
+    inner.set_time_limit(time_limit);
+    let mut referee = Referee {
+        inner,
+        actions: Vec::new(),
+        failure: None,
+    };
+    let state = hooks::play_from(
+        &mut referee,
+        MazeState::from_seed_with_params(seed, params),
+        observers,
+    );
+    match referee.failure {
+        None => Ok(state),
+        Some((state, failure)) => Err(Box::new(ReproBundle {
+            spec: spec.to_string(),
+            seed,
+            params,
+            actions: referee.actions,
+            time_limit,
+            state: state.pack(),
+            failure,
+        })),
+    }
+}
+
+impl ReproBundle {
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        writeln!(writer, "repro-bundle 2")?;
+        writeln!(writer, "{}", Provenance::collect(1))?;
+        writeln!(writer, "spec {}", self.spec)?;
+        writeln!(writer, "seed {}", self.seed)?;
+        writeln!(writer, "maze {}", self.params)?;
+        match self.time_limit {
+            Some(time_limit) => writeln!(writer, "time_ms {}", time_limit.as_millis())?,
+            None => writeln!(writer, "time_ms -")?,
+        }
+        write!(writer, "actions")?;
+        for action in &self.actions {
+            write!(writer, " {}", action)?;
+        }
+        writeln!(writer)?;
+        writeln!(writer, "state {}", to_hex(&self.state))?;
+        // panic のメッセージは複数行のことがあるので1行にまとめる
+        writeln!(writer, "failure {}", self.failure.replace('\n', " "))?;
+        writer.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let invalid = |message: String| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, message))
+        };
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines().filter(|line| !line.starts_with('#'));
+        let mut field = |name: &str| -> io::Result<String> {
+            let line = lines
+                .next()
+                .ok_or_else(|| invalid(format!("missing `{}`", name)))?;
+            line.strip_prefix(name)
+                .map(|rest| rest.trim().to_string())
+                .ok_or_else(|| invalid(format!("expected `{}`, got `{}`", name, line)))
+        };
+        // 版 1 は盤面の大きさを持たず、既定の大きさで指していた
+        let version = field("repro-bundle")?;
+        if version != "1" && version != "2" {
+            return Err(invalid("unsupported bundle version".to_string()));
+        }
+        let spec = field("spec")?;
+        let seed = field("seed")?;
+        let seed = seed
+            .parse()
+            .map_err(|_| invalid(format!("invalid seed `{}`", seed)))?;
+        let params = match version.as_str() {
+            "1" => MazeParams::default(),
+            _ => field("maze")?.parse().map_err(invalid)?,
+        };
+        let time_limit = match field("time_ms")?.as_str() {
+            "-" => None,
+            text => Some(Duration::from_millis(
+                text.parse()
+                    .map_err(|_| invalid(format!("invalid time `{}`", text)))?,
+            )),
+        };
+        let actions = field("actions")?
+            .split_whitespace()
+            .map(|action| {
+                action
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid action `{}`", action)))
+            })
+            .collect::<io::Result<Vec<usize>>>()?;
+        let state = from_hex(&field("state")?).map_err(invalid)?;
+        let failure = field("failure")?;
+        Ok(Self {
+            spec,
+            seed,
+            params,
+            actions,
+            time_limit,
+            state,
+            failure,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReproOutcome {
+    // 指し直しで、記録と違う手を選んだ意思決定の数。0 でなければエージェントが決定的でない
+    pub divergences: usize,
+    pub action: usize,
+    pub legal: bool,
+}
+
+// バンドルの意思決定をこのスレッドでやり直す。panic は捕まえないので、デバッガやバックトレースでそのまま追える
+// with_time_limit が false なら記録した持ち時間を使わない (ブレークポイントで止めても探索が打ち切られない)
+pub fn reproduce(bundle: &ReproBundle, with_time_limit: bool) -> Result<ReproOutcome, String> {
+    let mut agent = parse_agent(&bundle.spec)?;
+    if with_time_limit {
+        agent.set_time_limit(bundle.time_limit);
+    }
+    let mut state = MazeState::from_seed_with_params(bundle.seed, bundle.params);
+    let mut divergences = 0;
+    for &action in &bundle.actions {
+        if agent.select_action(&state) != action {
+            divergences += 1;
+        }
+        state.advance(action);
+    }
+    if state.pack() != bundle.state {
+        return Err(format!(
+            "replaying {} actions on seed {} ({}) does not reach the recorded state",
+            bundle.actions.len(),
+            bundle.seed,
+            bundle.params
+        ));
+    }
+    let action = agent.select_action(&state);
+    Ok(ReproOutcome {
+        divergences,
+        action,
+        legal: state.legal_actions().contains(&action),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 3手目で盤面の外への手を返す greedy
+    struct IllegalOnThirdMove {
+        greedy: Box<dyn Agent>,
+    }
+
+    impl Agent for IllegalOnThirdMove {
+        fn name(&self) -> String {
+            self.greedy.name()
+        }
+
+        fn select_action(&mut self, state: &MazeState) -> usize {
+            if state.turn().index() == 2 {
+                return 99;
+            }
+            self.greedy.select_action(state)
+        }
+    }
+
+    #[test]
+    fn bundle_replays_on_the_recorded_board_size() {
+        let params = MazeParams {
+            h: 4,
+            w: 9,
+            end_turn: 6,
+        };
+        let inner = Box::new(IllegalOnThirdMove {
+            greedy: parse_agent("greedy").unwrap(),
+        });
+        let bundle = refereed_play(inner, "greedy", Seed(3), params, None, &mut []).unwrap_err();
+        assert_eq!(bundle.params, params);
+        assert_eq!(bundle.actions.len(), 2);
+
+        let path = std::env::temp_dir().join(format!("repro_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        bundle.save(path).unwrap();
+        let loaded = ReproBundle::load(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(loaded, *bundle);
+
+        let outcome = reproduce(&loaded, false).unwrap();
+        assert_eq!(outcome.divergences, 0);
+        assert!(outcome.legal);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::agent::{Agent, Capabilities};
+use crate::budget::Budget;
+use crate::local_search::{StopReason, Stopper, StoppingCriteria};
+use crate::{GameState, MazeState, ScoreType};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::cmp::Reverse;
+
+type Genome = Vec<usize>;
+
+// 遺伝子 g は各局面で legal_actions[g % 合法手数] と解釈するので、どんな列も実行可能になる
+fn decode(state: &MazeState, gene: usize) -> usize {
+    let legal_actions = state.legal_actions();
+    legal_actions[gene % legal_actions.len()]
+}
+
+fn fitness(state: &MazeState, genome: &Genome) -> ScoreType {
+    let mut state = state.clone();
+    for &gene in genome {
+        if state.is_done() {
+            break;
+        }
+        state.advance(decode(&state, gene));
+    }
+    state.game_score
+}
+
+// Rolling Horizon Evolutionary Algorithm
+// 固定長の行動列を進化させ、先頭の行動を実行する。次のターンでは列を1つずらして再利用する
+// 渡された局面が前の手を指した後の局面でなければ (新しい対局など)、集団を作り直す
+// stopping.budget の iterations は1手あたりの世代数で、初期集団の評価も1世代と数える
+pub struct RheaAgent {
+    pub horizon: usize,
+    pub population_size: usize,
+    pub stopping: StoppingCriteria,
+    pub mutation_rate: f64,
+    // 直前の意思決定で進化を止めた条件
+    pub last_stop_reason: Option<StopReason>,
+    population: Vec<Genome>,
+    // warm_start で受け取り、次の意思決定の集団に入れる行動列
+    seeded: Vec<Genome>,
+    // 前の意思決定で選んだ手を指した後の局面 (pack した形)
+    expected: Option<Vec<u8>>,
+    rng: ChaCha8Rng,
+}
+
+impl RheaAgent {
+    pub fn new(
+        horizon: usize,
+        population_size: usize,
+        budget: Budget,
+        mutation_rate: f64,
+        seed: u64,
+    ) -> Self {
+        Self {
+            horizon,
+            population_size,
+            stopping: StoppingCriteria {
+                budget,
+                no_improvement: None,
+                target_score: None,
+            },
+            mutation_rate,
+            last_stop_reason: None,
+            population: Vec::new(),
+            seeded: Vec::new(),
+            expected: None,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    // 保存しておいた行動列 (遺伝子の並び) を次の意思決定の集団に入れておく
+    // 集団が埋まっていれば最も古い個体と入れ替える
+    pub fn warm_start(&mut self, mut genome: Genome) {
+        // 意思決定の前に集団は1手ずらされるので、捨てられる分の遺伝子を先頭に足しておく
+        genome.insert(0, 0);
+        genome.truncate(self.horizon + 1);
+        self.seeded.push(genome);
+    }
+
+    // 前の意思決定から続いていない局面なら、前の対局で進化させた集団を捨てる
+    fn prepare_population(&mut self, state: &MazeState) {
+        if self.expected.as_ref() != Some(&state.pack()) {
+            self.population.clear();
+        }
+        self.population.append(&mut self.seeded);
+        if self.population.len() > self.population_size {
+            let excess = self.population.len() - self.population_size;
+            self.population.drain(..excess);
+        }
+    }
+
+    fn random_gene(&mut self) -> usize {
+        self.rng.gen_range(0..4)
+    }
+
+    fn shift_population(&mut self) {
+        let mut population = std::mem::take(&mut self.population);
+        for genome in &mut population {
+            if !genome.is_empty() {
+                genome.remove(0);
+            }
+            while genome.len() < self.horizon {
+                genome.push(self.random_gene());
+            }
+        }
+        while population.len() < self.population_size {
+            let genome = (0..self.horizon).map(|_| self.random_gene()).collect();
+            population.push(genome);
+        }
+        self.population = population;
+    }
+
+    fn tournament(&mut self, scored: &[(ScoreType, Genome)]) -> Genome {
+        let a = &scored[self.rng.gen_range(0..scored.len())];
+        let b = &scored[self.rng.gen_range(0..scored.len())];
+        if a.0 >= b.0 {
+            a.1.clone()
+        } else {
+            b.1.clone()
+        }
+    }
+
+    fn evolve(&mut self, state: &MazeState) -> (ScoreType, Genome) {
+        let mut stopper = Stopper::new(self.stopping);
+        loop {
+            let mut scored: Vec<(ScoreType, Genome)> = self
+                .population
+                .iter()
+                .map(|genome| (fitness(state, genome), genome.clone()))
+                .collect();
+            // 同点のときは元の並び順を保つ安定ソート
+            scored.sort_by_key(|&(score, _)| Reverse(score));
+            stopper.add_nodes(scored.len());
+
+            let mut next_population = vec![scored[0].1.clone()];
+            while next_population.len() < self.population_size {
+                let parent_a = self.tournament(&scored);
+                let parent_b = self.tournament(&scored);
+                let mut child: Genome = parent_a
+                    .iter()
+                    .zip(&parent_b)
+                    .map(|(&a, &b)| if self.rng.gen_bool(0.5) { a } else { b })
+                    .collect();
+                for gene in &mut child {
+                    if self.rng.gen::<f64>() < self.mutation_rate {
+                        *gene = self.rng.gen_range(0..4);
+                    }
+                }
+                next_population.push(child);
+            }
+            self.population = next_population;
+            if let Some(reason) = stopper.step(scored[0].0) {
+                self.last_stop_reason = Some(reason);
+                return scored.swap_remove(0);
+            }
+        }
+    }
+}
+
+impl Agent for RheaAgent {
+    fn name(&self) -> String {
+        format!(
+            "rhea(horizon={}, population={}, {}, mutation={})",
+            self.horizon, self.population_size, self.stopping, self.mutation_rate
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        self.select_action_with_score(state).0
+    }
+
+    fn select_action_with_score(&mut self, state: &MazeState) -> (usize, Option<ScoreType>) {
+        self.prepare_population(state);
+        self.shift_population();
+        let (score, best) = self.evolve(state);
+        let action = decode(state, best[0]);
+        let mut next_state = state.clone();
+        next_state.advance(action);
+        self.expected = Some(next_state.pack());
+        (action, Some(score))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            // 時間で打ち切ると世代数がマシンの速さに依存する
+            deterministic: self.stopping.budget.is_deterministic(),
+            ..Capabilities::SEARCH
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::agent::Agent;
+use crate::audit::{audit_score, ActionRecorder};
+use crate::hooks;
+use crate::ids::Seed;
+use crate::wall_maze::{WallEvaluation, WallMazeState};
+use crate::{MazeParams, MazeState, ScoreType};
+
+// 名前で呼べる一人用の迷路の盤面。結果を話すときは大きさや点の数の組ではなくこの名前を書く
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MazeScenario {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub params: MazeParams,
+    // 点を 0 にするマスの割合 (%)
+    pub empty_percent: u32,
+}
+
+impl MazeScenario {
+    // 名前のない、大きさだけを決めた盤面。マニフェストの "game maze h=.. w=.. end_turn=.." に当たる
+    pub fn custom(params: MazeParams) -> Self {
+        Self {
+            name: "maze",
+            description: "maze with the given size and turn count",
+            params,
+            empty_percent: 0,
+        }
+    }
+
+    pub fn state(&self, seed: Seed) -> MazeState {
+        let state = MazeState::from_seed_with_params(seed, self.params);
+        if self.empty_percent == 0 {
+            state
+        } else {
+            state.with_empty_cells(seed, self.empty_percent)
+        }
+    }
+
+    // play_seed と同じく、指した手を新しい盤面で指し直してスコアを確かめる
+    pub fn play(&self, agent: &mut dyn Agent, seed: Seed) -> ScoreType {
+        let mut recorder = ActionRecorder::default();
+        let score = hooks::play_from(agent, self.state(seed), &mut [&mut recorder]).game_score;
+        audit_score(self.state(seed), &recorder.actions, score)
+            .unwrap_or_else(|e| panic!("score audit failed on {} seed {}: {}", self.name, seed, e));
+        score
+    }
+}
+
+// 名前で呼べる壁のある迷路の盤面。大きさは WALL_H, WALL_W で決まっている
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WallScenario {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub empty_percent: u32,
+}
+
+impl WallScenario {
+    pub fn state(&self, seed: Seed, evaluation: WallEvaluation) -> WallMazeState {
+        WallMazeState::from_seed(seed, evaluation).with_empty_cells(seed, self.empty_percent)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scenario {
+    Maze(MazeScenario),
+    WallMaze(WallScenario),
+}
+
+impl Scenario {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scenario::Maze(scenario) => scenario.name,
+            Scenario::WallMaze(scenario) => scenario.name,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Scenario::Maze(scenario) => scenario.description,
+            Scenario::WallMaze(scenario) => scenario.description,
+        }
+    }
+}
+
+// 名前は "ゲーム::特徴"
+pub fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario::Maze(MazeScenario {
+            name: "maze::default",
+            description: "3x4 maze, 4 turns, every cell but the start has 0..9 points",
+            params: MazeParams::default(),
+            empty_percent: 0,
+        }),
+        Scenario::Maze(MazeScenario {
+            name: "maze::dense_small",
+            description: "5x5 maze, 12 turns, every cell but the start has 0..9 points",
+            params: MazeParams {
+                h: 5,
+                w: 5,
+                end_turn: 12,
+            },
+            empty_percent: 0,
+        }),
+        Scenario::Maze(MazeScenario {
+            name: "maze::sparse_large",
+            description: "30x30 maze, 100 turns, 90% of the cells are empty",
+            params: MazeParams {
+                h: 30,
+                w: 30,
+                end_turn: 100,
+            },
+            empty_percent: 90,
+        }),
+        Scenario::WallMaze(WallScenario {
+            name: "wallmaze::default",
+            description: "7x7 stick-knocking wall maze, 49 turns",
+            empty_percent: 0,
+        }),
+        // 点がまばらなので、壁を回り込んで遠くの点を取りに行けるかで差がつく
+        Scenario::WallMaze(WallScenario {
+            name: "wallmaze::labyrinth",
+            description: "7x7 stick-knocking wall maze, 49 turns, 75% of the open cells are empty",
+            empty_percent: 75,
+        }),
+    ]
+}
+
+pub fn find_scenario(name: &str) -> Option<Scenario> {
+    scenarios()
+        .into_iter()
+        .find(|scenario| scenario.name() == name)
+}
+
+// 一人用の迷路の名前付きの盤面。ほかのゲームの名前ならエラー
+pub fn find_maze_scenario(name: &str) -> Result<MazeScenario, String> {
+    match find_scenario(name) {
+        Some(Scenario::Maze(scenario)) => Ok(scenario),
+        Some(_) => Err(format!("`{}` is not a maze scenario", name)),
+        None => Err(format!("unknown scenario `{}`", name)),
+    }
+}
@@ -0,0 +1,192 @@
+use crate::agent::parse_agent;
+use crate::hooks::MoveTimer;
+use crate::ids::Seed;
+use crate::repro::{refereed_play, ReproBundle};
+use crate::{MazeParams, ScoreType};
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Task {
+    pub spec: String,
+    pub seed: Seed,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaskResult {
+    Score(ScoreType),
+    TimedOut,
+    Failed(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskOutcome {
+    pub task: Task,
+    pub result: TaskResult,
+    pub attempts: usize,
+    // 最後の試行で1手ごとに考えた時間。終局まで指せなかったときは空
+    pub move_times: Vec<Duration>,
+    // 最後の試行で探索が panic したか合法でない手を返したときの、やり直すための材料
+    pub repro: Option<Box<ReproBundle>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SchedulerOptions {
+    pub threads: usize,
+    pub timeout: Duration,
+    // 失敗やタイムアウトのあと同じシードでやり直す回数
+    pub retries: usize,
+    // 1手あたりの持ち時間。エージェントに set_time_limit で伝え、超えた手を数える
+    pub move_time: Option<Duration>,
+}
+
+// 1回の試行を専用のスレッドで走らせる。時間切れになったスレッドは待たずに切り捨てる
+fn attempt(task: &Task, options: &SchedulerOptions) -> Attempt {
+    let (sender, receiver) = mpsc::channel();
+    let spec = task.spec.clone();
+    let seed = task.seed;
+    let move_time = options.move_time;
+    thread::spawn(move || {
+        let result = match parse_agent(&spec) {
+            Ok(agent) => {
+                let mut timer = MoveTimer::new();
+                match refereed_play(
+                    agent,
+                    &spec,
+                    seed,
+                    MazeParams::default(),
+                    move_time,
+                    &mut [&mut timer],
+                ) {
+                    Ok(state) => (TaskResult::Score(state.game_score), timer.times, None),
+                    Err(bundle) => (
+                        TaskResult::Failed(bundle.failure.clone()),
+                        Vec::new(),
+                        Some(bundle),
+                    ),
+                }
+            }
+            Err(e) => (TaskResult::Failed(e), Vec::new(), None),
+        };
+        let _ = sender.send(result);
+    });
+    match receiver.recv_timeout(options.timeout) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => (TaskResult::TimedOut, Vec::new(), None),
+        // 送信前にスレッドが落ちた (panic)
+        Err(mpsc::RecvTimeoutError::Disconnected) => (
+            TaskResult::Failed("task panicked".to_string()),
+            Vec::new(),
+            None,
+        ),
+    }
+}
+
+type Attempt = (TaskResult, Vec<Duration>, Option<Box<ReproBundle>>);
+
+fn run_task(task: Task, options: &SchedulerOptions) -> TaskOutcome {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let (result, move_times, repro) = attempt(&task, options);
+        let retry = !matches!(result, TaskResult::Score(_)) && attempts <= options.retries;
+        if !retry {
+            return TaskOutcome {
+                task,
+                result,
+                attempts,
+                move_times,
+                repro,
+            };
+        }
+    }
+}
+
+// 実験の組み合わせを options.threads 本のスレッドで処理する。結果はタスクの順に並べて返す
+pub fn run_tasks(tasks: Vec<Task>, options: SchedulerOptions) -> Vec<TaskOutcome> {
+    let task_number = tasks.len();
+    let queue = Arc::new(Mutex::new(
+        tasks.into_iter().enumerate().collect::<VecDeque<_>>(),
+    ));
+    let (sender, receiver) = mpsc::channel();
+    let mut workers = Vec::new();
+    for _ in 0..options.threads.max(1) {
+        let queue = Arc::clone(&queue);
+        let sender = sender.clone();
+        workers.push(thread::spawn(move || loop {
+            let next = queue.lock().unwrap().pop_front();
+            let Some((index, task)) = next else {
+                return;
+            };
+            let _ = sender.send((index, run_task(task, &options)));
+        }));
+    }
+    drop(sender);
+
+    let mut outcomes: Vec<Option<TaskOutcome>> = vec![None; task_number];
+    for (index, outcome) in receiver {
+        outcomes[index] = Some(outcome);
+    }
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    outcomes.into_iter().map(Option::unwrap).collect()
+}
+
+pub fn matrix(specs: &[String], seeds: std::ops::Range<u64>) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    for spec in specs {
+        for seed in seeds.clone() {
+            tasks.push(Task {
+                spec: spec.clone(),
+                seed: Seed(seed),
+            });
+        }
+    }
+    tasks
+}
+
+// 1手ごとの考慮時間の分布
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TimeUsage {
+    pub moves: usize,
+    pub mean: Duration,
+    pub median: Duration,
+    pub p90: Duration,
+    pub max: Duration,
+    // 持ち時間を超えた手の数。持ち時間がなければ 0
+    pub over_limit: usize,
+}
+
+impl TimeUsage {
+    pub fn new(times: &[Duration], limit: Option<Duration>) -> Self {
+        if times.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = times.to_vec();
+        sorted.sort_unstable();
+        let quantile = |q: f64| sorted[((sorted.len() - 1) as f64 * q).round() as usize];
+        Self {
+            moves: sorted.len(),
+            mean: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+            median: quantile(0.5),
+            p90: quantile(0.9),
+            max: *sorted.last().unwrap(),
+            over_limit: limit.map_or(0, |limit| sorted.iter().filter(|&&t| t > limit).count()),
+        }
+    }
+}
+
+impl Display for TimeUsage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "time/move mean {:?} median {:?} p90 {:?} max {:?}, {} over limit",
+            self.mean, self.median, self.p90, self.max, self.over_limit
+        )
+    }
+}
@@ -0,0 +1,477 @@
+use crate::budget::{Budget, BudgetMeter, Consumed};
+use crate::stats;
+use crate::zobrist_hash::ZobristHash;
+use crate::{GameState, ScoreType, INF};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+use std::time::{Duration, Instant};
+
+// ビームに積む局面。評価値だけで順序を決めるので、同点のときの並びは積んだ順に依存する
+struct BeamNode<S> {
+    score: ScoreType,
+    first_action: Option<usize>,
+    state: S,
+}
+
+impl<S> PartialEq for BeamNode<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.score == other.score
+    }
+}
+
+impl<S> Eq for BeamNode<S> {}
+
+impl<S> PartialOrd for BeamNode<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S> Ord for BeamNode<S> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score.cmp(&other.score)
+    }
+}
+
+// ビームサーチが最後に残した最良の局面と、そこへ向かう最初の手
+#[derive(Clone, Debug)]
+pub struct BeamResult<S> {
+    pub state: S,
+    pub first_action: usize,
+    pub score: ScoreType,
+}
+
+impl<S> BeamNode<S> {
+    fn into_result(self) -> BeamResult<S> {
+        BeamResult {
+            state: self.state,
+            first_action: self.first_action.unwrap(),
+            score: self.score,
+        }
+    }
+}
+
+pub fn greedy_action<S: GameState>(state: &S) -> usize {
+    greedy_action_with_score(state).0
+}
+
+pub fn greedy_action_with_score<S: GameState>(state: &S) -> (usize, ScoreType) {
+    let mut best_score = -INF;
+    let mut best_action = None;
+    stats::record_expansion();
+    stats::record_depth(1);
+    for action in state.legal_actions() {
+        stats::record_clones(1);
+        let mut now_state = state.clone();
+        now_state.advance(action);
+        let score = now_state.evaluate_score();
+        if score > best_score {
+            best_score = score;
+            best_action = Some(action);
+        }
+    }
+    (best_action.unwrap(), best_score)
+}
+
+pub fn beam_search_action<S: GameState>(state: &S, beam_width: usize, beam_depth: usize) -> usize {
+    beam_search(state, beam_width, beam_depth).first_action
+}
+
+pub fn beam_search<S: GameState>(state: &S, beam_width: usize, beam_depth: usize) -> BeamResult<S> {
+    beam_search_by(state, beam_width, beam_depth, &S::evaluate_score)
+}
+
+pub fn beam_search_action_with_evaluator<S: GameState>(
+    state: &S,
+    beam_width: usize,
+    beam_depth: usize,
+    evaluate: &impl Fn(&S) -> ScoreType,
+) -> usize {
+    beam_search_with_evaluator(state, beam_width, beam_depth, evaluate).first_action
+}
+
+// evaluate_score の代わりに evaluate の値で局面を並べるビームサーチ。BeamResult::score も evaluate の値
+// evaluator::Evaluator を使うなら &|state| evaluator.evaluate(state) を渡す
+pub fn beam_search_with_evaluator<S: GameState>(
+    state: &S,
+    beam_width: usize,
+    beam_depth: usize,
+    evaluate: &impl Fn(&S) -> ScoreType,
+) -> BeamResult<S> {
+    beam_search_by(state, beam_width, beam_depth, &|state: &mut S| {
+        evaluate(state)
+    })
+}
+
+fn beam_search_by<S: GameState>(
+    state: &S,
+    beam_width: usize,
+    beam_depth: usize,
+    evaluate: &impl Fn(&mut S) -> ScoreType,
+) -> BeamResult<S> {
+    // 幅や深さが 0 でも1層目は展開し、合法手を返す
+    let (beam_width, beam_depth) = (beam_width.max(1), beam_depth.max(1));
+    let mut now_beam = root_beam(state);
+    for t in 0..beam_depth {
+        let mut next_beam = BinaryHeap::new();
+        for _ in 0..beam_width {
+            if let Some(now_node) = now_beam.pop() {
+                push_children(&now_node, t == 0, &mut next_beam, evaluate);
+            }
+        }
+
+        if next_beam.is_empty() {
+            break;
+        }
+        stats::record_depth(t + 1);
+        now_beam = next_beam;
+
+        if now_beam.peek().unwrap().state.is_done() {
+            break;
+        }
+    }
+    now_beam.pop().unwrap().into_result()
+}
+
+// ビームサーチが最後に残した最良の局面と、そこへ向かう手の列
+#[derive(Clone, Debug)]
+pub struct BeamPlan<S> {
+    pub state: S,
+    pub actions: Vec<usize>,
+    pub score: ScoreType,
+}
+
+// ビームの局面から手の履歴をたどるための記録。parent は history の添字
+struct PlanStep {
+    parent: Option<usize>,
+    action: usize,
+}
+
+// beam_search と同じ順に局面を展開し、最初の手だけでなく最良の局面までの手をすべて返す
+// 局面ごとに手の列を持つ代わりに、親の添字だけ持っておいて最後にたどり直す
+pub fn beam_search_plan<S: GameState>(
+    state: &S,
+    beam_width: usize,
+    beam_depth: usize,
+) -> BeamPlan<S> {
+    // 幅や深さが 0 でも1層目は展開し、合法手を返す
+    let (beam_width, beam_depth) = (beam_width.max(1), beam_depth.max(1));
+    let mut history: Vec<PlanStep> = Vec::new();
+    // BeamNode::first_action にはその局面へ来た最後の手の history の添字を入れる
+    let mut now_beam = root_beam(state);
+    for t in 0..beam_depth {
+        let mut next_beam = BinaryHeap::new();
+        for _ in 0..beam_width {
+            let Some(now_node) = now_beam.pop() else {
+                break;
+            };
+            let legal_actions = now_node.state.legal_actions();
+            stats::record_expansion();
+            stats::record_clones(legal_actions.len());
+            for action in legal_actions {
+                let mut next_state = now_node.state.clone();
+                next_state.advance(action);
+                history.push(PlanStep {
+                    parent: now_node.first_action,
+                    action,
+                });
+                next_beam.push(BeamNode {
+                    score: next_state.evaluate_score(),
+                    first_action: Some(history.len() - 1),
+                    state: next_state,
+                });
+            }
+        }
+
+        if next_beam.is_empty() {
+            break;
+        }
+        stats::record_depth(t + 1);
+        now_beam = next_beam;
+
+        if now_beam.peek().unwrap().state.is_done() {
+            break;
+        }
+    }
+    let best = now_beam.pop().unwrap();
+    let mut actions = Vec::new();
+    let mut step = best.first_action;
+    while let Some(index) = step {
+        actions.push(history[index].action);
+        step = history[index].parent;
+    }
+    actions.reverse();
+    BeamPlan {
+        state: best.state,
+        actions,
+        score: best.score,
+    }
+}
+
+// 同じ深さで既に展開した局面 (Zobrist ハッシュが同じもの) を飛ばし、その分ビームに別の局面を入れる
+// 評価値の高い順に取り出すので、同じ局面なら最初に見つけた手順の最初の手が残る
+// (結果, 飛ばした局面の数)
+pub fn beam_search_dedup<S: GameState + ZobristHash>(
+    state: &S,
+    beam_width: usize,
+    beam_depth: usize,
+) -> (BeamResult<S>, usize) {
+    // 幅や深さが 0 でも1層目は展開し、合法手を返す
+    let (beam_width, beam_depth) = (beam_width.max(1), beam_depth.max(1));
+    let mut now_beam = root_beam(state);
+    let mut duplicates = 0;
+    for t in 0..beam_depth {
+        let mut next_beam = BinaryHeap::new();
+        let mut seen = HashSet::new();
+        let mut expanded = 0;
+        while expanded < beam_width {
+            let Some(now_node) = now_beam.pop() else {
+                break;
+            };
+            let duplicate = !seen.insert(now_node.state.zobrist_hash());
+            stats::record_probe(duplicate);
+            if duplicate {
+                duplicates += 1;
+                continue;
+            }
+            push_children(&now_node, t == 0, &mut next_beam, &S::evaluate_score);
+            expanded += 1;
+        }
+
+        if next_beam.is_empty() {
+            break;
+        }
+        stats::record_depth(t + 1);
+        now_beam = next_beam;
+
+        if now_beam.peek().unwrap().state.is_done() {
+            break;
+        }
+    }
+    (now_beam.pop().unwrap().into_result(), duplicates)
+}
+
+// 深さの代わりに展開ノード数で打ち切るビームサーチ (マシン性能に依存しない)
+pub fn beam_search_action_with_node_budget<S: GameState>(
+    state: &S,
+    beam_width: usize,
+    node_budget: usize,
+) -> usize {
+    beam_search_with_node_budget(state, beam_width, node_budget).first_action
+}
+
+pub fn beam_search_with_node_budget<S: GameState>(
+    state: &S,
+    beam_width: usize,
+    node_budget: usize,
+) -> BeamResult<S> {
+    beam_search_with_budget(state, beam_width, &Budget::nodes(node_budget)).0
+}
+
+// budget の iterations は深さ、nodes は展開した子の数。1層目は budget に関係なく展開するので必ず手を返す
+pub fn beam_search_with_budget<S: GameState>(
+    state: &S,
+    beam_width: usize,
+    budget: &Budget,
+) -> (BeamResult<S>, Consumed) {
+    // 幅が 0 でも1層目は展開し、合法手を返す
+    let beam_width = beam_width.max(1);
+    let mut meter = BudgetMeter::new(*budget);
+    let mut now_beam = root_beam(state);
+
+    for t in 0.. {
+        let mut next_beam = BinaryHeap::new();
+        for _ in 0..beam_width {
+            if t > 0 && meter.exhausted().is_some() {
+                break;
+            }
+            if let Some(now_node) = now_beam.pop() {
+                meter.add_nodes(push_children(
+                    &now_node,
+                    t == 0,
+                    &mut next_beam,
+                    &S::evaluate_score,
+                ));
+            }
+        }
+
+        if next_beam.is_empty() {
+            break;
+        }
+        stats::record_depth(t + 1);
+        now_beam = next_beam;
+        meter.add_iteration();
+
+        if meter.exhausted().is_some() || now_beam.peek().unwrap().state.is_done() {
+            break;
+        }
+    }
+    (now_beam.pop().unwrap().into_result(), meter.consumed())
+}
+
+// 深さごとにビームを持ち、浅い層から1つずつ取り出して次の層へ進める操作を beam_number 回繰り返す
+// 各層から一度に取り出すのは beam_width 個。最も深い層の最良の局面へ向かう最初の手を返す
+pub fn chokudai_search_action<S: GameState>(
+    state: &S,
+    beam_width: usize,
+    beam_depth: usize,
+    beam_number: usize,
+) -> usize {
+    chokudai_search_with_budget(
+        state,
+        beam_width,
+        beam_depth,
+        &Budget::iterations(beam_number),
+    )
+    .0
+}
+
+// budget の iterations は beam_number、nodes は展開した子の数。1周目は budget に関係なく終えるので必ず手を返す
+// どの層からも取り出せなくなったら、budget が残っていても止める
+pub fn chokudai_search_with_budget<S: GameState>(
+    state: &S,
+    beam_width: usize,
+    beam_depth: usize,
+    budget: &Budget,
+) -> (usize, Consumed) {
+    chokudai_search_by(state, beam_width, beam_depth, budget, &S::evaluate_score)
+}
+
+// evaluate_score の代わりに evaluate の値で各層の局面を並べる Chokudai サーチ
+pub fn chokudai_search_action_with_evaluator<S: GameState>(
+    state: &S,
+    beam_width: usize,
+    beam_depth: usize,
+    beam_number: usize,
+    evaluate: &impl Fn(&S) -> ScoreType,
+) -> usize {
+    chokudai_search_by(
+        state,
+        beam_width,
+        beam_depth,
+        &Budget::iterations(beam_number),
+        &|state: &mut S| evaluate(state),
+    )
+    .0
+}
+
+fn chokudai_search_by<S: GameState>(
+    state: &S,
+    beam_width: usize,
+    beam_depth: usize,
+    budget: &Budget,
+    evaluate: &impl Fn(&mut S) -> ScoreType,
+) -> (usize, Consumed) {
+    // 幅や深さが 0 でも1層目は展開し、合法手を返す
+    let (beam_width, beam_depth) = (beam_width.max(1), beam_depth.max(1));
+    let mut meter = BudgetMeter::new(*budget);
+    let mut beams: Vec<BinaryHeap<BeamNode<S>>> =
+        (0..=beam_depth).map(|_| BinaryHeap::new()).collect();
+    beams[0] = root_beam(state);
+    loop {
+        let mut expanded = false;
+        'pass: for t in 0..beam_depth {
+            let (now_beams, next_beams) = beams.split_at_mut(t + 1);
+            let now_beam = &mut now_beams[t];
+            let next_beam = &mut next_beams[0];
+            for _ in 0..beam_width {
+                if meter.iterations > 0 && meter.exhausted().is_some() {
+                    break 'pass;
+                }
+                match now_beam.peek() {
+                    Some(now_node) if !now_node.state.is_done() => {}
+                    _ => break,
+                }
+                let now_node = now_beam.pop().unwrap();
+                meter.add_nodes(push_children(&now_node, t == 0, next_beam, evaluate));
+                expanded = true;
+                stats::record_depth(t + 1);
+            }
+        }
+        meter.add_iteration();
+        if !expanded || meter.exhausted().is_some() {
+            break;
+        }
+    }
+    let action = beams
+        .into_iter()
+        .skip(1)
+        .rev()
+        .find_map(|mut beam| beam.pop())
+        .and_then(|node| node.first_action)
+        .unwrap();
+    (action, meter.consumed())
+}
+
+// 探索を始めた時刻と使ってよい時間を覚えておき、時間切れかを答える
+pub struct TimeKeeper {
+    start: Instant,
+    time_threshold: Duration,
+}
+
+impl TimeKeeper {
+    pub fn new(time_threshold_ms: u64) -> Self {
+        Self {
+            start: Instant::now(),
+            time_threshold: Duration::from_millis(time_threshold_ms),
+        }
+    }
+
+    pub fn is_time_over(&self) -> bool {
+        self.start.elapsed() >= self.time_threshold
+    }
+}
+
+// 深さの代わりに経過時間で打ち切るビームサーチ。1層目は時間に関係なく展開するので必ず手を返す
+pub fn beam_search_action_with_time_threshold<S: GameState>(
+    state: &S,
+    beam_width: usize,
+    time_threshold_ms: u64,
+) -> usize {
+    beam_search_with_time_threshold(state, beam_width, time_threshold_ms).first_action
+}
+
+pub fn beam_search_with_time_threshold<S: GameState>(
+    state: &S,
+    beam_width: usize,
+    time_threshold_ms: u64,
+) -> BeamResult<S> {
+    beam_search_with_budget(state, beam_width, &Budget::time_ms(time_threshold_ms)).0
+}
+
+fn root_beam<S: GameState>(state: &S) -> BinaryHeap<BeamNode<S>> {
+    let mut beam = BinaryHeap::new();
+    stats::record_clones(1);
+    beam.push(BeamNode {
+        score: 0,
+        first_action: None,
+        state: state.clone(),
+    });
+    beam
+}
+
+// now_node の子を evaluate の値をつけて next_beam に積み、積んだ数を返す。root の子なら自分の手を最初の手として覚える
+fn push_children<S: GameState>(
+    now_node: &BeamNode<S>,
+    is_root: bool,
+    next_beam: &mut BinaryHeap<BeamNode<S>>,
+    evaluate: &impl Fn(&mut S) -> ScoreType,
+) -> usize {
+    let legal_actions = now_node.state.legal_actions();
+    stats::record_expansion();
+    stats::record_clones(legal_actions.len());
+    for &action in &legal_actions {
+        let mut next_state = now_node.state.clone();
+        next_state.advance(action);
+        next_beam.push(BeamNode {
+            score: evaluate(&mut next_state),
+            first_action: if is_root {
+                Some(action)
+            } else {
+                now_node.first_action
+            },
+            state: next_state,
+        });
+    }
+    legal_actions.len()
+}
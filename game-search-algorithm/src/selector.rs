@@ -0,0 +1,177 @@
+use crate::agent::{parse_agent, Agent};
+use crate::{GameState, MazeState, ScoreType};
+use std::fs;
+
+// 局面から安く計算できる特徴量
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionFeatures {
+    pub remaining_turns: usize,
+    pub branching_factor: usize,
+    pub point_density: f64,
+}
+
+const FEATURE_NAMES: [&str; 3] = ["remaining_turns", "branching_factor", "point_density"];
+
+impl PositionFeatures {
+    pub fn extract(state: &MazeState) -> Self {
+        let points = state.points.iter().flatten().filter(|&&p| p > 0).count();
+        Self {
+            remaining_turns: state.remaining_turns(),
+            branching_factor: state.legal_actions().len(),
+            point_density: points as f64 / (state.params.h * state.params.w) as f64,
+        }
+    }
+
+    fn get(&self, feature: &str) -> Option<f64> {
+        match feature {
+            "remaining_turns" => Some(self.remaining_turns as f64),
+            "branching_factor" => Some(self.branching_factor as f64),
+            "point_density" => Some(self.point_density),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Condition {
+    Always,
+    Compare(String, Comparison, f64),
+}
+
+impl Condition {
+    fn matches(&self, features: &PositionFeatures) -> bool {
+        match self {
+            Condition::Always => true,
+            Condition::Compare(feature, comparison, threshold) => {
+                let value = features.get(feature).unwrap();
+                match comparison {
+                    Comparison::Less => value < *threshold,
+                    Comparison::LessEqual => value <= *threshold,
+                    Comparison::Greater => value > *threshold,
+                    Comparison::GreaterEqual => value >= *threshold,
+                    Comparison::Equal => value == *threshold,
+                }
+            }
+        }
+    }
+}
+
+// 1行1ルールで、上から順に最初に条件を満たしたルールのエージェントを使う
+//   remaining_turns <= 1 => greedy
+//   * => beam width=2 depth=4
+pub const DEFAULT_RULES: &str = "\
+remaining_turns <= 1 => greedy
+point_density < 0.3 => beam width=5 nodes=64
+* => beam width=2 depth=4
+";
+
+fn parse_condition(text: &str) -> Result<Condition, String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    match words.as_slice() {
+        ["*"] => Ok(Condition::Always),
+        [feature, op, value] => {
+            if !FEATURE_NAMES.contains(feature) {
+                return Err(format!("unknown feature `{}`", feature));
+            }
+            let comparison = match *op {
+                "<" => Comparison::Less,
+                "<=" => Comparison::LessEqual,
+                ">" => Comparison::Greater,
+                ">=" => Comparison::GreaterEqual,
+                "==" => Comparison::Equal,
+                _ => return Err(format!("unknown comparison `{}`", op)),
+            };
+            let threshold = value
+                .parse()
+                .map_err(|_| format!("invalid threshold `{}`", value))?;
+            Ok(Condition::Compare(
+                feature.to_string(),
+                comparison,
+                threshold,
+            ))
+        }
+        _ => Err(format!("invalid condition `{}`", text)),
+    }
+}
+
+// 局面の特徴量に応じて使うアルゴリズムと予算を切り替えるメタエージェント
+pub struct SelectorAgent {
+    rules: Vec<(Condition, Box<dyn Agent>)>,
+    usage: Vec<usize>,
+}
+
+impl SelectorAgent {
+    pub fn from_rules(text: &str) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (condition, spec) = line
+                .split_once("=>")
+                .ok_or(format!("line {}: expected `condition => agent`", i + 1))?;
+            let condition =
+                parse_condition(condition).map_err(|e| format!("line {}: {}", i + 1, e))?;
+            let agent = parse_agent(spec).map_err(|e| format!("line {}: {}", i + 1, e))?;
+            rules.push((condition, agent));
+        }
+        if !rules
+            .iter()
+            .any(|(condition, _)| *condition == Condition::Always)
+        {
+            return Err("rules need a fallback `* => agent` line".to_string());
+        }
+        let usage = vec![0; rules.len()];
+        Ok(Self { rules, usage })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::from_rules(&text)
+    }
+
+    // 各ルールが何回選ばれたか
+    pub fn usage(&self) -> Vec<(String, usize)> {
+        self.rules
+            .iter()
+            .zip(&self.usage)
+            .map(|((_, agent), &count)| (agent.name(), count))
+            .collect()
+    }
+
+    fn choose(&mut self, state: &MazeState) -> &mut Box<dyn Agent> {
+        let features = PositionFeatures::extract(state);
+        let index = self
+            .rules
+            .iter()
+            .position(|(condition, _)| condition.matches(&features))
+            .unwrap();
+        self.usage[index] += 1;
+        &mut self.rules[index].1
+    }
+}
+
+impl Agent for SelectorAgent {
+    fn name(&self) -> String {
+        let members: Vec<String> = self.rules.iter().map(|(_, agent)| agent.name()).collect();
+        format!("selector([{}])", members.join(", "))
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        self.choose(state).select_action(state)
+    }
+
+    fn select_action_with_score(&mut self, state: &MazeState) -> (usize, Option<ScoreType>) {
+        self.choose(state).select_action_with_score(state)
+    }
+}
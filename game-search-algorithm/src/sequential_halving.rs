@@ -0,0 +1,364 @@
+use crate::agent::{Agent, Capabilities};
+use crate::budget::{Budget, BudgetMeter, Consumed};
+use crate::prior::{ordered_actions, sample_action, PriorPolicy, UniformPrior};
+use crate::{GameState, MazeState, ScoreType};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::time::Duration;
+
+// 事前確率に従って手を選ぶプレイアウト。通った局面の数を meter の nodes に、1回を iterations に足す
+// 終局した局面からのプレイアウトでも nodes が増えるように、始めの局面も数える
+fn playout(
+    state: &MazeState,
+    prior: &dyn PriorPolicy,
+    rng: &mut ChaCha8Rng,
+    meter: &mut BudgetMeter,
+) -> ScoreType {
+    let mut state = state.clone();
+    meter.add_nodes(1);
+    while !state.is_done() {
+        let action = sample_action(&prior.priors(&state), rng);
+        state.advance(action);
+        meter.add_nodes(1);
+    }
+    meter.add_iteration();
+    state.game_score
+}
+
+// 逐次半減法は配るプレイアウトの総数を先に決めておく必要がある
+// budget がプレイアウトの回数 (iterations) だけならその回数を1回で配り切る
+// nodes や time があれば、配る回数を倍にしながら使い切るまで繰り返し、最後に終えた回の手を使う
+// 1回の途中では止めないので、1回目は budget に関係なく終えて必ず手を返す
+// run は (手, 配った回数を使い切れなかったか) を返す。使い切れなければ回数を倍にしても budget が減らないので止める
+fn allot(
+    budget: &Budget,
+    arms: usize,
+    meter: &mut BudgetMeter,
+    mut run: impl FnMut(usize, &mut BudgetMeter) -> (usize, bool),
+) -> usize {
+    assert!(
+        !budget.is_unlimited(),
+        "sequential halving needs a finite budget"
+    );
+    if budget.nodes.is_none() && budget.time.is_none() {
+        return run(budget.iterations.unwrap(), meter).0;
+    }
+    let mut playouts = 2 * arms.max(1);
+    loop {
+        let remaining = budget
+            .iterations
+            .map_or(playouts, |max| max.saturating_sub(meter.iterations).max(1));
+        let (action, solved) = run(playouts.min(remaining), meter);
+        if solved || meter.exhausted().is_some() {
+            return action;
+        }
+        playouts *= 2;
+    }
+}
+
+pub(crate) fn halving_rounds(arms: usize) -> usize {
+    let mut rounds = 0;
+    while (1 << rounds) < arms {
+        rounds += 1;
+    }
+    rounds.max(1)
+}
+
+// 半減で比べる子。平均の高い子を残す
+pub(crate) trait HalvingArm {
+    fn mean(&self) -> f64;
+}
+
+// 逐次半減法で budget 回のプレイアウトを children に配り、平均の悪い半分を捨てることを繰り返す
+// search(子, 回数) はその子で回数分のプレイアウトを行い、(合計評価値, 使った回数) を返す
+// 同じ平均なら先に並んでいる子が残る。1つに絞れたら残りの回数はその子に配る
+// SHOT (ShotNode) と、根の手を半減で選ぶ MCTS (mcts::Node) はどちらもこれを使う
+pub(crate) fn halve<N: HalvingArm>(
+    children: &mut [(usize, N)],
+    budget: usize,
+    mut search: impl FnMut(&mut N, usize) -> (ScoreType, usize),
+) -> (ScoreType, usize) {
+    let rounds = halving_rounds(children.len());
+    let mut candidates: Vec<usize> = (0..children.len()).collect();
+    let mut total = 0;
+    let mut used = 0;
+    while candidates.len() > 1 && used < budget {
+        let per_child = ((budget - used) / (candidates.len() * rounds)).max(1);
+        for &i in &candidates {
+            let (score, playouts) = search(&mut children[i].1, per_child);
+            total += score;
+            used += playouts;
+        }
+        candidates.sort_by(|&a, &b| {
+            children[b]
+                .1
+                .mean()
+                .partial_cmp(&children[a].1.mean())
+                .unwrap()
+        });
+        candidates.truncate(candidates.len() - candidates.len() / 2);
+    }
+    if candidates.len() == 1 && used < budget {
+        let (score, playouts) = search(&mut children[candidates[0]].1, budget - used);
+        total += score;
+        used += playouts;
+    }
+    (total, used)
+}
+
+// ルートの各手にプレイアウトを均等に配り、平均の悪い半分を捨てることを繰り返す
+pub fn sequential_halving_action(
+    state: &MazeState,
+    playout_number: usize,
+    prior: &dyn PriorPolicy,
+    rng: &mut ChaCha8Rng,
+) -> usize {
+    sequential_halving_with_budget(state, &Budget::iterations(playout_number), prior, rng).0
+}
+
+// 合法手が1つ以下なら探索せずにその手を返す (半減するものがなく、nodes や time の budget が減らない)
+fn only_action(state: &MazeState, budget: &Budget) -> Option<(usize, Consumed)> {
+    match state.legal_actions()[..] {
+        [action] => Some((action, BudgetMeter::new(*budget).consumed())),
+        _ => None,
+    }
+}
+
+// budget の iterations はプレイアウトの回数、nodes はプレイアウトで進めた局面の数
+pub fn sequential_halving_with_budget(
+    state: &MazeState,
+    budget: &Budget,
+    prior: &dyn PriorPolicy,
+    rng: &mut ChaCha8Rng,
+) -> (usize, Consumed) {
+    if let Some(result) = only_action(state, budget) {
+        return result;
+    }
+    let mut meter = BudgetMeter::new(*budget);
+    let arms = state.legal_actions().len();
+    let action = allot(budget, arms, &mut meter, |playouts, meter| {
+        (halving_pass(state, playouts, prior, rng, meter), false)
+    });
+    (action, meter.consumed())
+}
+
+fn halving_pass(
+    state: &MazeState,
+    playout_number: usize,
+    prior: &dyn PriorPolicy,
+    rng: &mut ChaCha8Rng,
+    meter: &mut BudgetMeter,
+) -> usize {
+    let legal_actions = state.legal_actions();
+    let rounds = halving_rounds(legal_actions.len());
+    // (行動, 合計スコア, プレイアウト回数)
+    let mut arms: Vec<(usize, ScoreType, usize)> =
+        legal_actions.iter().map(|&action| (action, 0, 0)).collect();
+    while arms.len() > 1 {
+        let per_arm = (playout_number / (arms.len() * rounds)).max(1);
+        for arm in &mut arms {
+            let mut next_state = state.clone();
+            next_state.advance(arm.0);
+            for _ in 0..per_arm {
+                arm.1 += playout(&next_state, prior, rng, meter);
+                arm.2 += 1;
+            }
+        }
+        arms.sort_by(|a, b| (b.1 * a.2 as ScoreType).cmp(&(a.1 * b.2 as ScoreType)));
+        arms.truncate(arms.len() - arms.len() / 2);
+    }
+    arms[0].0
+}
+
+struct ShotNode {
+    state: MazeState,
+    total_score: ScoreType,
+    visits: usize,
+    children: Vec<(usize, ShotNode)>,
+}
+
+impl ShotNode {
+    fn new(state: MazeState) -> Self {
+        Self {
+            state,
+            total_score: 0,
+            visits: 0,
+            children: Vec::new(),
+        }
+    }
+
+    // SHOT (Sequential Halving applied to Trees)
+    // 子ノードにも逐次半減法を再帰的に適用する。戻り値は (合計スコア, 使ったプレイアウト数)
+    fn search(
+        &mut self,
+        budget: usize,
+        prior: &dyn PriorPolicy,
+        rng: &mut ChaCha8Rng,
+        meter: &mut BudgetMeter,
+    ) -> (ScoreType, usize) {
+        if self.state.is_done() || budget <= 1 {
+            let score = playout(&self.state, prior, rng, meter);
+            self.total_score += score;
+            self.visits += 1;
+            return (score, 1);
+        }
+        if self.children.is_empty() {
+            // 半減の同点は先に並んだ子が残るので、事前確率の高い順に展開しておく
+            for action in ordered_actions(&self.state, prior) {
+                let mut next_state = self.state.clone();
+                next_state.advance(action);
+                self.children.push((action, ShotNode::new(next_state)));
+            }
+        }
+
+        let (total, used) = halve(&mut self.children, budget, |child, playouts| {
+            child.search(playouts, prior, rng, meter)
+        });
+        self.total_score += total;
+        self.visits += used;
+        (total, used)
+    }
+
+    fn best_action(&self) -> usize {
+        let mut best = &self.children[0];
+        for child in &self.children {
+            if child.1.visits > best.1.visits
+                || (child.1.visits == best.1.visits && child.1.mean() > best.1.mean())
+            {
+                best = child;
+            }
+        }
+        best.0
+    }
+}
+
+impl HalvingArm for ShotNode {
+    fn mean(&self) -> f64 {
+        if self.visits == 0 {
+            0.0
+        } else {
+            self.total_score as f64 / self.visits as f64
+        }
+    }
+}
+
+pub fn shot_action(
+    state: &MazeState,
+    playout_number: usize,
+    prior: &dyn PriorPolicy,
+    rng: &mut ChaCha8Rng,
+) -> usize {
+    shot_with_budget(state, &Budget::iterations(playout_number), prior, rng).0
+}
+
+// budget の iterations はプレイアウトの回数、nodes はプレイアウトで進めた局面の数
+// 繰り返すときは同じ木に足していく
+pub fn shot_with_budget(
+    state: &MazeState,
+    budget: &Budget,
+    prior: &dyn PriorPolicy,
+    rng: &mut ChaCha8Rng,
+) -> (usize, Consumed) {
+    if let Some(result) = only_action(state, budget) {
+        return result;
+    }
+    let mut meter = BudgetMeter::new(*budget);
+    let mut root = ShotNode::new(state.clone());
+    let arms = state.legal_actions().len();
+    let action = allot(budget, arms, &mut meter, |playouts, meter| {
+        // 終局した節は1回のプレイアウトで済ませるので、終局が近いと配った回数を使い切らないことがある
+        let playouts = playouts.max(2);
+        let (_, used) = root.search(playouts, prior, rng, meter);
+        (root.best_action(), used < playouts)
+    });
+    (action, meter.consumed())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RootPolicy {
+    SequentialHalving,
+    Shot,
+}
+
+// budget の iterations はプレイアウトの回数
+pub struct SequentialHalvingAgent {
+    pub policy: RootPolicy,
+    pub budget: Budget,
+    pub prior: Box<dyn PriorPolicy>,
+    // set_time_limit で受け取った1手の時間
+    time_limit: Option<Duration>,
+    rng: ChaCha8Rng,
+}
+
+impl SequentialHalvingAgent {
+    pub fn new(policy: RootPolicy, playout_number: usize, seed: u64) -> Self {
+        Self::with_budget(policy, Budget::iterations(playout_number), seed)
+    }
+
+    pub fn with_budget(policy: RootPolicy, budget: Budget, seed: u64) -> Self {
+        Self {
+            policy,
+            budget,
+            prior: Box::new(UniformPrior),
+            time_limit: None,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Agent for SequentialHalvingAgent {
+    fn name(&self) -> String {
+        let policy = match self.policy {
+            RootPolicy::SequentialHalving => "sequential_halving",
+            RootPolicy::Shot => "shot",
+        };
+        format!(
+            "{}({}, prior={})",
+            policy,
+            self.budget.describe("playouts"),
+            self.prior.name()
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        let search = match self.policy {
+            RootPolicy::SequentialHalving => sequential_halving_with_budget,
+            RootPolicy::Shot => shot_with_budget,
+        };
+        let budget = self.budget.with_time_limit(self.time_limit);
+        search(state, &budget, self.prior.as_ref(), &mut self.rng).0
+    }
+
+    fn set_time_limit(&mut self, time: Option<Duration>) {
+        self.time_limit = time;
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::budgeted(&self.budget, self.time_limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::Seed;
+    use crate::MazeParams;
+
+    // 1列の盤面の端では合法手が1つしかなく、nodes だけの budget でも止まる
+    #[test]
+    fn single_legal_action_returns_with_nodes_budget() {
+        let params: MazeParams = "h=1 w=3 end_turn=4".parse().unwrap();
+        let budget = Budget::nodes(300);
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for search in [sequential_halving_with_budget, shot_with_budget] {
+            for seed in Seed::first(20) {
+                let mut state = MazeState::from_seed_with_params(seed, params);
+                while !state.is_done() {
+                    let (action, _) = search(&state, &budget, &UniformPrior, &mut rng);
+                    assert!(state.legal_actions().contains(&action));
+                    state.advance(action);
+                }
+            }
+        }
+    }
+}
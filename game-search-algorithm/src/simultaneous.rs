@@ -0,0 +1,500 @@
+use crate::ids::{PlayerId, Seed};
+use crate::provenance::Provenance;
+use crate::{Coord, ScoreType, DX, DY, END_TURN, H, W};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::str::FromStr;
+
+// 2人が同じ点のマスに同時に入ったときの扱い
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollisionRule {
+    // 半分ずつ分ける。割り切れない端数は誰も得ない
+    Split,
+    // 優先権を持つプレイヤーが全部取る
+    Priority(PlayerId),
+    // どちらも取れず、点はマスに残る
+    Neither,
+}
+
+impl FromStr for CollisionRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "split" => Ok(CollisionRule::Split),
+            "priority0" => Ok(CollisionRule::Priority(PlayerId::FIRST)),
+            "priority1" => Ok(CollisionRule::Priority(PlayerId::SECOND)),
+            "neither" => Ok(CollisionRule::Neither),
+            _ => Err(format!(
+                "unknown collision rule `{}` (split, priority0, priority1, neither)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for CollisionRule {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            CollisionRule::Split => write!(f, "split"),
+            CollisionRule::Priority(player) => write!(f, "priority{}", player),
+            CollisionRule::Neither => write!(f, "neither"),
+        }
+    }
+}
+
+// 移動の 0..4 に加えて、その場に留まる行動
+pub const PASS: usize = 4;
+
+// プレイヤーごとに変えられるルール。ハンディキャップ戦や非対称なゲームを表す
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlayerRules {
+    // 指定するとシードで決まる初期位置の代わりにここから始める
+    pub start: Option<(usize, usize)>,
+    // 序盤に強制的にパスさせるターン数
+    pub forced_passes: usize,
+    // K ターンごとに追加でもう1回動ける
+    pub extra_move_every: Option<usize>,
+    // 自分からのマンハッタン距離がこれ以下のマスの点しか見えない
+    pub vision: Option<usize>,
+}
+
+// "start=0,1 pass=2 extra=3 vision=1" のような文字列から読む
+impl FromStr for PlayerRules {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = PlayerRules::default();
+        for word in s.split_whitespace() {
+            let (key, value) = word
+                .split_once('=')
+                .ok_or(format!("expected key=value, got `{}`", word))?;
+            let number = |text: &str| {
+                text.parse::<usize>()
+                    .map_err(|_| format!("invalid number `{}` for `{}`", text, key))
+            };
+            match key {
+                "start" => {
+                    let (y, x) = value
+                        .split_once(',')
+                        .ok_or(format!("expected start=y,x, got `{}`", value))?;
+                    let (y, x) = (number(y)?, number(x)?);
+                    if y >= H || x >= W {
+                        return Err(format!("start ({}, {}) is outside the board", y, x));
+                    }
+                    rules.start = Some((y, x));
+                }
+                "pass" => rules.forced_passes = number(value)?,
+                "extra" => rules.extra_move_every = Some(number(value)?.max(1)),
+                "vision" => rules.vision = Some(number(value)?),
+                _ => return Err(format!("unknown player rule `{}`", key)),
+            }
+        }
+        Ok(rules)
+    }
+}
+
+// 局面を誰の目で描くか。見えないのはマスの点だけで、位置と得点は両者に見えている
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum View {
+    // 全部の点が見える。ログを読む人のための視点
+    Omniscient,
+    // そのプレイヤーの vision で見えるマスだけ。探索に渡してよいのはこちら
+    Player(PlayerId),
+}
+
+impl View {
+    pub const ALL: [View; 3] = [
+        View::Omniscient,
+        View::Player(PlayerId::FIRST),
+        View::Player(PlayerId::SECOND),
+    ];
+}
+
+impl Display for View {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            View::Omniscient => write!(f, "omniscient"),
+            View::Player(player) => write!(f, "player{}", player.index()),
+        }
+    }
+}
+
+impl FromStr for View {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        View::ALL
+            .into_iter()
+            .find(|view| view.to_string() == s)
+            .ok_or(format!("unknown view `{}`", s))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    Win(PlayerId),
+    Draw,
+}
+
+// 2人が毎ターン同時に行動を選ぶ迷路
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimultaneousMazeState {
+    points: Vec<Vec<ScoreType>>,
+    turn: usize,
+    characters: [Coord; 2],
+    pub game_scores: [ScoreType; 2],
+    pub rule: CollisionRule,
+    pub player_rules: [PlayerRules; 2],
+    // 同じ点のマスに同時に入った回数
+    pub collisions: usize,
+}
+
+impl SimultaneousMazeState {
+    pub fn from_seed(seed: Seed, rule: CollisionRule, player_rules: [PlayerRules; 2]) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+
+        let mut first = (rng.gen_range(0..H), rng.gen_range(0..W));
+        let mut second = first;
+        while second == first {
+            second = (rng.gen_range(0..H), rng.gen_range(0..W));
+        }
+        // 盤面の点は初期位置を差し替えても変わらないように、乱数を引く順番は同じにする
+        let random_starts = [first, second];
+        first = player_rules[0].start.unwrap_or(first);
+        second = player_rules[1].start.unwrap_or(second);
+
+        let mut points = vec![vec![0; W]; H];
+        for (j, row) in points.iter_mut().enumerate() {
+            for (i, point) in row.iter_mut().enumerate() {
+                if random_starts.contains(&(j, i)) {
+                    continue;
+                }
+                *point = rng.gen_range(0..10);
+            }
+        }
+
+        points[first.0][first.1] = 0;
+        points[second.0][second.1] = 0;
+
+        let coord = |(y, x): (usize, usize)| Coord {
+            y: y as i64,
+            x: x as i64,
+        };
+        Self {
+            points,
+            turn: 0,
+            characters: [coord(first), coord(second)],
+            game_scores: [0, 0],
+            rule,
+            player_rules,
+            collisions: 0,
+        }
+    }
+
+    // 点を取り尽くしたら END_TURN を待たずに終わる
+    pub fn is_done(&self) -> bool {
+        self.turn == END_TURN || self.points.iter().flatten().all(|&point| point == 0)
+    }
+
+    // パスしか指せない局面では [PASS] を返す
+    pub fn legal_actions(&self, player: PlayerId) -> Vec<usize> {
+        if self.turn < self.player_rules[player.index()].forced_passes {
+            return vec![PASS];
+        }
+        let character = &self.characters[player.index()];
+        (0..4)
+            .filter(|&action| {
+                let y = character.y + DY[action];
+                let x = character.x + DX[action];
+                y >= 0 && y < H as i64 && x >= 0 && x < W as i64
+            })
+            .collect()
+    }
+
+    pub fn advance(&mut self, actions: [usize; 2]) {
+        for (character, &action) in self.characters.iter_mut().zip(actions.iter()) {
+            if action == PASS {
+                continue;
+            }
+            character.y += DY[action];
+            character.x += DX[action];
+        }
+
+        let [first, second] = &self.characters;
+        if first == second {
+            let point = &mut self.points[first.y as usize][first.x as usize];
+            if *point > 0 {
+                self.collisions += 1;
+                match self.rule {
+                    CollisionRule::Split => {
+                        self.game_scores[0] += *point / 2;
+                        self.game_scores[1] += *point / 2;
+                        *point = 0;
+                    }
+                    CollisionRule::Priority(player) => {
+                        self.game_scores[player.index()] += *point;
+                        *point = 0;
+                    }
+                    CollisionRule::Neither => {}
+                }
+            }
+        } else {
+            for (player, character) in self.characters.iter().enumerate() {
+                let point = &mut self.points[character.y as usize][character.x as usize];
+                self.game_scores[player] += *point;
+                *point = 0;
+            }
+        }
+        self.turn += 1;
+    }
+
+    // いま終わったターンのあとに追加で1回動けるプレイヤー
+    pub fn extra_movers(&self) -> Vec<PlayerId> {
+        if self.turn == 0 || self.is_done() {
+            return Vec::new();
+        }
+        PlayerId::BOTH
+            .into_iter()
+            .filter(|&player| {
+                self.player_rules[player.index()]
+                    .extra_move_every
+                    .map_or(false, |every| self.turn % every == 0)
+            })
+            .collect()
+    }
+
+    // 1人だけの追加の移動。ターンは進めない
+    pub fn advance_extra(&mut self, player: PlayerId, action: usize) {
+        if action == PASS {
+            return;
+        }
+        let character = &mut self.characters[player.index()];
+        character.y += DY[action];
+        character.x += DX[action];
+        let point = &mut self.points[character.y as usize][character.x as usize];
+        self.game_scores[player.index()] += *point;
+        *point = 0;
+    }
+
+    // player のキャラクターのいるマス (y, x)
+    pub fn position(&self, player: PlayerId) -> (usize, usize) {
+        let character = &self.characters[player.index()];
+        (character.y as usize, character.x as usize)
+    }
+
+    // 見えないマスは None
+    pub fn observe(&self, player: PlayerId) -> Vec<Vec<Option<ScoreType>>> {
+        let character = &self.characters[player.index()];
+        let vision = self.player_rules[player.index()].vision;
+        self.points
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(x, &point)| {
+                        let distance =
+                            (y as i64 - character.y).abs() + (x as i64 - character.x).abs();
+                        match vision {
+                            Some(vision) if distance > vision as i64 => None,
+                            _ => Some(point),
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // view から見た盤面。キャラクターは A と B、見えないマスは ?
+    pub fn render(&self, view: View) -> String {
+        let observation = match view {
+            View::Omniscient => self
+                .points
+                .iter()
+                .map(|row| row.iter().map(|&point| Some(point)).collect())
+                .collect(),
+            View::Player(player) => self.observe(player),
+        };
+        let mut text = format!(
+            "turn:{} score:{},{}\n",
+            self.turn, self.game_scores[0], self.game_scores[1]
+        );
+        for (y, row) in observation.iter().enumerate() {
+            for (x, point) in row.iter().enumerate() {
+                let here = |player: PlayerId| {
+                    let character = &self.characters[player.index()];
+                    character.y == y as i64 && character.x == x as i64
+                };
+                let c = if here(PlayerId::FIRST) {
+                    'A'
+                } else if here(PlayerId::SECOND) {
+                    'B'
+                } else {
+                    match point {
+                        Some(0) => '.',
+                        Some(point) => char::from_digit(*point as u32, 10).unwrap_or('+'),
+                        None => '?',
+                    }
+                };
+                text.push(c);
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    // player に見えないマスの点を乱数で埋めた局面。見えているマスと位置・得点はそのまま
+    pub fn determinize(&self, player: PlayerId, rng: &mut ChaCha8Rng) -> Self {
+        let mut world = self.clone();
+        for (y, row) in self.observe(player).iter().enumerate() {
+            for (x, point) in row.iter().enumerate() {
+                if point.is_none() {
+                    world.points[y][x] = rng.gen_range(0..10);
+                }
+            }
+        }
+        world
+    }
+
+    // 点が同じなら引き分け
+    pub fn result(&self) -> GameResult {
+        let [first, second] = self.game_scores;
+        match first.cmp(&second) {
+            std::cmp::Ordering::Greater => GameResult::Win(PlayerId::FIRST),
+            std::cmp::Ordering::Less => GameResult::Win(PlayerId::SECOND),
+            std::cmp::Ordering::Equal => GameResult::Draw,
+        }
+    }
+
+    // 相手の行動を無視して、次のマスの見えている点が最も高い行動を選ぶ
+    pub fn greedy_action(&self, player: PlayerId) -> usize {
+        let character = &self.characters[player.index()];
+        let observation = self.observe(player);
+        self.legal_actions(player)
+            .into_iter()
+            .max_by_key(|&action| {
+                if action == PASS {
+                    return 0;
+                }
+                let y = (character.y + DY[action]) as usize;
+                let x = (character.x + DX[action]) as usize;
+                observation[y][x].unwrap_or(0)
+            })
+            .unwrap()
+    }
+
+    // 行動ごとの特徴量 (見えている点だけを使う)
+    // [移動先の点, 相手も次に届くなら移動先の点, 移動先に隣接する最大の点]
+    pub fn action_features(&self, player: PlayerId, action: usize) -> [f64; 3] {
+        if action == PASS {
+            return [0.0; 3];
+        }
+        let character = &self.characters[player.index()];
+        let opponent = &self.characters[player.opponent().index()];
+        let observation = self.observe(player);
+        let (y, x) = (character.y + DY[action], character.x + DX[action]);
+        let point = observation[y as usize][x as usize].unwrap_or(0) as f64;
+        let contested = (opponent.y - y).abs() + (opponent.x - x).abs() == 1;
+        let mut next_best = 0;
+        for (dy, dx) in DY.iter().zip(DX.iter()) {
+            let (ny, nx) = (y + dy, x + dx);
+            if ny >= 0 && ny < H as i64 && nx >= 0 && nx < W as i64 {
+                next_best = next_best.max(observation[ny as usize][nx as usize].unwrap_or(0));
+            }
+        }
+        [point, if contested { point } else { 0.0 }, next_best as f64]
+    }
+
+    pub fn random_action(&self, player: PlayerId, rng: &mut impl Rng) -> usize {
+        let legal_actions = self.legal_actions(player);
+        legal_actions[rng.gen_range(0..legal_actions.len())]
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchSummary {
+    pub wins: [usize; 2],
+    pub draws: usize,
+    pub collisions: usize,
+    pub total_scores: [ScoreType; 2],
+}
+
+// プレイヤー0は貪欲法、プレイヤー1はランダムで seed の盤面を1局指し、初期局面と毎ターンの局面を返す
+pub fn record_greedy_vs_random(
+    seed: Seed,
+    rule: CollisionRule,
+    player_rules: [PlayerRules; 2],
+) -> Vec<SimultaneousMazeState> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+    let mut state = SimultaneousMazeState::from_seed(seed, rule, player_rules);
+    let mut frames = vec![state.clone()];
+    while !state.is_done() {
+        let actions = [
+            state.greedy_action(PlayerId::FIRST),
+            state.random_action(PlayerId::SECOND, &mut rng),
+        ];
+        state.advance(actions);
+        for player in state.extra_movers() {
+            let action = if player == PlayerId::FIRST {
+                state.greedy_action(player)
+            } else {
+                state.random_action(player, &mut rng)
+            };
+            state.advance_extra(player, action);
+        }
+        frames.push(state.clone());
+    }
+    frames
+}
+
+// 1局の局面を view から見た形で書く。プレイヤーの視点のファイルには見えない点は残らない
+pub fn save_replay(path: &str, frames: &[SimultaneousMazeState], view: View) -> io::Result<()> {
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    writeln!(writer, "{}", Provenance::collect(1))?;
+    writeln!(writer, "view {}", view)?;
+    for frame in frames {
+        writeln!(writer, "{}", frame.render(view))?;
+    }
+    writer.flush()
+}
+
+// prefix.omniscient.txt, prefix.player0.txt, prefix.player1.txt を書き、書いたパスを返す
+pub fn save_replay_views(
+    prefix: &str,
+    frames: &[SimultaneousMazeState],
+) -> io::Result<Vec<String>> {
+    let mut paths = Vec::new();
+    for view in View::ALL {
+        let path = format!("{}.{}.txt", prefix, view);
+        save_replay(&path, frames, view)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+// プレイヤー0は貪欲法、プレイヤー1はランダムで対戦させる
+pub fn greedy_vs_random(
+    rule: CollisionRule,
+    player_rules: [PlayerRules; 2],
+    game_number: usize,
+) -> MatchSummary {
+    let mut summary = MatchSummary::default();
+    for seed in Seed::first(game_number) {
+        let state = record_greedy_vs_random(seed, rule, player_rules)
+            .pop()
+            .unwrap();
+        match state.result() {
+            GameResult::Win(player) => summary.wins[player.index()] += 1,
+            GameResult::Draw => summary.draws += 1,
+        }
+        summary.collisions += state.collisions;
+        for player in 0..2 {
+            summary.total_scores[player] += state.game_scores[player];
+        }
+    }
+    summary
+}
@@ -0,0 +1,184 @@
+use crate::budget::{Budget, BudgetMeter, Consumed};
+use crate::ids::Seed;
+use crate::search;
+use crate::{GameState, MazeParams, MazeState, ScoreType, DX, DY};
+use std::time::{Duration, Instant};
+
+// ビームを構造体の配列ではなく配列の構造体として持つ
+// 盤面は1状態あたり h * w 個の連続した要素として boards に詰める
+struct SoaBeam {
+    params: MazeParams,
+    scores: Vec<ScoreType>,
+    first_actions: Vec<u8>,
+    ys: Vec<i64>,
+    xs: Vec<i64>,
+    turns: Vec<usize>,
+    boards: Vec<ScoreType>,
+}
+
+impl SoaBeam {
+    fn new(params: MazeParams) -> Self {
+        Self {
+            params,
+            scores: Vec::new(),
+            first_actions: Vec::new(),
+            ys: Vec::new(),
+            xs: Vec::new(),
+            turns: Vec::new(),
+            boards: Vec::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    fn clear(&mut self) {
+        self.scores.clear();
+        self.first_actions.clear();
+        self.ys.clear();
+        self.xs.clear();
+        self.turns.clear();
+        self.boards.clear();
+    }
+
+    fn board(&self, i: usize) -> &[ScoreType] {
+        let cells = self.params.h * self.params.w;
+        &self.boards[i * cells..(i + 1) * cells]
+    }
+
+    // MazeState::is_done と同じく、最後のターンか点を取り尽くしたら終わり
+    fn is_done(&self, i: usize) -> bool {
+        self.turns[i] == self.params.end_turn || self.board(i).iter().all(|&point| point == 0)
+    }
+
+    // i 番目の状態から action で進めた状態を other の末尾に追加する
+    fn push_child(&self, i: usize, action: usize, first_action: u8, other: &mut SoaBeam) {
+        let y = self.ys[i] + DY[action];
+        let x = self.xs[i] + DX[action];
+        let start = other.boards.len();
+        other.boards.extend_from_slice(self.board(i));
+        let cell = &mut other.boards[start + y as usize * self.params.w + x as usize];
+        other.scores.push(self.scores[i] + *cell);
+        *cell = 0;
+        other.first_actions.push(first_action);
+        other.ys.push(y);
+        other.xs.push(x);
+        other.turns.push(self.turns[i] + 1);
+    }
+}
+
+// search::beam_search_action と同じ探索を配列の構造体で行う
+pub fn soa_beam_search_action(state: &MazeState, beam_width: usize, beam_depth: usize) -> usize {
+    soa_beam_search_with_budget(state, beam_width, &Budget::iterations(beam_depth)).0
+}
+
+// search::beam_search_with_budget と同じく、budget の iterations は深さ、nodes は展開した子の数
+// 1層目は budget に関係なく展開するので必ず手を返す
+pub fn soa_beam_search_with_budget(
+    state: &MazeState,
+    beam_width: usize,
+    budget: &Budget,
+) -> (usize, Consumed) {
+    let mut meter = BudgetMeter::new(*budget);
+    let params = state.params;
+    let mut now_beam = SoaBeam::new(params);
+    let mut next_beam = SoaBeam::new(params);
+    now_beam.scores.push(state.game_score);
+    now_beam.first_actions.push(u8::MAX);
+    now_beam.ys.push(state.character.y);
+    now_beam.xs.push(state.character.x);
+    now_beam.turns.push(state.turn);
+    now_beam.boards.extend(state.points.iter().flatten());
+
+    let mut order: Vec<usize> = vec![0];
+    for t in 0.. {
+        next_beam.clear();
+        for &i in order.iter().take(beam_width) {
+            if t > 0 && meter.exhausted().is_some() {
+                break;
+            }
+            for action in 0..4 {
+                let y = now_beam.ys[i] + DY[action];
+                let x = now_beam.xs[i] + DX[action];
+                if y < 0 || y >= params.h as i64 || x < 0 || x >= params.w as i64 {
+                    continue;
+                }
+                let first_action = if t == 0 {
+                    action as u8
+                } else {
+                    now_beam.first_actions[i]
+                };
+                now_beam.push_child(i, action, first_action, &mut next_beam);
+                meter.add_nodes(1);
+            }
+        }
+        if next_beam.len() == 0 {
+            break;
+        }
+        std::mem::swap(&mut now_beam, &mut next_beam);
+        meter.add_iteration();
+
+        // 上位 beam_width 個の添字だけを選んでおく (スコアだけの配列を触るので局所性が高い)
+        order = (0..now_beam.len()).collect();
+        if order.len() > beam_width {
+            order.select_nth_unstable_by(beam_width - 1, |&a, &b| {
+                now_beam.scores[b].cmp(&now_beam.scores[a])
+            });
+            order.truncate(beam_width);
+        }
+        order.sort_by(|&a, &b| now_beam.scores[b].cmp(&now_beam.scores[a]));
+
+        if meter.exhausted().is_some() || now_beam.is_done(order[0]) {
+            break;
+        }
+    }
+    (now_beam.first_actions[order[0]] as usize, meter.consumed())
+}
+
+type SearchFn = fn(&MazeState, usize, &Budget) -> usize;
+
+// benchmark で測った1つの探索の平均スコアと時間
+pub struct BenchmarkResult {
+    pub name: &'static str,
+    pub score: f64,
+    pub elapsed: Duration,
+}
+
+// 同じ盤面・同じ幅・同じ budget で AoS 版と SoA 版の時間と平均スコアを比べる
+// budget を指定しなければ params の終わりまで読む
+pub fn benchmark(
+    beam_width: usize,
+    budget: &Budget,
+    params: MazeParams,
+    game_number: usize,
+) -> Vec<BenchmarkResult> {
+    let searches: [(&str, SearchFn); 2] = [
+        ("array of structs", |state, beam_width, budget| {
+            search::beam_search_with_budget(state, beam_width, budget)
+                .0
+                .first_action
+        }),
+        ("struct of arrays", |state, beam_width, budget| {
+            soa_beam_search_with_budget(state, beam_width, budget).0
+        }),
+    ];
+    let mut results = Vec::new();
+    for (name, search) in searches {
+        let start = Instant::now();
+        let mut total_score = 0;
+        for i in 0..game_number {
+            let mut state = MazeState::from_seed_with_params(Seed(i as u64), params);
+            while !state.is_done() {
+                state.advance(search(&state, beam_width, budget));
+            }
+            total_score += state.game_score;
+        }
+        results.push(BenchmarkResult {
+            name,
+            score: total_score as f64 / game_number as f64,
+            elapsed: start.elapsed(),
+        });
+    }
+    results
+}
@@ -0,0 +1,154 @@
+use crate::agent::Agent;
+use crate::hooks::{self, MoveTimer};
+use crate::ids::Seed;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoakOptions {
+    // 対局を続ける時間
+    pub duration: Duration,
+    // この間隔ごとに標本を取る。1手あたりの時間はこの間隔の中で平均する
+    pub sample_every: Duration,
+    // 最初の標本からの常駐メモリの増加がこれを超えたら失敗
+    pub max_memory_growth_bytes: usize,
+    // 最後の間隔の1手あたりの時間が、最初の間隔のこの倍を超えたら失敗
+    pub max_latency_drift: f64,
+}
+
+impl Default for SoakOptions {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_secs(3600),
+            sample_every: Duration::from_secs(10),
+            max_memory_growth_bytes: 64 << 20,
+            max_latency_drift: 1.5,
+        }
+    }
+}
+
+// プロセスの常駐メモリ (/proc/self/status の VmRSS)。読めない環境では None
+pub fn resident_bytes() -> Option<usize> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoakSample {
+    pub elapsed: Duration,
+    // それまでに指し終えた対局の数
+    pub games: usize,
+    pub resident_bytes: Option<usize>,
+    // Agent::retained_states
+    pub retained_states: Option<usize>,
+    // この間隔の1手あたりの平均時間
+    pub mean_latency: Duration,
+    pub decisions: usize,
+}
+
+impl Display for SoakSample {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let rss = self
+            .resident_bytes
+            .map_or("-".to_string(), |bytes| format!("{}KiB", bytes >> 10));
+        let retained = self
+            .retained_states
+            .map_or("-".to_string(), |states| states.to_string());
+        write!(
+            f,
+            "{:>7.1}s games={} rss={} retained={} decisions={} latency={:?}",
+            self.elapsed.as_secs_f64(),
+            self.games,
+            rss,
+            retained,
+            self.decisions,
+            self.mean_latency
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SoakReport {
+    pub samples: Vec<SoakSample>,
+    // 閾値を超えた項目。空なら合格
+    pub failures: Vec<String>,
+}
+
+impl SoakReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+fn check(samples: &[SoakSample], options: &SoakOptions) -> Vec<String> {
+    let mut failures = Vec::new();
+    let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+        return failures;
+    };
+    // 最初の標本はアロケータが落ち着くまでの分を含むので、増加はそこから測る
+    if let (Some(start), Some(end)) = (first.resident_bytes, last.resident_bytes) {
+        let growth = end.saturating_sub(start);
+        if growth > options.max_memory_growth_bytes {
+            failures.push(format!(
+                "resident memory grew by {} KiB (limit {} KiB)",
+                growth >> 10,
+                options.max_memory_growth_bytes >> 10
+            ));
+        }
+    }
+    if first.decisions > 0 && last.decisions > 0 {
+        let drift = last.mean_latency.as_secs_f64() / first.mean_latency.as_secs_f64();
+        if drift > options.max_latency_drift {
+            failures.push(format!(
+                "latency drifted from {:?} to {:?} ({:.2}x, limit {:.2}x)",
+                first.mean_latency, last.mean_latency, drift, options.max_latency_drift
+            ));
+        }
+    }
+    failures
+}
+
+// options.duration の間、シード 0, 1, 2, ... の盤面を agent で指し続ける
+// 標本を取るたびに on_sample を呼ぶ。対局の途中では止めないので、終わるのは duration を少し過ぎてから
+pub fn run_soak(
+    agent: &mut dyn Agent,
+    options: &SoakOptions,
+    on_sample: &mut dyn FnMut(&SoakSample),
+) -> SoakReport {
+    let started = Instant::now();
+    let mut next_sample = options.sample_every;
+    let mut report = SoakReport::default();
+    // 標本のあいだに考えた時間の合計と手数。1手ずつ持つと測っているメモリ自体が増える
+    let mut window_time = Duration::ZERO;
+    let mut window_decisions = 0;
+    let mut games = 0;
+    while started.elapsed() < options.duration {
+        let mut timer = MoveTimer::new();
+        hooks::play(agent, Seed(games as u64), &mut [&mut timer]);
+        games += 1;
+        window_time += timer.times.iter().sum::<Duration>();
+        window_decisions += timer.times.len();
+        let elapsed = started.elapsed();
+        if elapsed < next_sample && elapsed < options.duration {
+            continue;
+        }
+        let sample = SoakSample {
+            elapsed,
+            games,
+            resident_bytes: resident_bytes(),
+            retained_states: agent.retained_states(),
+            mean_latency: window_time / window_decisions.max(1) as u32,
+            decisions: window_decisions,
+        };
+        on_sample(&sample);
+        report.samples.push(sample);
+        window_time = Duration::ZERO;
+        window_decisions = 0;
+        next_sample = elapsed + options.sample_every;
+    }
+    report.failures = check(&report.samples, options);
+    report
+}
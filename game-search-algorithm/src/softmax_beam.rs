@@ -0,0 +1,168 @@
+use crate::agent::{describe_beam_budget, Agent, Capabilities};
+use crate::budget::{Budget, BudgetMeter, Consumed};
+use crate::{GameState, MazeState};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::time::Duration;
+
+// 上位K個ではなく exp(score / T) に比例した確率で生き残りを選ぶビームサーチ
+// 深さ d での温度は temperature * annealing^d
+pub fn softmax_beam_search_action(
+    state: &MazeState,
+    beam_width: usize,
+    beam_depth: usize,
+    temperature: f64,
+    annealing: f64,
+    rng: &mut ChaCha8Rng,
+) -> usize {
+    softmax_beam_search_with_budget(
+        state,
+        beam_width,
+        &Budget::iterations(beam_depth),
+        temperature,
+        annealing,
+        rng,
+    )
+    .0
+}
+
+// budget の iterations は深さ、nodes は生成した候補の数。1層目は budget に関係なく展開するので必ず手を返す
+pub fn softmax_beam_search_with_budget(
+    state: &MazeState,
+    beam_width: usize,
+    budget: &Budget,
+    temperature: f64,
+    annealing: f64,
+    rng: &mut ChaCha8Rng,
+) -> (usize, Consumed) {
+    let mut meter = BudgetMeter::new(*budget);
+    let mut now_beam = vec![state.clone()];
+    let mut best_state: Option<MazeState> = None;
+
+    for t in 0usize.. {
+        let mut candidates = Vec::new();
+        for now_state in &now_beam {
+            if t > 0 && meter.exhausted().is_some() {
+                break;
+            }
+            for action in now_state.legal_actions() {
+                let mut next_state = now_state.clone();
+                next_state.advance(action);
+                next_state.evaluate_score();
+                if t == 0 {
+                    next_state.first_action = Some(action);
+                }
+                candidates.push(next_state);
+                meter.add_nodes(1);
+            }
+        }
+        if candidates.is_empty() {
+            break;
+        }
+
+        let depth_best = candidates.iter().max().unwrap().clone();
+        match &best_state {
+            Some(best) if *best >= depth_best => {}
+            _ => best_state = Some(depth_best.clone()),
+        }
+        meter.add_iteration();
+        if depth_best.is_done() || meter.exhausted().is_some() {
+            break;
+        }
+
+        let depth_temperature = (temperature * annealing.powi(t as i32)).max(1e-9);
+        now_beam = sample_survivors(candidates, beam_width, depth_temperature, rng);
+    }
+    (best_state.unwrap().first_action.unwrap(), meter.consumed())
+}
+
+fn sample_survivors(
+    mut candidates: Vec<MazeState>,
+    beam_width: usize,
+    temperature: f64,
+    rng: &mut ChaCha8Rng,
+) -> Vec<MazeState> {
+    let mut survivors = Vec::with_capacity(beam_width);
+    while survivors.len() < beam_width && !candidates.is_empty() {
+        // オーバーフローを避けるため最大値を引いてから exp を取る
+        let max_score = candidates.iter().map(|s| s.evaluated_score).max().unwrap() as f64;
+        let weights: Vec<f64> = candidates
+            .iter()
+            .map(|s| ((s.evaluated_score as f64 - max_score) / temperature).exp())
+            .collect();
+        let total: f64 = weights.iter().sum();
+        let mut r = rng.gen::<f64>() * total;
+        let mut index = candidates.len() - 1;
+        for (i, weight) in weights.iter().enumerate() {
+            if r < *weight {
+                index = i;
+                break;
+            }
+            r -= weight;
+        }
+        survivors.push(candidates.swap_remove(index));
+    }
+    survivors
+}
+
+// budget の iterations は深さ。何も指定しなければ終局まで読む
+pub struct SoftmaxBeamAgent {
+    pub beam_width: usize,
+    pub budget: Budget,
+    pub temperature: f64,
+    pub annealing: f64,
+    // set_time_limit で受け取った1手の時間
+    time_limit: Option<Duration>,
+    rng: ChaCha8Rng,
+}
+
+impl SoftmaxBeamAgent {
+    pub fn new(
+        beam_width: usize,
+        budget: Budget,
+        temperature: f64,
+        annealing: f64,
+        seed: u64,
+    ) -> Self {
+        Self {
+            beam_width,
+            budget,
+            temperature,
+            annealing,
+            time_limit: None,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Agent for SoftmaxBeamAgent {
+    fn name(&self) -> String {
+        format!(
+            "softmax_beam(width={}, {}, temperature={}, annealing={})",
+            self.beam_width,
+            describe_beam_budget(&self.budget),
+            self.temperature,
+            self.annealing
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        softmax_beam_search_with_budget(
+            state,
+            self.beam_width,
+            &self.budget.with_time_limit(self.time_limit),
+            self.temperature,
+            self.annealing,
+            &mut self.rng,
+        )
+        .0
+    }
+
+    fn set_time_limit(&mut self, time: Option<Duration>) {
+        self.time_limit = time;
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::budgeted(&self.budget, self.time_limit)
+    }
+}
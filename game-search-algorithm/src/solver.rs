@@ -0,0 +1,295 @@
+use crate::agent::Agent;
+use crate::packing::{from_hex, to_hex};
+use crate::provenance::Provenance;
+use crate::stats;
+use crate::{GameState, MazeState, ScoreType};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+// キャッシュのキーと照合に使うバイト列。同じ配置でも盤面の大きさやターン数が違えば残りの最善が変わるので
+// params も含める。大きさ・座標・ターンは切り詰めないように8バイトずつ書き、続けて盤面を1マス4ビットで詰める
+fn position_bytes(state: &MazeState) -> Vec<u8> {
+    let params = state.params;
+    let mut bytes = Vec::new();
+    for value in [
+        params.h,
+        params.w,
+        params.end_turn,
+        state.character.y as usize,
+        state.character.x as usize,
+        state.turn,
+    ] {
+        bytes.extend_from_slice(&(value as u64).to_le_bytes());
+    }
+    let cells = (params.h * params.w + 1) / 2;
+    bytes.extend_from_slice(&state.pack_position()[..cells]);
+    bytes
+}
+
+// 局面を一意に表すハッシュ。ファイルに保存するのでプロセスやバージョンによらない FNV-1a を使う
+pub fn position_hash(state: &MazeState) -> u64 {
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    let mut hash = OFFSET;
+    for byte in position_bytes(state) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(PRIME);
+    }
+    hash
+}
+
+pub fn position_hash128(state: &MazeState) -> u128 {
+    const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+    let mut hash = OFFSET;
+    for byte in position_bytes(state) {
+        hash ^= byte as u128;
+        hash = hash.wrapping_mul(PRIME);
+    }
+    hash
+}
+
+// 全探索で残りターンに得られる最大の追加得点と、そのときの最初の行動を求める
+pub fn exhaustive_search(state: &MazeState) -> (ScoreType, Option<usize>) {
+    if state.is_done() {
+        return (0, None);
+    }
+    let mut best = (-1, None);
+    for action in state.legal_actions() {
+        let mut next_state = state.clone();
+        next_state.advance(action);
+        let gained = next_state.game_score - state.game_score;
+        let value = gained + exhaustive_search(&next_state).0;
+        if value > best.0 {
+            best = (value, Some(action));
+        }
+    }
+    best
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SolvedEntry {
+    pub action: usize,
+    // この局面から最善を尽くしたときに追加で得られる得点
+    pub value: ScoreType,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyWidth {
+    Bits64,
+    Bits128,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheOptions {
+    pub key_width: KeyWidth,
+    // ヒット時に保存しておいた局面と照合し、衝突していれば使わない
+    pub verify: bool,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        Self {
+            key_width: KeyWidth::Bits64,
+            verify: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub probes: usize,
+    pub hits: usize,
+    pub verified_hits: usize,
+    pub collisions: usize,
+}
+
+struct StoredEntry {
+    entry: SolvedEntry,
+    signature: Option<Vec<u8>>,
+}
+
+#[derive(Default)]
+pub struct SolverCache {
+    pub options: CacheOptions,
+    pub stats: CacheStats,
+    entries: HashMap<u128, StoredEntry>,
+}
+
+impl SolverCache {
+    pub fn new(options: CacheOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn key(&self, state: &MazeState) -> u128 {
+        match self.options.key_width {
+            KeyWidth::Bits64 => position_hash(state) as u128,
+            KeyWidth::Bits128 => position_hash128(state),
+        }
+    }
+
+    pub fn probe(&mut self, state: &MazeState) -> Option<SolvedEntry> {
+        self.stats.probes += 1;
+        let stored = self.entries.get(&self.key(state));
+        stats::record_probe(stored.is_some());
+        let stored = stored?;
+        self.stats.hits += 1;
+        if self.options.verify {
+            if let Some(signature) = &stored.signature {
+                let collided = *signature != position_bytes(state);
+                stats::record_verification(collided);
+                if collided {
+                    self.stats.collisions += 1;
+                    return None;
+                }
+                self.stats.verified_hits += 1;
+            }
+        }
+        Some(stored.entry)
+    }
+
+    pub fn insert(&mut self, state: &MazeState, entry: SolvedEntry) {
+        let signature = if self.options.verify {
+            Some(position_bytes(state))
+        } else {
+            None
+        };
+        self.entries
+            .insert(self.key(state), StoredEntry { entry, signature });
+    }
+
+    // 残りターンが max_remaining_turns 以下の局面を、与えられた盤面から到達できる範囲で全て解いて登録する
+    pub fn solve_endgames(&mut self, state: &MazeState, max_remaining_turns: usize) {
+        if state.is_done() {
+            return;
+        }
+        if state.remaining_turns() <= max_remaining_turns
+            && !self.entries.contains_key(&self.key(state))
+        {
+            let (value, action) = exhaustive_search(state);
+            self.insert(
+                state,
+                SolvedEntry {
+                    action: action.unwrap(),
+                    value,
+                },
+            );
+        }
+        for action in state.legal_actions() {
+            let mut next_state = state.clone();
+            next_state.advance(action);
+            self.solve_endgames(&next_state, max_remaining_turns);
+        }
+    }
+
+    // 1行に "キー 行動 値 [局面]" を書く。差分が取りやすいようにキー順に並べる
+    // キーの桁数 (16 か 32) でキー幅を、局面の有無で照合の有無を読み取る
+    // '#' で始まる行はコメントとして読み飛ばす
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|&(key, _)| *key);
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        writeln!(writer, "{}", Provenance::collect(1))?;
+        for (key, stored) in entries {
+            match self.options.key_width {
+                KeyWidth::Bits64 => write!(writer, "{:016x}", key)?,
+                KeyWidth::Bits128 => write!(writer, "{:032x}", key)?,
+            }
+            write!(writer, " {} {}", stored.entry.action, stored.entry.value)?;
+            if let Some(signature) = &stored.signature {
+                write!(writer, " {}", to_hex(signature))?;
+            }
+            writeln!(writer)?;
+        }
+        writer.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid cache line `{}`", line),
+            )
+        };
+        let mut cache = Self::default();
+        let text = fs::read_to_string(path)?;
+        let lines = text.lines().filter(|line| !line.starts_with('#'));
+        for (i, line) in lines.enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.len() != 3 && words.len() != 4 {
+                return Err(invalid(line));
+            }
+            let key_width = match words[0].len() {
+                16 => KeyWidth::Bits64,
+                32 => KeyWidth::Bits128,
+                _ => return Err(invalid(line)),
+            };
+            let verify = words.len() == 4;
+            if i == 0 {
+                cache.options = CacheOptions { key_width, verify };
+            } else if cache.options != (CacheOptions { key_width, verify }) {
+                return Err(invalid(line));
+            }
+            let key = u128::from_str_radix(words[0], 16).map_err(|_| invalid(line))?;
+            let action = words[1].parse().map_err(|_| invalid(line))?;
+            let value = words[2].parse().map_err(|_| invalid(line))?;
+            let signature = match words.get(3) {
+                Some(signature) => Some(from_hex(signature).map_err(|_| invalid(line))?),
+                None => None,
+            };
+            cache.entries.insert(
+                key,
+                StoredEntry {
+                    entry: SolvedEntry { action, value },
+                    signature,
+                },
+            );
+        }
+        Ok(cache)
+    }
+}
+
+// 探索の前にキャッシュを引き、解決済みの局面では完全な手を指す
+pub struct CachedAgent {
+    pub inner: Box<dyn Agent>,
+    pub cache: SolverCache,
+}
+
+impl CachedAgent {
+    pub fn new(inner: Box<dyn Agent>, cache: SolverCache) -> Self {
+        Self { inner, cache }
+    }
+}
+
+impl Agent for CachedAgent {
+    fn name(&self) -> String {
+        format!("cached({})", self.inner.name())
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        self.select_action_with_score(state).0
+    }
+
+    fn select_action_with_score(&mut self, state: &MazeState) -> (usize, Option<ScoreType>) {
+        match self.cache.probe(state) {
+            Some(entry) => (entry.action, Some(state.game_score + entry.value)),
+            None => self.inner.select_action_with_score(state),
+        }
+    }
+
+    fn retained_states(&self) -> Option<usize> {
+        Some(self.cache.len() + self.inner.retained_states().unwrap_or(0))
+    }
+}
@@ -0,0 +1,187 @@
+use crate::agent::Agent;
+use crate::MazeState;
+use std::cell::RefCell;
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
+
+// 探索が1回の意思決定でしたこと。数えていない探索の項目は None のまま
+// 探索は record_* で知らせ、呼び出し側は collect で囲んで受け取る
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchStats {
+    // 子を作った局面の数
+    pub nodes_expanded: Option<usize>,
+    // clone した局面の数 (プレイアウト用の複製も含む)
+    pub states_cloned: Option<usize>,
+    // 根から何手先まで読んだか
+    pub max_depth: Option<usize>,
+    // 置換表・キャッシュを引いた回数と、そのうち見つかった回数
+    pub table_probes: Option<usize>,
+    pub table_hits: Option<usize>,
+    // 見つかった項目を保存しておいた局面と照合して、別の局面だった回数
+    pub hash_collisions: Option<usize>,
+    // 評価関数のアンサンブルで評価した局面の数と、評価関数同士の意見の割れの合計・最大
+    pub evaluations: Option<usize>,
+    pub total_disagreement: Option<f64>,
+    pub max_disagreement: Option<f64>,
+    // 評価が割れたためにビーム幅を超えて展開した状態の数
+    pub extra_expansions: Option<usize>,
+    pub elapsed: Duration,
+}
+
+impl SearchStats {
+    // 複数の意思決定をまとめる。max_depth と max_disagreement は最大、ほかは合計
+    pub fn add(&mut self, other: &SearchStats) {
+        let sum = |a: Option<usize>, b: Option<usize>| match (a, b) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+        };
+        let sum_f64 = |a: Option<f64>, b: Option<f64>| match (a, b) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(0.0) + b.unwrap_or(0.0)),
+        };
+        self.nodes_expanded = sum(self.nodes_expanded, other.nodes_expanded);
+        self.states_cloned = sum(self.states_cloned, other.states_cloned);
+        self.max_depth = self.max_depth.max(other.max_depth);
+        self.table_probes = sum(self.table_probes, other.table_probes);
+        self.table_hits = sum(self.table_hits, other.table_hits);
+        self.hash_collisions = sum(self.hash_collisions, other.hash_collisions);
+        self.evaluations = sum(self.evaluations, other.evaluations);
+        self.total_disagreement = sum_f64(self.total_disagreement, other.total_disagreement);
+        self.max_disagreement = match (self.max_disagreement, other.max_disagreement) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        self.extra_expansions = sum(self.extra_expansions, other.extra_expansions);
+        self.elapsed += other.elapsed;
+    }
+
+    // 評価1回あたりの意見の割れ。アンサンブルで評価していなければ None
+    pub fn mean_disagreement(&self) -> Option<f64> {
+        let evaluations = self.evaluations.filter(|&n| n > 0)?;
+        Some(self.total_disagreement.unwrap_or(0.0) / evaluations as f64)
+    }
+}
+
+impl Display for SearchStats {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let or_dash = |value: Option<usize>| value.map_or("-".to_string(), |v| v.to_string());
+        write!(
+            f,
+            "expanded={} cloned={} max_depth={} probes={} hits={} collisions={} evaluations={} mean_disagreement={} max_disagreement={} extra={} elapsed={:?}",
+            or_dash(self.nodes_expanded),
+            or_dash(self.states_cloned),
+            or_dash(self.max_depth),
+            or_dash(self.table_probes),
+            or_dash(self.table_hits),
+            or_dash(self.hash_collisions),
+            or_dash(self.evaluations),
+            self.mean_disagreement().map_or("-".to_string(), |d| format!("{:.3}", d)),
+            self.max_disagreement.map_or("-".to_string(), |d| format!("{:.3}", d)),
+            or_dash(self.extra_expansions),
+            self.elapsed
+        )
+    }
+}
+
+thread_local! {
+    // collect の中で探索から届いた数。None なら誰も聞いていない
+    static CURRENT: RefCell<Option<SearchStats>> = const { RefCell::new(None) };
+}
+
+fn record(update: impl FnOnce(&mut SearchStats)) {
+    CURRENT.with(|current| {
+        if let Some(stats) = current.borrow_mut().as_mut() {
+            update(stats);
+        }
+    });
+}
+
+pub(crate) fn record_expansion() {
+    record(|stats| *stats.nodes_expanded.get_or_insert(0) += 1);
+}
+
+pub(crate) fn record_clones(states: usize) {
+    record(|stats| *stats.states_cloned.get_or_insert(0) += states);
+}
+
+pub(crate) fn record_depth(depth: usize) {
+    record(|stats| stats.max_depth = stats.max_depth.max(Some(depth)));
+}
+
+pub(crate) fn record_probe(hit: bool) {
+    record(|stats| {
+        *stats.table_probes.get_or_insert(0) += 1;
+        *stats.table_hits.get_or_insert(0) += hit as usize;
+    });
+}
+
+// 照合しない表では数えようがないので、照合したときだけ知らせる
+pub(crate) fn record_verification(collided: bool) {
+    record(|stats| *stats.hash_collisions.get_or_insert(0) += collided as usize);
+}
+
+pub(crate) fn record_evaluation(disagreement: f64) {
+    record(|stats| {
+        *stats.evaluations.get_or_insert(0) += 1;
+        *stats.total_disagreement.get_or_insert(0.0) += disagreement;
+        let max = stats.max_disagreement.get_or_insert(disagreement);
+        *max = max.max(disagreement);
+    });
+}
+
+pub(crate) fn record_extra_expansions(states: usize) {
+    record(|stats| *stats.extra_expansions.get_or_insert(0) += states);
+}
+
+// f の間に同じスレッドの探索が知らせた数を集める。入れ子にすると外側にも足される
+// 別のスレッドで動く探索 (並列のポートフォリオなど) の分は数えない
+pub fn collect<R>(f: impl FnOnce() -> R) -> (R, SearchStats) {
+    let outer = CURRENT.with(|current| current.borrow_mut().replace(SearchStats::default()));
+    let start = Instant::now();
+    let result = f();
+    let mut stats = CURRENT.with(|current| current.borrow_mut().take()).unwrap();
+    stats.elapsed = start.elapsed();
+    CURRENT.with(|current| {
+        // 外側の elapsed は外側の collect が終わるときに測り直す
+        *current.borrow_mut() = outer.map(|mut outer| {
+            outer.add(&stats);
+            outer
+        })
+    });
+    (result, stats)
+}
+
+// agent の手と、その手を選ぶまでの探索の統計
+pub fn select_action_with_stats(agent: &mut dyn Agent, state: &MazeState) -> (usize, SearchStats) {
+    collect(|| agent.select_action(state))
+}
+
+// 手を選ぶたびに統計を足していくエージェント。対局のループを書き換えずに使える
+pub struct StatsAgent {
+    pub inner: Box<dyn Agent>,
+    pub total: SearchStats,
+    pub decisions: usize,
+}
+
+impl StatsAgent {
+    pub fn new(inner: Box<dyn Agent>) -> Self {
+        Self {
+            inner,
+            total: SearchStats::default(),
+            decisions: 0,
+        }
+    }
+}
+
+impl Agent for StatsAgent {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        let (action, stats) = select_action_with_stats(self.inner.as_mut(), state);
+        self.total.add(&stats);
+        self.decisions += 1;
+        action
+    }
+}
@@ -0,0 +1,81 @@
+use crate::agent::parse_agent_with_budget;
+use crate::budget::Budget;
+use crate::ids::Seed;
+use crate::{play_seed, ScoreSummary, ScoreType};
+use std::ops::Range;
+
+// 名前で参照できる固定のベンチマーク
+// 結果を共有するときは数値の組ではなくこの名前を書けば、誰が測っても同じ条件になる
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Suite {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub seeds: Range<u64>,
+    // 予算を指定しなかったエージェントに与える展開ノード数
+    pub node_budget: usize,
+}
+
+// parse_agent のエージェントは 3x4 の迷路しか指せないので、壁付き迷路や Connect Four のスイートはない
+pub fn suites() -> Vec<Suite> {
+    vec![
+        Suite {
+            name: "maze-small-100",
+            description: "3x4 maze, 4 turns, seeds 0..100",
+            seeds: 0..100,
+            node_budget: 64,
+        },
+        Suite {
+            name: "maze-small-1000",
+            description: "3x4 maze, 4 turns, seeds 0..1000",
+            seeds: 0..1000,
+            node_budget: 64,
+        },
+        // チューニングに使っていないシード
+        Suite {
+            name: "maze-holdout-100",
+            description: "3x4 maze, 4 turns, seeds 1000000..1000100",
+            seeds: 1_000_000..1_000_100,
+            node_budget: 64,
+        },
+    ]
+}
+
+pub fn find_suite(name: &str) -> Option<Suite> {
+    suites().into_iter().find(|suite| suite.name == name)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SuiteResult {
+    pub suite: &'static str,
+    pub agent: String,
+    pub games: usize,
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+pub fn run_suite(suite: &Suite, spec: &str) -> Result<SuiteResult, String> {
+    let mut agent = parse_agent_with_budget(spec, Budget::nodes(suite.node_budget))?;
+    let scores: Vec<ScoreType> = suite
+        .seeds
+        .clone()
+        .map(|seed| play_seed(agent.as_mut(), Seed(seed)))
+        .collect();
+    let summary = ScoreSummary::new(&scores);
+    Ok(SuiteResult {
+        suite: suite.name,
+        agent: agent.name(),
+        games: summary.games,
+        mean: summary.mean,
+        stddev: summary.stddev,
+    })
+}
+
+impl SuiteResult {
+    // 結果の行には必ずスイート名を含める
+    pub fn report_line(&self) -> String {
+        format!(
+            "suite={} agent=\"{}\" games={} mean={:.3} stddev={:.3}",
+            self.suite, self.agent, self.games, self.mean, self.stddev
+        )
+    }
+}
@@ -0,0 +1,109 @@
+use crate::policy_iteration::Sample;
+use crate::{Coord, MazeState, DX, DY, H, W};
+
+// 盤面の対称変換 (二面体群の元)。正方形でない盤面では前の4つだけが使える
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    Identity,
+    FlipHorizontal,
+    FlipVertical,
+    Rotate180,
+    Transpose,
+    AntiTranspose,
+    Rotate90,
+    Rotate270,
+}
+
+impl Symmetry {
+    fn map_cell(self, y: usize, x: usize) -> (usize, usize) {
+        match self {
+            Symmetry::Identity => (y, x),
+            Symmetry::FlipHorizontal => (y, W - 1 - x),
+            Symmetry::FlipVertical => (H - 1 - y, x),
+            Symmetry::Rotate180 => (H - 1 - y, W - 1 - x),
+            Symmetry::Transpose => (x, y),
+            Symmetry::AntiTranspose => (W - 1 - x, H - 1 - y),
+            Symmetry::Rotate90 => (x, H - 1 - y),
+            Symmetry::Rotate270 => (W - 1 - x, y),
+        }
+    }
+
+    fn map_vector(self, dy: i64, dx: i64) -> (i64, i64) {
+        match self {
+            Symmetry::Identity => (dy, dx),
+            Symmetry::FlipHorizontal => (dy, -dx),
+            Symmetry::FlipVertical => (-dy, dx),
+            Symmetry::Rotate180 => (-dy, -dx),
+            Symmetry::Transpose => (dx, dy),
+            Symmetry::AntiTranspose => (-dx, -dy),
+            Symmetry::Rotate90 => (dx, -dy),
+            Symmetry::Rotate270 => (-dx, dy),
+        }
+    }
+
+    pub fn map_action(self, action: usize) -> usize {
+        let (dy, dx) = self.map_vector(DY[action], DX[action]);
+        (0..4).find(|&a| DY[a] == dy && DX[a] == dx).unwrap()
+    }
+}
+
+impl MazeState {
+    // この盤面の形で成り立つ対称変換
+    pub fn symmetry_group() -> &'static [Symmetry] {
+        const ALL: [Symmetry; 8] = [
+            Symmetry::Identity,
+            Symmetry::FlipHorizontal,
+            Symmetry::FlipVertical,
+            Symmetry::Rotate180,
+            Symmetry::Transpose,
+            Symmetry::AntiTranspose,
+            Symmetry::Rotate90,
+            Symmetry::Rotate270,
+        ];
+        if H == W {
+            &ALL
+        } else {
+            &ALL[..4]
+        }
+    }
+
+    pub fn transformed(&self, symmetry: Symmetry) -> MazeState {
+        let mut state = self.clone();
+        for (y, row) in self.points.iter().enumerate() {
+            for (x, &point) in row.iter().enumerate() {
+                let (ty, tx) = symmetry.map_cell(y, x);
+                state.points[ty][tx] = point;
+            }
+        }
+        let (y, x) = symmetry.map_cell(self.character.y as usize, self.character.x as usize);
+        state.character = Coord {
+            y: y as i64,
+            x: x as i64,
+        };
+        state.first_action = self.first_action.map(|a| symmetry.map_action(a));
+        state.last_action = self.last_action.map(|a| symmetry.map_action(a));
+        state
+    }
+}
+
+// 各サンプルを対称変換で増やす。行動も同じ変換で読み替える
+// 変換後に同じ盤面になる (対称な盤面の) ものは重複させない
+pub fn augment(samples: &[Sample]) -> Vec<Sample> {
+    let mut augmented = Vec::new();
+    for sample in samples {
+        let start = augmented.len();
+        for &symmetry in MazeState::symmetry_group() {
+            let candidate = Sample {
+                state: sample.state.transformed(symmetry),
+                action: symmetry.map_action(sample.action),
+            };
+            let duplicate = augmented[start..].iter().any(|s: &Sample| {
+                s.state.pack() == candidate.state.pack() && s.action == candidate.action
+            });
+            if !duplicate {
+                augmented.push(candidate);
+            }
+        }
+    }
+    augmented
+}
@@ -0,0 +1,268 @@
+use crate::agent::Agent;
+use crate::ids::Seed;
+use crate::pattern::PatternTable;
+use crate::provenance::Provenance;
+use crate::{test_ai_score, GameState, MazeState, END_TURN};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+// これから得られる点の見込みを学習する価値関数
+pub trait ValueFunction: Send {
+    fn name(&self) -> String;
+    fn value(&self, state: &MazeState) -> f64;
+    // value(state) を target に近づける
+    fn update(&mut self, state: &MazeState, target: f64, learning_rate: f64);
+    fn save(&self, path: &str) -> io::Result<()>;
+    // 学習中の比較相手として今の重みを固定した複製
+    fn snapshot(&self) -> Box<dyn ValueFunction>;
+}
+
+// 得る点と次の局面の価値の和が最大の手
+pub fn value_greedy_action(value: &dyn ValueFunction, state: &MazeState) -> usize {
+    let mut best = (f64::NEG_INFINITY, 0);
+    for action in state.legal_actions() {
+        let mut next_state = state.clone();
+        next_state.advance(action);
+        let total = (next_state.game_score - state.game_score) as f64 + value.value(&next_state);
+        if total > best.0 {
+            best = (total, action);
+        }
+    }
+    best.1
+}
+
+pub struct ValueAgent {
+    pub value: Box<dyn ValueFunction>,
+}
+
+impl Agent for ValueAgent {
+    fn name(&self) -> String {
+        format!("value_greedy({})", self.value.name())
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        value_greedy_action(self.value.as_ref(), state)
+    }
+}
+
+const LINEAR_FEATURES: usize = 5;
+
+// 手作り特徴量の重み付き和
+// [距離1の最大の点, 距離2の最大の点, 残りターン内に届く上位の点の和, 残りターン数, 1]
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinearValue {
+    pub weights: [f64; LINEAR_FEATURES],
+}
+
+impl LinearValue {
+    fn features(state: &MazeState) -> [f64; LINEAR_FEATURES] {
+        let remaining_turns = END_TURN - state.turn;
+        let mut nearest = [0; 2];
+        let mut reachable = Vec::new();
+        for (y, row) in state.points.iter().enumerate() {
+            for (x, &point) in row.iter().enumerate() {
+                let distance = ((y as i64 - state.character.y).abs()
+                    + (x as i64 - state.character.x).abs()) as usize;
+                if (1..=2).contains(&distance) {
+                    nearest[distance - 1] = nearest[distance - 1].max(point);
+                }
+                if distance <= remaining_turns {
+                    reachable.push(point);
+                }
+            }
+        }
+        reachable.sort_unstable_by(|a, b| b.cmp(a));
+        let top: i64 = reachable.iter().take(remaining_turns).sum();
+        [
+            nearest[0] as f64,
+            nearest[1] as f64,
+            top as f64,
+            remaining_turns as f64,
+            1.0,
+        ]
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: invalid linear weights", path),
+            )
+        };
+        let text = fs::read_to_string(path)?;
+        let line = text
+            .lines()
+            .find(|line| !line.starts_with('#'))
+            .and_then(|line| line.strip_prefix("linear "))
+            .ok_or_else(invalid)?;
+        let values: Vec<f64> = line
+            .split_whitespace()
+            .map(|w| w.parse().map_err(|_| invalid()))
+            .collect::<io::Result<_>>()?;
+        let weights = values.try_into().map_err(|_| invalid())?;
+        Ok(Self { weights })
+    }
+}
+
+impl ValueFunction for LinearValue {
+    fn name(&self) -> String {
+        let weights: Vec<String> = self.weights.iter().map(|w| format!("{:.2}", w)).collect();
+        format!("linear([{}])", weights.join(", "))
+    }
+
+    fn value(&self, state: &MazeState) -> f64 {
+        if state.is_done() {
+            return 0.0;
+        }
+        Self::features(state)
+            .iter()
+            .zip(&self.weights)
+            .map(|(f, w)| f * w)
+            .sum()
+    }
+
+    fn update(&mut self, state: &MazeState, target: f64, learning_rate: f64) {
+        let features = Self::features(state);
+        let error = target - self.value(state);
+        // 特徴量の大きさで歩幅が変わらないように正規化する
+        let norm: f64 = features.iter().map(|f| f * f).sum::<f64>().max(1.0);
+        for (w, f) in self.weights.iter_mut().zip(features) {
+            *w += learning_rate * error * f / norm;
+        }
+    }
+
+    fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        writeln!(writer, "{}", Provenance::collect(1))?;
+        let weights: Vec<String> = self.weights.iter().map(f64::to_string).collect();
+        writeln!(writer, "linear {}", weights.join(" "))?;
+        writer.flush()
+    }
+
+    fn snapshot(&self) -> Box<dyn ValueFunction> {
+        Box::new(self.clone())
+    }
+}
+
+impl ValueFunction for PatternTable {
+    fn name(&self) -> String {
+        format!("pattern_table({} tuples)", self.tuples.len())
+    }
+
+    fn value(&self, state: &MazeState) -> f64 {
+        PatternTable::value(self, state)
+    }
+
+    fn update(&mut self, state: &MazeState, target: f64, learning_rate: f64) {
+        PatternTable::update(self, state, target, learning_rate)
+    }
+
+    fn save(&self, path: &str) -> io::Result<()> {
+        PatternTable::save(self, path)
+    }
+
+    fn snapshot(&self) -> Box<dyn ValueFunction> {
+        Box::new(self.clone())
+    }
+}
+
+// 保存した価値関数を、ファイルの中身から種類を見分けて読む
+pub fn load_value(path: &str) -> io::Result<Box<dyn ValueFunction>> {
+    let text = fs::read_to_string(path)?;
+    if text.lines().any(|line| line.starts_with("linear ")) {
+        Ok(Box::new(LinearValue::load(path)?))
+    } else {
+        Ok(Box::new(PatternTable::load(path)?))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TdOptions {
+    pub games: usize,
+    pub epsilon: f64,
+    pub lambda: f64,
+    pub learning_rate: f64,
+    // この対局数ごとに保存して評価する
+    pub checkpoint_interval: usize,
+    pub checkpoint_path: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckpointReport {
+    pub games: usize,
+    pub score: f64,
+    // 前のチェックポイントで固定した価値関数の成績
+    pub previous_score: f64,
+}
+
+// ε-greedy で1局指し、終局後に λ-収益に向けて各局面の価値を更新する (オフラインの TD(λ))
+fn train_episode(
+    value: &mut dyn ValueFunction,
+    seed: Seed,
+    options: &TdOptions,
+    rng: &mut ChaCha8Rng,
+) {
+    let mut state = MazeState::from_seed(seed);
+    let mut states = Vec::new();
+    let mut rewards = Vec::new();
+    while !state.is_done() {
+        let action = if rng.gen::<f64>() < options.epsilon {
+            let legal_actions = state.legal_actions();
+            legal_actions[rng.gen_range(0..legal_actions.len())]
+        } else {
+            value_greedy_action(value, &state)
+        };
+        let before = state.game_score;
+        states.push(state.clone());
+        state.advance(action);
+        rewards.push((state.game_score - before) as f64);
+    }
+    // G_t = r_t + (1 - λ) V(s_{t+1}) + λ G_{t+1}、終局では V = G = 0
+    let mut targets = vec![0.0; states.len()];
+    let mut lambda_return = 0.0;
+    for t in (0..states.len()).rev() {
+        let next_value = states.get(t + 1).map_or(0.0, |next| value.value(next));
+        lambda_return =
+            rewards[t] + (1.0 - options.lambda) * next_value + options.lambda * lambda_return;
+        targets[t] = lambda_return;
+    }
+    for (state, target) in states.iter().zip(targets) {
+        value.update(state, target, options.learning_rate);
+    }
+}
+
+// 評価用 (0..100) とは別のシードで学習し、定期的に保存して固定した相手と比べる
+pub fn td_lambda_train(
+    value: &mut dyn ValueFunction,
+    options: &TdOptions,
+    rng: &mut ChaCha8Rng,
+) -> io::Result<Vec<CheckpointReport>> {
+    let mut reports = Vec::new();
+    let mut frozen = ValueAgent {
+        value: value.snapshot(),
+    };
+    let mut game = 0;
+    while game < options.games {
+        let end = (game + options.checkpoint_interval.max(1)).min(options.games);
+        for seed in game..end {
+            train_episode(value, Seed(1_000_000 + seed as u64), options, rng);
+        }
+        game = end;
+
+        if let Some(path) = &options.checkpoint_path {
+            value.save(path)?;
+        }
+        let mut current = ValueAgent {
+            value: value.snapshot(),
+        };
+        reports.push(CheckpointReport {
+            games: game,
+            score: test_ai_score(&mut current, 100),
+            previous_score: test_ai_score(&mut frozen, 100),
+        });
+        frozen = current;
+    }
+    Ok(reports)
+}
@@ -0,0 +1,157 @@
+use crate::local_search::{
+    plan_score, random_plan, LocalSearchOutcome, Neighborhood, Plan, Stopper, StoppingCriteria,
+};
+use crate::{MazeState, ScoreType};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::thread;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TemperingOptions {
+    pub chains: usize,
+    // 温度は min_temperature から max_temperature まで等比で並べる
+    pub min_temperature: f64,
+    pub max_temperature: f64,
+    // 各鎖がこの回数だけ焼きなましを進めるごとに、隣り合う温度の鎖で解の交換を試みる
+    pub exchange_interval: usize,
+    pub parallel: bool,
+}
+
+impl Default for TemperingOptions {
+    fn default() -> Self {
+        Self {
+            chains: 4,
+            min_temperature: 0.1,
+            max_temperature: 5.0,
+            exchange_interval: 10,
+            parallel: true,
+        }
+    }
+}
+
+impl TemperingOptions {
+    pub fn temperatures(&self) -> Vec<f64> {
+        if self.chains == 1 {
+            return vec![self.min_temperature];
+        }
+        let ratio =
+            (self.max_temperature / self.min_temperature).powf(1.0 / (self.chains - 1) as f64);
+        (0..self.chains)
+            .map(|k| self.min_temperature * ratio.powi(k as i32))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TemperingStats {
+    // k 番目は温度 k と k + 1 の鎖の間の交換の試行回数と成立回数
+    pub exchange_attempts: Vec<usize>,
+    pub exchange_accepts: Vec<usize>,
+    pub rounds: usize,
+}
+
+// 一定温度で焼きなましを続ける1本の鎖。乱数は鎖ごとに持つので、並列でも結果はスレッドの順に依存しない
+struct Chain {
+    temperature: f64,
+    plan: Plan,
+    score: ScoreType,
+    best: (ScoreType, Plan),
+    rng: ChaCha8Rng,
+}
+
+impl Chain {
+    fn run(&mut self, state: &MazeState, steps: usize) {
+        for _ in 0..steps {
+            let mut candidate = self.plan.clone();
+            Neighborhood::Mutate.apply(&mut candidate, &mut self.rng);
+            let score = plan_score(state, &candidate);
+            let accept = score >= self.score
+                || self.rng.gen::<f64>() < ((score - self.score) as f64 / self.temperature).exp();
+            if accept {
+                self.plan = candidate;
+                self.score = score;
+                if self.score > self.best.0 {
+                    self.best = (self.score, self.plan.clone());
+                }
+            }
+        }
+    }
+}
+
+// 温度の違う複数の鎖で焼きなましを進め、interval ごとに隣の鎖と解を交換する (レプリカ交換法)
+// criteria の反復は、全鎖が exchange_interval 回進んで交換を試みるまでの1ラウンドを数える
+// ノード数は全鎖で評価した解の数なので、1ラウンドで 鎖の数 * exchange_interval 増える
+pub fn parallel_tempering(
+    state: &MazeState,
+    options: &TemperingOptions,
+    criteria: StoppingCriteria,
+    rng: &mut ChaCha8Rng,
+) -> (LocalSearchOutcome, TemperingStats) {
+    let mut chains: Vec<Chain> = options
+        .temperatures()
+        .into_iter()
+        .map(|temperature| {
+            let plan = random_plan(rng);
+            let score = plan_score(state, &plan);
+            Chain {
+                temperature,
+                best: (score, plan.clone()),
+                plan,
+                score,
+                rng: ChaCha8Rng::seed_from_u64(rng.gen()),
+            }
+        })
+        .collect();
+    let pairs = chains.len().saturating_sub(1);
+    let mut stats = TemperingStats {
+        exchange_attempts: vec![0; pairs],
+        exchange_accepts: vec![0; pairs],
+        rounds: 0,
+    };
+    let mut stopper = Stopper::new(criteria);
+    stopper.add_nodes(chains.len());
+    loop {
+        if options.parallel {
+            thread::scope(|scope| {
+                for chain in chains.iter_mut() {
+                    scope.spawn(move || chain.run(state, options.exchange_interval));
+                }
+            });
+        } else {
+            for chain in chains.iter_mut() {
+                chain.run(state, options.exchange_interval);
+            }
+        }
+        // 偶数番目と奇数番目のペアを交互に試し、同じ鎖が1ラウンドに2度動かないようにする
+        for k in (stats.rounds % 2..pairs).step_by(2) {
+            let (low, high) = (&chains[k], &chains[k + 1]);
+            let exponent =
+                (high.score - low.score) as f64 * (1.0 / low.temperature - 1.0 / high.temperature);
+            stats.exchange_attempts[k] += 1;
+            if exponent >= 0.0 || rng.gen::<f64>() < exponent.exp() {
+                stats.exchange_accepts[k] += 1;
+                let (left, right) = chains.split_at_mut(k + 1);
+                std::mem::swap(&mut left[k].plan, &mut right[0].plan);
+                std::mem::swap(&mut left[k].score, &mut right[0].score);
+            }
+        }
+        stats.rounds += 1;
+        stopper.add_nodes(chains.len() * options.exchange_interval);
+        let best = chains
+            .iter()
+            .map(|chain| &chain.best)
+            .max_by_key(|best| best.0)
+            .unwrap()
+            .clone();
+        if let Some(reason) = stopper.step(best.0) {
+            let outcome = LocalSearchOutcome {
+                plan: best.1,
+                score: best.0,
+                reason,
+                consumed: stopper.consumed(),
+                trace: stopper.trace,
+            };
+            return (outcome, stats);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::agent::Agent;
+use crate::blunder::Blunder;
+use crate::packing::{from_hex, to_hex};
+use crate::provenance::Provenance;
+use crate::solver::exhaustive_search;
+use crate::{GameState, MazeState, ScoreType};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+// 最善手がわかっている局面。EPD にならって1行に "局面 bm 手,手 ; id 名前" と書く
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestPosition {
+    pub state: MazeState,
+    // 最善手が複数あるときはどれを選んでも正解
+    pub best_moves: Vec<usize>,
+    pub id: String,
+}
+
+// 全探索で最善手をすべて求める
+pub fn best_moves(state: &MazeState) -> Vec<usize> {
+    let values: Vec<(usize, ScoreType)> = state
+        .legal_actions()
+        .into_iter()
+        .map(|action| {
+            let mut next_state = state.clone();
+            next_state.advance(action);
+            let gained = next_state.game_score - state.game_score;
+            (action, gained + exhaustive_search(&next_state).0)
+        })
+        .collect();
+    let best = values.iter().map(|&(_, value)| value).max().unwrap();
+    values
+        .into_iter()
+        .filter(|&(_, value)| value == best)
+        .map(|(action, _)| action)
+        .collect()
+}
+
+// 悪手を指した局面を、全探索で確かめた最善手とともに取り出す
+pub fn from_blunders(blunders: &[Blunder]) -> Vec<TestPosition> {
+    blunders
+        .iter()
+        .map(|blunder| TestPosition {
+            state: blunder.state.clone(),
+            best_moves: best_moves(&blunder.state),
+            id: format!("seed {} turn {}", blunder.seed, blunder.turn),
+        })
+        .collect()
+}
+
+pub fn save(path: &str, positions: &[TestPosition]) -> io::Result<()> {
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    writeln!(writer, "{}", Provenance::collect(1))?;
+    for position in positions {
+        let best_moves: Vec<String> = position.best_moves.iter().map(usize::to_string).collect();
+        writeln!(
+            writer,
+            "{} bm {} ; id \"{}\"",
+            to_hex(&position.state.pack()),
+            best_moves.join(","),
+            position.id
+        )?;
+    }
+    writer.flush()
+}
+
+pub fn load(path: &str) -> io::Result<Vec<TestPosition>> {
+    let invalid = |line: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid test position `{}`", line),
+        )
+    };
+    let mut positions = Vec::new();
+    for line in fs::read_to_string(path)?.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let (body, id) = line.split_once(" ; id ").ok_or_else(|| invalid(line))?;
+        let mut words = body.split_whitespace();
+        let state = words
+            .next()
+            .and_then(|hex| {
+                from_hex(hex)
+                    .and_then(|bytes| MazeState::unpack(&bytes))
+                    .ok()
+            })
+            .ok_or_else(|| invalid(line))?;
+        if words.next() != Some("bm") {
+            return Err(invalid(line));
+        }
+        let best_moves = words
+            .next()
+            .ok_or_else(|| invalid(line))?
+            .split(',')
+            .map(|action| action.parse().map_err(|_| invalid(line)))
+            .collect::<io::Result<Vec<usize>>>()?;
+        positions.push(TestPosition {
+            state,
+            best_moves,
+            id: id.trim_matches('"').to_string(),
+        });
+    }
+    Ok(positions)
+}
+
+// 各局面で1手だけ選ばせ、最善手を選べた局面の数と外した局面を返す
+pub fn solve_suite<'a>(
+    agent: &mut dyn Agent,
+    positions: &'a [TestPosition],
+) -> (usize, Vec<&'a TestPosition>) {
+    let mut missed = Vec::new();
+    for position in positions {
+        let action = agent.select_action(&position.state);
+        if !position.best_moves.contains(&action) {
+            missed.push(position);
+        }
+    }
+    (positions.len() - missed.len(), missed)
+}
@@ -0,0 +1,237 @@
+use crate::agent::{parse_agent, Capabilities};
+use crate::alternate::{
+    alpha_beta_action, iterative_deepening_action, mcts_action, min_regret_action, minimax_action,
+    thunder_search_action, TwoPlayerGame,
+};
+use crate::ids::{PlayerId, Seed};
+use crate::simultaneous::GameResult;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+
+// 2人ゲームの AI の種類と、パラメータの既定値
+pub const TWO_PLAYER_AI_KINDS: &[(&str, &[(&str, usize)])] = &[
+    ("random", &[("seed", 0)]),
+    ("minregret", &[]),
+    ("minimax", &[("depth", 3)]),
+    ("alphabeta", &[("depth", 4)]),
+    ("deepening", &[("time_ms", 10)]),
+    ("thunder", &[("playouts", 1000)]),
+    ("mcts", &[("playouts", 1000), ("seed", 0)]),
+];
+
+// 2人ゲームの AI の既定値。時間で打ち切る deepening だけ deterministic を外す
+const TWO_PLAYER: Capabilities = Capabilities {
+    two_player: true,
+    ..Capabilities::SEARCH
+};
+
+// 局面から手を選ぶ2人ゲームの AI
+pub struct TwoPlayerAi<G> {
+    pub name: String,
+    pub capabilities: Capabilities,
+    choose: Box<dyn FnMut(&G) -> usize>,
+}
+
+impl<G> TwoPlayerAi<G> {
+    pub fn new(
+        name: String,
+        capabilities: Capabilities,
+        choose: Box<dyn FnMut(&G) -> usize>,
+    ) -> Self {
+        Self {
+            name,
+            capabilities,
+            choose,
+        }
+    }
+
+    pub fn select_action(&mut self, state: &G) -> usize {
+        (self.choose)(state)
+    }
+}
+
+// 2人ゲームの対戦に出せるか確かめる
+pub fn check_two_player(name: &str, capabilities: Capabilities) -> Result<(), String> {
+    if capabilities.two_player {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} cannot play a two-player game ({})",
+            name, capabilities
+        ))
+    }
+}
+
+// "alphabeta depth=4", "mcts playouts=500 seed=1" のような文字列から作る
+// 1人用のエージェント (parse_agent の種類) を指定したら、その能力を添えてエラーにする
+pub fn parse_two_player_ai<G: TwoPlayerGame + 'static>(
+    spec: &str,
+) -> Result<TwoPlayerAi<G>, String> {
+    let mut words = spec.split_whitespace();
+    let kind = words.next().ok_or("empty AI spec")?;
+    let Some(&(_, defaults)) = TWO_PLAYER_AI_KINDS.iter().find(|&&(name, _)| name == kind) else {
+        let agent = parse_agent(spec).map_err(|_| format!("unknown two-player AI `{}`", kind))?;
+        check_two_player(&agent.name(), agent.capabilities())?;
+        return Err(format!("unknown two-player AI `{}`", kind));
+    };
+    let mut params = defaults.to_vec();
+    for word in words {
+        let (key, value) = word
+            .split_once('=')
+            .ok_or(format!("expected key=value, got `{}`", word))?;
+        let param = params
+            .iter_mut()
+            .find(|(name, _)| *name == key)
+            .ok_or(format!("`{}` does not take `{}`", kind, key))?;
+        param.1 = value
+            .parse()
+            .map_err(|_| format!("invalid number `{}` for `{}`", value, key))?;
+    }
+    let param = |key: &str| params.iter().find(|(k, _)| *k == key).unwrap().1;
+    let name = params.iter().fold(kind.to_string(), |name, (key, value)| {
+        format!("{} {}={}", name, key, value)
+    });
+    let choose: Box<dyn FnMut(&G) -> usize> = match kind {
+        "random" => {
+            let mut rng = ChaCha8Rng::seed_from_u64(param("seed") as u64);
+            Box::new(move |state: &G| state.random_action(&mut rng))
+        }
+        "minregret" => Box::new(|state: &G| min_regret_action(state)),
+        "minimax" => {
+            let depth = param("depth");
+            Box::new(move |state: &G| minimax_action(state, depth))
+        }
+        "alphabeta" => {
+            let depth = param("depth");
+            Box::new(move |state: &G| alpha_beta_action(state, depth))
+        }
+        "deepening" => {
+            let time_ms = param("time_ms") as u64;
+            Box::new(move |state: &G| iterative_deepening_action(state, time_ms))
+        }
+        "thunder" => {
+            let playouts = param("playouts");
+            Box::new(move |state: &G| thunder_search_action(state, playouts))
+        }
+        "mcts" => {
+            let playouts = param("playouts");
+            let mut rng = ChaCha8Rng::seed_from_u64(param("seed") as u64);
+            Box::new(move |state: &G| mcts_action(state, playouts, &mut rng))
+        }
+        _ => unreachable!("{} is listed in TWO_PLAYER_AI_KINDS but not built", kind),
+    };
+    let capabilities = Capabilities {
+        deterministic: kind != "deepening",
+        ..TWO_PLAYER
+    };
+    Ok(TwoPlayerAi::new(name, capabilities, choose))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Record {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl Record {
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+}
+
+// a から見た対戦成績。by_seat[0] は a が先手の対局、by_seat[1] は後手の対局
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TournamentReport {
+    pub a: String,
+    pub b: String,
+    pub by_seat: [Record; 2],
+}
+
+impl TournamentReport {
+    pub fn total(&self) -> Record {
+        let [first, second] = self.by_seat;
+        Record {
+            wins: first.wins + second.wins,
+            draws: first.draws + second.draws,
+            losses: first.losses + second.losses,
+        }
+    }
+
+    // (勝ち, 引き分け, 負け) の割合
+    pub fn rates(&self) -> (f64, f64, f64) {
+        let total = self.total();
+        let games = total.games().max(1) as f64;
+        (
+            total.wins as f64 / games,
+            total.draws as f64 / games,
+            total.losses as f64 / games,
+        )
+    }
+}
+
+impl Display for TournamentReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let total = self.total();
+        let (win, draw, loss) = self.rates();
+        write!(
+            f,
+            "{} vs {}: {} wins, {} draws, {} losses over {} games (win {:.3}, draw {:.3}, loss {:.3})",
+            self.a,
+            self.b,
+            total.wins,
+            total.draws,
+            total.losses,
+            total.games(),
+            win,
+            draw,
+            loss
+        )?;
+        for (seat, record) in ["first", "second"].iter().zip(self.by_seat.iter()) {
+            write!(
+                f,
+                "\n  as {}: {} wins, {} draws, {} losses",
+                seat, record.wins, record.draws, record.losses
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// 最初の seed_number 個の盤面で、先手と後手を入れ替えて2局ずつ a と b を対戦させる
+// 2人ゲームに対応していない AI が混ざっていればエラー
+pub fn play_tournament<G: TwoPlayerGame>(
+    new_game: &dyn Fn(Seed) -> G,
+    a: &mut TwoPlayerAi<G>,
+    b: &mut TwoPlayerAi<G>,
+    seed_number: usize,
+) -> Result<TournamentReport, String> {
+    check_two_player(&a.name, a.capabilities)?;
+    check_two_player(&b.name, b.capabilities)?;
+    let mut report = TournamentReport {
+        a: a.name.clone(),
+        b: b.name.clone(),
+        by_seat: [Record::default(); 2],
+    };
+    for seed in Seed::first(seed_number) {
+        for a_seat in PlayerId::BOTH {
+            let mut state = new_game(seed);
+            while !state.is_done() {
+                let action = if state.current_player() == a_seat {
+                    a.select_action(&state)
+                } else {
+                    b.select_action(&state)
+                };
+                state.advance(action);
+            }
+            let record = &mut report.by_seat[a_seat.index()];
+            match state.result() {
+                GameResult::Win(player) if player == a_seat => record.wins += 1,
+                GameResult::Win(_) => record.losses += 1,
+                GameResult::Draw => record.draws += 1,
+            }
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,376 @@
+use crate::agent::parse_agent;
+use crate::ids::Seed;
+use crate::provenance::Provenance;
+use crate::{play_seed, ScoreType};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+// 調整の記録。1局ごとに "シード<TAB>得点<TAB>エージェント" を追記する
+// 同じ候補とシードの組がすでにあれば、指し直さずに記録を使う
+pub struct Journal {
+    pub path: String,
+    entries: HashMap<(String, Seed), ScoreType>,
+}
+
+impl Journal {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let mut entries = HashMap::new();
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                for line in text.lines().filter(|line| !line.starts_with('#')) {
+                    let fields: Vec<&str> = line.splitn(3, '\t').collect();
+                    let entry = match fields[..] {
+                        [seed, score, spec] => {
+                            seed.parse().ok().zip(score.parse().ok()).map(|e| (spec, e))
+                        }
+                        _ => None,
+                    };
+                    let (spec, (seed, score)) = entry.ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("invalid journal line `{}`", line),
+                        )
+                    })?;
+                    entries.insert((spec.to_string(), seed), score);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                fs::write(path, format!("{}\n", Provenance::collect(1)))?;
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(Self {
+            path: path.to_string(),
+            entries,
+        })
+    }
+
+    // 候補ごとに集計する。得点はシード順に並べる
+    pub fn candidates(&self) -> Vec<CandidateStats> {
+        let mut games: Vec<(&String, Seed, ScoreType)> = self
+            .entries
+            .iter()
+            .map(|((spec, seed), &score)| (spec, *seed, score))
+            .collect();
+        games.sort();
+        let mut candidates: Vec<CandidateStats> = Vec::new();
+        for (spec, _, score) in games {
+            match candidates.last_mut() {
+                Some(candidate) if candidate.spec == *spec => candidate.scores.push(score),
+                _ => candidates.push(CandidateStats {
+                    spec: spec.clone(),
+                    scores: vec![score],
+                }),
+            }
+        }
+        candidates
+    }
+
+    fn get(&self, spec: &str, seed: Seed) -> Option<ScoreType> {
+        self.entries.get(&(spec.to_string(), seed)).copied()
+    }
+
+    fn append(&mut self, spec: &str, seed: Seed, score: ScoreType) -> io::Result<()> {
+        let file = fs::OpenOptions::new().append(true).open(&self.path)?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{}\t{}\t{}", seed, score, spec)?;
+        writer.flush()?;
+        self.entries.insert((spec.to_string(), seed), score);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CandidateStats {
+    pub spec: String,
+    pub scores: Vec<ScoreType>,
+}
+
+impl CandidateStats {
+    pub fn mean(&self) -> f64 {
+        self.scores.iter().sum::<ScoreType>() as f64 / self.scores.len().max(1) as f64
+    }
+
+    // 平均の 95% 信頼区間の半幅
+    pub fn half_width(&self) -> f64 {
+        let n = self.scores.len() as f64;
+        if n < 2.0 {
+            return f64::INFINITY;
+        }
+        let mean = self.mean();
+        let variance = self
+            .scores
+            .iter()
+            .map(|&s| (s as f64 - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.0);
+        1.96 * (variance / n).sqrt()
+    }
+}
+
+// 候補ごとの対局数をバンディットで割り振る
+// 最良の候補の下限を上限が下回った候補 (明らかに負けている候補) には対局を回さず、
+// 残った候補のうち信頼区間が最も広いものを指す。シードはどの候補も 0 から順に使う
+pub fn bandit_tune(
+    specs: &[String],
+    budget: usize,
+    initial_games: usize,
+    mut journal: Option<&mut Journal>,
+) -> Result<Vec<CandidateStats>, String> {
+    let mut agents = specs
+        .iter()
+        .map(|spec| parse_agent(spec))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut stats: Vec<CandidateStats> = specs
+        .iter()
+        .map(|spec| CandidateStats {
+            spec: spec.clone(),
+            scores: Vec::new(),
+        })
+        .collect();
+
+    let mut play = |i: usize, stats: &mut Vec<CandidateStats>| -> Result<(), String> {
+        let seed = Seed(stats[i].scores.len() as u64);
+        let recorded = journal
+            .as_ref()
+            .and_then(|journal| journal.get(&specs[i], seed));
+        let score = match recorded {
+            Some(score) => score,
+            None => {
+                let score = play_seed(agents[i].as_mut(), seed);
+                if let Some(journal) = journal.as_mut() {
+                    journal
+                        .append(&specs[i], seed, score)
+                        .map_err(|e| e.to_string())?;
+                }
+                score
+            }
+        };
+        stats[i].scores.push(score);
+        Ok(())
+    };
+
+    let mut played = 0;
+    for i in 0..specs.len() {
+        for _ in 0..initial_games {
+            play(i, &mut stats)?;
+            played += 1;
+        }
+    }
+    while played < budget {
+        let best_lower = stats
+            .iter()
+            .map(|s| s.mean() - s.half_width())
+            .fold(f64::NEG_INFINITY, f64::max);
+        let next = (0..stats.len())
+            .filter(|&i| stats[i].mean() + stats[i].half_width() >= best_lower)
+            .max_by(|&a, &b| stats[a].half_width().total_cmp(&stats[b].half_width()));
+        match next {
+            Some(i) => play(i, &mut stats)?,
+            None => break,
+        }
+        played += 1;
+    }
+    Ok(stats)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParameterImportance {
+    pub parameter: String,
+    pub levels: usize,
+    // 得点の分散のうち、このパラメータの値ごとの平均の違いで説明できる割合
+    pub explained: f64,
+}
+
+// "beam width=2 depth=4" を [("agent", "beam"), ("width", "2"), ("depth", "4")] にする
+fn spec_parameters(spec: &str) -> Vec<(String, String)> {
+    let mut words = spec.split_whitespace();
+    let kind = words.next().unwrap_or_default();
+    let mut parameters = vec![("agent".to_string(), kind.to_string())];
+    for word in words {
+        if let Some((key, value)) = word.split_once('=') {
+            parameters.push((key.to_string(), value.to_string()));
+        }
+    }
+    parameters
+}
+
+// パラメータごとに、値で局を分けたときの群間分散 / 全分散を求める (主効果だけの簡易 fANOVA)
+// 指定していない候補は "default" という値を持つものとして扱う。説明率の大きい順に返す
+pub fn parameter_importance(candidates: &[CandidateStats]) -> Vec<ParameterImportance> {
+    let scores = candidates
+        .iter()
+        .flat_map(|c| c.scores.iter().map(|&s| s as f64));
+    let n = scores.clone().count() as f64;
+    let mean = scores.clone().sum::<f64>() / n;
+    let total = scores.map(|s| (s - mean).powi(2)).sum::<f64>();
+
+    let parameters: Vec<Vec<(String, String)>> = candidates
+        .iter()
+        .map(|c| spec_parameters(&c.spec))
+        .collect();
+    let mut names: Vec<&String> = parameters.iter().flatten().map(|(key, _)| key).collect();
+    names.sort();
+    names.dedup();
+
+    let mut importances: Vec<ParameterImportance> = names
+        .into_iter()
+        .map(|name| {
+            // 値 -> (得点の和, 局数)
+            let mut groups: HashMap<&str, (f64, f64)> = HashMap::new();
+            for (candidate, parameters) in candidates.iter().zip(&parameters) {
+                let value = parameters
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map_or("default", |(_, value)| value.as_str());
+                let group = groups.entry(value).or_default();
+                group.0 += candidate.scores.iter().sum::<ScoreType>() as f64;
+                group.1 += candidate.scores.len() as f64;
+            }
+            let between = groups
+                .values()
+                .map(|&(sum, count)| count * (sum / count - mean).powi(2))
+                .sum::<f64>();
+            ParameterImportance {
+                parameter: name.clone(),
+                levels: groups.len(),
+                explained: if total > 0.0 { between / total } else { 0.0 },
+            }
+        })
+        .collect();
+    importances.sort_by(|a, b| b.explained.total_cmp(&a.explained));
+    importances
+}
+
+#[cfg(feature = "plot")]
+fn parameter_value(parameters: &[(String, String)], name: &str) -> String {
+    parameters
+        .iter()
+        .find(|(key, _)| key == name)
+        .map_or("default".to_string(), |(_, value)| value.clone())
+}
+
+// 数値は数値の順に、それ以外 ("default" など) は先頭に並べる
+#[cfg(feature = "plot")]
+fn sort_levels(levels: &mut Vec<String>) {
+    levels.sort_by_key(|level| (level.parse::<usize>().ok(), level.clone()));
+    levels.dedup();
+}
+
+// 2つのパラメータの値の組ごとに平均得点を色で塗った SVG を作る
+// 同じ組に複数の候補があれば、局をまとめて平均する
+#[cfg(feature = "plot")]
+pub fn score_heatmap_svg(
+    candidates: &[CandidateStats],
+    x_parameter: &str,
+    y_parameter: &str,
+) -> Result<String, String> {
+    use std::fmt::Write as _;
+
+    const CELL_WIDTH: usize = 80;
+    const CELL_HEIGHT: usize = 40;
+    const MARGIN: usize = 80;
+
+    // (x の値, y の値) -> (得点の和, 局数)
+    let mut cells: HashMap<(String, String), (f64, usize)> = HashMap::new();
+    for candidate in candidates {
+        let parameters = spec_parameters(&candidate.spec);
+        let key = (
+            parameter_value(&parameters, x_parameter),
+            parameter_value(&parameters, y_parameter),
+        );
+        let cell = cells.entry(key).or_default();
+        cell.0 += candidate.scores.iter().sum::<ScoreType>() as f64;
+        cell.1 += candidate.scores.len();
+    }
+    if cells.is_empty() {
+        return Err("no results to plot".to_string());
+    }
+    let mut xs: Vec<String> = cells.keys().map(|(x, _)| x.clone()).collect();
+    let mut ys: Vec<String> = cells.keys().map(|(_, y)| y.clone()).collect();
+    sort_levels(&mut xs);
+    sort_levels(&mut ys);
+    let means: HashMap<&(String, String), f64> = cells
+        .iter()
+        .map(|(key, &(sum, count))| (key, sum / count as f64))
+        .collect();
+    let low = means.values().copied().fold(f64::INFINITY, f64::min);
+    let high = means.values().copied().fold(f64::NEG_INFINITY, f64::max);
+
+    let width = MARGIN + CELL_WIDTH * xs.len();
+    let height = MARGIN + CELL_HEIGHT * ys.len();
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="12">"#,
+        width, height
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<text x="{}" y="20" text-anchor="middle">{} (x) / {} (y), mean score {:.2} - {:.2}</text>"#,
+        width / 2, x_parameter, y_parameter, low, high
+    )
+    .unwrap();
+    for (i, x) in xs.iter().enumerate() {
+        let cx = MARGIN + CELL_WIDTH * i + CELL_WIDTH / 2;
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#,
+            cx,
+            MARGIN - 8,
+            x
+        )
+        .unwrap();
+    }
+    for (j, y) in ys.iter().enumerate() {
+        let cy = MARGIN + CELL_HEIGHT * j + CELL_HEIGHT / 2 + 4;
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#,
+            MARGIN - 8,
+            cy,
+            y
+        )
+        .unwrap();
+        for (i, x) in xs.iter().enumerate() {
+            let left = MARGIN + CELL_WIDTH * i;
+            let top = MARGIN + CELL_HEIGHT * j;
+            let Some(&mean) = means.get(&(x.clone(), y.clone())) else {
+                // 試していない組は灰色にする
+                writeln!(
+                    svg,
+                    r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#dddddd"/>"##,
+                    left, top, CELL_WIDTH, CELL_HEIGHT
+                )
+                .unwrap();
+                continue;
+            };
+            // 低いほど青く、高いほど赤くする
+            let t = if high > low {
+                (mean - low) / (high - low)
+            } else {
+                0.5
+            };
+            let red = (255.0 * t) as u8;
+            let blue = (255.0 * (1.0 - t)) as u8;
+            writeln!(
+                svg,
+                r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#{:02x}40{:02x}" stroke="white"/>"##,
+                left, top, CELL_WIDTH, CELL_HEIGHT, red, blue
+            )
+            .unwrap();
+            writeln!(
+                svg,
+                r#"<text x="{}" y="{}" text-anchor="middle" fill="white">{:.2}</text>"#,
+                left + CELL_WIDTH / 2,
+                top + CELL_HEIGHT / 2 + 4,
+                mean
+            )
+            .unwrap();
+        }
+    }
+    writeln!(svg, "</svg>").unwrap();
+    Ok(svg)
+}
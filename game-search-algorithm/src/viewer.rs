@@ -0,0 +1,128 @@
+use crate::MazeState;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+// 画面を消してカーソルを左上に戻す
+const CLEAR: &str = "\x1b[2J\x1b[H";
+// 反転表示とその解除
+const HIGHLIGHT: &str = "\x1b[7m";
+const RESET: &str = "\x1b[0m";
+
+// 行動の番号の向き (DX, DY の順)
+const ACTION_NAMES: [&str; 4] = ["right", "left", "down", "up"];
+
+// 1手分の画面。キャラクターのマスを反転表示し、次に指す手を添える
+// ansi が false なら色を付けず、キャラクターは @ で描く
+pub fn render_frame(state: &MazeState, next_action: Option<usize>, ansi: bool) -> String {
+    let mut text = format!(
+        "turn {}/{}  score {}\n",
+        state.turn, state.params.end_turn, state.game_score
+    );
+    for (y, row) in state.points.iter().enumerate() {
+        for (x, &point) in row.iter().enumerate() {
+            let cell = if point > 0 {
+                point.to_string()
+            } else {
+                ".".to_string()
+            };
+            if state.character.y == y as i64 && state.character.x == x as i64 {
+                if ansi {
+                    text.push_str(&format!("{}{}{}", HIGHLIGHT, cell, RESET));
+                } else {
+                    text.push('@');
+                }
+            } else {
+                text.push_str(&cell);
+            }
+        }
+        text.push('\n');
+    }
+    match next_action {
+        Some(action) => text.push_str(&format!(
+            "next: {} ({})\n",
+            action,
+            ACTION_NAMES.get(action).unwrap_or(&"?")
+        )),
+        None => text.push_str("game over\n"),
+    }
+    text
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Next,
+    Previous,
+    First,
+    Last,
+    Goto(usize),
+    Quit,
+}
+
+// 1行に1つ。空行は n と同じ
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        match (words.next(), words.next()) {
+            (None, _) | (Some("n"), None) => Ok(Command::Next),
+            (Some("p"), None) => Ok(Command::Previous),
+            (Some("f"), None) => Ok(Command::First),
+            (Some("l"), None) => Ok(Command::Last),
+            (Some("g"), Some(turn)) => turn
+                .parse()
+                .map(Command::Goto)
+                .map_err(|_| format!("invalid turn `{}`", turn)),
+            (Some("q"), None) => Ok(Command::Quit),
+            _ => Err(format!("unknown command `{}`", s.trim())),
+        }
+    }
+}
+
+const HELP: &str =
+    "[enter/n] next  [p] previous  [f] first  [l] last  [g N] go to turn N  [q] quit";
+
+// frames[t] は t 手目を指す前の局面 (最後は終局の局面)、actions[t] はそこで指した手
+// input から1行ずつコマンドを読み、output に描き直す。入力が尽きたら終わる
+pub fn step_through(
+    frames: &[MazeState],
+    actions: &[usize],
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    ansi: bool,
+) -> io::Result<()> {
+    let last = frames.len().saturating_sub(1);
+    let mut turn = 0;
+    let mut message = String::new();
+    loop {
+        if ansi {
+            write!(output, "{}", CLEAR)?;
+        }
+        write!(
+            output,
+            "{}",
+            render_frame(&frames[turn], actions.get(turn).copied(), ansi)
+        )?;
+        writeln!(output, "{}", HELP)?;
+        if !message.is_empty() {
+            writeln!(output, "{}", message)?;
+            message.clear();
+        }
+        write!(output, "> ")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        match line.parse() {
+            Ok(Command::Next) => turn = (turn + 1).min(last),
+            Ok(Command::Previous) => turn = turn.saturating_sub(1),
+            Ok(Command::First) => turn = 0,
+            Ok(Command::Last) => turn = last,
+            Ok(Command::Goto(t)) if t <= last => turn = t,
+            Ok(Command::Goto(t)) => message = format!("turn {} is past the end ({})", t, last),
+            Ok(Command::Quit) => return Ok(()),
+            Err(e) => message = e,
+        }
+    }
+}
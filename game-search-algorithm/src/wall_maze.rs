@@ -0,0 +1,323 @@
+use crate::conformance::{encoded_hash, GameSpec};
+use crate::ids::Seed;
+use crate::search::beam_search_action;
+use crate::{GameState, ScoreType, DX, DY};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+
+// 棒倒し法は奇数の大きさの盤面で柱が1マスおきに並ぶ
+pub const WALL_H: usize = 7;
+pub const WALL_W: usize = 7;
+pub const WALL_END_TURN: usize = 49;
+
+// 評価値の計算方法
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WallEvaluation {
+    // これまでに得たスコアだけ
+    Score,
+    // スコアを優先し、同じなら壁を回り込んだ最短距離で一番近い点に近いほど良い
+    BfsDistance,
+}
+
+// 壁のマスには入れない迷路。点は取ると 0 になる
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WallMazeState {
+    points: [[ScoreType; WALL_W]; WALL_H],
+    walls: [[bool; WALL_W]; WALL_H],
+    turn: usize,
+    character: (usize, usize),
+    pub game_score: ScoreType,
+    pub evaluation: WallEvaluation,
+}
+
+impl WallMazeState {
+    // 棒倒し法で壁を作る。(奇数, 奇数) のマスに柱を立て、1段目は上下左右、2段目以降は上以外のどれかに倒す
+    // キャラクターのいるマスには柱も倒した壁も置かない
+    pub fn from_seed(seed: Seed, evaluation: WallEvaluation) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        let character = (rng.gen_range(0..WALL_H), rng.gen_range(0..WALL_W));
+
+        let mut walls = [[false; WALL_W]; WALL_H];
+        for y in (1..WALL_H).step_by(2) {
+            for x in (1..WALL_W).step_by(2) {
+                if (y, x) == character {
+                    continue;
+                }
+                walls[y][x] = true;
+                // DY の 3 番目が上
+                let direction_number = if y == 1 { 4 } else { 3 };
+                let direction = rng.gen_range(0..direction_number);
+                let ty = (y as i64 + DY[direction]) as usize;
+                let tx = (x as i64 + DX[direction]) as usize;
+                if (ty, tx) == character {
+                    continue;
+                }
+                walls[ty][tx] = true;
+            }
+        }
+
+        let mut points = [[0; WALL_W]; WALL_H];
+        for y in 0..WALL_H {
+            for x in 0..WALL_W {
+                if walls[y][x] || (y, x) == character {
+                    continue;
+                }
+                points[y][x] = rng.gen_range(0..10);
+            }
+        }
+
+        Self {
+            points,
+            walls,
+            turn: 0,
+            character,
+            game_score: 0,
+            evaluation,
+        }
+    }
+
+    // empty_percent % のマスの点を 0 にする。壁と点の残るマスは from_seed と同じ
+    pub fn with_empty_cells(mut self, seed: Seed, empty_percent: u32) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
+        rng.set_stream(1);
+        for point in self.points.iter_mut().flatten() {
+            if rng.gen_range(0..100) < empty_percent {
+                *point = 0;
+            }
+        }
+        self
+    }
+
+    // 壁を通らずに、点の残っている一番近いマスまで何手かかるか。届く点がなければ None
+    pub fn distance_to_nearest_point(&self) -> Option<usize> {
+        let mut distances = [[None; WALL_W]; WALL_H];
+        let mut queue = VecDeque::new();
+        distances[self.character.0][self.character.1] = Some(0);
+        queue.push_back(self.character);
+        while let Some((y, x)) = queue.pop_front() {
+            let distance = distances[y][x].unwrap();
+            if self.points[y][x] > 0 {
+                return Some(distance);
+            }
+            for (ny, nx) in self.neighbors(y, x) {
+                if distances[ny][nx].is_none() {
+                    distances[ny][nx] = Some(distance + 1);
+                    queue.push_back((ny, nx));
+                }
+            }
+        }
+        None
+    }
+
+    // (y, x) から1手で動ける、盤の中で壁でないマス
+    fn neighbors(&self, y: usize, x: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..4).filter_map(move |action| {
+            let ny = y as i64 + DY[action];
+            let nx = x as i64 + DX[action];
+            if ny < 0 || ny >= WALL_H as i64 || nx < 0 || nx >= WALL_W as i64 {
+                return None;
+            }
+            let (ny, nx) = (ny as usize, nx as usize);
+            (!self.walls[ny][nx]).then_some((ny, nx))
+        })
+    }
+}
+
+impl GameState for WallMazeState {
+    fn legal_actions(&self) -> Vec<usize> {
+        let (y, x) = self.character;
+        (0..4)
+            .filter(|&action| {
+                let ny = y as i64 + DY[action];
+                let nx = x as i64 + DX[action];
+                ny >= 0
+                    && ny < WALL_H as i64
+                    && nx >= 0
+                    && nx < WALL_W as i64
+                    && !self.walls[ny as usize][nx as usize]
+            })
+            .collect()
+    }
+
+    fn advance(&mut self, action: usize) {
+        let y = (self.character.0 as i64 + DY[action]) as usize;
+        let x = (self.character.1 as i64 + DX[action]) as usize;
+        self.character = (y, x);
+        self.game_score += self.points[y][x];
+        self.points[y][x] = 0;
+        self.turn += 1;
+    }
+
+    fn is_done(&self) -> bool {
+        self.turn == WALL_END_TURN
+    }
+
+    // BfsDistance では距離が盤面のマス数より小さいので、スコアに盤面のマス数を掛けてから距離を引く
+    fn evaluate_score(&mut self) -> ScoreType {
+        match self.evaluation {
+            WallEvaluation::Score => self.game_score,
+            WallEvaluation::BfsDistance => {
+                let distance = self.distance_to_nearest_point().unwrap_or(0);
+                self.game_score * (WALL_H * WALL_W) as ScoreType - distance as ScoreType
+            }
+        }
+    }
+}
+
+// 検査では得点だけで評価する盤面を使う。encode は評価方法も含めて全部のフィールドを書く
+// 手番 (1)、キャラクター (2)、評価方法 (1)、得点 (4)、点 (マスごとに1)、壁 (マスごとに1)
+const WALL_ENCODED_LEN: usize = 8 + 2 * WALL_H * WALL_W;
+
+impl GameSpec for WallMazeState {
+    fn game_name() -> &'static str {
+        "wall maze"
+    }
+
+    fn from_seed(seed: Seed) -> Self {
+        WallMazeState::from_seed(seed, WallEvaluation::Score)
+    }
+
+    fn max_turns() -> usize {
+        WALL_END_TURN
+    }
+
+    fn position_hash(&self) -> u64 {
+        encoded_hash(&self.encode())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![
+            self.turn as u8,
+            self.character.0 as u8,
+            self.character.1 as u8,
+            match self.evaluation {
+                WallEvaluation::Score => 0,
+                WallEvaluation::BfsDistance => 1,
+            },
+        ];
+        bytes.extend_from_slice(&(self.game_score as u32).to_le_bytes());
+        bytes.extend(self.points.iter().flatten().map(|&point| point as u8));
+        bytes.extend(self.walls.iter().flatten().map(|&wall| wall as u8));
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() != WALL_ENCODED_LEN {
+            return Err(format!(
+                "encoded wall maze must be {} bytes, got {}",
+                WALL_ENCODED_LEN,
+                bytes.len()
+            ));
+        }
+        let (turn, y, x) = (bytes[0] as usize, bytes[1] as usize, bytes[2] as usize);
+        if turn > WALL_END_TURN {
+            return Err(format!(
+                "turn {} is past the last turn {}",
+                turn, WALL_END_TURN
+            ));
+        }
+        if y >= WALL_H || x >= WALL_W {
+            return Err(format!("character ({}, {}) is outside the board", y, x));
+        }
+        let evaluation = match bytes[3] {
+            0 => WallEvaluation::Score,
+            1 => WallEvaluation::BfsDistance,
+            other => return Err(format!("unknown evaluation {}", other)),
+        };
+        let mut score = [0; 4];
+        score.copy_from_slice(&bytes[4..8]);
+        let cells = WALL_H * WALL_W;
+        let mut points = [[0; WALL_W]; WALL_H];
+        for (point, &byte) in points.iter_mut().flatten().zip(&bytes[8..8 + cells]) {
+            *point = byte as ScoreType;
+        }
+        let mut walls = [[false; WALL_W]; WALL_H];
+        for (wall, &byte) in walls.iter_mut().flatten().zip(&bytes[8 + cells..]) {
+            *wall = match byte {
+                0 => false,
+                1 => true,
+                other => return Err(format!("wall cell must be 0 or 1, got {}", other)),
+            };
+        }
+        if walls[y][x] {
+            return Err(format!("character ({}, {}) is on a wall", y, x));
+        }
+        Ok(WallMazeState {
+            points,
+            walls,
+            turn,
+            character: (y, x),
+            game_score: u32::from_le_bytes(score) as ScoreType,
+            evaluation,
+        })
+    }
+}
+
+// 壁を #、キャラクターを @ で書く
+impl Display for WallMazeState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "turn:{}", self.turn)?;
+        writeln!(f, "score:{}", self.game_score)?;
+        for y in 0..WALL_H {
+            for x in 0..WALL_W {
+                if (y, x) == self.character {
+                    write!(f, "@")?;
+                } else if self.walls[y][x] {
+                    write!(f, "#")?;
+                } else if self.points[y][x] > 0 {
+                    write!(f, "{}", self.points[y][x])?;
+                } else {
+                    write!(f, ".")?;
+                }
+            }
+            writeln!(f)?;
+        }
+        writeln!(f)
+    }
+}
+
+// 最初の game_number 個の盤面をビームサーチで最後まで指した平均スコア
+pub fn average_score(
+    evaluation: WallEvaluation,
+    beam_width: usize,
+    beam_depth: usize,
+    game_number: usize,
+) -> f64 {
+    average_score_from(
+        &|seed| WallMazeState::from_seed(seed, evaluation),
+        beam_width,
+        beam_depth,
+        game_number,
+    )
+}
+
+// new_state で作った最初の game_number 個の盤面の平均スコア (scenario の盤面など)
+pub fn average_score_from(
+    new_state: &dyn Fn(Seed) -> WallMazeState,
+    beam_width: usize,
+    beam_depth: usize,
+    game_number: usize,
+) -> f64 {
+    let mut total_score = 0;
+    for seed in Seed::first(game_number) {
+        let mut state = new_state(seed);
+        while !state.is_done() {
+            state.advance(beam_search_action(&state, beam_width, beam_depth));
+        }
+        total_score += state.game_score;
+    }
+    total_score as f64 / game_number as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::assert_conformance;
+
+    #[test]
+    fn wall_maze_state_conforms() {
+        assert_conformance::<WallMazeState>(0..100);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::constraints::ConstraintSet;
+use crate::ids::Seed;
+use crate::local_search::{plan_score, Plan};
+use crate::provenance::Provenance;
+use crate::{GameState, MazeState, ScoreType, END_TURN};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+// 最適化の結果を別のセッションで続きから改善するための保存形式
+// 行動列は遺伝子ではなく実際に指す手で持ち、読み込むときに今の盤面で指せるかを確かめる
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Solution {
+    pub seed: Seed,
+    pub score: ScoreType,
+    pub actions: Vec<usize>,
+}
+
+impl Solution {
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        writeln!(writer, "{}", Provenance::collect(1))?;
+        let actions: Vec<String> = self.actions.iter().map(usize::to_string).collect();
+        writeln!(
+            writer,
+            "seed {} score {} actions {}",
+            self.seed,
+            self.score,
+            actions.join(",")
+        )?;
+        writer.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let line = text
+            .lines()
+            .find(|line| !line.starts_with('#') && !line.trim().is_empty())
+            .unwrap_or("");
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid solution `{}`", line),
+            )
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            ["seed", seed, "score", score, "actions", actions] => Ok(Solution {
+                seed: seed.parse().map_err(|_| invalid())?,
+                score: score.parse().map_err(|_| invalid())?,
+                actions: actions
+                    .split(',')
+                    .map(|a| a.parse().map_err(|_| invalid()))
+                    .collect::<Result<_, _>>()?,
+            }),
+            // 空の行動列は "actions" のあとに何も書かない
+            ["seed", seed, "score", score, "actions"] => Ok(Solution {
+                seed: seed.parse().map_err(|_| invalid())?,
+                score: score.parse().map_err(|_| invalid())?,
+                actions: Vec::new(),
+            }),
+            _ => Err(invalid()),
+        }
+    }
+
+    // 今の局面で最初から指したときの行動列 (遺伝子は legal_actions の中の位置)
+    pub fn from_plan(seed: Seed, state: &MazeState, plan: &Plan) -> Self {
+        let actions = crate::local_search::decode_plan(state, plan);
+        Self {
+            seed,
+            score: plan_score(state, plan),
+            actions,
+        }
+    }
+
+    // 今のインスタンスで使える初期解に直す。別のシードの解、指せない手、記録と食い違う得点、
+    // 制約を満たさない解は理由をつけて断る
+    pub fn to_plan(
+        &self,
+        seed: Seed,
+        state: &MazeState,
+        constraints: Option<&ConstraintSet>,
+    ) -> Result<Plan, String> {
+        if self.seed != seed {
+            return Err(format!(
+                "solution is for seed {}, not seed {}",
+                self.seed, seed
+            ));
+        }
+        let mut now_state = state.clone();
+        let mut plan = Vec::new();
+        for (turn, &action) in self.actions.iter().enumerate() {
+            if now_state.is_done() {
+                return Err(format!("turn {}: the game is already over", turn));
+            }
+            let legal_actions = now_state.legal_actions();
+            let gene = legal_actions
+                .iter()
+                .position(|&legal| legal == action)
+                .ok_or(format!("turn {}: action {} is not legal", turn, action))?;
+            plan.push(gene);
+            now_state.advance(action);
+        }
+        let score = plan_score(state, &plan);
+        if score != self.score {
+            return Err(format!(
+                "solution scores {} here but {} was recorded",
+                score, self.score
+            ));
+        }
+        if let Some(constraints) = constraints {
+            let violation = constraints.violation(state, &plan);
+            if violation > 0 {
+                return Err(format!(
+                    "solution violates {} by {}",
+                    constraints, violation
+                ));
+            }
+        }
+        // 早く終わった解も、近傍が書き換えられるよう1局分の長さにしておく (終局後の遺伝子は読まれない)
+        plan.resize(plan.len().max(END_TURN), 0);
+        Ok(plan)
+    }
+}
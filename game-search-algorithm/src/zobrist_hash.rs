@@ -0,0 +1,131 @@
+use crate::agent::{depth_or_remaining, describe_depth, Agent};
+use crate::search::beam_search_dedup;
+use crate::{MazeParams, MazeState};
+use std::cell::RefCell;
+
+// マスの点は 0..10 (MazeState::from_seed)
+const POINT_VALUES: usize = 10;
+
+// 局面の特徴 (マスとその点、キャラクターの位置、ターン) ごとの乱数。局面のハッシュは持っている特徴の乱数の xor
+// 盤面の大きさとターン数ごとに、固定のシードから同じ順に作るので同じ値になる
+pub struct ZobristTable {
+    params: MazeParams,
+    // [y][x][点]
+    points: Vec<Vec<[u64; POINT_VALUES]>>,
+    character: Vec<Vec<u64>>,
+    turn: Vec<u64>,
+}
+
+// splitmix64
+fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl ZobristTable {
+    pub fn generate(seed: u64, params: MazeParams) -> Self {
+        let mut state = seed;
+        let mut points = vec![vec![[0; POINT_VALUES]; params.w]; params.h];
+        let mut character = vec![vec![0; params.w]; params.h];
+        for y in 0..params.h {
+            for x in 0..params.w {
+                for value in points[y][x].iter_mut() {
+                    *value = next_random(&mut state);
+                }
+                character[y][x] = next_random(&mut state);
+            }
+        }
+        let turn = (0..=params.end_turn)
+            .map(|_| next_random(&mut state))
+            .collect();
+        Self {
+            params,
+            points,
+            character,
+            turn,
+        }
+    }
+
+    pub fn params(&self) -> MazeParams {
+        self.params
+    }
+
+    // state は表と同じ大きさの盤面でなければならない
+    pub fn hash(&self, state: &MazeState) -> u64 {
+        assert_eq!(
+            state.params, self.params,
+            "the Zobrist table was generated for another board"
+        );
+        let mut hash = self.character[state.character.y as usize][state.character.x as usize]
+            ^ self.turn[state.turn];
+        for (y, row) in state.points.iter().enumerate() {
+            for (x, &point) in row.iter().enumerate() {
+                hash ^= self.points[y][x][point as usize];
+            }
+        }
+        hash
+    }
+}
+
+thread_local! {
+    // 直前に使った盤面の大きさの表。大きさが変わったら作り直す
+    static ZOBRIST: RefCell<Option<ZobristTable>> = const { RefCell::new(None) };
+}
+
+// ビームサーチで同じ局面を見分けるためのハッシュ
+pub trait ZobristHash {
+    fn zobrist_hash(&self) -> u64;
+}
+
+impl ZobristHash for MazeState {
+    fn zobrist_hash(&self) -> u64 {
+        ZOBRIST.with(|table| {
+            let mut table = table.borrow_mut();
+            if table
+                .as_ref()
+                .map_or(true, |table| table.params != self.params)
+            {
+                *table = Some(ZobristTable::generate(0, self.params));
+            }
+            table.as_ref().unwrap().hash(self)
+        })
+    }
+}
+
+// 同じ深さで既に展開した局面を飛ばすビームサーチ。beam_depth が None なら局面の残りターンを深さにする
+pub struct DedupBeamAgent {
+    pub beam_width: usize,
+    pub beam_depth: Option<usize>,
+    // これまでに飛ばした局面の数
+    pub duplicates: usize,
+}
+
+impl DedupBeamAgent {
+    pub fn new(beam_width: usize, beam_depth: Option<usize>) -> Self {
+        Self {
+            beam_width,
+            beam_depth,
+            duplicates: 0,
+        }
+    }
+}
+
+impl Agent for DedupBeamAgent {
+    fn name(&self) -> String {
+        format!(
+            "dedup_beam(width={}, depth={})",
+            self.beam_width,
+            describe_depth(self.beam_depth)
+        )
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        let beam_depth = depth_or_remaining(self.beam_depth, state);
+        let (result, duplicates) = beam_search_dedup(state, self.beam_width, beam_depth);
+        self.duplicates += duplicates;
+        result.first_action
+    }
+}
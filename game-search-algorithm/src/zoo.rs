@@ -0,0 +1,127 @@
+use crate::agent::{Agent, BeamSearchAgent, GreedyAgent, RandomAgent};
+use crate::budget::Budget;
+use crate::mcts::MctsAgent;
+use crate::{test_ai_score, GameState, MazeState, ScoreType, END_TURN};
+
+// depth 手先までを全探索し、その間に得られる得点が最大になる最初の行動を選ぶ
+pub struct LookaheadAgent {
+    pub depth: usize,
+}
+
+fn lookahead(state: &MazeState, depth: usize) -> (ScoreType, Option<usize>) {
+    if depth == 0 || state.is_done() {
+        return (state.game_score, None);
+    }
+    let mut best = (-1, None);
+    for action in state.legal_actions() {
+        let mut next_state = state.clone();
+        next_state.advance(action);
+        let (score, _) = lookahead(&next_state, depth - 1);
+        if score > best.0 {
+            best = (score, Some(action));
+        }
+    }
+    best
+}
+
+impl Agent for LookaheadAgent {
+    fn name(&self) -> String {
+        format!("lookahead(depth={})", self.depth)
+    }
+
+    fn select_action(&mut self, state: &MazeState) -> usize {
+        lookahead(state, self.depth).1.unwrap()
+    }
+}
+
+// 振る舞いを固定した基準エージェント
+// 平均スコアはシード 0..ZOO_GAMES で測ったもので、アルゴリズムの改善はこれとの比較で測る
+// 変わってしまった場合は意図した変更かを確認してから値を更新すること
+pub const ZOO_GAMES: usize = 100;
+
+pub struct ZooEntry {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub expected_score: f64,
+    pub build: fn() -> Box<dyn Agent>,
+}
+
+pub fn zoo() -> Vec<ZooEntry> {
+    vec![
+        ZooEntry {
+            name: "random",
+            description: "uniformly random legal move",
+            expected_score: 12.88,
+            build: || Box::new(RandomAgent::new(0)),
+        },
+        ZooEntry {
+            name: "greedy",
+            description: "best one-step score",
+            expected_score: 24.85,
+            build: || Box::new(GreedyAgent),
+        },
+        // 1人ゲームなので depth-2 minimax は2手先の全探索になる
+        ZooEntry {
+            name: "lookahead-2",
+            description: "exhaustive two-move lookahead",
+            expected_score: 25.10,
+            build: || Box::new(LookaheadAgent { depth: 2 }),
+        },
+        ZooEntry {
+            name: "mcts-100",
+            description: "UCB1 tree search over 100 random playouts",
+            expected_score: 25.40,
+            build: || Box::new(MctsAgent::new(100, 0)),
+        },
+        ZooEntry {
+            name: "beam-3",
+            description: "beam search, width 3, full depth",
+            expected_score: 25.86,
+            build: || Box::new(BeamSearchAgent::new(3, Budget::iterations(END_TURN))),
+        },
+    ]
+}
+
+// 基準エージェント1つを測った結果
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZooCheck {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub expected_score: f64,
+    pub score: f64,
+}
+
+impl ZooCheck {
+    // 固定値からずれたか
+    pub fn drifted(&self) -> bool {
+        (self.score - self.expected_score).abs() >= 1e-9
+    }
+}
+
+// 全ての基準エージェントの平均スコアを測る
+pub fn verify_zoo() -> Vec<ZooCheck> {
+    zoo()
+        .into_iter()
+        .map(|entry| {
+            let mut agent = (entry.build)();
+            ZooCheck {
+                name: entry.name,
+                description: entry.description,
+                expected_score: entry.expected_score,
+                score: test_ai_score(agent.as_mut(), ZOO_GAMES),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zoo_scores_are_pinned() {
+        for check in verify_zoo() {
+            assert!(!check.drifted(), "{:?}", check);
+        }
+    }
+}